@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Context};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use tracing::instrument;
+
+use crate::compare::resolve_matchers;
+use crate::paths::FieldPath;
+
+/// A single assertion about the Logstash output, addressed by a JSONPath expression (e.g.
+/// `$.url.port`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct Assertion {
+    /// A file written by the file output (relative to its capture directory), whose lines the
+    /// assertion checks as an array (e.g. `$[0].message`) instead of each output event
+    #[serde(default)]
+    pub file: Option<String>,
+    pub path: String,
+    #[serde(flatten)]
+    pub predicate: Predicate,
+}
+
+/// The condition that all values selected by an assertion must satisfy
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Predicate {
+    /// The selected values are equal to the given value (which may contain value matchers)
+    Equals(Value),
+    /// The path selects at least one value (`true`) or none at all (`false`)
+    Exists(bool),
+    /// The selected values are strings that match the given regular expression
+    Matches(String),
+    /// The selected values are arrays containing the given element, or strings containing the
+    /// given substring
+    Contains(Value),
+    /// The given jq expression evaluates to a truthy value against the selected values (e.g.
+    /// `.port == 443 and .scheme == "https"`), as determined by `jq --exit-status`
+    Jq(String),
+}
+
+impl fmt::Display for Predicate {
+    /// Describes the condition as a verb phrase (e.g. `equal 443`), with the values as JSON
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Predicate::Equals(expected) => write!(f, "equal {expected}"),
+            Predicate::Exists(true) => write!(f, "exist"),
+            Predicate::Exists(false) => write!(f, "not exist"),
+            Predicate::Matches(pattern) => {
+                write!(f, "match the pattern {}", Value::from(pattern.as_str()))
+            }
+            Predicate::Contains(element) => write!(f, "contain {element}"),
+            Predicate::Jq(expression) => {
+                write!(
+                    f,
+                    "satisfy the jq expression {}",
+                    Value::from(expression.as_str())
+                )
+            }
+        }
+    }
+}
+
+impl Assertion {
+    /// Evaluates the assertion against an event and returns a description of the failure, if
+    /// any
+    pub fn evaluate(&self, event: &Value) -> anyhow::Result<Option<String>> {
+        let path = JsonPath::parse(&self.path)
+            .with_context(|| format!("Parsing the JSONPath expression '{}'", self.path))?;
+        let nodes = path.query(event).all();
+
+        if let Predicate::Exists(exists) = self.predicate {
+            return Ok((exists == nodes.is_empty()).then(|| {
+                if exists {
+                    format!("{}: expected a value, found none", self.path)
+                } else {
+                    format!("{}: expected no value, found {}", self.path, nodes.len())
+                }
+            }));
+        }
+
+        if nodes.is_empty() {
+            return Ok(Some(format!("{}: no value was found", self.path)));
+        }
+
+        for node in nodes {
+            let satisfied = match &self.predicate {
+                Predicate::Equals(expected) => {
+                    let mut expected = expected.clone();
+                    resolve_matchers(node, &mut expected)?;
+                    node == &expected
+                }
+                Predicate::Matches(pattern) => {
+                    let re = Regex::new(pattern)
+                        .with_context(|| format!("Compiling the regular expression '{pattern}'"))?;
+                    node.as_str().is_some_and(|s| re.is_match(s))
+                }
+                Predicate::Contains(element) => match (node, element) {
+                    (Value::Array(items), element) => items.contains(element),
+                    (Value::String(s), Value::String(sub)) => s.contains(sub.as_str()),
+                    _ => false,
+                },
+                Predicate::Jq(expression) => evaluate_jq(expression, node)?,
+                Predicate::Exists(_) => unreachable!(),
+            };
+
+            if !satisfied {
+                return Ok(Some(format!(
+                    "{}: found {}, which does not {}",
+                    self.path, node, self.predicate
+                )));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Whether any of the assertions is a jq expression, which requires the `jq` program
+pub fn uses_jq(assertions: &[Assertion]) -> bool {
+    assertions
+        .iter()
+        .any(|a| matches!(a.predicate, Predicate::Jq(_)))
+}
+
+/// The version of the installed `jq` program (e.g. `jq-1.7.1`)
+#[instrument]
+pub fn jq_version() -> anyhow::Result<String> {
+    let output = Command::new("jq")
+        .arg("--version")
+        .output()
+        .context("Running jq --version")?;
+    if !output.status.success() {
+        return Err(anyhow!("jq --version exited with {}", output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Fails if the assertions contain jq expressions but the `jq` program cannot be run, before
+/// any of them is evaluated
+pub fn require_jq(assertions: &[Assertion]) -> anyhow::Result<()> {
+    static JQ_VERSION: OnceLock<Result<String, String>> = OnceLock::new();
+
+    if !uses_jq(assertions) {
+        return Ok(());
+    }
+    match JQ_VERSION.get_or_init(|| jq_version().map_err(|e| format!("{e:#}"))) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(anyhow!(
+            "The assertions contain jq expressions, which require jq to be installed: {e}"
+        )),
+    }
+}
+
+/// Runs the `jq` program with an expression against a value, and returns whether its result is
+/// truthy (i.e. neither `false` nor `null`)
+fn evaluate_jq(expression: &str, value: &Value) -> anyhow::Result<bool> {
+    let mut process = Command::new("jq")
+        .arg("--exit-status")
+        .arg(expression)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Running jq")?;
+    if let Some(mut stdin) = process.stdin.take() {
+        stdin
+            .write_all(value.to_string().as_bytes())
+            .context("Writing the value to jq")?;
+    }
+    let output = process.wait_with_output().context("Running jq")?;
+
+    // Exit code 1 denotes a falsy last result, and 4 no result at all
+    match output.status.code() {
+        Some(0) => Ok(true),
+        Some(1 | 4) => Ok(false),
+        _ => Err(anyhow!(
+            "jq {expression:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+/// Reads a list of assertions from a JSON or YAML file (depending on the file extension), or a
+/// file of jq expressions (`.jq`) that must each be truthy against the event, one per line
+#[instrument]
+pub fn load_assertions(path: &Path) -> anyhow::Result<Vec<Assertion>> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("Reading the assertions file: {}", path.display()))?;
+
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("jq") => Ok(data
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|l| Assertion {
+                file: None,
+                path: String::from("$"),
+                predicate: Predicate::Jq(l.to_string()),
+            })
+            .collect()),
+        Some("yaml" | "yml") => serde_yaml::from_str(&data)
+            .with_context(|| format!("Parsing the assertions file: {}", path.display())),
+        _ => serde_json::from_str(&data)
+            .with_context(|| format!("Parsing the assertions file: {}", path.display())),
+    }
+}
+
+/// Evaluates the assertions without a `file` against an event and fails with a list of every
+/// failed assertion
+#[instrument(skip_all)]
+pub fn check_assertions(assertions: &[Assertion], event: &Value) -> anyhow::Result<()> {
+    let assertions: Vec<Assertion> = assertions
+        .iter()
+        .filter(|a| a.file.is_none())
+        .cloned()
+        .collect();
+    let mut failures = Vec::new();
+    for assertion in &assertions {
+        if let Some(failure) = assertion.evaluate(event)? {
+            failures.push(failure);
+        }
+    }
+    conclude(&assertions, failures)
+}
+
+/// Evaluates the assertions with a `file` against the contents of the captured files (as
+/// returned by [`read_captured_files`]), a missing file counting as an empty one, and fails with
+/// a list of every failed assertion
+#[instrument(skip_all)]
+pub fn check_file_assertions(
+    assertions: &[Assertion],
+    files: &BTreeMap<String, Value>,
+) -> anyhow::Result<()> {
+    let assertions: Vec<Assertion> = assertions
+        .iter()
+        .filter(|a| a.file.is_some())
+        .cloned()
+        .collect();
+    let mut failures = Vec::new();
+    for assertion in &assertions {
+        let file = assertion.file.as_deref().unwrap_or_default();
+        let contents = files.get(file).cloned().unwrap_or(Value::Array(Vec::new()));
+        if let Some(failure) = assertion.evaluate(&contents)? {
+            failures.push(format!("{file}: {failure}"));
+        }
+    }
+    conclude(&assertions, failures)
+}
+
+/// Checks that an event contains every field of `must_exist` and none of `must_not_exist`, and
+/// fails with a list of every violation
+#[instrument(skip(event))]
+pub fn check_presence(
+    must_exist: &[FieldPath],
+    must_not_exist: &[FieldPath],
+    event: &Value,
+) -> anyhow::Result<()> {
+    let missing = must_exist
+        .iter()
+        .filter(|p| p.get(event).is_none())
+        .map(|p| format!("{p}: expected the field to exist, found none"));
+    let present = must_not_exist
+        .iter()
+        .filter_map(|p| p.get(event).map(|v| (p, v)))
+        .map(|(p, v)| format!("{p}: expected no field, found {v}"));
+    let failures: Vec<String> = missing.chain(present).collect();
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "{} of {} field presence checks failed:\n{}",
+        failures.len(),
+        must_exist.len() + must_not_exist.len(),
+        failures.join("\n")
+    ))
+}
+
+/// Reads the files captured in a directory, by their path relative to it. The contents of each
+/// file are an array of its lines, each parsed as JSON if possible.
+#[instrument]
+pub fn read_captured_files(dir: &Path) -> anyhow::Result<BTreeMap<String, Value>> {
+    let mut files = BTreeMap::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current)
+            .with_context(|| format!("Reading the directory: {}", current.display()))?
+        {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("Reading the captured file: {}", path.display()))?;
+            let lines = text
+                .lines()
+                .filter(|l| !l.trim().is_empty())
+                .map(|l| serde_json::from_str(l).unwrap_or_else(|_| Value::String(l.to_string())))
+                .collect();
+            let name = path.strip_prefix(dir)?.to_string_lossy().replace('\\', "/");
+            files.insert(name, Value::Array(lines));
+        }
+    }
+    Ok(files)
+}
+
+fn conclude(assertions: &[Assertion], failures: Vec<String>) -> anyhow::Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "{} of {} assertions failed:\n{}",
+        failures.len(),
+        assertions.len(),
+        failures.join("\n")
+    ))
+}
@@ -7,3 +7,11 @@ pub struct ConfigAssets;
 #[derive(RustEmbed)]
 #[folder = "logstash/pipeline"]
 pub struct PipelineAssets;
+
+#[derive(RustEmbed)]
+#[folder = "report"]
+pub struct ReportAssets;
+
+#[derive(RustEmbed)]
+#[folder = "scaffold"]
+pub struct ScaffoldAssets;
@@ -0,0 +1,203 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context};
+use clap::Args;
+use serde_json::{json, Value};
+use tokio::task::JoinSet;
+use tokio::time::{interval, timeout, MissedTickBehavior};
+use tracing::{debug, instrument};
+
+use crate::runner::{TestContext, TestOptions};
+use crate::BENCH_ID_PREFIX;
+
+#[derive(Debug, Args)]
+pub struct BenchArguments {
+    /// A file of input events, one JSON object or raw log line (sent as the `message` field) per
+    /// line, which is repeated as needed [default: generated events]
+    #[arg(long, value_name = "FILE")]
+    pub events: Option<PathBuf>,
+    /// The number of events sent [default: the number of events in the file, or 1000]
+    #[arg(long)]
+    pub count: Option<usize>,
+    /// The number of events sent per second [default: as fast as Logstash accepts them]
+    #[arg(long, value_name = "EVENTS")]
+    pub rate: Option<f64>,
+}
+
+/// The throughput and latency of the pipeline measured by a benchmark
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchReport {
+    /// The number of events sent to Logstash
+    pub sent: usize,
+    /// The latency of each output event received, i.e. the time between sending its input event
+    /// and receiving it
+    pub latencies: Vec<Duration>,
+    /// The time between sending the first event and receiving the last one
+    pub duration: Duration,
+}
+
+impl BenchReport {
+    /// The number of output events received per second
+    pub fn throughput(&self) -> f64 {
+        match self.duration.as_secs_f64() {
+            secs if secs > 0.0 => self.latencies.len() as f64 / secs,
+            _ => 0.0,
+        }
+    }
+
+    /// The latency below which the given percentage of the output events were received
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        let mut latencies = self.latencies.clone();
+        latencies.sort();
+        percentile(&latencies, percent)
+    }
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ms = |d: Option<Duration>| match d {
+            Some(d) => format!("{:.1} ms", d.as_secs_f64() * 1000.0),
+            None => String::from("-"),
+        };
+        writeln!(f, "events sent:     {}", self.sent)?;
+        writeln!(
+            f,
+            "events received: {} ({} lost)",
+            self.latencies.len(),
+            self.sent.saturating_sub(self.latencies.len())
+        )?;
+        writeln!(f, "duration:        {:.2?}", self.duration)?;
+        writeln!(f, "throughput:      {:.1} events/s", self.throughput())?;
+        writeln!(f, "latency p50:     {}", ms(self.percentile(50.0)))?;
+        writeln!(f, "latency p95:     {}", ms(self.percentile(95.0)))?;
+        writeln!(f, "latency p99:     {}", ms(self.percentile(99.0)))?;
+        write!(
+            f,
+            "latency max:     {}",
+            ms(self.latencies.iter().max().copied())
+        )
+    }
+}
+
+/// The nearest-rank percentile of sorted values
+pub fn percentile(sorted: &[Duration], percent: f64) -> Option<Duration> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+/// The correlation ID of a benchmark event, which carries the time it was sent (relative to the
+/// start of the benchmark) through the pipeline in `[@metadata][lotus_test_id]`
+pub fn bench_id(seq: usize, sent: Duration) -> String {
+    format!("{BENCH_ID_PREFIX}{seq}-{}", sent.as_micros())
+}
+
+/// The sequence number and the time of sending carried by the correlation ID of a benchmark event
+pub fn parse_bench_id(test_id: &str) -> Option<(usize, Duration)> {
+    let (seq, sent) = test_id.strip_prefix(BENCH_ID_PREFIX)?.split_once('-')?;
+    Some((seq.parse().ok()?, Duration::from_micros(sent.parse().ok()?)))
+}
+
+/// Reads an input event from a line, which is either a JSON object or a raw log line that becomes
+/// the `message` field of the event
+pub fn event_from_line(line: &str) -> Value {
+    match serde_json::from_str::<Value>(line) {
+        Ok(event @ Value::Object(_)) => event,
+        _ => json!({ "message": line }),
+    }
+}
+
+/// Reads the input events of a benchmark: each non-empty line is either a JSON object or a raw
+/// log line, which becomes the `message` field of an event
+#[instrument]
+pub fn load_events(path: &Path) -> anyhow::Result<Vec<Value>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Reading the benchmark events: {}", path.display()))?;
+    let events: Vec<Value> = text
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(event_from_line)
+        .collect();
+    if events.is_empty() {
+        return Err(anyhow!(
+            "The benchmark events file is empty: {}",
+            path.display()
+        ));
+    }
+    Ok(events)
+}
+
+/// Generates input events for a benchmark, each with a distinct `message` field
+pub fn generate_events(count: usize) -> Vec<Value> {
+    (0..count)
+        .map(|i| json!({ "message": format!("lotus benchmark event {i}") }))
+        .collect()
+}
+
+/// Sends `count` events (repeating the given ones as needed) at the given rate through the
+/// Logstash containers, and measures the latency of each output event
+#[instrument(skip(context, events))]
+pub async fn run_bench(
+    context: &TestContext,
+    events: &[Value],
+    count: usize,
+    rate: Option<f64>,
+    options: &TestOptions,
+) -> anyhow::Result<BenchReport> {
+    if let Some(rate) = rate.filter(|r| !(r.is_finite() && *r > 0.0)) {
+        return Err(anyhow!("Invalid benchmark rate: {rate}"));
+    }
+    let host = options.connection.logstash_host();
+    let wait = options.timeout;
+    let mut ticks = rate.map(|rate| {
+        let mut ticks = interval(Duration::from_secs_f64(1.0 / rate));
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Burst);
+        ticks
+    });
+
+    debug!("Send {count} events to Logstash");
+    let started = Instant::now();
+    let mut receipts = JoinSet::new();
+    for (seq, event) in events.iter().cycle().take(count).enumerate() {
+        if let Some(ticks) = ticks.as_mut() {
+            ticks.tick().await;
+        }
+        let test_id = bench_id(seq, started.elapsed());
+        let mut receiver = context.router().register(&test_id);
+        context
+            .send_event(&host, seq, &test_id, event)
+            .await
+            .with_context(|| format!("Sending benchmark event {seq}"))?;
+
+        let router = context.router().clone();
+        receipts.spawn(async move {
+            let received = match wait {
+                Some(wait) => timeout(wait, receiver.recv()).await.ok().flatten(),
+                None => receiver.recv().await,
+            };
+            let received_at = started.elapsed();
+            router.unregister(&test_id);
+            let (_, sent) = parse_bench_id(&test_id)?;
+            received.map(|_| (received_at, received_at.saturating_sub(sent)))
+        });
+    }
+
+    debug!("Wait for the output events of Logstash");
+    let mut latencies = Vec::with_capacity(count);
+    let mut duration = Duration::ZERO;
+    while let Some(receipt) = receipts.join_next().await {
+        if let Some((received_at, latency)) = receipt.context("Joining an output event receipt")? {
+            duration = duration.max(received_at);
+            latencies.push(latency);
+        }
+    }
+
+    Ok(BenchReport {
+        sent: count,
+        latencies,
+        duration,
+    })
+}
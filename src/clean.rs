@@ -0,0 +1,40 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::Args;
+use tracing::{debug, instrument};
+
+#[derive(Debug, Args)]
+pub struct CleanArguments {
+    /// Only delete the cache directories, and leave the Docker images and containers untouched
+    #[arg(long)]
+    pub cache_only: bool,
+}
+
+/// Deletes the cache directories of all projects tested so far, which are kept in the given
+/// directory. Returns the deleted directories.
+#[instrument]
+pub fn remove_cache_dirs(cache_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !cache_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut removed = Vec::new();
+    for entry in std::fs::read_dir(cache_dir)
+        .with_context(|| format!("Reading the cache directory: {}", cache_dir.display()))?
+    {
+        let path = entry
+            .with_context(|| format!("Reading the cache directory: {}", cache_dir.display()))?
+            .path();
+        if !path.is_dir() {
+            continue;
+        }
+        debug!("Delete the cache directory {}", path.display());
+        std::fs::remove_dir_all(&path)
+            .with_context(|| format!("Deleting the cache directory: {}", path.display()))?;
+        removed.push(path);
+    }
+
+    removed.sort();
+    Ok(removed)
+}
@@ -1,47 +1,296 @@
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use crate::runner::TestCase;
-use crate::{EXPECTED_FILE, INPUT_FILE, RULE_EXTENSION, SCRIPT_EXTENSION};
+use crate::config::TestMetadata;
+use crate::runner::{Expectation, Source, TestCase};
+use crate::{
+    ASSERTIONS_FILES, CASE_FILE_EXTENSIONS, DROP_MARKER_FILE, EXPECTED_FILE, INPUT_FILE,
+    INPUT_NDJSON_FILE, INPUT_TEXT_FILE, REGEX_PATTERN_PREFIX, RULE_EXTENSION, SCHEMA_FILE,
+    SCRIPT_EXTENSION,
+};
 use anyhow::{anyhow, Context};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
 use tracing::instrument;
 
+/// A test case stored in a single YAML file, containing its input, expectation and metadata
+#[derive(Debug, Deserialize)]
+struct TestCaseFile {
+    input: Value,
+    #[serde(default)]
+    expected: Option<Value>,
+    #[serde(default)]
+    assertions: Option<Value>,
+    #[serde(default)]
+    schema: Option<Value>,
+    #[serde(default)]
+    drop: bool,
+    #[serde(flatten)]
+    metadata: TestMetadata,
+}
+
+/// A pattern matched against test case names: a glob (e.g. `apache*`) if it contains `*` or `?`,
+/// a regular expression if prefixed with `re:`, and a substring otherwise
+#[derive(Debug, Clone)]
+pub struct NamePattern(Regex);
+
+impl NamePattern {
+    pub fn matches(&self, name: &str) -> bool {
+        self.0.is_match(name)
+    }
+}
+
+impl FromStr for NamePattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let re = if let Some(re) = s.strip_prefix(REGEX_PATTERN_PREFIX) {
+            re.to_string()
+        } else if s.contains(['*', '?']) {
+            let glob: String = s
+                .chars()
+                .map(|c| match c {
+                    '*' => String::from(".*"),
+                    '?' => String::from("."),
+                    c => regex::escape(&c.to_string()),
+                })
+                .collect();
+            format!("^{glob}$")
+        } else {
+            regex::escape(s)
+        };
+
+        Regex::new(&re)
+            .map(NamePattern)
+            .with_context(|| format!("Compiling the test case name pattern '{s}'"))
+    }
+}
+
+/// Selects a subset of the collected test cases
+#[derive(Debug, Default, Clone)]
+pub struct TestFilter {
+    /// Only select test cases whose name matches at least one of these patterns (all if empty)
+    pub names: Vec<NamePattern>,
+    /// Only select test cases with at least one of these tags (all if empty)
+    pub tags: Vec<String>,
+    /// Never select test cases with any of these tags
+    pub exclude_tags: Vec<String>,
+}
+
+impl TestFilter {
+    pub fn matches(&self, test_case: &TestCase) -> bool {
+        let tags = &test_case.metadata.tags;
+        (self.names.is_empty() || self.names.iter().any(|p| p.matches(&test_case.name)))
+            && (self.tags.is_empty() || self.tags.iter().any(|t| tags.contains(t)))
+            && !self.exclude_tags.iter().any(|t| tags.contains(t))
+    }
+}
+
+/// Randomizes the order of the test cases. The same seed always results in the same order.
+pub fn shuffle_tests(test_cases: &mut [TestCase], seed: u64) {
+    test_cases.shuffle(&mut StdRng::seed_from_u64(seed));
+}
+
+/// Collects the test cases in the given directory and its subdirectories, named by their path
+/// relative to it (e.g. `apache/access/basic`). If `record` is set, test cases without an
+/// expectation are accepted, and their actual output is to be recorded to `expected.json`.
 #[instrument]
-pub fn collect_tests(tests_dir: &Path) -> anyhow::Result<Vec<TestCase>> {
+pub fn collect_tests(tests_dir: &Path, record: bool) -> anyhow::Result<Vec<TestCase>> {
     let mut test_cases: Vec<TestCase> = Vec::new();
-    let dir_iter = std::fs::read_dir(tests_dir)
-        .with_context(|| format!("Reading the test cases directory: {}", tests_dir.display()))?;
+    collect_tests_recursive(tests_dir, tests_dir, record, &mut test_cases)?;
+    test_cases.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(test_cases)
+}
+
+/// Directories containing an input or expectation file are test cases, all others are searched
+/// for nested test cases
+#[instrument(skip(test_cases))]
+fn collect_tests_recursive(
+    tests_dir: &Path,
+    directory: &Path,
+    record: bool,
+    test_cases: &mut Vec<TestCase>,
+) -> anyhow::Result<()> {
+    let dir_iter = std::fs::read_dir(directory)
+        .with_context(|| format!("Reading the test cases directory: {}", directory.display()))?;
 
     for dir_entry in dir_iter {
         let dir_entry = dir_entry.context("Collecting a test case")?;
         let file_type = dir_entry
             .file_type()
             .context("Determining the file type of the test case")?;
-        if !file_type.is_dir() {
-            continue;
+        let path = dir_entry.path();
+        if file_type.is_dir() {
+            if is_test_case_dir(&path) {
+                let name = test_case_name(tests_dir, &path);
+                test_cases.push(collect_test_case_dir(&path, name, record)?);
+            } else {
+                collect_tests_recursive(tests_dir, &path, record, test_cases)?;
+            }
+        } else if file_type.is_file()
+            && path
+                .extension()
+                .is_some_and(|e| CASE_FILE_EXTENSIONS.iter().any(|c| e == *c))
+        {
+            let name = test_case_name(tests_dir, &path.with_extension(""));
+            test_cases.push(collect_test_case_file(&path, name, record)?);
         }
-        let test_case_dir = dir_entry.path();
-        let input_file = test_case_dir.join(INPUT_FILE);
-        if !input_file.is_file() {
+    }
+
+    Ok(())
+}
+
+fn is_test_case_dir(directory: &Path) -> bool {
+    [
+        INPUT_FILE,
+        INPUT_NDJSON_FILE,
+        INPUT_TEXT_FILE,
+        EXPECTED_FILE,
+        SCHEMA_FILE,
+        DROP_MARKER_FILE,
+    ]
+    .iter()
+    .chain(ASSERTIONS_FILES.iter())
+    .any(|f| directory.join(f).is_file())
+}
+
+/// The path of a test case relative to the tests directory, separated by forward slashes
+fn test_case_name(tests_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(tests_dir)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[instrument]
+fn collect_test_case_dir(
+    test_case_dir: &Path,
+    name: String,
+    record: bool,
+) -> anyhow::Result<TestCase> {
+    let input_names = [INPUT_FILE, INPUT_NDJSON_FILE, INPUT_TEXT_FILE];
+    let mut input_files: Vec<PathBuf> = input_names
+        .iter()
+        .map(|f| test_case_dir.join(f))
+        .filter(|f| f.is_file())
+        .collect();
+    let input_file = match input_files.len() {
+        0 => {
             return Err(anyhow!(
                 "The input file was not found: {}",
-                input_file.display()
-            ));
+                test_case_dir.join(INPUT_FILE).display()
+            ))
+        }
+        1 => input_files.remove(0),
+        _ => {
+            return Err(anyhow!(
+                "More than one of {} was found, expected only one: {}",
+                input_names.join(", "),
+                test_case_dir.display()
+            ))
+        }
+    };
+    let expectation = find_expectation(test_case_dir, record)?;
+
+    let metadata = TestMetadata::load(test_case_dir)
+        .with_context(|| format!("Loading the test metadata: {}", test_case_dir.display()))?;
+
+    Ok(TestCase {
+        name,
+        input: Source::File(input_file),
+        expectation,
+        metadata,
+    })
+}
+
+#[instrument]
+fn collect_test_case_file(
+    test_case_file: &Path,
+    name: String,
+    record: bool,
+) -> anyhow::Result<TestCase> {
+    let data = std::fs::read_to_string(test_case_file)
+        .with_context(|| format!("Reading the test case file: {}", test_case_file.display()))?;
+    let case: TestCaseFile = serde_yaml::from_str(&data)
+        .with_context(|| format!("Parsing the test case file: {}", test_case_file.display()))?;
+
+    let inline = |data: Value| Source::Inline {
+        file: test_case_file.to_path_buf(),
+        data,
+    };
+    let expectation = match (case.expected, case.assertions, case.schema, case.drop) {
+        (Some(expected), None, None, false) => Expectation::Document(inline(expected)),
+        (None, Some(assertions), None, false) => Expectation::Assertions(inline(assertions)),
+        (None, None, Some(schema), false) => Expectation::Schema(inline(schema)),
+        (None, None, None, true) => Expectation::Drop,
+        (None, None, None, false) if record => Expectation::Document(inline(Value::Null)),
+        (None, None, None, false) => {
+            return Err(anyhow!(
+                "The test case file contains neither 'expected', 'assertions', 'schema' nor 'drop': {}",
+                test_case_file.display()
+            ))
         }
-        let expected_file = test_case_dir.join(EXPECTED_FILE);
-        if !expected_file.is_file() {
+        _ => {
             return Err(anyhow!(
-                "The expected output file was not found: {}",
-                expected_file.display()
-            ));
+                "More than one of 'expected', 'assertions', 'schema' or 'drop' was found, expected only one: {}",
+                test_case_file.display()
+            ))
         }
+    };
+
+    Ok(TestCase {
+        name,
+        input: inline(case.input),
+        expectation,
+        metadata: case.metadata,
+    })
+}
 
-        test_cases.push(TestCase {
-            input: input_file,
-            expected: expected_file,
-        });
+/// Determines how the output of a test case is verified, based on which expectation file it
+/// contains (exactly one is permitted)
+#[instrument]
+fn find_expectation(test_case_dir: &Path, record: bool) -> anyhow::Result<Expectation> {
+    let mut expectations = Vec::new();
+    let expected_file = test_case_dir.join(EXPECTED_FILE);
+    if expected_file.is_file() {
+        expectations.push(Expectation::Document(Source::File(expected_file.clone())));
+    }
+    if let Some(assertions_file) = ASSERTIONS_FILES
+        .iter()
+        .map(|f| test_case_dir.join(f))
+        .find(|f| f.is_file())
+    {
+        expectations.push(Expectation::Assertions(Source::File(assertions_file)));
+    }
+    let schema_file = test_case_dir.join(SCHEMA_FILE);
+    if schema_file.is_file() {
+        expectations.push(Expectation::Schema(Source::File(schema_file)));
+    }
+    if test_case_dir.join(DROP_MARKER_FILE).is_file() {
+        expectations.push(Expectation::Drop);
     }
 
-    Ok(test_cases)
+    match expectations.len() {
+        0 if record => Ok(Expectation::Document(Source::File(expected_file))),
+        0 => Err(anyhow!(
+            "The expected output file was not found: {}",
+            expected_file.display()
+        )),
+        1 => Ok(expectations.remove(0)),
+        _ => Err(anyhow!(
+            "More than one of {}, {}, {} or {} was found, expected only one: {}",
+            EXPECTED_FILE,
+            ASSERTIONS_FILES.join(", "),
+            SCHEMA_FILE,
+            DROP_MARKER_FILE,
+            test_case_dir.display()
+        )),
+    }
 }
 
 #[instrument(skip(predicate))]
@@ -0,0 +1,867 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use anyhow::Context;
+use console::style;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{Number, Value};
+use tracing::instrument;
+
+use crate::paths::FieldPath;
+
+const TOKEN_PREFIX: &str = "{{";
+const TOKEN_SUFFIX: &str = "}}";
+const UUID_PATTERN: &str =
+    "^[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$";
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\{\{(today(?::[^}]*)?|input\.[^}]+)\}\}").expect("a valid pattern")
+});
+static INSTANT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\d{4})-(\d{2})-(\d{2})[Tt ](\d{2}):(\d{2}):(\d{2})(?:[.,](\d+))?([Zz]|([+-])(\d{2}):?(\d{2}))$")
+        .expect("a valid pattern")
+});
+
+/// How strictly the actual output is compared with the expected output
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CompareMode {
+    /// The actual output must be identical to the expected output
+    #[default]
+    Strict,
+    /// The actual output may contain fields that are not present in the expected output
+    Inclusive,
+}
+
+/// A transformation applied to a field of both the actual and the expected output before they are
+/// compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    /// Converts strings to lowercase
+    Lowercase,
+    /// Converts strings to uppercase
+    Uppercase,
+    /// Removes leading and trailing whitespace from strings
+    Trim,
+    /// Sorts the elements of arrays
+    Sort,
+}
+
+impl Normalization {
+    /// Applies the transformation to a value, and to each element if it is an array of strings
+    pub fn apply(&self, value: &mut Value) {
+        match (self, value) {
+            (Normalization::Sort, Value::Array(items)) => {
+                items.sort_by_cached_key(|item| item.to_string())
+            }
+            (_, Value::Array(items)) => items.iter_mut().for_each(|item| self.apply(item)),
+            (Normalization::Lowercase, Value::String(s)) => *s = s.to_lowercase(),
+            (Normalization::Uppercase, Value::String(s)) => *s = s.to_uppercase(),
+            (Normalization::Trim, Value::String(s)) => *s = s.trim().to_string(),
+            _ => (),
+        }
+    }
+}
+
+/// A single difference between the actual and the expected output, located by its path in
+/// Logstash notation (e.g. `[url][port]` or `[tags][0]`)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Difference {
+    /// The expected value is absent from the actual output
+    Missing { path: String, expected: Value },
+    /// The actual value is absent from the expected output
+    Unexpected { path: String, actual: Value },
+    /// The actual value differs from the expected value
+    Changed {
+        path: String,
+        actual: Value,
+        expected: Value,
+    },
+}
+
+impl Difference {
+    /// Prepends a prefix to the path of the difference (e.g. that of the subtree it was found in)
+    pub fn prefixed(self, prefix: &str) -> Self {
+        match self {
+            Difference::Missing { path, expected } => Difference::Missing {
+                path: format!("{prefix}{path}"),
+                expected,
+            },
+            Difference::Unexpected { path, actual } => Difference::Unexpected {
+                path: format!("{prefix}{path}"),
+                actual,
+            },
+            Difference::Changed {
+                path,
+                actual,
+                expected,
+            } => Difference::Changed {
+                path: format!("{prefix}{path}"),
+                actual,
+                expected,
+            },
+        }
+    }
+
+    /// Formats the difference, colored by its kind if the terminal supports it
+    pub fn styled(&self) -> String {
+        let path = |p: &str| match p {
+            "" => String::from("(root)"),
+            p => p.to_string(),
+        };
+        match self {
+            Difference::Missing { path: p, expected } => format!(
+                "{} missing, expected {}",
+                style(format!("- {}", path(p))).red(),
+                expected
+            ),
+            Difference::Unexpected { path: p, actual } => format!(
+                "{} unexpected, actual {}",
+                style(format!("+ {}", path(p))).green(),
+                actual
+            ),
+            Difference::Changed {
+                path: p,
+                actual,
+                expected,
+            } => format!(
+                "{} expected {}, actual {}",
+                style(format!("~ {}", path(p))).yellow(),
+                expected,
+                actual
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", console::strip_ansi_codes(&self.styled()))
+    }
+}
+
+/// How the differences between the actual and the expected output are displayed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DiffStyle {
+    /// A list of the missing, unexpected and changed values with their paths
+    #[default]
+    Paths,
+    /// The actual and the expected output next to each other, with differing lines aligned
+    SideBySide,
+    /// Only the paths that differ with their actual and expected values, without the complete
+    /// output even if verbose or in reports (e.g. for events with hundreds of fields)
+    PathsOnly,
+}
+
+/// How much detail of a mismatch between the actual and the expected output is displayed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only the number of differences
+    Quiet,
+    /// Each difference
+    #[default]
+    Normal,
+    /// Each difference, followed by the complete actual and expected output
+    Verbose,
+}
+
+/// The differences between the actual and the expected output of a test case
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub differences: Vec<Difference>,
+    pub actual: Value,
+    pub expected: Value,
+    pub verbosity: Verbosity,
+    pub style: DiffStyle,
+}
+
+impl Mismatch {
+    /// Whether the complete actual and expected output are displayed along with the differences
+    pub fn shows_documents(&self) -> bool {
+        self.style != DiffStyle::PathsOnly
+    }
+}
+
+impl Serialize for Mismatch {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let documents = self.shows_documents();
+        let mut state = serializer.serialize_struct("Mismatch", if documents { 3 } else { 1 })?;
+        state.serialize_field("differences", &self.differences)?;
+        if documents {
+            state.serialize_field("actual", &self.actual)?;
+            state.serialize_field("expected", &self.expected)?;
+        }
+        state.end()
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The actual output differs from the expected output in {} place(s):",
+            self.differences.len()
+        )?;
+        if self.verbosity == Verbosity::Quiet {
+            return Ok(());
+        }
+        match self.style {
+            DiffStyle::Paths | DiffStyle::PathsOnly => {
+                for difference in &self.differences {
+                    write!(f, "\n    {}", difference.styled())?;
+                }
+            }
+            DiffStyle::SideBySide => {
+                write!(f, "\n\n{}", side_by_side(&self.actual, &self.expected))?;
+                return Ok(());
+            }
+        }
+        if self.verbosity == Verbosity::Verbose && self.shows_documents() {
+            let actual = serde_json::to_string_pretty(&self.actual).map_err(|_| fmt::Error)?;
+            let expected = serde_json::to_string_pretty(&self.expected).map_err(|_| fmt::Error)?;
+            write!(f, "\n\nactual:\n{actual}\n\nexpected:\n{expected}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+/// Renders the actual (left) and the expected (right) document next to each other. Lines that
+/// differ are aligned and marked with `|`, lines only present in the actual document with `<`,
+/// and lines only present in the expected document with `>`.
+pub fn side_by_side(actual: &Value, expected: &Value) -> String {
+    let actual = serde_json::to_string_pretty(actual).unwrap_or_default();
+    let expected = serde_json::to_string_pretty(expected).unwrap_or_default();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let width = actual_lines
+        .iter()
+        .map(|l| l.chars().count())
+        .max()
+        .unwrap_or_default()
+        .max("actual".len());
+
+    let mut rows: Vec<(Option<&str>, Option<&str>)> = Vec::new();
+    for op in
+        similar::capture_diff_slices(similar::Algorithm::Myers, &actual_lines, &expected_lines)
+    {
+        let (old, new) = (op.old_range(), op.new_range());
+        for i in 0..old.len().max(new.len()) {
+            rows.push((
+                old.clone().nth(i).map(|j| actual_lines[j]),
+                new.clone().nth(i).map(|j| expected_lines[j]),
+            ));
+        }
+    }
+
+    let mut output = format!("{:<width$}   expected", "actual");
+    for (left, right) in rows {
+        let (marker, line) = match (left, right) {
+            (Some(l), Some(r)) if l == r => (" ", format!("{l:<width$}   {r}")),
+            (Some(l), Some(r)) => ("|", format!("{l:<width$} | {r}")),
+            (Some(l), None) => ("<", format!("{l:<width$} <")),
+            (None, Some(r)) => (">", format!("{:<width$} > {r}", "")),
+            (None, None) => continue,
+        };
+        output.push('\n');
+        output.push_str(&match marker {
+            "|" => style(line).yellow().to_string(),
+            "<" => style(line).green().to_string(),
+            ">" => style(line).red().to_string(),
+            _ => line,
+        });
+    }
+    output
+}
+
+/// Lists the differences between the actual and the expected document. In inclusive mode, fields
+/// and trailing array elements that are only present in the actual document are permitted.
+pub fn differences(actual: &Value, expected: &Value, mode: CompareMode) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    collect_differences(String::new(), actual, expected, mode, &mut differences);
+    differences
+}
+
+fn collect_differences(
+    path: String,
+    actual: &Value,
+    expected: &Value,
+    mode: CompareMode,
+    differences: &mut Vec<Difference>,
+) {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => {
+            for (key, expected_value) in expected {
+                let path = format!("{path}[{key}]");
+                match actual.get(key) {
+                    Some(actual_value) => {
+                        collect_differences(path, actual_value, expected_value, mode, differences)
+                    }
+                    None => differences.push(Difference::Missing {
+                        path,
+                        expected: expected_value.clone(),
+                    }),
+                }
+            }
+            if mode == CompareMode::Strict {
+                for (key, actual_value) in actual {
+                    if !expected.contains_key(key) {
+                        differences.push(Difference::Unexpected {
+                            path: format!("{path}[{key}]"),
+                            actual: actual_value.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        (Value::Array(actual), Value::Array(expected)) => {
+            for i in 0..actual.len().max(expected.len()) {
+                let path = format!("{path}[{i}]");
+                match (actual.get(i), expected.get(i)) {
+                    (Some(actual_value), Some(expected_value)) => {
+                        collect_differences(path, actual_value, expected_value, mode, differences)
+                    }
+                    (None, Some(expected_value)) => differences.push(Difference::Missing {
+                        path,
+                        expected: expected_value.clone(),
+                    }),
+                    (Some(actual_value), None) if mode == CompareMode::Strict => {
+                        differences.push(Difference::Unexpected {
+                            path,
+                            actual: actual_value.clone(),
+                        })
+                    }
+                    _ => (),
+                }
+            }
+        }
+        (Value::Number(actual), Value::Number(expected)) if numbers_equal(actual, expected) => (),
+        (actual, expected) if actual == expected => (),
+        (actual, expected) => differences.push(Difference::Changed {
+            path,
+            actual: actual.clone(),
+            expected: expected.clone(),
+        }),
+    }
+}
+
+/// Lists the differences between the actual and the expected document according to the matcher,
+/// comparing the subtree at each of the scoped paths in its own mode instead of the default one.
+/// A scope only applies if both documents contain its path, and the innermost scope of a field
+/// takes precedence.
+pub fn scoped_differences(
+    matcher: &dyn Matcher,
+    actual: &Value,
+    expected: &Value,
+    mode: CompareMode,
+    scopes: &BTreeMap<FieldPath, CompareMode>,
+) -> Vec<Difference> {
+    let applicable: Vec<(&FieldPath, CompareMode)> = scopes
+        .iter()
+        .filter(|(path, _)| path.get(actual).is_some() && path.get(expected).is_some())
+        .map(|(path, mode)| (path, *mode))
+        .collect();
+
+    let mut differences = Vec::new();
+    let subtrees =
+        std::iter::once((None, mode)).chain(applicable.iter().map(|(p, m)| (Some(*p), *m)));
+    for (scope, scope_mode) in subtrees {
+        let mut actual = actual.clone();
+        let mut expected = expected.clone();
+        for (nested, _) in &applicable {
+            let is_nested = scope.is_none_or(|scope| {
+                nested.segments().len() > scope.segments().len()
+                    && nested.segments().starts_with(scope.segments())
+            });
+            if is_nested {
+                nested.remove(&mut actual);
+                nested.remove(&mut expected);
+            }
+        }
+
+        let (actual, expected) = match scope {
+            Some(scope) => match (scope.get(&actual), scope.get(&expected)) {
+                (Some(actual), Some(expected)) => (actual, expected),
+                _ => continue,
+            },
+            None => (&actual, &expected),
+        };
+        let prefix = scope.map(|s| s.to_string()).unwrap_or_default();
+        differences.extend(
+            matcher
+                .differences(actual, expected, scope_mode)
+                .into_iter()
+                .map(|d| d.prefixed(&prefix)),
+        );
+    }
+
+    differences
+}
+
+/// Whether two numbers have the same value, regardless of their representation (e.g. `1` and
+/// `1.0` or `1e3` and `1000`). An integer only equals a float without a fractional part that
+/// converts to exactly that integer, lest it lose precision.
+pub fn numbers_equal(a: &Number, b: &Number) -> bool {
+    // 2^63 and 2^64, which are exactly representable as floats
+    const I64_LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    const U64_LIMIT: f64 = 18_446_744_073_709_551_616.0;
+    let integer_equals = |n: &Number, f: f64| {
+        if f.fract() != 0.0 {
+            return false;
+        }
+        match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) if (-I64_LIMIT..I64_LIMIT).contains(&f) => f as i64 == i,
+            (_, Some(u)) if (0.0..U64_LIMIT).contains(&f) => f as u64 == u,
+            _ => false,
+        }
+    };
+    match (a.as_f64(), b.as_f64(), a.is_f64(), b.is_f64()) {
+        (Some(x), Some(y), true, true) => x == y,
+        (_, Some(y), false, true) => integer_equals(a, y),
+        (Some(x), _, true, false) => integer_equals(b, x),
+        _ => a == b,
+    }
+}
+
+/// Replaces the strings in the actual document that encode a number equal to the one at the same
+/// location in the expected document with it, and vice versa (e.g. `"42"` and `42`)
+#[instrument(skip_all)]
+pub fn coerce_numbers(actual: &mut Value, expected: &Value) {
+    let number = |s: &str| s.trim().parse::<Number>().ok();
+    match (&mut *actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => {
+            for (key, actual_value) in actual.iter_mut() {
+                if let Some(expected_value) = expected.get(key) {
+                    coerce_numbers(actual_value, expected_value);
+                }
+            }
+        }
+        (Value::Array(actual), Value::Array(expected)) => {
+            for (actual_value, expected_value) in actual.iter_mut().zip(expected) {
+                coerce_numbers(actual_value, expected_value);
+            }
+        }
+        (Value::String(s), Value::Number(n)) if number(s).is_some_and(|m| numbers_equal(&m, n)) => {
+            *actual = expected.clone();
+        }
+        (Value::Number(n), Value::String(s)) if number(s).is_some_and(|m| numbers_equal(n, &m)) => {
+            *actual = expected.clone();
+        }
+        _ => (),
+    }
+}
+
+/// Compares the actual output of a test case with its expected output. The runner consults the
+/// matcher of the run after stripping the ignored fields and resolving the value matchers, so
+/// library users can substitute their own comparison logic (e.g. tolerating small differences of
+/// timestamps).
+pub trait Matcher: fmt::Debug + Send + Sync {
+    /// Lists the differences between the actual and the expected document, none if they match
+    fn differences(&self, actual: &Value, expected: &Value, mode: CompareMode) -> Vec<Difference>;
+}
+
+/// Requires the actual output to equal the expected output, except for the fields the
+/// [`CompareMode`] permits
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultMatcher;
+
+impl Matcher for DefaultMatcher {
+    fn differences(&self, actual: &Value, expected: &Value, mode: CompareMode) -> Vec<Difference> {
+        differences(actual, expected, mode)
+    }
+}
+
+/// An assertion embedded in an expected output file as a string of the form `{{kind:argument}}`,
+/// used in place of a literal value.
+#[derive(Debug, Clone)]
+pub enum ValueMatcher {
+    /// `{{regex:PATTERN}}` matches if the entire actual value matches the regular expression,
+    /// and `{{uuid}}` if it is a UUID
+    Regex(Regex),
+    /// `{{type:TYPE}}` matches if the actual value is present and of the given JSON type
+    /// (`string`, `number`, `integer`, `boolean`, `array`, `object` or `null`)
+    Type(String),
+}
+
+impl ValueMatcher {
+    /// Parses a matcher token, returning `None` if the string is not a token
+    pub fn parse(s: &str) -> Option<anyhow::Result<Self>> {
+        let token = s.strip_prefix(TOKEN_PREFIX)?.strip_suffix(TOKEN_SUFFIX)?;
+        if token == "uuid" {
+            return Some(
+                Regex::new(UUID_PATTERN)
+                    .map(ValueMatcher::Regex)
+                    .context("Compiling the UUID matcher"),
+            );
+        }
+        let (kind, argument) = token.split_once(':')?;
+        match kind {
+            "regex" => Some(
+                Regex::new(&format!("^(?:{argument})$"))
+                    .map(ValueMatcher::Regex)
+                    .with_context(|| format!("Compiling the regular expression matcher '{s}'")),
+            ),
+            "type" => Some(match argument {
+                "string" | "number" | "integer" | "boolean" | "array" | "object" | "null" => {
+                    Ok(ValueMatcher::Type(argument.to_string()))
+                }
+                _ => Err(anyhow::anyhow!("Unknown type of the matcher '{s}'")),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn matches(&self, actual: &Value) -> bool {
+        match self {
+            ValueMatcher::Regex(re) => match actual {
+                Value::String(s) => re.is_match(s),
+                Value::Number(n) => re.is_match(&n.to_string()),
+                Value::Bool(b) => re.is_match(&b.to_string()),
+                _ => false,
+            },
+            ValueMatcher::Type(kind) => match actual {
+                Value::String(_) => kind == "string",
+                Value::Number(n) => kind == "number" || (kind == "integer" && !n.is_f64()),
+                Value::Bool(_) => kind == "boolean",
+                Value::Array(_) => kind == "array",
+                Value::Object(_) => kind == "object",
+                Value::Null => kind == "null",
+            },
+        }
+    }
+}
+
+/// Replaces every matcher token in the expected document with the value found at the same
+/// location in the actual document, provided that the value satisfies the matcher. Tokens that
+/// are not satisfied are left in place, so that they show up in the subsequent comparison.
+#[instrument(skip_all)]
+pub fn resolve_matchers(actual: &Value, expected: &mut Value) -> anyhow::Result<()> {
+    if let Value::String(s) = expected {
+        if let Some(matcher) = ValueMatcher::parse(s) {
+            if matcher?.matches(actual) {
+                *expected = actual.clone();
+            }
+        }
+        return Ok(());
+    }
+
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => {
+            for (key, expected_value) in expected.iter_mut() {
+                if let Some(actual_value) = actual.get(key) {
+                    resolve_matchers(actual_value, expected_value)?;
+                }
+            }
+        }
+        (Value::Array(actual), Value::Array(expected)) => {
+            for (actual_value, expected_value) in actual.iter().zip(expected.iter_mut()) {
+                resolve_matchers(actual_value, expected_value)?;
+            }
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+/// The current date in UTC as `YYYY-MM-DD`, or that of the pinned clock
+pub fn current_date(clock: Option<&str>) -> String {
+    if let Some(date) = clock.and_then(|c| c.get(..10)) {
+        return date.to_string();
+    }
+    let days = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() / 86_400)
+        .unwrap_or_default() as i64;
+
+    // Convert the days since the epoch to a civil date (H. Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Parses an RFC 3339 timestamp (e.g. `2023-07-16T13:35:40.682+00:00`) into the number of
+/// nanoseconds since the epoch
+pub fn parse_instant(s: &str) -> Option<i128> {
+    let captures = INSTANT.captures(s)?;
+    let field = |i: usize| -> i128 {
+        captures
+            .get(i)
+            .and_then(|m| m.as_str().parse().ok())
+            .unwrap_or_default()
+    };
+    let (year, month, day) = (field(1), field(2), field(3));
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Convert the civil date to days since the epoch (H. Hinnant's algorithm)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let offset = match captures.get(9).map(|m| m.as_str()) {
+        Some("-") => -(field(10) * 3_600 + field(11) * 60),
+        Some(_) => field(10) * 3_600 + field(11) * 60,
+        None => 0,
+    };
+    let seconds = days * 86_400 + field(4) * 3_600 + field(5) * 60 + field(6) - offset;
+    let fraction = captures.get(7).map_or(0, |m| {
+        let digits: String = m
+            .as_str()
+            .chars()
+            .chain(std::iter::repeat('0'))
+            .take(9)
+            .collect();
+        digits.parse().unwrap_or_default()
+    });
+
+    Some(seconds * 1_000_000_000 + fraction)
+}
+
+/// Replaces the timestamp at each of the paths in the actual document with the one at the same
+/// path in the expected document if both denote the same instant, give or take the tolerance,
+/// regardless of their format (e.g. `Z` and `+00:00`). Timestamps that differ are left in place,
+/// so that the subsequent comparison reports them.
+#[instrument(skip(actual, expected))]
+pub fn canonicalize_timestamps(
+    actual: &mut Value,
+    expected: &Value,
+    paths: &[FieldPath],
+    tolerance: Duration,
+) {
+    for path in paths {
+        let (Some(actual_value), Some(expected_value)) = (path.get_mut(actual), path.get(expected))
+        else {
+            continue;
+        };
+        let instants = actual_value
+            .as_str()
+            .and_then(parse_instant)
+            .zip(expected_value.as_str().and_then(parse_instant));
+        if let Some((actual_instant, expected_instant)) = instants {
+            if actual_instant.abs_diff(expected_instant) <= tolerance.as_nanos() {
+                *actual_value = expected_value.clone();
+            }
+        }
+    }
+}
+
+/// Replaces the string at each of the paths in the actual document (or each string element of an
+/// array) with the one at the same path in the expected document if they only differ in case
+#[instrument(skip(actual, expected))]
+pub fn fold_case(actual: &mut Value, expected: &Value, paths: &[FieldPath]) {
+    fn fold(actual: &mut Value, expected: &Value) {
+        match (&mut *actual, expected) {
+            (Value::String(a), Value::String(e)) if a.to_lowercase() == e.to_lowercase() => {
+                *actual = expected.clone();
+            }
+            (Value::Array(actual_items), Value::Array(expected_items)) => {
+                for (a, e) in actual_items.iter_mut().zip(expected_items) {
+                    fold(a, e);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    for path in paths {
+        if let (Some(actual_value), Some(expected_value)) =
+            (path.get_mut(actual), path.get(expected))
+        {
+            fold(actual_value, expected_value);
+        }
+    }
+}
+
+/// Looks up a field of an input event by its dotted path (e.g. `url.path`). Raw text input
+/// events have the single field `message`.
+fn input_field<'a>(input: &'a Value, path: &str) -> Option<&'a Value> {
+    if let Value::String(_) = input {
+        return (path == "message").then_some(input);
+    }
+    path.split('.')
+        .try_fold(input, |value, segment| match value {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => value.get(segment),
+        })
+}
+
+/// Replaces the placeholders in the strings of an expected output event: `{{today}}` (or
+/// `{{today:FORMAT}}` with `%Y`, `%m` and `%d`) with the given date (`YYYY-MM-DD`), and
+/// `{{input.FIELD}}` with a field of the given input event. A string that consists of a single
+/// input placeholder is replaced with the value of the field, whatever its type. Placeholders that
+/// cannot be resolved are left in place, so that they show up in the comparison.
+pub fn render_placeholders(expected: &mut Value, today: &str, input: Option<&Value>) {
+    match expected {
+        Value::String(s) => {
+            if let Some(path) = s
+                .strip_prefix("{{input.")
+                .and_then(|p| p.strip_suffix(TOKEN_SUFFIX))
+                .filter(|p| !p.contains(TOKEN_SUFFIX))
+            {
+                if let Some(value) = input.and_then(|i| input_field(i, path)) {
+                    *expected = value.clone();
+                }
+                return;
+            }
+            let rendered = PLACEHOLDER.replace_all(s, |captures: &regex::Captures| {
+                let token = &captures[1];
+                if let Some(path) = token.strip_prefix("input.") {
+                    return match input.and_then(|i| input_field(i, path)) {
+                        Some(Value::String(value)) => value.clone(),
+                        Some(value) => value.to_string(),
+                        None => captures[0].to_string(),
+                    };
+                }
+                let format = token.strip_prefix("today:").unwrap_or("%Y-%m-%d");
+                let (year, rest) = today.split_once('-').unwrap_or_default();
+                let (month, day) = rest.split_once('-').unwrap_or_default();
+                format
+                    .replace("%Y", year)
+                    .replace("%m", month)
+                    .replace("%d", day)
+            });
+            *s = rendered.into_owned();
+        }
+        Value::Array(values) => {
+            for value in values {
+                render_placeholders(value, today, input);
+            }
+        }
+        Value::Object(fields) => {
+            for value in fields.values_mut() {
+                render_placeholders(value, today, input);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Reorders the actual output events such that each one lines up with an expected event it
+/// matches according to the matcher, if possible. Events without a counterpart keep their relative order and fill the
+/// remaining positions, so that the subsequent comparison reports them.
+#[instrument(skip(actual, expected))]
+pub fn align_unordered(
+    actual: &mut Vec<Value>,
+    expected: &[Value],
+    mode: CompareMode,
+    matcher: &dyn Matcher,
+) -> anyhow::Result<()> {
+    align_by(actual, expected, |actual_event, expected_event| {
+        let mut resolved = expected_event.clone();
+        resolve_matchers(actual_event, &mut resolved)?;
+        Ok(matcher
+            .differences(actual_event, &resolved, mode)
+            .is_empty())
+    })
+}
+
+/// Reorders the actual events as [`align_unordered`] does, but pairs an actual with an expected
+/// event whenever the predicate holds for them
+pub fn align_by(
+    actual: &mut Vec<Value>,
+    expected: &[Value],
+    matches: impl Fn(&Value, &Value) -> anyhow::Result<bool>,
+) -> anyhow::Result<()> {
+    let mut candidates: Vec<Vec<usize>> = Vec::with_capacity(expected.len());
+    for expected_event in expected {
+        let mut matching = Vec::new();
+        for (j, actual_event) in actual.iter().enumerate() {
+            if matches(actual_event, expected_event)? {
+                matching.push(j);
+            }
+        }
+        candidates.push(matching);
+    }
+
+    // Find a maximum bipartite matching of expected to actual events (Kuhn's algorithm)
+    let mut assigned: Vec<Option<usize>> = vec![None; actual.len()];
+    for i in 0..expected.len() {
+        let mut visited = vec![false; actual.len()];
+        augment(i, &candidates, &mut assigned, &mut visited);
+    }
+
+    let mut slots: Vec<Option<Value>> = vec![None; expected.len().max(actual.len())];
+    let mut leftovers = Vec::new();
+    for (j, event) in actual.drain(..).enumerate() {
+        match assigned[j] {
+            Some(i) => slots[i] = Some(event),
+            None => leftovers.push(event),
+        }
+    }
+    let mut leftovers = leftovers.into_iter();
+    actual.extend(
+        slots
+            .into_iter()
+            .filter_map(|slot| slot.or_else(|| leftovers.next())),
+    );
+
+    Ok(())
+}
+
+/// Reorders the elements of the actual array at each of the paths such that they line up with the
+/// expected elements they match, as [`align_unordered`] does for events. With `contains`, the
+/// actual elements beyond the expected ones are dropped, so that the actual array only needs to
+/// contain the expected elements.
+#[instrument(skip(actual, expected))]
+pub fn align_arrays(
+    actual: &mut Value,
+    expected: &Value,
+    paths: &[FieldPath],
+    contains: bool,
+    mode: CompareMode,
+    matcher: &dyn Matcher,
+) -> anyhow::Result<()> {
+    for path in paths {
+        if let (Some(Value::Array(actual_items)), Some(Value::Array(expected_items))) =
+            (path.get_mut(actual), path.get(expected))
+        {
+            align_unordered(actual_items, expected_items, mode, matcher)
+                .with_context(|| format!("Aligning the elements of the array {path}"))?;
+            if contains {
+                actual_items.truncate(expected_items.len());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn augment(
+    i: usize,
+    candidates: &[Vec<usize>],
+    assigned: &mut [Option<usize>],
+    visited: &mut [bool],
+) -> bool {
+    for &j in &candidates[i] {
+        if visited[j] {
+            continue;
+        }
+        visited[j] = true;
+        if assigned[j].is_none_or(|k| augment(k, candidates, assigned, visited)) {
+            assigned[j] = Some(i);
+            return true;
+        }
+    }
+
+    false
+}
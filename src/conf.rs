@@ -0,0 +1,357 @@
+use std::fmt;
+
+use anyhow::anyhow;
+
+/// A token of a Logstash configuration
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    Open,
+    Close,
+    OpenBracket,
+    CloseBracket,
+    Comma,
+    /// A quoted string without its quotes, and the quote character
+    Str(String, char),
+    /// A bare word, number, operator or regular expression
+    Word(String),
+    /// A comment, including its leading `#`
+    Comment(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "{{"),
+            Token::Close => write!(f, "}}"),
+            Token::OpenBracket => write!(f, "["),
+            Token::CloseBracket => write!(f, "]"),
+            Token::Comma => write!(f, ","),
+            Token::Str(s, quote) => write!(f, "{quote}{s}{quote}"),
+            Token::Word(w) | Token::Comment(w) => write!(f, "{w}"),
+        }
+    }
+}
+
+/// Splits a Logstash configuration into tokens, each with its line number. Regular expressions
+/// following `=~` or `!~` are kept as a single word.
+pub(crate) fn tokenize(text: &str) -> anyhow::Result<Vec<(Token, usize)>> {
+    Ok(spanned_tokens(text)?
+        .into_iter()
+        .map(|(token, line, _)| (token, line))
+        .collect())
+}
+
+/// Splits a Logstash configuration into tokens, each with its line number and the byte offset at
+/// which it starts
+pub(crate) fn spanned_tokens(text: &str) -> anyhow::Result<Vec<(Token, usize, usize)>> {
+    let mut tokens: Vec<(Token, usize, usize)> = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    let mut line = 1;
+    while let Some((offset, c)) = chars.next() {
+        let token = match c {
+            '\n' => {
+                line += 1;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '#' => {
+                let mut comment = String::from('#');
+                while let Some((_, ch)) = chars.next_if(|(_, c)| *c != '\n') {
+                    comment.push(ch);
+                }
+                Token::Comment(comment.trim_end().to_string())
+            }
+            '{' => Token::Open,
+            '}' => Token::Close,
+            '[' => Token::OpenBracket,
+            ']' => Token::CloseBracket,
+            ',' => Token::Comma,
+            '"' | '\'' => {
+                let start = line;
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => {
+                            s.push('\\');
+                            s.extend(chars.next().map(|(_, ch)| ch));
+                        }
+                        Some((_, q)) if q == c => break,
+                        Some((_, ch)) => {
+                            if ch == '\n' {
+                                line += 1;
+                            }
+                            s.push(ch);
+                        }
+                        None => {
+                            return Err(anyhow!("Unterminated string starting at line {start}"))
+                        }
+                    }
+                }
+                tokens.push((Token::Str(s, c), start, offset));
+                continue;
+            }
+            '/' if matches!(tokens.last(), Some((Token::Word(w), _, _)) if w == "=~" || w == "!~") =>
+            {
+                let mut w = String::from('/');
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => {
+                            w.push('\\');
+                            w.extend(chars.next().map(|(_, ch)| ch));
+                        }
+                        Some((_, '/')) => break,
+                        Some((_, ch)) => w.push(ch),
+                        None => {
+                            return Err(anyhow!("Unterminated regular expression at line {line}"))
+                        }
+                    }
+                }
+                w.push('/');
+                Token::Word(w)
+            }
+            c => {
+                let mut w = String::from(c);
+                while let Some((_, ch)) = chars.next_if(|(_, ch)| {
+                    !ch.is_whitespace()
+                        && !matches!(ch, '{' | '}' | '[' | ']' | ',' | '"' | '\'' | '#')
+                }) {
+                    w.push(ch);
+                }
+                Token::Word(w)
+            }
+        };
+        tokens.push((token, line, offset));
+    }
+    Ok(tokens)
+}
+
+/// The value of a plugin setting
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ConfValue {
+    Str(String),
+    Word(String),
+    Array(Vec<ConfValue>),
+    Hash(Vec<(String, ConfValue, usize)>),
+}
+
+impl ConfValue {
+    /// All strings contained in the value, including nested ones
+    pub(crate) fn strings(&self) -> Vec<&str> {
+        match self {
+            ConfValue::Str(s) | ConfValue::Word(s) => vec![s],
+            ConfValue::Array(values) => values.iter().flat_map(|v| v.strings()).collect(),
+            ConfValue::Hash(entries) => entries.iter().flat_map(|(_, v, _)| v.strings()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Setting {
+    pub(crate) name: String,
+    pub(crate) value: ConfValue,
+    pub(crate) line: usize,
+}
+
+/// A plugin or a conditional within a section
+#[derive(Debug, Clone)]
+pub(crate) enum Item {
+    Plugin {
+        name: String,
+        settings: Vec<Setting>,
+    },
+    /// An `if`, followed by any `else if` and `else` branches. The condition of an `else` branch
+    /// is `None`.
+    Conditional(Vec<(Option<String>, Vec<Item>, usize)>),
+}
+
+/// A top-level section of a Logstash configuration (i.e. `input`, `filter` or `output`)
+#[derive(Debug, Clone)]
+pub(crate) struct Section {
+    pub(crate) kind: String,
+    pub(crate) items: Vec<Item>,
+    pub(crate) line: usize,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, l)| *l)
+    }
+
+    fn next(&mut self) -> anyhow::Result<Token> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .map(|(t, _)| t.clone())
+            .ok_or_else(|| anyhow!("Unexpected end of file"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> anyhow::Result<()> {
+        let line = self.line();
+        match self.next()? {
+            t if t == expected => Ok(()),
+            t => Err(anyhow!(
+                "Expected '{expected}' but found '{t}' at line {line}"
+            )),
+        }
+    }
+
+    fn word(&mut self) -> anyhow::Result<String> {
+        let line = self.line();
+        match self.next()? {
+            Token::Word(w) | Token::Str(w, _) => Ok(w),
+            t => Err(anyhow!("Expected a name but found '{t}' at line {line}")),
+        }
+    }
+
+    fn sections(&mut self) -> anyhow::Result<Vec<Section>> {
+        let mut sections = Vec::new();
+        while self.peek().is_some() {
+            let line = self.line();
+            let kind = self.word()?;
+            self.expect(Token::Open)?;
+            let items = self.items()?;
+            sections.push(Section { kind, items, line });
+        }
+        Ok(sections)
+    }
+
+    /// Parses plugins and conditionals up to and including the closing brace
+    fn items(&mut self) -> anyhow::Result<Vec<Item>> {
+        let mut items = Vec::new();
+        loop {
+            let line = self.line();
+            match self.next()? {
+                Token::Close => return Ok(items),
+                Token::Word(w) if w == "if" => items.push(self.conditional(line)?),
+                Token::Word(name) => {
+                    self.expect(Token::Open)?;
+                    let settings = self.settings()?;
+                    items.push(Item::Plugin { name, settings });
+                }
+                t => return Err(anyhow!("Unexpected '{t}' at line {line}")),
+            }
+        }
+    }
+
+    fn conditional(&mut self, line: usize) -> anyhow::Result<Item> {
+        let condition = self.condition()?;
+        let mut branches = vec![(Some(condition), self.items()?, line)];
+        while matches!(self.peek(), Some(Token::Word(w)) if w == "else") {
+            let line = self.line();
+            self.pos += 1;
+            let condition = match self.peek() {
+                Some(Token::Word(w)) if w == "if" => {
+                    self.pos += 1;
+                    Some(self.condition()?)
+                }
+                _ => {
+                    self.expect(Token::Open)?;
+                    None
+                }
+            };
+            branches.push((condition, self.items()?, line));
+        }
+        Ok(Item::Conditional(branches))
+    }
+
+    /// Reads a condition up to and including the opening brace of its block
+    fn condition(&mut self) -> anyhow::Result<String> {
+        let mut parts = Vec::new();
+        let mut depth = 0;
+        loop {
+            match self.next()? {
+                Token::Open if depth == 0 => return Ok(parts.join(" ")),
+                t => {
+                    match t {
+                        Token::OpenBracket => depth += 1,
+                        Token::CloseBracket => depth -= 1,
+                        _ => (),
+                    }
+                    parts.push(t.to_string());
+                }
+            }
+        }
+    }
+
+    /// Parses the settings of a plugin up to and including the closing brace
+    fn settings(&mut self) -> anyhow::Result<Vec<Setting>> {
+        let mut settings = Vec::new();
+        loop {
+            if self.peek() == Some(&Token::Close) {
+                self.pos += 1;
+                return Ok(settings);
+            }
+            let line = self.line();
+            let name = self.word()?;
+            self.expect(Token::Word(String::from("=>")))?;
+            let value = self.value()?;
+            settings.push(Setting { name, value, line });
+        }
+    }
+
+    fn value(&mut self) -> anyhow::Result<ConfValue> {
+        let line = self.line();
+        match self.next()? {
+            Token::Str(s, _) => Ok(ConfValue::Str(s)),
+            Token::Word(w) => Ok(ConfValue::Word(w)),
+            Token::OpenBracket => {
+                let mut values = Vec::new();
+                loop {
+                    match self.peek() {
+                        Some(Token::CloseBracket) => {
+                            self.pos += 1;
+                            return Ok(ConfValue::Array(values));
+                        }
+                        Some(Token::Comma) => self.pos += 1,
+                        _ => values.push(self.value()?),
+                    }
+                }
+            }
+            Token::Open => {
+                let mut entries = Vec::new();
+                loop {
+                    match self.peek() {
+                        Some(Token::Close) => {
+                            self.pos += 1;
+                            return Ok(ConfValue::Hash(entries));
+                        }
+                        Some(Token::Comma) => self.pos += 1,
+                        _ => {
+                            let line = self.line();
+                            let key = self.word()?;
+                            self.expect(Token::Word(String::from("=>")))?;
+                            entries.push((key, self.value()?, line));
+                        }
+                    }
+                }
+            }
+            t => Err(anyhow!("Unexpected '{t}' at line {line}")),
+        }
+    }
+}
+
+/// Parses a Logstash configuration, disregarding its comments
+pub(crate) fn parse(text: &str) -> anyhow::Result<Vec<Section>> {
+    Parser {
+        tokens: tokenize(text)?
+            .into_iter()
+            .filter(|(t, _)| !matches!(t, Token::Comment(_)))
+            .collect(),
+        pos: 0,
+    }
+    .sections()
+}
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::compare::{CompareMode, Normalization};
+use crate::docker::{InputProtocol, SyslogFormat};
+use crate::hooks::Hooks;
+use crate::logs::LogPattern;
+use crate::paths::FieldPath;
+use crate::stats::StatsAssertions;
+use crate::{CONFIG_FILE, METADATA_FILE};
+
+/// Project-wide settings, read from the optional file `lotus.toml` in the target directory.
+/// Command line arguments take precedence over or extend these settings.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Fields stripped from the actual and expected output of every test case before comparison
+    pub ignore: Vec<FieldPath>,
+    /// How strictly the actual output of every test case is compared with the expected output,
+    /// unless overridden by the test case
+    pub compare: Option<CompareMode>,
+    /// Fields compared as instants rather than strings (e.g. `@timestamp`), so that the format of
+    /// equal timestamps does not matter
+    pub timestamps: Vec<FieldPath>,
+    /// The maximum difference in milliseconds between timestamps that are considered equal
+    pub timestamp_tolerance: Option<u64>,
+    /// Consider strings that encode a number equal to that number (e.g. `"42"` and `42`)
+    pub coerce_numbers: bool,
+    /// Fields whose strings are compared regardless of case (e.g. `http.request.method`)
+    pub case_insensitive: Vec<FieldPath>,
+    /// The Logstash codec that raw text input events (e.g. from `input.txt`) are decoded with
+    pub input_codec: Option<String>,
+    /// The protocol over which the input events are sent to Logstash (`http`, `beats`, `tcp`,
+    /// `udp`, `syslog-udp` or `syslog-tcp`)
+    pub input_protocol: Option<InputProtocol>,
+    /// The codec of the `tcp` or `udp` input
+    pub socket_codec: Option<String>,
+    /// The format of the messages that the `syslog` input parses (`rfc3164` or `rfc5424`)
+    pub syslog_format: Option<SyslogFormat>,
+    /// The maximum time in seconds to wait for Logstash to accept each input event and to send
+    /// each output event
+    pub test_timeout: Option<u64>,
+    /// How often the health status of each Logstash container is checked before giving up
+    pub health_retries: Option<usize>,
+    /// The time in seconds between two checks of the health status of each Logstash container
+    pub health_interval: Option<u64>,
+    /// The maximum time in seconds to wait for Logstash to start
+    pub startup_timeout: Option<u64>,
+    /// The version of Logstash the test cases run against (i.e. the tag of the official Logstash
+    /// image)
+    pub logstash_version: Option<String>,
+    /// An image that replaces the official Logstash image as the base of the container image,
+    /// taking precedence over the Logstash version
+    pub base_image: Option<String>,
+    /// The digest the base image is pinned to (e.g. `sha256:…`), which is verified before the
+    /// container image is built
+    pub base_image_digest: Option<String>,
+    /// The platform the images are built for and the containers run on (e.g. `linux/amd64`),
+    /// instead of that of the container runtime
+    pub platform: Option<String>,
+    /// Logstash plugins that are not bundled with Logstash, installed into the container image
+    /// (e.g. `logstash-filter-tld`)
+    pub plugins: Vec<String>,
+    /// Additional output channels that events are routed to by conditions, in addition to the
+    /// default one
+    pub outputs: Vec<OutputChannel>,
+    /// Send the output events to an Elasticsearch output instead, whose bulk requests Lotus
+    /// receives as a mock Elasticsearch cluster
+    pub elasticsearch: Option<ElasticsearchOutput>,
+    /// Include the `@metadata` of the output events in the actual output, except that of Lotus
+    pub capture_metadata: bool,
+    /// The instant (e.g. `2024-01-01T00:00:00Z`) that `@timestamp` of the input events and
+    /// `[event][created]` of the output events are pinned to
+    pub clock: Option<String>,
+    /// Also write the output events with a file output, whose files assertions can check
+    pub file_output: Option<FileOutput>,
+    /// Assertions on what the plugins of the pipeline did over the whole run
+    pub stats: StatsAssertions,
+    /// Regular expressions of lines in the output of Logstash (e.g. `\[WARN`) that fail the run
+    /// if logged while the test cases run
+    pub fail_on_log: Vec<LogPattern>,
+    /// The host port under which the HTTP input of Logstash is published
+    pub input_port: Option<u16>,
+    /// The port on which Lotus receives the output events of Logstash
+    pub output_port: Option<u16>,
+    /// The host port under which the API of Logstash is published
+    pub api_port: Option<u16>,
+    /// Options of the Java virtual machine running Logstash (e.g. `-Xmx512m`)
+    pub java_opts: Option<String>,
+    /// The number of workers of the pipeline, which then does not preserve the order of events
+    pub workers: Option<NonZeroUsize>,
+    /// Environment variables of the Logstash container, substituted into the pipeline where it
+    /// refers to them (e.g. `${MY_VAR}`)
+    pub env: BTreeMap<String, String>,
+    /// Shell commands or priming events run before and after all or each of the test cases
+    pub hooks: Hooks,
+}
+
+/// An output channel of the pipeline: the output events that satisfy its condition (and no
+/// condition of an earlier channel) arrive on it instead of the default channel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputChannel {
+    /// The name of the channel, recorded in each output event that arrived on it
+    pub name: String,
+    /// A Logstash condition (e.g. `"error" in [tags]`)
+    pub condition: String,
+}
+
+/// The settings of the Elasticsearch output of the pipeline, as in production. Each output event
+/// records the action, target index (or data stream), document ID, routing and ingest pipeline of
+/// its bulk operation in the field `@lotus_elasticsearch`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ElasticsearchOutput {
+    /// The index the documents are written to (e.g. `logs-%{+YYYY.MM.dd}`)
+    pub index: Option<String>,
+    /// The action of the bulk operations (e.g. `create` or `%{[@metadata][action]}`)
+    pub action: Option<String>,
+    /// The ID of the documents (e.g. `%{[@metadata][fingerprint]}`)
+    pub document_id: Option<String>,
+    /// The routing of the documents
+    pub routing: Option<String>,
+    /// The ingest pipeline that Elasticsearch runs the documents through
+    pub pipeline: Option<String>,
+    /// Write the documents to a data stream instead of an index
+    pub data_stream: bool,
+    /// The type of the data stream (e.g. `logs`)
+    pub data_stream_type: Option<String>,
+    /// The dataset of the data stream (e.g. `nginx.access`)
+    pub data_stream_dataset: Option<String>,
+    /// The namespace of the data stream (e.g. `production`)
+    pub data_stream_namespace: Option<String>,
+}
+
+/// The settings of the file output of the pipeline, as in production. The files each test case
+/// causes are captured separately, and assertions with a `file` check their contents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileOutput {
+    /// The path of the files, relative to the capture directory (e.g.
+    /// `%{[service]}/%{+YYYY-MM-dd}.log`)
+    pub path: String,
+    /// The codec the events are written with (e.g. `line { format => "%{message}" }`)
+    /// [default: json_lines]
+    #[serde(default)]
+    pub codec: Option<String>,
+}
+
+impl ProjectConfig {
+    #[instrument]
+    pub fn load(target: &Path) -> anyhow::Result<Self> {
+        load_toml(&target.join(CONFIG_FILE))
+    }
+}
+
+/// Per-test settings, read from the optional file `test.toml` in a test case directory.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TestMetadata {
+    /// A human-readable name, displayed instead of the name of the test case directory
+    pub name: Option<String>,
+    /// A description of what the test case verifies
+    pub description: Option<String>,
+    /// Arbitrary labels used to group test cases (e.g. `slow` or `grok`)
+    pub tags: Vec<String>,
+    /// The maximum time in seconds to wait for Logstash to accept each input event and to send
+    /// each output event of this test case
+    pub timeout: Option<u64>,
+    /// How strictly the actual output is compared with the expected output, instead of the mode
+    /// of the run
+    pub compare: Option<CompareMode>,
+    /// Strip only the fields of `ignore`, instead of those of the run as well
+    pub replace_ignore: bool,
+    /// Fields compared as instants rather than strings, in addition to those of the run
+    pub timestamps: Vec<FieldPath>,
+    /// The maximum difference in milliseconds between timestamps that are considered equal,
+    /// instead of that of the run
+    pub timestamp_tolerance: Option<u64>,
+    /// Whether strings that encode a number are considered equal to that number, instead of the
+    /// setting of the run
+    pub coerce_numbers: Option<bool>,
+    /// Fields whose strings are compared regardless of case, in addition to those of the run
+    pub case_insensitive: Vec<FieldPath>,
+    /// Compare the expected output literally, without rendering placeholders or resolving value
+    /// matchers (e.g. for snapshots whose values look like them)
+    pub literal: bool,
+    /// Compare with the default matcher, even if the run substitutes a matcher of its own
+    pub default_matcher: bool,
+    /// How strictly the fields at the given paths of each event are compared, instead of
+    /// `compare` (e.g. `{ event = "strict" }`)
+    pub compare_paths: BTreeMap<FieldPath, CompareMode>,
+    /// Fields stripped from the actual and expected output of this test case before comparison
+    pub ignore: Vec<FieldPath>,
+    /// If the expected output is an array of events, match the actual output events regardless
+    /// of the order in which they arrive
+    pub unordered: bool,
+    /// Arrays whose elements are compared regardless of their order (e.g. `tags`)
+    pub unordered_arrays: Vec<FieldPath>,
+    /// Arrays that only need to contain the expected elements, in any order
+    pub contains_arrays: Vec<FieldPath>,
+    /// Mark the test case as expected to fail (e.g. due to a known bug in the pipeline). The run
+    /// fails if the test case passes unexpectedly.
+    pub xfail: bool,
+    /// The Logstash codec that raw text input events of this test case are decoded with
+    pub codec: Option<String>,
+    /// The stages that the actual and the expected output of this test case pass through in
+    /// order, instead of a single comparison
+    pub stages: Vec<CompareStage>,
+    /// Fields that every actual output event of this test case must contain
+    pub must_exist: Vec<FieldPath>,
+    /// Fields that no actual output event of this test case may contain (e.g. personal data
+    /// scrubbed by the pipeline)
+    pub must_not_exist: Vec<FieldPath>,
+}
+
+/// A step of the comparison of the actual with the expected output of a test case
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum CompareStage {
+    /// Strips fields from the actual and the expected output
+    Ignore { paths: Vec<FieldPath> },
+    /// Transforms fields of the actual and the expected output
+    Normalize {
+        paths: Vec<FieldPath>,
+        transform: Normalization,
+    },
+    /// Validates each actual output event against a JSON Schema file, relative to the test case
+    /// directory
+    Schema { file: PathBuf },
+    /// Compares the actual with the expected output, in the mode of the test case unless given
+    Diff {
+        #[serde(default)]
+        compare: Option<CompareMode>,
+    },
+}
+
+impl TestMetadata {
+    #[instrument]
+    pub fn load(test_case_dir: &Path) -> anyhow::Result<Self> {
+        load_toml(&test_case_dir.join(METADATA_FILE))
+    }
+}
+
+fn load_toml<T: Default + for<'de> Deserialize<'de>>(path: &Path) -> anyhow::Result<T> {
+    if !path.is_file() {
+        return Ok(T::default());
+    }
+
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("Reading the file: {}", path.display()))?;
+    toml::from_str(&data).with_context(|| format!("Parsing the file: {}", path.display()))
+}
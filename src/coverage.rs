@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::conf::{parse, spanned_tokens, Token};
+use crate::stats::PluginStats;
+use crate::COVERAGE_ID_PREFIX;
+
+/// A part of a rule file that the test cases may exercise: the body of a filter section or of a
+/// conditional branch within it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CoveragePoint {
+    /// The ID of the marker plugin injected at the start of the body
+    #[serde(skip)]
+    pub id: String,
+    /// The file name of the rule
+    pub rule: String,
+    /// The line on which the section or branch starts
+    pub line: usize,
+    /// The section (`filter`) or the branch (e.g. `else if [status] >= 500`)
+    pub branch: String,
+}
+
+/// The name under which a rule file is reported
+pub fn rule_name(rule: &Path) -> String {
+    rule.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| rule.display().to_string())
+}
+
+/// What a block opened by a brace in a rule file is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    Section {
+        filter: bool,
+    },
+    Branch,
+    /// The body of a plugin, or a hash within it
+    Other,
+}
+
+/// Reads a condition from the token at `start` up to the opening brace of its block, and returns
+/// it with the position of the brace
+fn condition(tokens: &[(Token, usize, usize)], start: usize) -> (String, usize) {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut i = start;
+    while let Some((token, _, _)) = tokens.get(i) {
+        match token {
+            Token::Open if depth == 0 => break,
+            Token::OpenBracket => depth += 1,
+            Token::CloseBracket => depth -= 1,
+            _ => (),
+        }
+        parts.push(token.to_string());
+        i += 1;
+    }
+    (parts.join(" "), i)
+}
+
+/// Injects a marker plugin (a `mutate` filter without operations and with an ID of its own) at
+/// the start of each filter section and each conditional branch within it, so that the node
+/// stats API of Logstash reports which of them events passed through. Returns the instrumented
+/// rule with the same line numbers, and the injected coverage points.
+pub fn instrument_rule(
+    text: &str,
+    rule_index: usize,
+    rule_name: &str,
+) -> anyhow::Result<(String, Vec<CoveragePoint>)> {
+    parse(text)?;
+    let tokens: Vec<_> = spanned_tokens(text)?
+        .into_iter()
+        .filter(|(t, _, _)| !matches!(t, Token::Comment(_)))
+        .collect();
+
+    let mut points = Vec::new();
+    let mut insertions = Vec::new();
+    let mut stack: Vec<Block> = Vec::new();
+    let mut pending: Option<(Block, String, usize)> = None;
+    let mut i = 0;
+    while let Some((token, line, offset)) = tokens.get(i) {
+        let item_level = matches!(
+            stack.last(),
+            None | Some(Block::Section { .. } | Block::Branch)
+        );
+        match token {
+            Token::Open => {
+                let (block, branch, line) =
+                    pending
+                        .take()
+                        .unwrap_or((Block::Other, String::new(), *line));
+                let in_filter = match (block, stack.first()) {
+                    (Block::Section { filter }, _) => filter,
+                    (Block::Branch, Some(Block::Section { filter })) => *filter,
+                    _ => false,
+                };
+                if in_filter {
+                    let id = format!("{COVERAGE_ID_PREFIX}{rule_index}-{}", points.len());
+                    insertions.push((offset + 1, format!(" mutate {{ id => \"{id}\" }}")));
+                    points.push(CoveragePoint {
+                        id,
+                        rule: rule_name.to_string(),
+                        line,
+                        branch,
+                    });
+                }
+                stack.push(block);
+            }
+            Token::Close => {
+                stack.pop();
+            }
+            Token::Word(w) if item_level && pending.is_none() => {
+                let (block, branch, next) = match (stack.is_empty(), w.as_str()) {
+                    (true, kind) => (
+                        Block::Section {
+                            filter: kind == "filter",
+                        },
+                        kind.to_string(),
+                        i + 1,
+                    ),
+                    (false, "if") => {
+                        let (condition, end) = condition(&tokens, i + 1);
+                        (Block::Branch, format!("if {condition}"), end)
+                    }
+                    (false, "else") => match tokens.get(i + 1) {
+                        Some((Token::Word(w), _, _)) if w == "if" => {
+                            let (condition, end) = condition(&tokens, i + 2);
+                            (Block::Branch, format!("else if {condition}"), end)
+                        }
+                        _ => (Block::Branch, String::from("else"), i + 1),
+                    },
+                    (false, _) => (Block::Other, String::new(), i + 1),
+                };
+                pending = Some((block, branch, *line));
+                i = next;
+                continue;
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+
+    let mut instrumented = String::from(text);
+    for (offset, marker) in insertions.into_iter().rev() {
+        instrumented.insert_str(offset, &marker);
+    }
+    Ok((instrumented, points))
+}
+
+/// A coverage point and the number of events that passed through it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CoveredPoint {
+    #[serde(flatten)]
+    pub point: CoveragePoint,
+    pub events: u64,
+}
+
+/// Which parts of the rules the test cases exercised
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CoverageReport {
+    pub points: Vec<CoveredPoint>,
+}
+
+impl CoverageReport {
+    /// Counts the events that passed through each coverage point between the two snapshots of
+    /// the plugin counters
+    pub fn new(
+        points: &[CoveragePoint],
+        before: &BTreeMap<String, PluginStats>,
+        after: &BTreeMap<String, PluginStats>,
+    ) -> Self {
+        let events = |id: &str| {
+            let count =
+                |stats: &BTreeMap<String, PluginStats>| stats.get(id).map_or(0, |s| s.events_in);
+            count(after).saturating_sub(count(before))
+        };
+        CoverageReport {
+            points: points
+                .iter()
+                .map(|point| CoveredPoint {
+                    point: point.clone(),
+                    events: events(&point.id),
+                })
+                .collect(),
+        }
+    }
+
+    /// The coverage points no event passed through
+    pub fn untested(&self) -> impl Iterator<Item = &CoveragePoint> {
+        self.points
+            .iter()
+            .filter(|p| p.events == 0)
+            .map(|p| &p.point)
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let untested: Vec<_> = self.untested().collect();
+        let exercised = self.points.len() - untested.len();
+        write!(
+            f,
+            "coverage: {} of {} sections and branches exercised",
+            exercised,
+            self.points.len()
+        )?;
+        if !self.points.is_empty() {
+            write!(
+                f,
+                " ({:.0}%)",
+                exercised as f64 * 100.0 / self.points.len() as f64
+            )?;
+        }
+        for point in untested {
+            write!(
+                f,
+                "\n    untested  {}:{}  {}",
+                point.rule, point.line, point.branch
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, Context};
+use clap::Args;
+use serde_json::Value;
+use tracing::{debug, instrument};
+
+use crate::collectors::collect_rules;
+use crate::compare::{differences, CompareMode, Difference};
+
+#[derive(Debug, Args)]
+pub struct DiffArguments {
+    /// The git revision whose rules the working tree is compared with (e.g. `main`)
+    #[arg(default_value = "HEAD")]
+    pub git_ref: String,
+}
+
+/// The outputs of a test case that differ between the rules at a git revision and the rules of
+/// the working tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputDiff {
+    /// The name displayed for the test case
+    pub title: String,
+    /// The changes from the output of the git revision to that of the working tree, or why the
+    /// test case could not run against either of them
+    pub changes: Result<Vec<Difference>, String>,
+}
+
+/// Writes the rule files of the rules directory (relative to the project directory) at a git
+/// revision to the given directory, and returns their paths
+#[instrument]
+pub fn checkout_rules(
+    target: &Path,
+    rules_dir: &str,
+    git_ref: &str,
+    dest: &Path,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let git = |args: &[&str]| -> anyhow::Result<Vec<u8>> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(target)
+            .args(args)
+            .output()
+            .context("Running git")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(output.stdout)
+    };
+
+    debug!("List the rule files at {git_ref}");
+    let listing = git(&[
+        "ls-tree",
+        "-r",
+        "-z",
+        "--name-only",
+        git_ref,
+        "--",
+        rules_dir,
+    ])?;
+    let names: Vec<String> = listing
+        .split(|b| *b == 0)
+        .filter(|n| !n.is_empty())
+        .map(|n| String::from_utf8_lossy(n).into_owned())
+        .collect();
+
+    for name in &names {
+        // Names are relative to the project directory, and so are the rules in the destination
+        let Some(relative) = Path::new(name).strip_prefix(rules_dir).ok() else {
+            continue;
+        };
+        let path = dest.join(relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Creating the directory: {}", parent.display()))?;
+        }
+        let data = git(&["show", &format!("{git_ref}:./{name}")])?;
+        std::fs::write(&path, data)
+            .with_context(|| format!("Writing the rule file: {}", path.display()))?;
+    }
+
+    let rules = collect_rules(dest).context("Collecting the rules of the git revision")?;
+    if rules.is_empty() {
+        return Err(anyhow!("No rules were found at the git revision {git_ref}"));
+    }
+    Ok(rules)
+}
+
+/// The changes from the output events of the git revision (`before`) to those of the working
+/// tree (`after`)
+pub fn output_changes(before: Vec<Value>, after: Vec<Value>) -> Vec<Difference> {
+    differences(
+        &Value::Array(after),
+        &Value::Array(before),
+        CompareMode::Strict,
+    )
+}
+
+/// Describes a change of an output event from the git revision to the working tree
+pub fn describe_change(difference: &Difference) -> String {
+    let path = |p: &str| match p {
+        "" => String::from("(root)"),
+        p => p.to_string(),
+    };
+    match difference {
+        Difference::Missing { path: p, expected } => {
+            format!("- {} removed, was {}", path(p), expected)
+        }
+        Difference::Unexpected { path: p, actual } => {
+            format!("+ {} added: {}", path(p), actual)
+        }
+        Difference::Changed {
+            path: p,
+            actual,
+            expected,
+        } => format!("~ {} changed from {} to {}", path(p), expected, actual),
+    }
+}
+
+impl std::fmt::Display for OutputDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.changes {
+            Ok(changes) => {
+                write!(f, "test {}: {} change(s)", self.title, changes.len())?;
+                for change in changes {
+                    write!(f, "\n    {}", describe_change(change))?;
+                }
+                Ok(())
+            }
+            Err(e) => write!(f, "test {}: not compared\n    {}", self.title, e),
+        }
+    }
+}
@@ -1,29 +1,407 @@
 use std::{
+    collections::{BTreeMap, HashMap},
     fs::File,
+    hash::{Hash, Hasher},
     io::Read,
+    net::{IpAddr, Ipv4Addr},
+    num::NonZeroUsize,
     path::{Path, PathBuf},
+    str::FromStr,
     time::Duration,
 };
 
 use anyhow::{anyhow, Context};
 use bollard::{
-    container::Config,
-    image::BuildImageOptions,
-    models::{BuildInfo, HealthStatusEnum, HostConfig, ImageId, PortBinding},
+    container::{
+        Config, CreateContainerOptions, KillContainerOptions, ListContainersOptions, LogsOptions,
+        RemoveContainerOptions, UploadToContainerOptions, WaitContainerOptions,
+    },
+    exec::{CreateExecOptions, StartExecResults},
+    image::{BuildImageOptions, CreateImageOptions, ListImagesOptions, RemoveImageOptions},
+    models::{BuildInfo, HealthConfig, HealthStatusEnum, HostConfig, ImageId, PortBinding},
 };
 use futures_util::stream::StreamExt;
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::task::JoinHandle;
 use tokio::time::sleep;
-use tracing::instrument;
+use tracing::{debug, instrument};
 
+use crate::config::{ElasticsearchOutput, FileOutput, OutputChannel};
+use crate::coverage::{instrument_rule, rule_name};
+use crate::tunnel::SshTunnel;
 use crate::{
     assets::{ConfigAssets, PipelineAssets},
-    PATTERNS_DIR, SCRIPTS_DIR,
+    BUILT_IMAGE_FILE, PATTERNS_DIR, RELOAD_TIMEOUT, SCRIPTS_DIR, TEST_ID_HEADER,
+    WARM_CONTAINER_FILE,
 };
 use crate::{
-    API_PORT, FQAN, IMAGE_ARCHIVE_NAME, INPUT_PORT, INPUT_TEMPLATE_NAME, LOCALHOST, OUTPUT_PORT,
-    OUTPUT_TEMPLATE_NAME, PIPELINE_NAME,
+    API_PORT, CODEC_CONTENT_TYPE_PREFIX, CONNECTION_TIMEOUT, DEFAULT_CALLBACK_HOST,
+    DEFAULT_HEALTH_INTERVAL, DEFAULT_HEALTH_RETRIES, DEFAULT_LOGSTASH_VERSION,
+    DEFAULT_STARTUP_TIMEOUT, DOCKERFILE_NAME, DOCKER_SOCKET, ELASTICSEARCH_PATH,
+    ELASTICSEARCH_TEST_ID_FIELD, FILES_DIR, FILES_MOUNT_DIR, FQAN, GROK_PATTERNS_GLOB,
+    HOSTNAME_LABEL, IMAGE_ARCHIVE_NAME, INPUT_PORT, INPUT_TEMPLATE_NAME, JAVA_OPTS_VARIABLE,
+    LOCALHOST, LOGSTASH_HOME_DIR, LOGSTASH_IMAGE, LOGSTASH_PIPELINE_DIR, METADATA_FIELD,
+    OUTPUTS_PATH, OUTPUT_PORT, OUTPUT_PORT_VARIABLE, OUTPUT_TEMPLATE_NAME, PID_LABEL, PIPELINE_ID,
+    PIPELINE_NAME, PODMAN_SYSTEM_SOCKET, PODMAN_USER_SOCKET, POOL_PORT_STRIDE, PROJECT_LABEL,
 };
 
+/// The container engine that builds the Logstash image and runs its container
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ContainerRuntime {
+    /// Docker if it is available, otherwise Podman if its socket is found
+    #[default]
+    Auto,
+    /// Docker, as configured by `DOCKER_HOST`
+    Docker,
+    /// Podman, through its Docker-compatible API socket (rootless or system-wide)
+    Podman,
+}
+
+/// The protocol over which the input events are sent to Logstash
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    clap::ValueEnum,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum InputProtocol {
+    /// JSON documents or raw text posted to an `http` input
+    #[default]
+    Http,
+    /// Events sent over the Lumberjack protocol to a `beats` input, as Filebeat does. Raw text
+    /// input events become the `message` field.
+    Beats,
+    /// Events written as JSON lines to a `tcp` input. Raw text input events become the `message`
+    /// field.
+    Tcp,
+    /// Events sent as one JSON datagram each to a `udp` input. Raw text input events become the
+    /// `message` field.
+    Udp,
+    /// Raw text input events sent as one syslog datagram each to a `syslog` input
+    SyslogUdp,
+    /// Raw text input events written as syslog lines to a `syslog` input
+    SyslogTcp,
+}
+
+impl InputProtocol {
+    /// The transport protocol of the input port of the Logstash container
+    pub fn transport(&self) -> &'static str {
+        match self {
+            InputProtocol::Udp | InputProtocol::SyslogUdp => "udp",
+            _ => "tcp",
+        }
+    }
+
+    /// Whether the input events are sent to a `syslog` input
+    pub fn is_syslog(&self) -> bool {
+        matches!(self, InputProtocol::SyslogUdp | InputProtocol::SyslogTcp)
+    }
+
+    /// The codec of the input plugin, unless configured otherwise
+    pub fn default_codec(&self) -> &'static str {
+        match self {
+            InputProtocol::Tcp => "json_lines",
+            _ => "json",
+        }
+    }
+}
+
+/// The format of the syslog messages that the `syslog` input parses
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    clap::ValueEnum,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogFormat {
+    /// BSD syslog messages (e.g. `<34>Oct 11 22:14:15 mymachine su: 'su root' failed`)
+    #[default]
+    Rfc3164,
+    /// IETF syslog messages (e.g. `<34>1 2003-10-11T22:14:15.003Z mymachine su - ID47 - 'su root'
+    /// failed`)
+    Rfc5424,
+}
+
+impl SyslogFormat {
+    /// The grok pattern with which the `syslog` input parses the messages of this format, with
+    /// the fields of ECS
+    pub fn grok_pattern(&self) -> &'static str {
+        match self {
+            SyslogFormat::Rfc3164 => "<%{POSINT:[log][syslog][priority]:int}>%{SYSLOGLINE}",
+            SyslogFormat::Rfc5424 => "%{SYSLOG5424LINE}",
+        }
+    }
+}
+
+/// The locations of the Podman API socket, in order of preference: the one given by
+/// `CONTAINER_HOST`, the rootless one of the current user, and the system-wide one
+pub fn podman_sockets() -> Vec<PathBuf> {
+    let mut sockets = Vec::new();
+    if let Some(host) = std::env::var_os("CONTAINER_HOST") {
+        if let Some(path) = host.to_str().and_then(|h| h.strip_prefix("unix://")) {
+            sockets.push(PathBuf::from(path));
+        }
+    }
+    if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        sockets.push(PathBuf::from(runtime_dir).join(PODMAN_USER_SOCKET));
+    }
+    sockets.push(PathBuf::from(PODMAN_SYSTEM_SOCKET));
+    sockets
+}
+
+/// The host ports through which Lotus and the Logstash container communicate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ports {
+    /// The port under which the HTTP input of Logstash is published
+    pub input: u16,
+    /// The port on which Lotus receives the output events of Logstash
+    pub output: u16,
+    /// The port under which the API of Logstash is published
+    pub api: u16,
+}
+
+impl Ports {
+    /// Whether any of the ports is chosen freely when it is bound (i.e. given as 0)
+    pub fn are_ephemeral(&self) -> bool {
+        [self.input, self.output, self.api].contains(&0)
+    }
+
+    /// The ports of the Logstash container in the given slot of the container pool
+    pub fn slot(&self, slot: usize) -> anyhow::Result<Self> {
+        Ok(Self {
+            input: host_port(self.input, slot)?,
+            api: host_port(self.api, slot)?,
+            ..*self
+        })
+    }
+
+    /// Checks that the ports of every container of a pool of the given size are valid, and that
+    /// no two of them, nor the output port, are the same (except freely chosen ones)
+    pub fn check_pool(&self, containers: usize) -> anyhow::Result<()> {
+        let mut used: BTreeMap<u16, String> = BTreeMap::new();
+        if self.output != 0 {
+            used.insert(self.output, String::from("the output port"));
+        }
+        for slot in 0..containers.max(1) {
+            let ports = self.slot(slot)?;
+            for (port, name) in [(ports.input, "input"), (ports.api, "API")] {
+                if port == 0 {
+                    continue;
+                }
+                let owner = format!("the {name} port of container {slot}");
+                if let Some(other) = used.insert(port, owner.clone()) {
+                    return Err(anyhow!("The port {port} is both {other} and {owner}"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Ports {
+    fn default() -> Self {
+        Self {
+            input: INPUT_PORT,
+            output: OUTPUT_PORT,
+            api: API_PORT,
+        }
+    }
+}
+
+/// An amount of memory in bytes, parsed from a number with an optional unit `b`, `k`, `m` or `g`
+/// (e.g. `512m` or `2g`, in multiples of 1024 as with `docker run`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemorySize(pub i64);
+
+impl FromStr for MemorySize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_ascii_lowercase();
+        let (number, factor) = match lower.char_indices().last() {
+            Some((i, 'b')) => (&lower[..i], 1),
+            Some((i, 'k')) => (&lower[..i], 1 << 10),
+            Some((i, 'm')) => (&lower[..i], 1 << 20),
+            Some((i, 'g')) => (&lower[..i], 1 << 30),
+            _ => (lower.as_str(), 1),
+        };
+        number
+            .parse::<i64>()
+            .ok()
+            .and_then(|n| n.checked_mul(factor))
+            .filter(|n| *n > 0)
+            .map(Self)
+            .ok_or_else(|| anyhow!("Invalid amount of memory '{s}', expected e.g. 512m or 2g"))
+    }
+}
+
+/// The resources each Logstash container may use at most
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ResourceLimits {
+    pub memory: Option<MemorySize>,
+    /// The memory including swap space
+    pub memory_swap: Option<MemorySize>,
+    /// The number of CPUs (e.g. `1.5`)
+    pub cpus: Option<f64>,
+}
+
+/// How the container runtime is reached, and with it the ports of the Logstash container
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Connection {
+    /// The container engine, unless a daemon address is given
+    pub runtime: ContainerRuntime,
+    /// The address of a Docker daemon, e.g. `tcp://build-host:2376` or `ssh://user@build-host`
+    /// for a remote one
+    pub host: Option<String>,
+    /// Connect to the daemon with TLS, verifying its certificate and authenticating with a client
+    /// certificate
+    pub tls_verify: bool,
+    /// The directory holding `ca.pem`, `cert.pem` and `key.pem` for TLS connections (by default
+    /// `~/.docker`)
+    pub cert_path: Option<PathBuf>,
+    pub ports: Ports,
+}
+
+impl Connection {
+    /// Connects to the API of the container runtime. Without a daemon address and with
+    /// [`ContainerRuntime::Auto`], Docker is preferred if its socket exists, and Podman is used
+    /// otherwise if its socket exists. For `ssh://` addresses, an SSH tunnel is opened, which
+    /// must be kept as long as the connection is used.
+    #[instrument]
+    pub async fn connect(&self) -> anyhow::Result<(bollard::Docker, Option<SshTunnel>)> {
+        if let Some(ref host) = self.host {
+            if host.starts_with("ssh://") {
+                if self.ports.are_ephemeral() {
+                    return Err(anyhow!(
+                        "Freely chosen ports cannot be forwarded through an SSH tunnel"
+                    ));
+                }
+                let tunnel = SshTunnel::open(host, self.ports)
+                    .await
+                    .with_context(|| format!("Opening the SSH tunnel to {host}"))?;
+                let docker = self.connect_to(&format!("unix://{}", tunnel.socket().display()))?;
+                return Ok((docker, Some(tunnel)));
+            }
+            return Ok((self.connect_to(host)?, None));
+        }
+
+        let runtime = match self.runtime {
+            ContainerRuntime::Auto
+                if !Path::new(DOCKER_SOCKET).exists()
+                    && podman_sockets().iter().any(|s| s.exists()) =>
+            {
+                ContainerRuntime::Podman
+            }
+            runtime => runtime,
+        };
+        let docker = match runtime {
+            ContainerRuntime::Podman => {
+                let socket = podman_sockets()
+                    .into_iter()
+                    .find(|s| s.exists())
+                    .ok_or(anyhow!(
+                        "No Podman API socket was found (start it with e.g. `systemctl --user start podman.socket`)"
+                    ))?;
+                debug!("Connect to the Podman API at {}", socket.display());
+                bollard::Docker::connect_with_socket(
+                    &socket.to_string_lossy(),
+                    CONNECTION_TIMEOUT,
+                    bollard::API_DEFAULT_VERSION,
+                )
+                .with_context(|| format!("Connecting to the Podman API at {}", socket.display()))?
+            }
+            _ => bollard::Docker::connect_with_local_defaults()
+                .context("Connecting to the Docker API")?,
+        };
+        Ok((docker, None))
+    }
+
+    /// Whether the daemon runs on another host, i.e. it cannot access local files
+    pub fn is_remote(&self) -> bool {
+        self.remote_host().is_some() || self.is_tunneled()
+    }
+
+    /// Whether the daemon is reached through an SSH tunnel
+    pub fn is_tunneled(&self) -> bool {
+        self.host
+            .as_deref()
+            .is_some_and(|h| h.starts_with("ssh://"))
+    }
+
+    fn connect_to(&self, host: &str) -> anyhow::Result<bollard::Docker> {
+        debug!("Connect to the Docker API at {host}");
+        let docker = if let Some(path) = host.strip_prefix("unix://") {
+            bollard::Docker::connect_with_socket(
+                path,
+                CONNECTION_TIMEOUT,
+                bollard::API_DEFAULT_VERSION,
+            )
+        } else if self.tls_verify {
+            let cert_path = match self.cert_path {
+                Some(ref path) => path.clone(),
+                None => directories::BaseDirs::new()
+                    .ok_or(anyhow!("Unable to determine the home directory"))?
+                    .home_dir()
+                    .join(".docker"),
+            };
+            bollard::Docker::connect_with_ssl(
+                host,
+                &cert_path.join("key.pem"),
+                &cert_path.join("cert.pem"),
+                &cert_path.join("ca.pem"),
+                CONNECTION_TIMEOUT,
+                bollard::API_DEFAULT_VERSION,
+            )
+        } else {
+            bollard::Docker::connect_with_http(
+                host,
+                CONNECTION_TIMEOUT,
+                bollard::API_DEFAULT_VERSION,
+            )
+        };
+        docker.with_context(|| format!("Connecting to the Docker API at {host}"))
+    }
+
+    /// The name of the remote host running the daemon, if the daemon is reached over the network
+    fn remote_host(&self) -> Option<String> {
+        let url = reqwest::Url::parse(self.host.as_deref()?).ok()?;
+        match url.scheme() {
+            "tcp" | "http" | "https" => url.host_str().map(String::from),
+            _ => None,
+        }
+    }
+
+    /// The host on which the ports of the Logstash container are reached: the host running a
+    /// remote daemon, or the local one
+    pub fn logstash_host(&self) -> String {
+        self.remote_host().unwrap_or_else(|| LOCALHOST.to_string())
+    }
+
+    /// The address the ports of the Logstash container are published on: all addresses of the
+    /// host running a remote daemon, so that they can be reached from here, or only the loopback
+    /// address otherwise
+    pub fn publish_address(&self) -> IpAddr {
+        match self.remote_host() {
+            Some(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            None => LOCALHOST,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Image {
     pub(crate) id: String,
@@ -34,11 +412,100 @@ pub struct Container {
     pub(crate) id: String,
 }
 
+/// Settings that determine the contents of the Logstash container image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageOptions {
+    /// The Logstash codecs that raw text input events may be decoded with
+    pub codecs: Vec<String>,
+    /// The version of Logstash, i.e. the tag of the official Logstash image the container image
+    /// is based on
+    pub logstash_version: String,
+    /// An image that replaces the official Logstash image as the base of the container image
+    /// (e.g. one with additional plugins or certificates), which must be derived from the latter
+    pub custom_base_image: Option<String>,
+    /// The digest the base image is pinned to (e.g. `sha256:…`). The base image is pulled by this
+    /// digest and verified before the container image is built.
+    pub base_image_digest: Option<String>,
+    /// The platform the images are pulled and built for, and the containers run on (e.g.
+    /// `linux/amd64`), instead of that of the container runtime
+    pub platform: Option<String>,
+    /// Never pull from a registry: the base image must already be present locally
+    pub offline: bool,
+    /// Inject a marker plugin into each filter section and conditional branch of the rules, whose
+    /// counters tell which of them the test cases exercised
+    pub coverage: bool,
+    /// Copy the `@metadata` of the output events (except that of Lotus) into a field that the
+    /// outputs send along
+    pub capture_metadata: bool,
+    /// The instant (RFC 3339) that `@timestamp` of the input events and `[event][created]` of the
+    /// output events are pinned to
+    pub clock: Option<String>,
+    /// Logstash plugins installed into the container image in addition to the bundled ones
+    pub plugins: Vec<String>,
+    /// Additional output channels that events are routed to by conditions
+    pub outputs: Vec<OutputChannel>,
+    /// The Elasticsearch output that the output events are sent to instead
+    pub elasticsearch: Option<ElasticsearchOutput>,
+    /// The file output that the output events are also written with
+    pub file_output: Option<FileOutput>,
+    /// The host name under which the Logstash container reaches Lotus to send its output events
+    pub callback_host: String,
+    /// The protocol over which the input events are sent to Logstash
+    pub input_protocol: InputProtocol,
+    /// The codec of the `tcp` or `udp` input, instead of the default one of the protocol
+    pub socket_codec: Option<String>,
+    /// The format of the messages that the `syslog` input parses
+    pub syslog_format: SyslogFormat,
+    /// The number of workers of the pipeline, one if none
+    pub workers: Option<NonZeroUsize>,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            codecs: Vec::default(),
+            logstash_version: String::from(DEFAULT_LOGSTASH_VERSION),
+            custom_base_image: None,
+            base_image_digest: None,
+            platform: None,
+            offline: false,
+            coverage: false,
+            capture_metadata: false,
+            clock: None,
+            plugins: Vec::default(),
+            outputs: Vec::default(),
+            callback_host: String::from(DEFAULT_CALLBACK_HOST),
+            input_protocol: InputProtocol::Http,
+            socket_codec: None,
+            syslog_format: SyslogFormat::Rfc3164,
+            workers: None,
+            elasticsearch: None,
+            file_output: None,
+        }
+    }
+}
+
+impl ImageOptions {
+    /// The image the Logstash container image is based on: the custom base image if any, or the
+    /// official Logstash image of the selected version, pinned to the digest if any
+    pub fn base_image(&self) -> String {
+        let image = match self.custom_base_image {
+            Some(ref image) => image.clone(),
+            None => format!("{}:{}", LOGSTASH_IMAGE, self.logstash_version),
+        };
+        match self.base_image_digest {
+            Some(ref digest) => format!("{image}@{digest}"),
+            None => image,
+        }
+    }
+}
+
 pub fn build_image_archive(
     cache_dir: &Path,
     rules: &[PathBuf],
     scripts: &[PathBuf],
     patterns: &[PathBuf],
+    options: &ImageOptions,
 ) -> anyhow::Result<PathBuf> {
     // Create the tar archive
     let archive_path = cache_dir.join(IMAGE_ARCHIVE_NAME);
@@ -49,12 +516,39 @@ pub fn build_image_archive(
 
     // Prepare the Handlebars templating context
     let ctx = handlebars::Context::wraps(serde_json::json!({
+        "base_image": options.base_image(),
         "input_port": INPUT_PORT,
-        "output_port": OUTPUT_PORT,
+        "output_host": options.callback_host,
         "api_port": API_PORT,
         "pipeline_name": PIPELINE_NAME,
         "scripts_dir": SCRIPTS_DIR,
         "patterns_dir": PATTERNS_DIR,
+        "test_id_field": TEST_ID_HEADER.to_lowercase().replace('-', "_"),
+        "codecs": options
+            .codecs
+            .iter()
+            .map(|c| serde_json::json!({ "name": c, "content_type": codec_content_type(c) }))
+            .collect::<Vec<_>>(),
+        "plugins": options.plugins,
+        "outputs_path": OUTPUTS_PATH,
+        "outputs": options.outputs,
+        "elasticsearch": options.elasticsearch,
+        "elasticsearch_path": ELASTICSEARCH_PATH,
+        "file_output": options.file_output,
+        "capture_metadata": options.capture_metadata,
+        "clock": options.clock,
+        "metadata_field": METADATA_FIELD,
+        "files_dir": FILES_MOUNT_DIR,
+        "elasticsearch_test_id_field": ELASTICSEARCH_TEST_ID_FIELD,
+        "protocol": options.input_protocol,
+        "transport": options.input_protocol.transport(),
+        "socket_codec": options
+            .socket_codec
+            .as_deref()
+            .unwrap_or(options.input_protocol.default_codec()),
+        "syslog": options.input_protocol.is_syslog(),
+        "syslog_pattern": options.syslog_format.grok_pattern(),
+        "pipeline_workers": options.workers.map_or(1, NonZeroUsize::get),
     }))
     .context("Creating the Handlebars variable context")?;
 
@@ -65,8 +559,11 @@ pub fn build_image_archive(
     hbs.register_embed_templates::<ConfigAssets>()
         .context("Loading the Logstash config assets")?;
 
-    // Template all fixed Logstash configuration and add them to the archive
-    for name in hbs.get_templates().keys() {
+    // Template all fixed Logstash configuration and add them to the archive (in a stable order,
+    // so that the archive is identical if nothing changed)
+    let mut names: Vec<_> = hbs.get_templates().keys().cloned().collect();
+    names.sort();
+    for name in &names {
         let pth = cache_dir.join(name);
         hbs.render_with_context_to_write(
             name,
@@ -95,7 +592,16 @@ pub fn build_image_archive(
             .with_context(|| format!("Creating the pipeline file: {}", pipeline_path.display()))?;
         hbs.render_with_context_to_write(INPUT_TEMPLATE_NAME, &ctx, &mut pipeline)
             .context("Rendering the template input.conf to the pipeline file")?;
-        for rule in rules {
+        for (i, rule) in rules.iter().enumerate() {
+            if options.coverage {
+                let text = std::fs::read_to_string(rule)
+                    .with_context(|| format!("Reading the rule file: {}", rule.display()))?;
+                let (instrumented, _) = instrument_rule(&text, i, &rule_name(rule))
+                    .with_context(|| format!("Instrumenting the rule file: {}", rule.display()))?;
+                std::io::Write::write_all(&mut pipeline, instrumented.as_bytes())
+                    .context("Adding the rule file to the pipeline file")?;
+                continue;
+            }
             std::io::copy(
                 &mut File::open(rule)
                     .with_context(|| format!("Opening the rule file: {}", rule.display()))?,
@@ -173,6 +679,235 @@ pub fn build_image_archive(
     Ok(archive_path)
 }
 
+/// The prefix of the names of all container images built by Lotus
+fn image_name_prefix() -> String {
+    format!("{}/{}-", FQAN[1], FQAN[2])
+}
+
+/// The name of the project, i.e. of its cache directory (the hash of the target location)
+pub fn project_name(cache_dir: &Path) -> anyhow::Result<&str> {
+    cache_dir
+        .file_name()
+        .and_then(|f| f.to_str())
+        .ok_or(anyhow!("Cannot determine the name of the cache directory"))
+}
+
+/// The full name of a label attached to the containers and images created by Lotus
+pub fn label(name: &str) -> String {
+    format!("{}.{}", FQAN.join("."), name)
+}
+
+/// The host port under which a port of the Logstash container in the given slot of the container
+/// pool is published. The ports of the first container, and freely chosen ones (i.e. 0), are
+/// published as they are.
+pub fn host_port(port: u16, slot: usize) -> anyhow::Result<u16> {
+    match port {
+        0 => Ok(0),
+        port => u16::try_from(slot)
+            .ok()
+            .and_then(|s| s.checked_mul(POOL_PORT_STRIDE))
+            .and_then(|offset| port.checked_add(offset))
+            .ok_or_else(|| {
+                anyhow!("The port {port} of container {slot} of the pool exceeds the highest port")
+            }),
+    }
+}
+
+/// The HTTP content type under which raw text input events are sent to Logstash, so that they are
+/// decoded with the given codec
+pub fn codec_content_type(codec: &str) -> String {
+    format!("{}{}", CODEC_CONTENT_TYPE_PREFIX, codec)
+}
+
+/// Whether any of the repository digests of an image (e.g. `name@sha256:…`) is the given digest
+pub fn has_digest(repo_digests: &[String], digest: &str) -> bool {
+    repo_digests
+        .iter()
+        .any(|d| d.rsplit_once('@').is_some_and(|(_, d)| d == digest))
+}
+
+/// Whether an image of the given platform (`os/architecture[/variant]`) was requested for the
+/// given platform, which may omit the variant
+pub fn is_platform(image_platform: &str, platform: &str) -> bool {
+    image_platform == platform
+        || (platform.matches('/').count() == 1
+            && image_platform.starts_with(platform)
+            && image_platform[platform.len()..].starts_with('/'))
+}
+
+/// Whether an image exists locally and, if a platform is given, is one of that platform
+#[instrument]
+pub async fn image_available(
+    docker: &bollard::Docker,
+    image: &str,
+    platform: Option<&str>,
+) -> bool {
+    let Ok(inspect) = docker.inspect_image(image).await else {
+        return false;
+    };
+    let Some(platform) = platform else {
+        return true;
+    };
+    let image_platform = [inspect.os, inspect.architecture, inspect.variant]
+        .into_iter()
+        .flatten()
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join("/");
+    is_platform(&image_platform, platform)
+}
+
+/// Pulls the base image, or in offline mode only checks that it is present locally
+async fn pull_image(
+    docker: &bollard::Docker,
+    options: &ImageOptions,
+    progress: &ProgressBar,
+) -> anyhow::Result<()> {
+    let image = options.base_image();
+    let platform = options.platform.as_deref();
+    if options.offline {
+        return match image_available(docker, &image, platform).await {
+            true => Ok(()),
+            false => Err(anyhow!(
+                "The base image {}{} is not present locally and cannot be pulled in offline mode. \
+                 Pull it beforehand (e.g. `docker pull {}`) or run without --offline",
+                image,
+                platform.map(|p| format!(" ({p})")).unwrap_or_default(),
+                image
+            )),
+        };
+    }
+
+    progress.set_message(format!("Pulling the base image {image}"));
+    let mut pull_stream = docker.create_image(
+        Some(CreateImageOptions {
+            from_image: image.as_str(),
+            platform: platform.unwrap_or_default(),
+            ..Default::default()
+        }),
+        None,
+        None,
+    );
+    while let Some(info) = pull_stream.next().await {
+        info.with_context(|| format!("Pulling the base image {image}"))?;
+    }
+    Ok(())
+}
+
+/// Makes the base image available without building anything on top of it: it is pulled if it is
+/// pinned to a digest (and verified) or missing locally
+#[instrument]
+pub async fn pull_base_image(
+    docker: &bollard::Docker,
+    options: &ImageOptions,
+    progress: &ProgressBar,
+) -> anyhow::Result<Image> {
+    let image = options.base_image();
+    if options.base_image_digest.is_some() {
+        verify_base_image(docker, options, progress).await?;
+    } else if !image_available(docker, &image, options.platform.as_deref()).await {
+        pull_image(docker, options, progress).await?;
+    }
+    Ok(Image { id: image })
+}
+
+/// The bind mounts that place the rendered Logstash configuration and pipeline (which must have
+/// been written to the cache directory before, see [`build_image_archive`]), the ruby scripts and
+/// the grok patterns in a container of the base image
+pub fn bind_mounts(
+    cache_dir: &Path,
+    scripts: &[PathBuf],
+    patterns: &[PathBuf],
+) -> anyhow::Result<Vec<String>> {
+    let mut mounts = Vec::new();
+    let mut mount = |source: &Path, target: String| -> anyhow::Result<()> {
+        let source = source
+            .canonicalize()
+            .with_context(|| format!("Resolving the file: {}", source.display()))?;
+        mounts.push(format!("{}:{}:ro", source.display(), target));
+        Ok(())
+    };
+
+    for name in ConfigAssets::iter().filter(|n| n != DOCKERFILE_NAME) {
+        mount(
+            &cache_dir.join(name.as_ref()),
+            format!("{LOGSTASH_HOME_DIR}/config/{name}"),
+        )?;
+    }
+    mount(
+        &cache_dir.join(PIPELINE_NAME),
+        format!("{LOGSTASH_PIPELINE_DIR}/{PIPELINE_NAME}"),
+    )?;
+    for (dir, files) in [(SCRIPTS_DIR, scripts), (PATTERNS_DIR, patterns)] {
+        for file in files {
+            let name = file
+                .file_name()
+                .ok_or_else(|| anyhow!("Finding the file name of {}", file.display()))?;
+            mount(
+                file,
+                format!("{LOGSTASH_HOME_DIR}/{dir}/{}", name.to_string_lossy()),
+            )?;
+        }
+    }
+
+    Ok(mounts)
+}
+
+/// Empties the directory that the file output of the pipeline writes to, and returns how it is
+/// bind-mounted into the Logstash container (writable by the user of Logstash)
+#[instrument]
+pub fn capture_mount(cache_dir: &Path) -> anyhow::Result<String> {
+    let dir = cache_dir.join(FILES_DIR);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)
+            .with_context(|| format!("Removing the captured files: {}", dir.display()))?;
+    }
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Creating the capture directory: {}", dir.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777))
+            .with_context(|| format!("Opening the capture directory: {}", dir.display()))?;
+    }
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("Resolving the directory: {}", dir.display()))?;
+    Ok(format!("{}:{FILES_MOUNT_DIR}", dir.display()))
+}
+
+/// Pulls the base image by its pinned digest, if any, and checks that the pulled image has that
+/// digest
+#[instrument]
+pub async fn verify_base_image(
+    docker: &bollard::Docker,
+    options: &ImageOptions,
+    progress: &ProgressBar,
+) -> anyhow::Result<()> {
+    let Some(ref digest) = options.base_image_digest else {
+        return Ok(());
+    };
+    let image = options.base_image();
+    pull_image(docker, options, progress).await?;
+
+    let repo_digests = docker
+        .inspect_image(&image)
+        .await
+        .with_context(|| format!("Inspecting the base image {image}"))?
+        .repo_digests
+        .unwrap_or_default();
+    if !has_digest(&repo_digests, digest) {
+        return Err(anyhow!(
+            "The base image {} does not have the pinned digest {}, but: {}",
+            image,
+            digest,
+            repo_digests.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
 #[instrument]
 pub async fn build_container_image(
     docker: &bollard::Docker,
@@ -180,9 +915,20 @@ pub async fn build_container_image(
     rules: &[PathBuf],
     scripts: &[PathBuf],
     patterns: &[PathBuf],
+    options: &ImageOptions,
+    progress: &ProgressBar,
 ) -> anyhow::Result<Image> {
+    // Make sure that the base image is the pinned one
+    verify_base_image(docker, options, progress)
+        .await
+        .context("Verifying the base image")?;
+    // Otherwise, the build would pull a missing base image
+    if options.offline {
+        pull_image(docker, options, progress).await?;
+    }
+
     // Copy the static files over to the cache directory and build the tar archive
-    let archive_path = build_image_archive(cache_dir, rules, scripts, patterns)
+    let archive_path = build_image_archive(cache_dir, rules, scripts, patterns, options)
         .context("Creating the image archive")?;
 
     // Build the container image from the tar archive
@@ -191,14 +937,24 @@ pub async fn build_container_image(
         .context("Opening the archive file")?
         .read_to_end(&mut archive_buffer)
         .context("Reading the archive file into memory")?;
-    let cache_name = cache_dir
-        .file_name()
-        .and_then(|f| f.to_str())
-        .ok_or(anyhow!("Cannot determine the name of the cache directory"))?;
-    let image_tag = format!("{}/{}-{}:latest", FQAN[1], FQAN[2], cache_name);
+    // Skip the build if the image was built from an identical archive and still exists
+    let content_hash = content_hash(&archive_buffer);
+    if let Some(built) = BuiltImage::load(cache_dir)? {
+        if built.content_hash == content_hash
+            && image_available(docker, &built.image_id, options.platform.as_deref()).await
+        {
+            debug!("The Logstash image {} is up to date", built.image_id);
+            return Ok(built.image());
+        }
+    }
+
+    let project = project_name(cache_dir)?;
+    let image_tag = format!("{}{}:latest", image_name_prefix(), project);
     let mut builder_stream = docker.build_image::<String>(
         BuildImageOptions {
             t: image_tag.clone(),
+            labels: [(label(PROJECT_LABEL), project.to_string())].into(),
+            platform: options.platform.clone().unwrap_or_default(),
             ..Default::default()
         },
         None,
@@ -214,6 +970,11 @@ pub async fn build_container_image(
             }) => {
                 image_id = id.map(|id| Image { id });
             }
+            Ok(BuildInfo {
+                stream: Some(line), ..
+            }) if line.starts_with("Step") => {
+                progress.set_message(format!("Building the Logstash image: {}", line.trim()));
+            }
             Ok(_) => (),
             Err(bollard::errors::Error::DockerStreamError { error }) => {
                 return Err(anyhow!(
@@ -232,37 +993,99 @@ pub async fn build_container_image(
         }
     }
 
-    image_id.ok_or(anyhow!("No container image ID was found"))
+    let image = image_id.ok_or(anyhow!("No container image ID was found"))?;
+    BuiltImage {
+        content_hash,
+        image_id: image.id.clone(),
+    }
+    .save(cache_dir)?;
+
+    Ok(image)
 }
 
+#[allow(clippy::too_many_arguments)]
 #[instrument]
 pub async fn create_container(
     docker: &bollard::Docker,
     image: &Image,
+    connection: &Connection,
     delete_container: bool,
+    slot: usize,
+    binds: &[String],
+    limits: ResourceLimits,
+    env: &ContainerEnv,
+    project: &str,
+    platform: Option<&str>,
+    input_protocol: InputProtocol,
+    capture: Option<&str>,
 ) -> anyhow::Result<Container> {
+    // Containers of the base image lack the health check of the bespoke image
+    let healthcheck = (!binds.is_empty()).then(|| HealthConfig {
+        test: Some(vec![
+            String::from("CMD-SHELL"),
+            format!("curl -s \"http://{LOCALHOST}:{API_PORT}\" | grep \"green\""),
+        ]),
+        ..Default::default()
+    });
+
+    // Through an SSH tunnel, the output port of Lotus is forwarded to the remote host
+    let extra_hosts = connection
+        .is_tunneled()
+        .then(|| vec![format!("{DEFAULT_CALLBACK_HOST}:host-gateway")]);
+
+    // Ports given as 0 are left to the container runtime to choose
+    let ports = connection.ports.slot(slot)?;
+
     let response = docker
         .create_container::<String, String>(
-            None,
+            platform.map(|p| CreateContainerOptions {
+                name: String::new(),
+                platform: Some(p.to_string()),
+            }),
             Config {
                 image: Some(image.id.clone()),
                 attach_stdout: Some(true),
                 attach_stderr: Some(true),
+                env: Some(env.variables(ports.output)),
+                labels: Some(
+                    [
+                        (label(PROJECT_LABEL), project.to_string()),
+                        (label(PID_LABEL), std::process::id().to_string()),
+                        (label(HOSTNAME_LABEL), hostname()),
+                    ]
+                    .into(),
+                ),
+                healthcheck,
                 host_config: Some(HostConfig {
                     auto_remove: Some(delete_container),
+                    memory: limits.memory.map(|m| m.0),
+                    memory_swap: limits.memory_swap.map(|m| m.0),
+                    nano_cpus: limits.cpus.map(|c| (c * 1e9) as i64),
+                    extra_hosts,
+                    binds: (!binds.is_empty() || capture.is_some()).then(|| {
+                        binds
+                            .iter()
+                            .map(String::as_str)
+                            .chain(capture)
+                            .map(String::from)
+                            .collect()
+                    }),
                     port_bindings: Some(
-                        [INPUT_PORT, API_PORT]
-                            .into_iter()
-                            .map(|p| {
-                                (
-                                    format!("{}/tcp", p),
-                                    Some(vec![PortBinding {
-                                        host_ip: Some(LOCALHOST.to_string()),
-                                        host_port: Some(format!("{}/tcp", p)),
-                                    }]),
-                                )
-                            })
-                            .collect(),
+                        [
+                            (INPUT_PORT, ports.input, input_protocol.transport()),
+                            (API_PORT, ports.api, "tcp"),
+                        ]
+                        .into_iter()
+                        .map(|(p, h, transport)| {
+                            (
+                                format!("{p}/{transport}"),
+                                Some(vec![PortBinding {
+                                    host_ip: Some(connection.publish_address().to_string()),
+                                    host_port: (h != 0).then(|| format!("{h}/{transport}")),
+                                }]),
+                            )
+                        })
+                        .collect(),
                     ),
                     ..Default::default()
                 }),
@@ -275,6 +1098,310 @@ pub async fn create_container(
     Ok(Container { id: response.id })
 }
 
+/// The ports under which the input and API ports of a started Logstash container are published,
+/// which may have been chosen by the container runtime
+#[instrument]
+pub async fn published_ports(
+    docker: &bollard::Docker,
+    container: &Container,
+    ports: Ports,
+    input_protocol: InputProtocol,
+) -> anyhow::Result<Ports> {
+    let inspect = docker
+        .inspect_container(&container.id, None)
+        .await
+        .context("Inspecting the Logstash container")?;
+    let bindings = inspect
+        .network_settings
+        .and_then(|n| n.ports)
+        .unwrap_or_default();
+    let published = |port: u16, transport: &str| -> anyhow::Result<u16> {
+        bindings
+            .get(&format!("{port}/{transport}"))
+            .and_then(|b| b.as_ref()?.first()?.host_port.as_ref()?.parse().ok())
+            .ok_or_else(|| anyhow!("The port {port} of the Logstash container is not published"))
+    };
+
+    Ok(Ports {
+        input: published(INPUT_PORT, input_protocol.transport())?,
+        api: published(API_PORT, "tcp")?,
+        ..ports
+    })
+}
+
+/// An environment variable given as `NAME=VALUE`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVar {
+    pub name: String,
+    pub value: String,
+}
+
+impl FromStr for EnvVar {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, value)) if !name.is_empty() => Ok(Self {
+                name: name.to_string(),
+                value: value.to_string(),
+            }),
+            _ => Err(anyhow!(
+                "Invalid environment variable '{s}', expected NAME=VALUE"
+            )),
+        }
+    }
+}
+
+/// The environment of the Logstash container, in addition to the output port of Lotus
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ContainerEnv {
+    /// Variables substituted into the pipeline (e.g. `${MY_VAR}`)
+    pub variables: BTreeMap<String, String>,
+    /// Options of the Java virtual machine running Logstash
+    pub java_opts: Option<String>,
+}
+
+impl ContainerEnv {
+    /// The environment variables of the container as `NAME=VALUE`. The output port and the options
+    /// of the Java virtual machine take precedence over variables of the same name.
+    pub fn variables(&self, output_port: u16) -> Vec<String> {
+        let mut env: Vec<String> = self
+            .variables
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect();
+        if let Some(ref java_opts) = self.java_opts {
+            env.push(format!("{JAVA_OPTS_VARIABLE}={java_opts}"));
+        }
+        env.push(format!("{OUTPUT_PORT_VARIABLE}={output_port}"));
+        env
+    }
+}
+
+/// Checks the configuration of the pipeline in a short-lived container, without running it.
+/// Returns whether the configuration is valid, and the output of Logstash.
+#[instrument]
+pub async fn test_pipeline_config(
+    docker: &bollard::Docker,
+    image: &Image,
+    env: &ContainerEnv,
+) -> anyhow::Result<(bool, String)> {
+    let response = docker
+        .create_container::<String, String>(
+            None,
+            Config {
+                image: Some(image.id.clone()),
+                // The output port is irrelevant, but must be defined to check the pipeline
+                env: Some(env.variables(OUTPUT_PORT)),
+                cmd: Some(vec![
+                    String::from("--path.config"),
+                    format!("{}/{}", LOGSTASH_PIPELINE_DIR, PIPELINE_NAME),
+                    String::from("--config.test_and_exit"),
+                ]),
+                attach_stdout: Some(true),
+                attach_stderr: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .context("Creating the Docker container")?;
+    let container = Container { id: response.id };
+
+    docker
+        .start_container::<String>(&container.id, None)
+        .await
+        .context("Starting the Docker container")?;
+
+    let mut exit_code = 0;
+    let mut wait_stream =
+        docker.wait_container(&container.id, None::<WaitContainerOptions<String>>);
+    while let Some(r) = wait_stream.next().await {
+        match r {
+            Ok(response) => exit_code = response.status_code,
+            Err(bollard::errors::Error::DockerContainerWaitError { code, .. }) => exit_code = code,
+            Err(e) => return Err(e).context("Waiting for the Docker container to exit"),
+        }
+    }
+
+    let output = container_logs(docker, &container, None).await?;
+
+    docker
+        .remove_container(
+            &container.id,
+            Some(RemoveContainerOptions {
+                force: true,
+                ..Default::default()
+            }),
+        )
+        .await
+        .context("Removing the Docker container")?;
+
+    Ok((exit_code == 0, output))
+}
+
+/// The error messages in the output of Logstash, or all of the output if there are none
+pub fn logstash_errors(output: &str) -> String {
+    let errors: Vec<&str> = output
+        .lines()
+        .filter(|l| l.contains("[ERROR]") || l.contains("[FATAL]"))
+        .collect();
+    match errors.is_empty() {
+        true => output.trim_end().to_string(),
+        false => errors.join("\n"),
+    }
+}
+
+/// The output of a container, or only its given number of last lines
+#[instrument]
+pub async fn container_logs(
+    docker: &bollard::Docker,
+    container: &Container,
+    tail: Option<usize>,
+) -> anyhow::Result<String> {
+    let mut output = String::new();
+    let mut logs_stream = docker.logs(
+        &container.id,
+        Some(LogsOptions::<String> {
+            stdout: true,
+            stderr: true,
+            tail: tail.map_or_else(|| String::from("all"), |t| t.to_string()),
+            ..Default::default()
+        }),
+    );
+    while let Some(log) = logs_stream.next().await {
+        output.push_str(
+            &log.context("Reading the Docker container logs")?
+                .to_string(),
+        );
+    }
+    Ok(output)
+}
+
+/// The definitions of the grok patterns shipped with Logstash (those of its ECS compatibility
+/// mode), read from a running container
+#[instrument]
+pub async fn core_grok_patterns(
+    docker: &bollard::Docker,
+    container: &Container,
+) -> anyhow::Result<String> {
+    let exec = docker
+        .create_exec(
+            &container.id,
+            CreateExecOptions {
+                cmd: Some(vec![
+                    String::from("sh"),
+                    String::from("-c"),
+                    format!("cat {GROK_PATTERNS_GLOB}"),
+                ]),
+                attach_stdout: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .context("Creating the command reading the grok patterns")?;
+    let StartExecResults::Attached { mut output, .. } = docker
+        .start_exec(&exec.id, None)
+        .await
+        .context("Reading the grok patterns")?
+    else {
+        return Err(anyhow!(
+            "The output of the command reading the grok patterns is detached"
+        ));
+    };
+
+    let mut patterns = String::new();
+    while let Some(log) = output.next().await {
+        patterns.push_str(&log.context("Reading the grok patterns")?.to_string());
+    }
+    Ok(patterns)
+}
+
+/// Streams the output of a container to standard error line by line, each prefixed with the given
+/// name, until the container stops or the returned task is aborted. Only output produced after
+/// the call is streamed unless `from_start` is set.
+pub fn stream_logs(
+    docker: &bollard::Docker,
+    container: &Container,
+    name: String,
+    from_start: bool,
+) -> JoinHandle<()> {
+    let docker = docker.clone();
+    let id = container.id.clone();
+    tokio::spawn(async move {
+        let mut logs_stream = docker.logs(
+            &id,
+            Some(LogsOptions::<String> {
+                follow: true,
+                stdout: true,
+                stderr: true,
+                tail: String::from(if from_start { "all" } else { "0" }),
+                ..Default::default()
+            }),
+        );
+        while let Some(Ok(log)) = logs_stream.next().await {
+            for line in log.to_string().lines() {
+                eprintln!("{name} | {line}");
+            }
+        }
+    })
+}
+
+/// Writes the output of a container to the given file until the container stops or the returned
+/// task is aborted. Only output produced after the call is written unless `from_start` is set.
+pub fn capture_logs(
+    docker: &bollard::Docker,
+    container: &Container,
+    path: PathBuf,
+    from_start: bool,
+) -> JoinHandle<anyhow::Result<()>> {
+    let docker = docker.clone();
+    let id = container.id.clone();
+    tokio::spawn(async move {
+        let mut file = tokio::fs::File::create(&path)
+            .await
+            .with_context(|| format!("Creating the log file: {}", path.display()))?;
+        let mut logs_stream = docker.logs(
+            &id,
+            Some(LogsOptions::<String> {
+                follow: true,
+                stdout: true,
+                stderr: true,
+                tail: String::from(if from_start { "all" } else { "0" }),
+                ..Default::default()
+            }),
+        );
+        while let Some(Ok(log)) = logs_stream.next().await {
+            file.write_all(&log.into_bytes())
+                .await
+                .with_context(|| format!("Writing the log file: {}", path.display()))?;
+        }
+        file.flush()
+            .await
+            .with_context(|| format!("Writing the log file: {}", path.display()))
+    })
+}
+
+/// How long to wait for the Logstash containers to become healthy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StartupOptions {
+    /// How often the health status of a container is checked before giving up
+    pub health_retries: usize,
+    /// The time between two checks of the health status
+    pub health_interval: Duration,
+    /// The time after which the start is given up, regardless of the checks
+    pub timeout: Duration,
+}
+
+impl Default for StartupOptions {
+    fn default() -> Self {
+        Self {
+            health_retries: DEFAULT_HEALTH_RETRIES,
+            health_interval: Duration::from_secs(DEFAULT_HEALTH_INTERVAL),
+            timeout: Duration::from_secs(DEFAULT_STARTUP_TIMEOUT),
+        }
+    }
+}
+
 #[instrument]
 pub async fn healthy(
     docker: &bollard::Docker,
@@ -322,3 +1449,442 @@ pub async fn healthy(
 
     Ok(())
 }
+
+/// A hash of the contents of the image archive, i.e. of the rules, ruby scripts, grok patterns and
+/// rendered templates
+pub fn content_hash(archive: &[u8]) -> String {
+    let mut hasher = std::collections::hash_map::DefaultHasher::default();
+    archive.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// A hash of what a running Logstash container cannot change without being recreated: the rendered
+/// configuration of Logstash (see [`build_image_archive`]), from which its image is built, and the
+/// settings the container is created with
+pub fn container_hash(
+    cache_dir: &Path,
+    options: &ImageOptions,
+    connection: &Connection,
+    limits: ResourceLimits,
+    env: &ContainerEnv,
+    binds: &[String],
+    capture: Option<&str>,
+) -> anyhow::Result<String> {
+    let mut settings = Vec::new();
+    for name in ConfigAssets::iter() {
+        let path = cache_dir.join(&*name);
+        settings.extend(
+            std::fs::read(&path)
+                .with_context(|| format!("Reading the config file: {}", path.display()))?,
+        );
+    }
+    let container = serde_json::json!({
+        "base_image": options.base_image(),
+        "platform": options.platform,
+        "plugins": options.plugins,
+        "codecs": options.codecs,
+        "clock": options.clock,
+        "protocol": options.input_protocol,
+        "transport": options.input_protocol.transport(),
+        "env": env.variables(connection.ports.output),
+        "ports": [connection.ports.input, connection.ports.api],
+        "publish_address": connection.publish_address(),
+        "memory": limits.memory.map(|m| m.0),
+        "memory_swap": limits.memory_swap.map(|m| m.0),
+        "cpus": limits.cpus,
+        "binds": binds,
+        "capture": capture,
+    });
+    settings.extend(container.to_string().into_bytes());
+    Ok(content_hash(&settings))
+}
+
+/// The Logstash image last built for a project, so that later runs can skip the build
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuiltImage {
+    /// The hash of the image archive the image was built from (see [`content_hash`])
+    pub content_hash: String,
+    pub image_id: String,
+}
+
+impl BuiltImage {
+    /// Reads the record of the last built image from the cache directory, if any
+    #[instrument]
+    pub fn load(cache_dir: &Path) -> anyhow::Result<Option<Self>> {
+        let path = cache_dir.join(BUILT_IMAGE_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let data = std::fs::read_to_string(&path)
+            .with_context(|| format!("Reading the file: {}", path.display()))?;
+        let record = serde_json::from_str(&data)
+            .with_context(|| format!("Parsing the file: {}", path.display()))?;
+        Ok(Some(record))
+    }
+
+    /// Records the built image in the cache directory
+    #[instrument]
+    pub fn save(&self, cache_dir: &Path) -> anyhow::Result<()> {
+        let path = cache_dir.join(BUILT_IMAGE_FILE);
+        let data = serde_json::to_string_pretty(self).context("Serializing the built image")?;
+        std::fs::write(&path, data).with_context(|| format!("Writing the file: {}", path.display()))
+    }
+
+    pub fn image(&self) -> Image {
+        Image {
+            id: self.image_id.clone(),
+        }
+    }
+}
+
+/// A Logstash container kept running after a run, so that later runs can reuse it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WarmContainer {
+    pub container_id: String,
+    pub image_id: String,
+    /// The hash of the configuration and settings the container was created with (see
+    /// [`container_hash`]), which cannot be changed without recreating it
+    #[serde(default)]
+    pub container_hash: String,
+}
+
+impl WarmContainer {
+    /// Reads the record of the warm container from the cache directory, if any
+    #[instrument]
+    pub fn load(cache_dir: &Path) -> anyhow::Result<Option<Self>> {
+        let path = cache_dir.join(WARM_CONTAINER_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let data = std::fs::read_to_string(&path)
+            .with_context(|| format!("Reading the file: {}", path.display()))?;
+        let record = serde_json::from_str(&data)
+            .with_context(|| format!("Parsing the file: {}", path.display()))?;
+        Ok(Some(record))
+    }
+
+    /// Records the warm container in the cache directory
+    #[instrument]
+    pub fn save(&self, cache_dir: &Path) -> anyhow::Result<()> {
+        let path = cache_dir.join(WARM_CONTAINER_FILE);
+        let data = serde_json::to_string_pretty(self).context("Serializing the warm container")?;
+        std::fs::write(&path, data).with_context(|| format!("Writing the file: {}", path.display()))
+    }
+
+    /// Whether the container is still running and healthy
+    #[instrument]
+    pub async fn is_ready(&self, docker: &bollard::Docker) -> bool {
+        let Ok(inspect) = docker.inspect_container(&self.container_id, None).await else {
+            return false;
+        };
+        let state = inspect.state.unwrap_or_default();
+        state.running == Some(true)
+            && state.health.and_then(|h| h.status) == Some(HealthStatusEnum::HEALTHY)
+    }
+
+    pub fn image(&self) -> Image {
+        Image {
+            id: self.image_id.clone(),
+        }
+    }
+
+    pub fn container(&self) -> Container {
+        Container {
+            id: self.container_id.clone(),
+        }
+    }
+}
+
+/// Creates a tar archive of the pipeline file (which must have been written to the cache directory
+/// before, see [`build_image_archive`]), the ruby scripts and the grok patterns, laid out as in the
+/// Logstash home directory
+pub fn build_pipeline_archive(
+    cache_dir: &Path,
+    scripts: &[PathBuf],
+    patterns: &[PathBuf],
+) -> anyhow::Result<Vec<u8>> {
+    let mut ark = tar::Builder::new(Vec::new());
+    ark.mode(tar::HeaderMode::Deterministic);
+
+    let pipeline_path = cache_dir.join(PIPELINE_NAME);
+    ark.append_path_with_name(&pipeline_path, Path::new("pipeline").join(PIPELINE_NAME))
+        .with_context(|| format!("Appending the pipeline file: {}", pipeline_path.display()))?;
+    for (dir, files) in [(SCRIPTS_DIR, scripts), (PATTERNS_DIR, patterns)] {
+        for file in files {
+            let name = file
+                .file_name()
+                .ok_or_else(|| anyhow!("Finding the file name of {}", file.display()))?;
+            ark.append_path_with_name(file, Path::new(dir).join(name))
+                .with_context(|| format!("Appending the file: {}", file.display()))?;
+        }
+    }
+
+    ark.into_inner().context("Finishing the tar archive")
+}
+
+/// The number of successful and failed reloads of the pipeline, and the last error if any
+async fn pipeline_reloads(
+    client: &reqwest::Client,
+    connection: &Connection,
+) -> anyhow::Result<(u64, u64, Option<String>)> {
+    let stats: serde_json::Value = client
+        .get(format!(
+            "http://{}:{}/_node/stats/pipelines/{}",
+            connection.logstash_host(),
+            connection.ports.api,
+            PIPELINE_ID
+        ))
+        .send()
+        .await
+        .context("Requesting the pipeline statistics of Logstash")?
+        .json()
+        .await
+        .context("Reading the pipeline statistics of Logstash")?;
+    let reloads = &stats["pipelines"][PIPELINE_ID]["reloads"];
+    Ok((
+        reloads["successes"].as_u64().unwrap_or(0),
+        reloads["failures"].as_u64().unwrap_or(0),
+        reloads["last_error"]["message"].as_str().map(String::from),
+    ))
+}
+
+/// Replaces the pipeline, ruby scripts and grok patterns of a running Logstash container, and
+/// waits until Logstash has reloaded the pipeline
+#[instrument(skip(archive))]
+pub async fn swap_pipeline(
+    docker: &bollard::Docker,
+    container: &Container,
+    connection: &Connection,
+    archive: Vec<u8>,
+) -> anyhow::Result<()> {
+    docker
+        .upload_to_container(
+            &container.id,
+            Some(UploadToContainerOptions {
+                path: LOGSTASH_HOME_DIR,
+                ..Default::default()
+            }),
+            archive.into(),
+        )
+        .await
+        .context("Uploading the pipeline to the Logstash container")?;
+
+    let client = reqwest::Client::new();
+    let (successes, failures, _) = pipeline_reloads(&client, connection).await?;
+
+    // Logstash reloads its pipeline on SIGHUP, even if automatic reloading is disabled
+    docker
+        .kill_container(
+            &container.id,
+            Some(KillContainerOptions { signal: "SIGHUP" }),
+        )
+        .await
+        .context("Signalling the Logstash container to reload its pipeline")?;
+
+    let started = std::time::Instant::now();
+    loop {
+        sleep(Duration::from_millis(500)).await;
+        match pipeline_reloads(&client, connection).await {
+            Ok((s, _, _)) if s > successes => return Ok(()),
+            Ok((_, f, error)) if f > failures => {
+                return Err(anyhow!(
+                    "Logstash failed to reload the pipeline: {}",
+                    error.unwrap_or_default()
+                ))
+            }
+            _ if started.elapsed() > RELOAD_TIMEOUT => {
+                return Err(anyhow!(
+                    "Logstash did not reload the pipeline within {RELOAD_TIMEOUT:?}"
+                ))
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Removes all container images built by Lotus, after stopping and removing the containers created
+/// from them. Returns the IDs of the removed containers and the tags of the removed images.
+#[instrument]
+pub async fn remove_artifacts(
+    docker: &bollard::Docker,
+) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+    let prefix = image_name_prefix();
+    let images: Vec<_> = docker
+        .list_images(Some(ListImagesOptions::<String>::default()))
+        .await
+        .context("Listing the Docker images")?
+        .into_iter()
+        .filter(|i| i.repo_tags.iter().any(|t| t.starts_with(&prefix)))
+        .collect();
+
+    let mut containers = Vec::new();
+    for container in docker
+        .list_containers(Some(ListContainersOptions::<String> {
+            all: true,
+            ..Default::default()
+        }))
+        .await
+        .context("Listing the Docker containers")?
+    {
+        let from_lotus_image = container
+            .image_id
+            .as_ref()
+            .is_some_and(|id| images.iter().any(|i| i.id == *id))
+            || container
+                .image
+                .as_ref()
+                .is_some_and(|name| name.starts_with(&prefix));
+        let Some(id) = container.id.filter(|_| from_lotus_image) else {
+            continue;
+        };
+        docker
+            .remove_container(
+                &id,
+                Some(RemoveContainerOptions {
+                    force: true,
+                    ..Default::default()
+                }),
+            )
+            .await
+            .with_context(|| format!("Removing the Docker container {id}"))?;
+        containers.push(id);
+    }
+
+    let mut tags = Vec::new();
+    for image in images {
+        docker
+            .remove_image(
+                &image.id,
+                Some(RemoveImageOptions {
+                    force: true,
+                    ..Default::default()
+                }),
+                None,
+            )
+            .await
+            .with_context(|| format!("Removing the Docker image {}", image.id))?;
+        tags.extend(image.repo_tags);
+    }
+
+    Ok((containers, tags))
+}
+
+/// The name of this machine, which tells the containers of its runs apart from those of other
+/// machines that share the Docker daemon
+pub fn hostname() -> String {
+    #[cfg(unix)]
+    {
+        let mut name = [0u8; 256];
+        // SAFETY: the buffer is valid for its whole length, which is passed along
+        if unsafe { libc::gethostname(name.as_mut_ptr().cast(), name.len()) } == 0 {
+            let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+            return String::from_utf8_lossy(&name[..len]).into_owned();
+        }
+    }
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_default()
+}
+
+/// Whether a process with the given ID runs on this machine. Where this cannot be determined,
+/// the process is assumed to run.
+fn is_process_alive(pid: &str) -> bool {
+    #[cfg(unix)]
+    {
+        let Some(pid) = pid.parse::<libc::pid_t>().ok().filter(|&p| p > 0) else {
+            return false;
+        };
+        // SAFETY: the signal 0 is not sent, it only checks whether the process exists
+        if unsafe { libc::kill(pid, 0) } == 0 {
+            return true;
+        }
+        // The processes of other users cannot be signalled, but they run all the same
+        std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        true
+    }
+}
+
+/// Whether a container of the project with the given labels is left over by an earlier run on
+/// this machine: it stopped, or the Lotus process that created it has ended. The containers of
+/// other machines sharing the Docker daemon are never left over, since their processes cannot be
+/// checked from here.
+pub fn is_leftover(labels: &HashMap<String, String>, running: bool) -> bool {
+    if labels.get(&label(HOSTNAME_LABEL)) != Some(&hostname()) {
+        return false;
+    }
+    let owner_alive = labels
+        .get(&label(PID_LABEL))
+        .is_some_and(|pid| is_process_alive(pid));
+    !(running && owner_alive)
+}
+
+/// Removes the containers of the given project left over by earlier runs that crashed (i.e. those
+/// which stopped, or whose Lotus process has ended), except the given one, as well as the images
+/// of the project that were superseded by later builds. Returns the IDs of the removed containers
+/// and images.
+#[instrument]
+pub async fn reap_leftovers(
+    docker: &bollard::Docker,
+    project: &str,
+    keep: Option<&str>,
+) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+    let filter = format!("{}={}", label(PROJECT_LABEL), project);
+
+    let mut containers = Vec::new();
+    for container in docker
+        .list_containers(Some(ListContainersOptions::<String> {
+            all: true,
+            filters: [(String::from("label"), vec![filter.clone()])].into(),
+            ..Default::default()
+        }))
+        .await
+        .context("Listing the Docker containers")?
+    {
+        let Some(id) = container.id else {
+            continue;
+        };
+        let running = container.state.as_deref() == Some("running");
+        let labels = container.labels.unwrap_or_default();
+        if keep == Some(id.as_str()) || !is_leftover(&labels, running) {
+            continue;
+        }
+        debug!("Remove the leftover Docker container {id}");
+        docker
+            .remove_container(
+                &id,
+                Some(RemoveContainerOptions {
+                    force: true,
+                    ..Default::default()
+                }),
+            )
+            .await
+            .with_context(|| format!("Removing the Docker container {id}"))?;
+        containers.push(id);
+    }
+
+    let mut images = Vec::new();
+    for image in docker
+        .list_images(Some(ListImagesOptions::<String> {
+            filters: [
+                (String::from("label"), vec![filter]),
+                (String::from("dangling"), vec![String::from("true")]),
+            ]
+            .into(),
+            ..Default::default()
+        }))
+        .await
+        .context("Listing the Docker images")?
+    {
+        // Superseded images still used by the containers of concurrent runs are kept
+        if docker.remove_image(&image.id, None, None).await.is_ok() {
+            debug!("Removed the superseded Docker image {}", image.id);
+            images.push(image.id);
+        }
+    }
+
+    Ok((containers, images))
+}
@@ -0,0 +1,198 @@
+use std::fmt;
+use std::net::TcpListener;
+use std::path::Path;
+
+use tracing::instrument;
+
+use crate::assertions::jq_version;
+use crate::LOCALHOST;
+
+/// The oldest Docker Engine API version Lotus works with (i.e. that of Docker 20.10)
+const MIN_DOCKER_API_VERSION: (u32, u32) = (1, 41);
+/// The free disk space needed to build the Logstash image and run its container
+const MIN_FREE_DISK_SPACE: u64 = 2 * 1024 * 1024 * 1024;
+
+/// The result of a single check of the environment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnosis {
+    /// What was checked
+    pub check: String,
+    /// What was found
+    pub finding: String,
+    /// How to fix the problem, if the check failed
+    pub remedy: Option<String>,
+}
+
+impl Diagnosis {
+    fn ok(check: impl Into<String>, finding: impl Into<String>) -> Self {
+        Self {
+            check: check.into(),
+            finding: finding.into(),
+            remedy: None,
+        }
+    }
+
+    pub(crate) fn failed(
+        check: impl Into<String>,
+        finding: impl Into<String>,
+        remedy: impl Into<String>,
+    ) -> Self {
+        Self {
+            check: check.into(),
+            finding: finding.into(),
+            remedy: Some(remedy.into()),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.remedy.is_none()
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.remedy {
+            None => write!(f, "ok    {}: {}", self.check, self.finding),
+            Some(remedy) => write!(
+                f,
+                "fail  {}: {}\n      hint: {}",
+                self.check, self.finding, remedy
+            ),
+        }
+    }
+}
+
+/// The remedy if the Docker daemon cannot be reached
+pub const DOCKER_REMEDY: &str = "start the Docker daemon (or the Podman API socket, e.g. with `systemctl --user start podman.socket`), and make sure that DOCKER_HOST (or CONTAINER_HOST) points to it and that your user may access its socket";
+
+/// Checks whether the Docker daemon responds and is recent enough
+#[instrument]
+pub async fn check_docker(docker: &bollard::Docker) -> Vec<Diagnosis> {
+    if let Err(e) = docker.ping().await {
+        return vec![Diagnosis::failed(
+            "Docker connectivity",
+            format!("the Docker daemon does not respond: {e}"),
+            DOCKER_REMEDY,
+        )];
+    }
+
+    let version = match docker.version().await {
+        Ok(version) => check_docker_version(
+            version.version.as_deref().unwrap_or("unknown"),
+            version.api_version.as_deref().unwrap_or("unknown"),
+        ),
+        Err(e) => Diagnosis::failed(
+            "Docker version",
+            format!("unable to determine the version: {e}"),
+            "upgrade the Docker daemon",
+        ),
+    };
+
+    vec![
+        Diagnosis::ok("Docker connectivity", "the Docker daemon responds"),
+        version,
+    ]
+}
+
+/// Checks whether the Docker Engine API version is at least the oldest supported one
+pub fn check_docker_version(version: &str, api_version: &str) -> Diagnosis {
+    let parsed = api_version
+        .split_once('.')
+        .and_then(|(major, minor)| Some((major.parse::<u32>().ok()?, minor.parse::<u32>().ok()?)));
+    let (major, minor) = MIN_DOCKER_API_VERSION;
+    match parsed {
+        Some(v) if v >= MIN_DOCKER_API_VERSION => Diagnosis::ok(
+            "Docker version",
+            format!("Docker {version} (API {api_version})"),
+        ),
+        _ => Diagnosis::failed(
+            "Docker version",
+            format!("Docker {version} (API {api_version}) is too old"),
+            format!(
+                "upgrade the Docker daemon to a version supporting API {major}.{minor} or later"
+            ),
+        ),
+    }
+}
+
+/// Checks whether the file system holding the given path has enough free space
+#[instrument]
+pub fn check_disk_space(name: &str, path: &Path) -> Diagnosis {
+    let check = format!("Free disk space ({name})");
+    let existing = path.ancestors().find(|p| p.exists()).unwrap_or(path);
+    match fs4::available_space(existing) {
+        Ok(free) if free >= MIN_FREE_DISK_SPACE => Diagnosis::ok(
+            check,
+            format!("{} MiB free at {}", free / (1024 * 1024), path.display()),
+        ),
+        Ok(free) => Diagnosis::failed(
+            check,
+            format!(
+                "only {} MiB free at {}",
+                free / (1024 * 1024),
+                path.display()
+            ),
+            format!(
+                "free at least {} MiB, e.g. with `lotus clean` or `docker system prune`",
+                MIN_FREE_DISK_SPACE / (1024 * 1024)
+            ),
+        ),
+        Err(e) => Diagnosis::failed(
+            check,
+            format!(
+                "unable to determine the free space at {}: {e}",
+                path.display()
+            ),
+            "make sure that the location exists and is readable",
+        ),
+    }
+}
+
+/// Checks whether the given local port is available to Lotus
+#[instrument]
+pub fn check_port(port: u16) -> Diagnosis {
+    let check = format!("Port {port}");
+    match TcpListener::bind((LOCALHOST, port)) {
+        Ok(_) => Diagnosis::ok(check, "available"),
+        Err(e) => Diagnosis::failed(
+            check,
+            format!("unavailable: {e}"),
+            format!("stop the process or container bound to port {port}, e.g. a leftover Lotus container (see `lotus clean`)"),
+        ),
+    }
+}
+
+/// Checks whether the `jq` program can be run, which is only required if any of the test cases
+/// has jq assertions
+#[instrument]
+pub fn check_jq(required: bool) -> Diagnosis {
+    let check = "jq";
+    match (jq_version(), required) {
+        (Ok(version), _) => Diagnosis::ok(check, format!("{version} installed")),
+        (Err(_), false) => Diagnosis::ok(check, "not installed, but no test case has jq assertions"),
+        (Err(e), true) => Diagnosis::failed(
+            check,
+            format!("unable to run jq, which the jq assertions of the test cases require: {e:#}"),
+            "install jq (see https://jqlang.github.io/jq/download/) and make sure that it is on the PATH",
+        ),
+    }
+}
+
+/// Checks whether the base image of the Logstash container image is available locally or can be
+/// pulled from its registry
+#[instrument]
+pub async fn check_base_image(docker: &bollard::Docker, image: &str) -> Diagnosis {
+    let check = format!("Base image {image}");
+
+    if docker.inspect_image(image).await.is_ok() {
+        return Diagnosis::ok(check, "available locally");
+    }
+    match docker.inspect_registry_image(image, None).await {
+        Ok(_) => Diagnosis::ok(check, "can be pulled"),
+        Err(e) => Diagnosis::failed(
+            check,
+            format!("cannot be pulled: {e}"),
+            "check your network connection and proxy settings, and whether the registry requires you to log in with `docker login`",
+        ),
+    }
+}
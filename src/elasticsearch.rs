@@ -0,0 +1,165 @@
+use anyhow::{anyhow, Context};
+use axum::{
+    extract::State,
+    http::{header::CONTENT_ENCODING, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Map, Value};
+use tracing::{debug, instrument, warn};
+
+use crate::server::{with_metadata, EventRouter};
+use crate::{
+    ELASTICSEARCH_FIELD, ELASTICSEARCH_TEST_ID_FIELD, ELASTICSEARCH_VERSION, ELASTIC_PRODUCT_HEADER,
+};
+
+/// A document operation of a bulk request that the Elasticsearch output of Logstash sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct BulkOperation {
+    /// The correlation ID carried by the document, if any
+    pub test_id: Option<String>,
+    /// The action (`index`, `create`, `update` or `delete`)
+    pub action: String,
+    /// The document, without the correlation ID, and with the action, the target index (or data
+    /// stream), the document ID, the routing and the ingest pipeline in `@lotus_elasticsearch`
+    pub event: Value,
+}
+
+/// Parses the body of a bulk request: pairs of an action line and a document line, except for
+/// deletions which have no document
+pub fn bulk_operations(body: &str) -> anyhow::Result<Vec<BulkOperation>> {
+    let mut lines = body.lines().filter(|l| !l.trim().is_empty());
+    let mut operations = Vec::new();
+    while let Some(line) = lines.next() {
+        let header: Map<String, Value> =
+            serde_json::from_str(line).context("Parsing the action of a bulk operation")?;
+        let (action, parameters) = header
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("A bulk operation has no action"))?;
+
+        let mut document = match action.as_str() {
+            "delete" => Map::new(),
+            "index" | "create" | "update" => {
+                let line = lines
+                    .next()
+                    .ok_or_else(|| anyhow!("The {action} operation has no document"))?;
+                let source: Value = serde_json::from_str(line)
+                    .context("Parsing the document of a bulk operation")?;
+                let source = match action.as_str() {
+                    "update" => source
+                        .get("doc")
+                        .or_else(|| source.get("upsert"))
+                        .cloned()
+                        .unwrap_or(source),
+                    _ => source,
+                };
+                match source {
+                    Value::Object(document) => document,
+                    _ => {
+                        return Err(anyhow!(
+                            "The document of the {action} operation is no object"
+                        ))
+                    }
+                }
+            }
+            _ => return Err(anyhow!("Unknown bulk action: {action}")),
+        };
+
+        let test_id = match document.remove(ELASTICSEARCH_TEST_ID_FIELD) {
+            Some(Value::String(test_id)) => Some(test_id),
+            _ => None,
+        };
+        let mut target = Map::new();
+        target.insert(String::from("action"), Value::String(action.clone()));
+        for (parameter, field) in [
+            ("_index", "index"),
+            ("_id", "id"),
+            ("routing", "routing"),
+            ("pipeline", "pipeline"),
+        ] {
+            if let Some(value) = parameters.get(parameter).filter(|v| !v.is_null()) {
+                target.insert(String::from(field), value.clone());
+            }
+        }
+        document.insert(String::from(ELASTICSEARCH_FIELD), Value::Object(target));
+
+        operations.push(BulkOperation {
+            test_id,
+            action,
+            event: Value::Object(document),
+        });
+    }
+    Ok(operations)
+}
+
+/// Answers the requests with which the Elasticsearch output discovers the cluster (e.g. its
+/// version, license, templates and policies) as if everything was in place
+#[instrument]
+pub async fn cluster() -> Response {
+    (
+        [(ELASTIC_PRODUCT_HEADER, "Elasticsearch")],
+        Json(json!({
+            "name": "lotus",
+            "cluster_name": "lotus",
+            "version": { "number": ELASTICSEARCH_VERSION },
+            "license": { "status": "active", "type": "basic" },
+            "tagline": "You Know, for Search",
+        })),
+    )
+        .into_response()
+}
+
+/// Receives a bulk request of the Elasticsearch output, and forwards each document to the test
+/// case it belongs to
+#[instrument(skip(router, body))]
+pub async fn bulk(State(router): State<EventRouter>, headers: HeaderMap, body: String) -> Response {
+    if headers.contains_key(CONTENT_ENCODING) {
+        warn!("Received a compressed bulk request; disable the compression of the Elasticsearch output");
+        return StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response();
+    }
+    let operations = match bulk_operations(&body) {
+        Ok(operations) => operations,
+        Err(e) => {
+            warn!("Received an invalid bulk request: {e:?}");
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    };
+
+    debug!(
+        "Forward the {} documents of the bulk request",
+        operations.len()
+    );
+    let mut items = Vec::with_capacity(operations.len());
+    for (i, operation) in operations.into_iter().enumerate() {
+        let target = &operation.event[ELASTICSEARCH_FIELD];
+        let (status, result) = match operation.action.as_str() {
+            "index" | "create" => (201, "created"),
+            "update" => (200, "updated"),
+            _ => (200, "deleted"),
+        };
+        items.push(json!({
+            operation.action.as_str(): {
+                "_index": target.get("index").cloned().unwrap_or_default(),
+                "_id": target.get("id").cloned().unwrap_or_else(|| json!(format!("lotus-{i}"))),
+                "status": status,
+                "result": result,
+            }
+        }));
+        if !router
+            .route(operation.test_id.as_deref(), with_metadata(operation.event))
+            .await
+        {
+            warn!(
+                "Discarded a document that does not belong to a running test case: {:?}",
+                operation.test_id
+            );
+        }
+    }
+
+    (
+        [(ELASTIC_PRODUCT_HEADER, "Elasticsearch")],
+        Json(json!({ "took": 1, "errors": false, "items": items })),
+    )
+        .into_response()
+}
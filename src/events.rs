@@ -0,0 +1,53 @@
+use serde::Serialize;
+use tracing::warn;
+
+use crate::report::TestResult;
+
+/// How the progress of a run is presented while the test cases run
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Progress bars and the result of each test case, for humans
+    #[default]
+    Human,
+    /// One JSON object per line for each lifecycle event, for tools following the run
+    Jsonl,
+}
+
+/// A step in the lifecycle of a run
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LifecycleEvent<'a> {
+    /// The run started with the given number of test cases
+    SuiteStarted { tests: usize },
+    /// The Logstash container image was built
+    ImageBuilt { image_id: &'a str },
+    /// The Logstash container is ready to accept input events
+    ContainerStarted { container_id: &'a str },
+    /// A test case started running
+    TestStarted { name: &'a str },
+    /// A test case finished running, possibly after retries
+    TestFinished {
+        #[serde(flatten)]
+        result: &'a TestResult,
+    },
+    /// The run finished, either after all test cases or after the first failure
+    SuiteFinished {
+        passed: usize,
+        failed: usize,
+        skipped: usize,
+        duration_ms: u128,
+    },
+}
+
+impl OutputFormat {
+    /// Writes the event to standard output as a single line of JSON, if requested
+    pub fn emit(&self, event: &LifecycleEvent) {
+        if *self != OutputFormat::Jsonl {
+            return;
+        }
+        match serde_json::to_string(event) {
+            Ok(line) => println!("{line}"),
+            Err(e) => warn!("Unable to serialize the lifecycle event {event:?}: {e}"),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use anyhow::Context;
+use serde_json::Value;
+use tracing::instrument;
+
+use crate::{FIXTURES_DIR, VARS_FILE};
+
+/// Variables shared by all test cases, read from the optional file `fixtures/vars.toml` in the
+/// target directory. Input events may reference them as Handlebars placeholders (e.g.
+/// `{{ hostname }}`).
+#[derive(Debug, Clone)]
+pub struct Fixtures {
+    vars: Value,
+}
+
+impl Default for Fixtures {
+    fn default() -> Self {
+        Self {
+            vars: Value::Object(Default::default()),
+        }
+    }
+}
+
+impl Fixtures {
+    #[instrument]
+    pub fn load(target: &Path) -> anyhow::Result<Self> {
+        let path = target.join(FIXTURES_DIR).join(VARS_FILE);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        let data = std::fs::read_to_string(&path)
+            .with_context(|| format!("Reading the file: {}", path.display()))?;
+        let vars = toml::from_str(&data)
+            .with_context(|| format!("Parsing the file: {}", path.display()))?;
+        Ok(Self { vars })
+    }
+
+    /// Replaces the placeholders in the given text with the values of the shared variables.
+    /// Unknown variables are an error.
+    #[instrument(skip(text))]
+    pub fn render(&self, text: &str) -> anyhow::Result<String> {
+        let mut hbs = handlebars::Handlebars::new();
+        hbs.set_strict_mode(true);
+        hbs.register_escape_fn(handlebars::no_escape);
+        hbs.render_template(text, &self.vars)
+            .context("Rendering the placeholders of the input events")
+    }
+
+    /// Renders the placeholders in every string of the given value
+    pub fn render_value(&self, value: &mut Value) -> anyhow::Result<()> {
+        match value {
+            Value::String(s) => *s = self.render(s)?,
+            Value::Array(values) => {
+                for v in values {
+                    self.render_value(v)?;
+                }
+            }
+            Value::Object(map) => {
+                for v in map.values_mut() {
+                    self.render_value(v)?;
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use clap::Args;
+
+use crate::conf::{parse, tokenize, Token};
+
+const INDENT: &str = "    ";
+
+#[derive(Debug, Args)]
+pub struct FmtArguments {
+    /// Only report the rule files that are not formatted canonically, instead of formatting them
+    #[arg(long)]
+    pub check: bool,
+}
+
+/// What separates two consecutive tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Separator {
+    Nothing,
+    Space,
+    Newline,
+    BlankLine,
+}
+
+/// Formats a Logstash configuration canonically: each block is indented by four spaces, opening
+/// braces are placed at the end of the line that opens the block and closing braces on a line of
+/// their own, and strings are quoted with double quotes where possible. Comments and single blank
+/// lines are kept.
+pub fn format_rule(text: &str) -> anyhow::Result<String> {
+    parse(text)?;
+    let tokens = tokenize(text)?;
+
+    let mut formatted = String::new();
+    let mut depth = 0usize;
+    let mut previous: Option<(&Token, usize)> = None;
+    for (token, line) in &tokens {
+        if matches!(token, Token::Close | Token::CloseBracket) {
+            depth = depth.saturating_sub(1);
+        }
+        let separator = match previous {
+            Some((previous, end)) => separator(previous, end, token, *line),
+            None => Separator::Nothing,
+        };
+        match separator {
+            Separator::Nothing => (),
+            Separator::Space => formatted.push(' '),
+            Separator::Newline | Separator::BlankLine => {
+                formatted.truncate(formatted.trim_end_matches(' ').len());
+                formatted.push('\n');
+                if separator == Separator::BlankLine {
+                    formatted.push('\n');
+                }
+                formatted.push_str(&INDENT.repeat(depth));
+            }
+        }
+        formatted.push_str(&render(token));
+        if matches!(token, Token::Open | Token::OpenBracket) {
+            depth += 1;
+        }
+        let end = match token {
+            Token::Str(s, _) => line + s.matches('\n').count(),
+            _ => *line,
+        };
+        previous = Some((token, end));
+    }
+    formatted.push('\n');
+
+    Ok(formatted)
+}
+
+fn separator(previous: &Token, previous_line: usize, token: &Token, line: usize) -> Separator {
+    let newline = || match line > previous_line + 1 {
+        true => Separator::BlankLine,
+        false => Separator::Newline,
+    };
+    let kept = || match line > previous_line {
+        true => newline(),
+        false => Separator::Space,
+    };
+
+    match (previous, token) {
+        (Token::Comment(_), _) => newline(),
+        (_, Token::Comment(_)) => kept(),
+        (_, Token::Open) => Separator::Space,
+        (Token::Open, Token::Close) => Separator::Nothing,
+        (Token::Open, _) => Separator::Newline,
+        (_, Token::Close) => Separator::Newline,
+        (Token::Close, Token::Word(w)) if w == "else" => Separator::Space,
+        (Token::Close, _) => newline(),
+        (_, Token::Comma) => Separator::Nothing,
+        (Token::OpenBracket, _) | (_, Token::CloseBracket) => match line > previous_line {
+            true => Separator::Newline,
+            false => Separator::Nothing,
+        },
+        (Token::CloseBracket, Token::OpenBracket) => Separator::Nothing,
+        (Token::Word(w), _) if w == "!" || w == "(" => Separator::Nothing,
+        (_, Token::Word(w)) if w.starts_with(')') => Separator::Nothing,
+        _ => kept(),
+    }
+}
+
+fn render(token: &Token) -> String {
+    match token {
+        Token::Str(s, '\'') if !s.contains('"') => format!("\"{s}\""),
+        token => token.to_string(),
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use clap::Args;
+use serde_json::{json, Value};
+use tracing::{debug, instrument};
+
+use crate::{EXPECTED_FILE, INPUT_FILE};
+
+#[derive(Debug, Args)]
+pub struct GenerateArguments {
+    /// A file containing one raw log line per test case
+    pub log_file: PathBuf,
+    /// The directory (relative to the tests directory) that the test cases are written to
+    /// [default: the name of the log file without its extension]
+    #[arg(long)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct NewTestArguments {
+    /// The name of the test case, i.e. its directory relative to the tests directory
+    pub name: String,
+    /// A file containing a captured event, used as the input of the test case
+    #[arg(long)]
+    pub from_event: Option<PathBuf>,
+    /// Record the expected output right away, instead of writing a placeholder
+    #[arg(long)]
+    pub record: bool,
+}
+
+/// Writes an `input.json` with the `message` field set to each non-empty line of the log file
+/// into a numbered test case directory (e.g. `0001`) below `test_cases_dir`. Returns the number
+/// of test cases written.
+#[instrument]
+pub fn generate_test_cases(log_file: &Path, test_cases_dir: &Path) -> anyhow::Result<usize> {
+    let log = std::fs::read_to_string(log_file)
+        .with_context(|| format!("Reading the log file: {}", log_file.display()))?;
+    let lines: Vec<&str> = log.lines().filter(|l| !l.trim().is_empty()).collect();
+    let width = lines.len().to_string().len().max(4);
+
+    for (i, line) in lines.iter().enumerate() {
+        let test_case_dir = test_cases_dir.join(format!("{:0width$}", i + 1));
+        debug!("Write the test case {}", test_case_dir.display());
+        std::fs::create_dir_all(&test_case_dir).with_context(|| {
+            format!(
+                "Creating the test case directory: {}",
+                test_case_dir.display()
+            )
+        })?;
+        let mut data = serde_json::to_string_pretty(&json!({ "message": line }))?;
+        data.push('\n');
+        let input_file = test_case_dir.join(INPUT_FILE);
+        std::fs::write(&input_file, data)
+            .with_context(|| format!("Writing the input file: {}", input_file.display()))?;
+    }
+
+    Ok(lines.len())
+}
+
+/// Creates a test case directory containing an `input.json`, either copied from the event file
+/// or a placeholder, and unless `record` is set, a placeholder `expected.json`
+#[instrument]
+pub fn new_test_case(
+    test_case_dir: &Path,
+    event_file: Option<&Path>,
+    record: bool,
+) -> anyhow::Result<()> {
+    if test_case_dir.exists() {
+        return Err(anyhow!(
+            "The test case directory already exists: {}",
+            test_case_dir.display()
+        ));
+    }
+
+    let input = match event_file {
+        Some(event_file) => {
+            let data = std::fs::read_to_string(event_file)
+                .with_context(|| format!("Reading the event file: {}", event_file.display()))?;
+            serde_json::from_str(&data)
+                .with_context(|| format!("Parsing the event file: {}", event_file.display()))?
+        }
+        None => json!({ "message": "" }),
+    };
+
+    debug!("Write the test case {}", test_case_dir.display());
+    std::fs::create_dir_all(test_case_dir).with_context(|| {
+        format!(
+            "Creating the test case directory: {}",
+            test_case_dir.display()
+        )
+    })?;
+    write_json(&test_case_dir.join(INPUT_FILE), &input)?;
+    if !record {
+        write_json(&test_case_dir.join(EXPECTED_FILE), &json!({}))?;
+    }
+
+    Ok(())
+}
+
+fn write_json(file: &Path, data: &Value) -> anyhow::Result<()> {
+    let mut data = serde_json::to_string_pretty(data)?;
+    data.push('\n');
+    std::fs::write(file, data).with_context(|| format!("Writing the file: {}", file.display()))
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::conf::{parse, ConfValue, Item};
+use crate::paths::FieldPath;
+use crate::GROK_FAILURE_TAG;
+
+/// The maximum nesting depth of grok pattern references, which guards against cyclic definitions
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+/// The patterns of a grok filter of the pipeline
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GrokFilter {
+    /// The patterns matched against each field, in the order they are tried
+    pub matches: Vec<(String, Vec<String>)>,
+    /// The patterns defined inline by the filter (`pattern_definitions`)
+    pub definitions: HashMap<String, String>,
+}
+
+/// Collects the grok filters of a rule file, including those in conditional branches. Rule files
+/// that cannot be parsed contain none.
+pub fn grok_filters(text: &str) -> Vec<GrokFilter> {
+    fn collect(items: &[Item], filters: &mut Vec<GrokFilter>) {
+        for item in items {
+            match item {
+                Item::Plugin { name, settings } if name == "grok" => {
+                    let mut filter = GrokFilter::default();
+                    for setting in settings {
+                        match (setting.name.as_str(), &setting.value) {
+                            ("match", ConfValue::Hash(entries)) => {
+                                filter
+                                    .matches
+                                    .extend(entries.iter().map(|(field, patterns, _)| {
+                                        let patterns = patterns.strings();
+                                        (
+                                            field.clone(),
+                                            patterns.into_iter().map(String::from).collect(),
+                                        )
+                                    }))
+                            }
+                            // The legacy form lists fields and patterns alternately
+                            ("match", ConfValue::Array(values)) => filter.matches.extend(
+                                values.chunks(2).filter_map(|pair| match pair {
+                                    [field, pattern] => Some((
+                                        field.strings().concat(),
+                                        pattern.strings().into_iter().map(String::from).collect(),
+                                    )),
+                                    _ => None,
+                                }),
+                            ),
+                            ("pattern_definitions", ConfValue::Hash(entries)) => filter
+                                .definitions
+                                .extend(entries.iter().map(|(name, value, _)| {
+                                    (name.clone(), value.strings().concat())
+                                })),
+                            _ => (),
+                        }
+                    }
+                    filters.push(filter);
+                }
+                Item::Plugin { .. } => (),
+                Item::Conditional(branches) => {
+                    for (_, body, _) in branches {
+                        collect(body, filters);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut filters = Vec::new();
+    for section in parse(text).unwrap_or_default() {
+        collect(&section.items, &mut filters);
+    }
+    filters
+}
+
+/// Reads the grok pattern definitions of a pattern file (one `NAME regex` per line)
+pub fn pattern_definitions(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| l.split_once(char::is_whitespace))
+        .map(|(name, regex)| (name.to_string(), regex.trim().to_string()))
+        .collect()
+}
+
+/// Replaces the pattern references (e.g. `%{IP:client}`) in a grok pattern with their
+/// definitions, and rewrites the Oniguruma constructs that the `regex` crate lacks: named groups
+/// become plain groups, atomic groups become non-capturing ones, and lookarounds are dropped.
+/// The result is thus only an approximation of the pattern that Logstash matches.
+pub fn expand_pattern(
+    pattern: &str,
+    definitions: &HashMap<String, String>,
+) -> anyhow::Result<String> {
+    fn expand(
+        pattern: &str,
+        definitions: &HashMap<String, String>,
+        depth: usize,
+    ) -> anyhow::Result<String> {
+        if depth > MAX_EXPANSION_DEPTH {
+            return Err(anyhow!(
+                "The grok pattern definitions are nested too deeply"
+            ));
+        }
+        let reference = Regex::new(r"%\{(\w+)(?::[^:}]+)?(?::\w+)?\}").expect("a valid regex");
+        let mut expanded = String::new();
+        let mut last = 0;
+        for captures in reference.captures_iter(pattern) {
+            let (Some(whole), Some(name)) = (captures.get(0), captures.get(1)) else {
+                continue;
+            };
+            let definition = definitions
+                .get(name.as_str())
+                .ok_or_else(|| anyhow!("The grok pattern '{}' is not defined", name.as_str()))?;
+            expanded.push_str(&pattern[last..whole.start()]);
+            expanded.push_str("(?:");
+            expanded.push_str(&expand(definition, definitions, depth + 1)?);
+            expanded.push(')');
+            last = whole.end();
+        }
+        expanded.push_str(&pattern[last..]);
+        Ok(expanded)
+    }
+
+    let expanded = expand(pattern, definitions, 0)?;
+    let named_group = Regex::new(r"\(\?P?<[A-Za-z_]\w*>").expect("a valid regex");
+    let lookaround = Regex::new(r"\(\?<?[=!][^()]*\)").expect("a valid regex");
+    let expanded = named_group.replace_all(&expanded, "(?:");
+    let expanded = lookaround.replace_all(&expanded, "");
+    Ok(expanded.replace("(?>", "(?:"))
+}
+
+/// Splits a grok pattern into its pattern references and the text between them
+fn segments(pattern: &str) -> Vec<&str> {
+    let reference = Regex::new(r"%\{[^}]*\}").expect("a valid regex");
+    let mut segments = Vec::new();
+    let mut last = 0;
+    for m in reference.find_iter(pattern) {
+        if m.start() > last {
+            segments.push(&pattern[last..m.start()]);
+        }
+        segments.push(m.as_str());
+        last = m.end();
+    }
+    if last < pattern.len() {
+        segments.push(&pattern[last..]);
+    }
+    segments
+}
+
+/// Explains why a grok pattern does not match a value: the longest leading part of the pattern
+/// that matches, and the part of the pattern at which the match stopped
+pub fn explain_mismatch(
+    pattern: &str,
+    definitions: &HashMap<String, String>,
+    value: &str,
+) -> String {
+    if let Err(e) = expand_pattern(pattern, definitions) {
+        return format!("{e}");
+    }
+
+    let segments = segments(pattern);
+    for k in (0..=segments.len()).rev() {
+        let prefix = segments[..k].concat();
+        let Some(m) = expand_pattern(&prefix, definitions)
+            .ok()
+            .and_then(|e| Regex::new(&e).ok())
+            .and_then(|re| re.find(value))
+        else {
+            continue;
+        };
+        if k == segments.len() {
+            return String::from(
+                "the pattern matches (as far as it can be checked outside of Logstash)",
+            );
+        }
+        if k == 0 {
+            return format!("{:?} does not match anywhere", segments[0]);
+        }
+        return format!(
+            "{:?} matched {:?}, but {:?} does not match what follows: {:?}",
+            prefix,
+            m.as_str(),
+            segments[k],
+            &value[m.end()..]
+        );
+    }
+
+    String::from("no part of the pattern can be checked outside of Logstash")
+}
+
+/// Explains for each output event tagged with a grok failure why each grok pattern does not match
+/// its field. Returns nothing if no event was tagged.
+pub fn explain_grok_failures(
+    events: &Value,
+    filters: &[GrokFilter],
+    definitions: &HashMap<String, String>,
+) -> Option<String> {
+    let events = match events {
+        Value::Array(events) => events.iter().collect(),
+        event => vec![event],
+    };
+    let failed: Vec<(usize, &Value)> = events
+        .into_iter()
+        .enumerate()
+        .filter(|(_, e)| {
+            e["tags"]
+                .as_array()
+                .is_some_and(|t| t.iter().any(|t| t == GROK_FAILURE_TAG))
+        })
+        .collect();
+    if failed.is_empty() {
+        return None;
+    }
+
+    let mut lines = Vec::new();
+    for (i, event) in failed {
+        lines.push(format!(
+            "Output event {i} is tagged with {GROK_FAILURE_TAG}:"
+        ));
+        for filter in filters {
+            let mut definitions = definitions.clone();
+            definitions.extend(filter.definitions.clone());
+            for (field, patterns) in &filter.matches {
+                let value = field
+                    .parse::<FieldPath>()
+                    .ok()
+                    .and_then(|p| p.get(event).and_then(Value::as_str).map(String::from));
+                let Some(value) = value else {
+                    continue;
+                };
+                for pattern in patterns {
+                    lines.push(format!(
+                        "  [{}] {:?}: {}",
+                        field,
+                        pattern,
+                        explain_mismatch(pattern, &definitions, &value)
+                    ));
+                }
+            }
+        }
+    }
+    Some(lines.join("\n"))
+}
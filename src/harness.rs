@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tracing::instrument;
+
+use crate::collectors::NamePattern;
+use crate::compare::{CompareMode, Matcher};
+use crate::paths::FieldPath;
+use crate::report::TestReport;
+use crate::runner::TestCase;
+use crate::{DefaultArguments, Project, Session};
+
+/// Runs the test cases of a project from Rust code (e.g. from the integration tests of a crate),
+/// and returns the report of the run instead of printing it. Options without a method of their
+/// own are set through [`Harness::arguments`].
+///
+/// ```no_run
+/// # async fn example() -> anyhow::Result<()> {
+/// let report = lotus::Harness::new()
+///     .target("logstash")
+///     .tests_dir("tests/lotus")
+///     .compare(lotus::compare::CompareMode::Inclusive)
+///     .run()
+///     .await?;
+/// assert!(report.is_success(), "{report}");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Harness {
+    args: DefaultArguments,
+    names: Option<Vec<String>>,
+    matcher: Option<Arc<dyn Matcher>>,
+}
+
+impl Harness {
+    /// A harness with the defaults of the command line, for the project in the current directory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The project directory
+    pub fn target(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.args.target = Some(dir.into());
+        self
+    }
+
+    /// The directory of the rules, relative to the project directory
+    pub fn rules_dir(mut self, dir: impl Into<String>) -> Self {
+        self.args.rules_dir = dir.into();
+        self
+    }
+
+    /// The directory of the test cases, relative to the project directory
+    pub fn tests_dir(mut self, dir: impl Into<String>) -> Self {
+        self.args.tests_dir = dir.into();
+        self
+    }
+
+    /// The directory of the ruby scripts, relative to the project directory
+    pub fn scripts_dir(mut self, dir: impl Into<String>) -> Self {
+        self.args.scripts_dir = dir.into();
+        self
+    }
+
+    /// The directory of the grok patterns, relative to the project directory
+    pub fn patterns_dir(mut self, dir: impl Into<String>) -> Self {
+        self.args.patterns_dir = dir.into();
+        self
+    }
+
+    /// How strictly the output of every test case is compared with its expected output, unless
+    /// overridden by its `test.toml`
+    pub fn compare(mut self, mode: CompareMode) -> Self {
+        self.args.compare = Some(mode);
+        self
+    }
+
+    /// Compare the actual with the expected output of every test case with a matcher of one's
+    /// own, instead of [`DefaultMatcher`](crate::compare::DefaultMatcher)
+    pub fn matcher(mut self, matcher: impl Matcher + 'static) -> Self {
+        self.matcher = Some(Arc::new(matcher));
+        self
+    }
+
+    /// Strip a field from the actual and expected output of every test case before comparison
+    pub fn ignore(mut self, path: FieldPath) -> Self {
+        self.args.ignore.push(path);
+        self
+    }
+
+    /// Only run the test cases whose name matches the pattern (or any other pattern given)
+    pub fn filter(mut self, pattern: NamePattern) -> Self {
+        self.args.filter.push(pattern);
+        self
+    }
+
+    /// Only run the test cases with exactly the given names
+    pub fn only(mut self, names: Vec<String>) -> Self {
+        self.names = Some(names);
+        self
+    }
+
+    /// Only run the test cases with the tag (or any other tag given)
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.args.tags.push(tag.into());
+        self
+    }
+
+    /// The version of Logstash the test cases run against
+    pub fn logstash_version(mut self, version: impl Into<String>) -> Self {
+        self.args.logstash_version = Some(version.into());
+        self
+    }
+
+    /// Run all test cases, instead of stopping at the first failure
+    pub fn keep_going(mut self, keep_going: bool) -> Self {
+        self.args.keep_going = keep_going;
+        self
+    }
+
+    /// Let the operating system choose free ports, so that harnesses of concurrent tests do not
+    /// collide
+    pub fn ephemeral_ports(mut self, ephemeral_ports: bool) -> Self {
+        self.args.ephemeral_ports = ephemeral_ports;
+        self
+    }
+
+    /// Sets any other option, as if given on the command line
+    pub fn arguments(mut self, configure: impl FnOnce(&mut DefaultArguments)) -> Self {
+        configure(&mut self.args);
+        self
+    }
+
+    /// Runs the selected test cases, and returns the report of the run. Failed test cases are
+    /// reported, not returned as an error.
+    #[instrument]
+    pub async fn run(self) -> anyhow::Result<TestReport> {
+        let project = Project::load(&self.args)?;
+        let test_cases = self.select(&project)?;
+        let mut options = project.test_options(&self.args)?;
+        options.matcher = self.matcher;
+        project.report(test_cases, options).await
+    }
+
+    /// Boots the Logstash environment of the project, to process arbitrary events with it instead
+    /// of running the test cases
+    pub async fn session(self) -> anyhow::Result<Session> {
+        Session::start(&self.args).await
+    }
+
+    /// The names of the test cases that [`Harness::run`] runs
+    #[instrument]
+    pub fn test_names(&self) -> anyhow::Result<Vec<String>> {
+        let project = Project::load(&self.args)?;
+        Ok(self
+            .select(&project)?
+            .iter()
+            .map(|t| t.name().to_string())
+            .collect())
+    }
+
+    fn select(&self, project: &Project) -> anyhow::Result<Vec<TestCase>> {
+        let mut test_cases = project.select_tests(&self.args)?;
+        if let Some(names) = &self.names {
+            test_cases.retain(|t| names.contains(&t.name));
+        }
+        Ok(test_cases)
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{debug, instrument};
+
+use crate::runner::TestContext;
+use crate::HOOK_ID_PREFIX;
+
+/// Steps run around the test cases, e.g. to seed the state of aggregate filters or to reset
+/// external stubs between test cases
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    /// Run once after Logstash started, before the first test case
+    pub before_all: Vec<Hook>,
+    /// Run before each test case, which fails if a step fails
+    pub before_each: Vec<Hook>,
+    /// Run after each test case, whether it passed or not. The test case fails if a step fails.
+    pub after_each: Vec<Hook>,
+    /// Run once after the last test case
+    pub after_all: Vec<Hook>,
+}
+
+/// A step of a hook
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum Hook {
+    /// A shell command, run in the project directory. It fails the hook if it exits with a
+    /// non-zero status.
+    Command { command: String },
+    /// Priming events posted to the HTTP input of Logstash. Their output events are discarded.
+    Events { events: Vec<Value> },
+}
+
+/// When a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    BeforeAll,
+    BeforeEach,
+    AfterEach,
+    AfterAll,
+}
+
+impl fmt::Display for HookKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookKind::BeforeAll => write!(f, "before_all"),
+            HookKind::BeforeEach => write!(f, "before_each"),
+            HookKind::AfterEach => write!(f, "after_each"),
+            HookKind::AfterAll => write!(f, "after_all"),
+        }
+    }
+}
+
+impl Hooks {
+    /// The steps of the given hook
+    pub fn steps(&self, kind: HookKind) -> &[Hook] {
+        match kind {
+            HookKind::BeforeAll => &self.before_all,
+            HookKind::BeforeEach => &self.before_each,
+            HookKind::AfterEach => &self.after_each,
+            HookKind::AfterAll => &self.after_all,
+        }
+    }
+}
+
+/// Runs a shell command of a hook in the given directory. The command learns which hook runs it
+/// (`LOTUS_HOOK`), and for which test case (`LOTUS_TEST_NAME`) if it runs for a single one.
+#[instrument]
+pub async fn run_command(
+    command: &str,
+    dir: &Path,
+    kind: HookKind,
+    test_name: Option<&str>,
+) -> anyhow::Result<()> {
+    let mut process = tokio::process::Command::new("sh");
+    process
+        .arg("-c")
+        .arg(command)
+        .current_dir(dir)
+        .env("LOTUS_HOOK", kind.to_string())
+        .kill_on_drop(true);
+    if let Some(test_name) = test_name {
+        process.env("LOTUS_TEST_NAME", test_name);
+    }
+    let output = process
+        .output()
+        .await
+        .with_context(|| format!("Running the command: {command}"))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "The command `{}` failed ({}): {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Runs the steps of a hook in order, sending priming events to the Logstash containers in the
+/// given slots of the pool
+#[instrument(skip(context, hooks))]
+pub async fn run_hooks(
+    context: &TestContext,
+    hooks: &Hooks,
+    kind: HookKind,
+    slots: &[usize],
+    dir: &Path,
+    host: &str,
+    test_name: Option<&str>,
+) -> anyhow::Result<()> {
+    for (i, step) in hooks.steps(kind).iter().enumerate() {
+        match step {
+            Hook::Command { command } => {
+                debug!("Run the command of step {i} of the {kind} hook");
+                run_command(command, dir, kind, test_name).await
+            }
+            Hook::Events { events } => {
+                debug!("Send the events of step {i} of the {kind} hook");
+                for &slot in slots {
+                    for event in events {
+                        context
+                            .send_event(host, slot, &format!("{HOOK_ID_PREFIX}{kind}"), event)
+                            .await?;
+                    }
+                }
+                Ok(())
+            }
+        }
+        .with_context(|| format!("Running step {i} of the {kind} hook"))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use tracing::{debug, instrument};
+
+use crate::assets::ScaffoldAssets;
+use crate::{PATTERNS_DIR, RULES_DIR, SCRIPTS_DIR, TESTS_DIR};
+
+/// The directories of a project, relative to its root
+#[derive(Debug, Clone)]
+pub struct Layout {
+    pub rules_dir: PathBuf,
+    pub tests_dir: PathBuf,
+    pub scripts_dir: PathBuf,
+    pub patterns_dir: PathBuf,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            rules_dir: PathBuf::from(RULES_DIR),
+            tests_dir: PathBuf::from(TESTS_DIR),
+            scripts_dir: PathBuf::from(SCRIPTS_DIR),
+            patterns_dir: PathBuf::from(PATTERNS_DIR),
+        }
+    }
+}
+
+impl Layout {
+    /// Places a scaffold file in the corresponding directory of the project, e.g.
+    /// `rules/00-example.conf` in the rules directory
+    fn place(&self, target: &Path, name: &str) -> PathBuf {
+        let name = Path::new(name);
+        let mut components = name.components();
+        let dir = match components.next() {
+            Some(Component::Normal(c)) if c == RULES_DIR => &self.rules_dir,
+            Some(Component::Normal(c)) if c == TESTS_DIR => &self.tests_dir,
+            _ => return target.join(name),
+        };
+        target.join(dir).join(components.as_path())
+    }
+}
+
+/// Creates the directories of a new project in the target directory, along with an example rule,
+/// an example test case and a starter configuration file. Fails without writing anything if any
+/// of the files exists already. Returns the created files.
+#[instrument]
+pub fn init_project(target: &Path, layout: &Layout) -> anyhow::Result<Vec<PathBuf>> {
+    let files: Vec<(PathBuf, _)> = ScaffoldAssets::iter()
+        .map(|name| (layout.place(target, &name), name))
+        .collect();
+    if let Some((path, _)) = files.iter().find(|(path, _)| path.exists()) {
+        return Err(anyhow!("The file exists already: {}", path.display()));
+    }
+
+    for dir in [
+        &layout.rules_dir,
+        &layout.tests_dir,
+        &layout.scripts_dir,
+        &layout.patterns_dir,
+    ] {
+        let dir = target.join(dir);
+        debug!("Create the directory {}", dir.display());
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Creating the directory: {}", dir.display()))?;
+    }
+
+    for (path, name) in &files {
+        let file = ScaffoldAssets::get(name)
+            .ok_or_else(|| anyhow!("The scaffold file is missing: {name}"))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Creating the directory: {}", parent.display()))?;
+        }
+        std::fs::write(path, file.data)
+            .with_context(|| format!("Writing the file: {}", path.display()))?;
+    }
+
+    let mut created: Vec<PathBuf> = files.into_iter().map(|(path, _)| path).collect();
+    created.sort();
+    Ok(created)
+}
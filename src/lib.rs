@@ -1,31 +1,137 @@
+use std::future::Future;
 use std::hash::{Hash, Hasher};
 use std::net::{IpAddr, Ipv4Addr};
-use std::path::PathBuf;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
 
 use anyhow::{anyhow, Context};
-use clap::{ArgAction, Parser};
+use clap::{ArgAction, Parser, Subcommand};
 use directories::ProjectDirs;
-use tokio::sync::mpsc::channel;
-use tracing::{debug, info, instrument};
+use regex::Regex;
+use tracing::{debug, info, instrument, Level};
 
 use crate::collectors::{collect_patterns, collect_scripts};
+use crate::compare::{CompareMode, DiffStyle, Verbosity};
+use crate::config::ProjectConfig;
+use crate::docker::{
+    build_container_image, logstash_errors, remove_artifacts, test_pipeline_config, Connection,
+    ContainerEnv, ContainerRuntime, EnvVar, ImageOptions, InputProtocol, MemorySize, Ports,
+    ResourceLimits, StartupOptions, SyslogFormat,
+};
+use crate::events::OutputFormat;
+use crate::fixtures::Fixtures;
+use crate::paths::FieldPath;
 
-use self::collectors::{collect_rules, collect_tests};
-use self::runner::run_tests;
-use self::server::run_server;
+use self::bench::{generate_events, load_events, run_bench, BenchArguments};
+use self::clean::{remove_cache_dirs, CleanArguments};
+use self::collectors::{collect_rules, collect_tests, shuffle_tests, NamePattern, TestFilter};
+use self::diff::{checkout_rules, output_changes, DiffArguments, OutputDiff};
+use self::doctor::{
+    check_base_image, check_disk_space, check_docker, check_jq, check_port, Diagnosis,
+    DOCKER_REMEDY,
+};
+use self::format::{format_rule, FmtArguments};
+use self::generate::{generate_test_cases, new_test_case, GenerateArguments, NewTestArguments};
+pub use self::harness::Harness;
+use self::init::{init_project, Layout};
+use self::lint::{defined_patterns, lint_rule, LintArguments};
+use self::logs::LogPattern;
+use self::mutate::{mutants, Mutant, MutantStatus};
+use self::report::TestReport;
+use self::report::{ReportFormat, ReportTarget};
+use self::runner::{
+    collect_test_outputs, run_test_cases, run_tests, PipelineFiles, TestCase, TestContext,
+    TestOptions,
+};
+use self::server::{bind_server, run_server, EventRouter};
+pub use self::session::{run_single_event, RunArguments, Session};
+use self::watch::{next_changes, watch, Reaction, WatchArguments, WatchedPaths};
 
+pub mod assertions;
 pub mod assets;
+pub mod bench;
+pub mod clean;
 pub mod collectors;
+pub mod compare;
+mod conf;
+pub mod config;
+pub mod coverage;
+pub mod diff;
 pub mod docker;
+pub mod doctor;
+pub mod elasticsearch;
+pub mod events;
+pub mod fixtures;
+pub mod format;
+pub mod generate;
+pub mod grok;
+pub mod harness;
+pub mod hooks;
+pub mod init;
+pub mod lint;
+pub mod logs;
+pub mod lumberjack;
+pub mod mutate;
+pub mod paths;
+pub mod progress;
+pub mod report;
 pub mod runner;
+pub mod runs;
+pub mod schema;
 pub mod server;
+pub mod session;
+pub mod sockets;
+pub mod stats;
+#[cfg(feature = "libtest-mimic")]
+pub mod trials;
+pub mod tunnel;
+pub mod watch;
 
 const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 const INPUT_PORT: u16 = 5066;
 const OUTPUT_PORT: u16 = 5067;
 const API_PORT: u16 = 9600;
+const POOL_PORT_STRIDE: u16 = 10;
+const OUTPUT_PORT_VARIABLE: &str = "LOTUS_OUTPUT_PORT";
+const JAVA_OPTS_VARIABLE: &str = "LS_JAVA_OPTS";
 const INPUT_FILE: &str = "input.json";
+const INPUT_NDJSON_FILE: &str = "input.ndjson";
+const INPUT_TEXT_FILE: &str = "input.txt";
+const NDJSON_EXTENSION: &str = "ndjson";
+const TEXT_EXTENSION: &str = "txt";
+const DEFAULT_CODEC: &str = "plain";
+const DOCKER_SOCKET: &str = "/var/run/docker.sock";
+const PODMAN_USER_SOCKET: &str = "podman/podman.sock";
+const PODMAN_SYSTEM_SOCKET: &str = "/run/podman/podman.sock";
+const CONNECTION_TIMEOUT: u64 = 120;
+const DEFAULT_CALLBACK_HOST: &str = "host.docker.internal";
+const LOGSTASH_IMAGE: &str = "docker.elastic.co/logstash/logstash";
+const DEFAULT_LOGSTASH_VERSION: &str = "8.6.2";
+const CODEC_CONTENT_TYPE_PREFIX: &str = "text/x-lotus-";
 const EXPECTED_FILE: &str = "expected.json";
+const DROP_MARKER_FILE: &str = "expected.drop";
+const SCHEMA_FILE: &str = "expected.schema.json";
+const DEFAULT_TEST_TIMEOUT: u64 = 60;
+const DEFAULT_HEALTH_RETRIES: usize = 10;
+const DEFAULT_HEALTH_INTERVAL: u64 = 10;
+const DEFAULT_STARTUP_TIMEOUT: u64 = 300;
+const STARTUP_LOG_LINES: usize = 50;
+const STALL_LOG_LINES: usize = 20;
+const DROP_TIMEOUT: Duration = Duration::from_secs(5);
+const ASSERTIONS_FILES: [&str; 4] = [
+    "assertions.json",
+    "assertions.yaml",
+    "assertions.yml",
+    "assertions.jq",
+];
+const REGEX_PATTERN_PREFIX: &str = "re:";
+const CASE_FILE_EXTENSIONS: [&str; 2] = ["yaml", "yml"];
+const CONFIG_FILE: &str = "lotus.toml";
+const METADATA_FILE: &str = "test.toml";
+const FIXTURES_DIR: &str = "fixtures";
+const VARS_FILE: &str = "vars.toml";
 const RULE_EXTENSION: &str = "conf";
 const SCRIPT_EXTENSION: &str = "rb";
 const RULES_DIR: &str = "rules";
@@ -33,23 +139,126 @@ const TESTS_DIR: &str = "tests";
 const SCRIPTS_DIR: &str = "scripts";
 const PATTERNS_DIR: &str = "patterns";
 const FQAN: [&str; 3] = ["net", "nausicaea", "lotus"];
+const PROJECT_LABEL: &str = "project";
+const PID_LABEL: &str = "pid";
+const HOSTNAME_LABEL: &str = "hostname";
+const TEST_ID_HEADER: &str = "X-Lotus-Test-Id";
+const OUTPUTS_PATH: &str = "outputs";
+const OUTPUT_CHANNEL_FIELD: &str = "@lotus_output";
+const METADATA_FIELD: &str = "@lotus_metadata";
+const ELASTICSEARCH_PATH: &str = "_elasticsearch";
+const ELASTICSEARCH_FIELD: &str = "@lotus_elasticsearch";
+const ELASTICSEARCH_TEST_ID_FIELD: &str = "_lotus_test_id";
+const ELASTICSEARCH_VERSION: &str = "8.6.2";
+const ELASTIC_PRODUCT_HEADER: &str = "X-Elastic-Product";
 const CHANNEL_CAPACITY: usize = 32;
 const IMAGE_ARCHIVE_NAME: &str = "image.tar";
+const DOCKERFILE_NAME: &str = "Dockerfile";
 const PIPELINE_NAME: &str = "logstash.conf";
+const PIPELINE_ID: &str = "main";
+const GROK_FAILURE_TAG: &str = "_grokparsefailure";
+const GROK_PATTERNS_GLOB: &str =
+    "/usr/share/logstash/vendor/bundle/jruby/*/gems/logstash-patterns-core-*/patterns/ecs-v1/*";
+const STATS_RESULT_NAME: &str = "_node/stats";
+const LOGS_RESULT_NAME: &str = "_logs";
+const BEFORE_ALL_RESULT_NAME: &str = "_hooks/before_all";
+const AFTER_ALL_RESULT_NAME: &str = "_hooks/after_all";
+const HOOK_ID_PREFIX: &str = "lotus-hook-";
+const SESSION_ID_PREFIX: &str = "lotus-session-";
+const SETTLE_TIMEOUT: Duration = Duration::from_millis(500);
+const LOG_MATCHES_SHOWN: usize = 10;
+const BENCH_ID_PREFIX: &str = "bench-";
+const DEFAULT_BENCH_EVENTS: usize = 1000;
+const DIFF_CACHE_DIR: &str = "diff";
+const COVERAGE_ID_PREFIX: &str = "lotus-coverage-";
+const MUTANT_PATTERN: &str = "^lotus-mutant$";
+const LOGSTASH_HOME_DIR: &str = "/usr/share/logstash";
+const LOGSTASH_PIPELINE_DIR: &str = "/usr/share/logstash/pipeline";
+const FILES_DIR: &str = "files";
+const FILES_MOUNT_DIR: &str = "/usr/share/logstash/lotus-files";
+const WARM_CONTAINER_FILE: &str = "container.json";
+const BUILT_IMAGE_FILE: &str = "image.json";
+const RUNS_DIR: &str = "runs";
+const MAX_RUN_DIRS: usize = 10;
+const RELOAD_TIMEOUT: Duration = Duration::from_secs(60);
 const INPUT_TEMPLATE_NAME: &str = "input.conf";
 const OUTPUT_TEMPLATE_NAME: &str = "output.conf";
+const REPORT_TEMPLATE_NAME: &str = "report.html";
+const SLOWEST_TESTS: usize = 10;
+const TESTS_FAILED_EXIT_CODE: u8 = 1;
+const ERROR_EXIT_CODE: u8 = 2;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct DefaultArguments {
+    #[command(subcommand)]
+    pub command: Option<Command>,
     /// Optional target path (e.g. the path to a directory containing `config` and `tests`
     /// subdirectories)
     pub target: Option<PathBuf>,
     /// If set, do not delete the Docker container after completion of the test run
     #[arg(short, long)]
     pub no_delete_container: bool,
+    /// Keep the Logstash container running after the test run, and reuse it in later runs with
+    /// this flag: its pipeline is replaced instead of rebuilding the image and waiting for
+    /// Logstash to start
+    #[arg(long, env = "LOTUS_REUSE_CONTAINER")]
+    pub reuse_container: bool,
+    /// The number of identical Logstash containers the test cases are distributed across. The
+    /// ports of each further container are offset by 10 (e.g. 5076 and 9610 for the second one).
+    #[arg(
+        long,
+        default_value_t = 1,
+        env = "LOTUS_CONTAINERS",
+        conflicts_with = "reuse_container"
+    )]
+    pub containers: usize,
+    /// Run the base image and bind-mount the configuration, rules, ruby scripts and grok patterns
+    /// into its containers, instead of building a Logstash image for the project. Plugins
+    /// installed by the image build (i.e. `json_encode`) are unavailable unless the base image
+    /// provides them. Requires a local Docker daemon.
+    #[arg(long, env = "LOTUS_MOUNT", conflicts_with = "reuse_container")]
+    pub mount: bool,
+    /// Run the pipeline with this many workers and without preserving the order of events
+    /// (`pipeline.ordered: false`), as in production, and compare the output events of each test
+    /// case regardless of their order
+    #[arg(long, value_name = "COUNT", env = "LOTUS_WORKERS")]
+    pub workers: Option<NonZeroUsize>,
+    /// The memory each Logstash container may use at most (e.g. `2g`)
+    #[arg(long, value_name = "SIZE", env = "LOTUS_MEMORY")]
+    pub memory: Option<MemorySize>,
+    /// The memory including swap space each Logstash container may use at most (e.g. `3g`)
+    #[arg(
+        long,
+        value_name = "SIZE",
+        env = "LOTUS_MEMORY_SWAP",
+        requires = "memory"
+    )]
+    pub memory_swap: Option<MemorySize>,
+    /// The number of CPUs each Logstash container may use at most (e.g. `1.5`)
+    #[arg(long, env = "LOTUS_CPUS")]
+    pub cpus: Option<f64>,
+    /// Options of the Java virtual machine running Logstash, e.g. `-Xms512m -Xmx512m` for a
+    /// smaller heap
+    #[arg(
+        long,
+        value_name = "OPTIONS",
+        env = "LOTUS_JAVA_OPTS",
+        allow_hyphen_values = true
+    )]
+    pub java_opts: Option<String>,
+    /// Set an environment variable of the Logstash container, e.g. to substitute a value into
+    /// the pipeline where it refers to `${MY_VAR}` (overrides the same variable in `lotus.toml`)
+    #[arg(short, long, value_name = "NAME=VALUE")]
+    pub env: Vec<EnvVar>,
+    /// Display more detail: the complete actual and expected output of failed test cases, and
+    /// increasingly detailed logs if repeated (e.g. `-vv`)
     #[arg(short, long, action = ArgAction::Count)]
-    pub verbose: usize,
+    pub verbose: u8,
+    /// Display less detail: only the number of differences between the actual and the expected
+    /// output of failed test cases, and only errors in the logs
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
     /// Optionally change the location of the Logstash rules
     #[arg(short, long, default_value_t = String::from(RULES_DIR), env = "LOTUS_RULES_DIR")]
     pub rules_dir: String,
@@ -62,9 +271,245 @@ pub struct DefaultArguments {
     /// Optionally change the location of the grok patterns associated with the pipeline
     #[arg(short, long, default_value_t = String::from(PATTERNS_DIR), env = "LOTUS_PATTERNS_DIR")]
     pub patterns_dir: String,
+    /// Strip a field (e.g. `host.ip` or `[host][ip]`) from the actual and expected output before
+    /// comparison (in addition to those listed in `lotus.toml`)
+    #[arg(long, value_name = "PATH", value_delimiter = ',', env = "LOTUS_IGNORE")]
+    pub ignore: Vec<FieldPath>,
+    /// How strictly the actual output is compared with the expected output, unless overridden by
+    /// a test case [default: strict]
+    #[arg(long, value_enum, value_name = "MODE", env = "LOTUS_COMPARE")]
+    pub compare: Option<CompareMode>,
+    /// Record the actual output of each test case to its `expected.json` instead of comparing
+    /// them (i.e. snapshot testing)
+    #[arg(long)]
+    pub update_expected: bool,
+    /// The Logstash codec that raw text input events (e.g. from `input.txt`) are decoded with
+    /// [default: plain]
+    #[arg(long, env = "LOTUS_INPUT_CODEC")]
+    pub input_codec: Option<String>,
+    /// The protocol over which the input events are sent to Logstash, i.e. the input plugin of
+    /// the pipeline [default: http]
+    #[arg(long, value_enum, env = "LOTUS_INPUT_PROTOCOL")]
+    pub input_protocol: Option<InputProtocol>,
+    /// The codec of the `tcp` or `udp` input, which must decode the JSON documents Lotus sends
+    /// (e.g. `json`) [default: json_lines for tcp, json for udp]
+    #[arg(long, value_name = "CODEC", env = "LOTUS_SOCKET_CODEC")]
+    pub socket_codec: Option<String>,
+    /// The format of the messages that the `syslog` input parses [default: rfc3164]
+    #[arg(long, value_enum, env = "LOTUS_SYSLOG_FORMAT")]
+    pub syslog_format: Option<SyslogFormat>,
+    /// The version of Logstash the test cases run against, i.e. the tag of the official Logstash
+    /// image (e.g. `7.17.16`) [default: 8.6.2]
+    #[arg(long, value_name = "VERSION", env = "LOTUS_LOGSTASH_VERSION")]
+    pub logstash_version: Option<String>,
+    /// An image that replaces the official Logstash image as the base of the container image
+    /// (e.g. one from an internal registry with additional plugins or certificates). It must be
+    /// derived from the official Logstash image.
+    #[arg(
+        long,
+        value_name = "IMAGE",
+        env = "LOTUS_BASE_IMAGE",
+        conflicts_with = "logstash_version"
+    )]
+    pub base_image: Option<String>,
+    /// Pin the base image to the given digest (e.g. `sha256:…`), which is verified before the
+    /// container image is built
+    #[arg(long, value_name = "DIGEST", env = "LOTUS_BASE_IMAGE_DIGEST")]
+    pub base_image_digest: Option<String>,
+    /// The platform the Logstash image is built for and its container runs on (e.g.
+    /// `linux/amd64` to emulate it on Apple silicon, or `linux/arm64`) [default: that of the
+    /// container runtime]
+    #[arg(long, value_name = "PLATFORM", env = "LOTUS_PLATFORM")]
+    pub platform: Option<String>,
+    /// Never pull images from a registry (e.g. on machines without internet access), and fail if
+    /// the base image is not present locally
+    #[arg(long, env = "LOTUS_OFFLINE")]
+    pub offline: bool,
+    /// Report which filter sections and conditional branches of the rules the test cases
+    /// exercised, by injecting a marker plugin into each of them
+    #[arg(long, env = "LOTUS_COVERAGE")]
+    pub coverage: bool,
+    /// Include the `@metadata` of the output events (e.g. `[@metadata][index]`) in the actual
+    /// output, except that of Lotus itself
+    #[arg(long, env = "LOTUS_CAPTURE_METADATA")]
+    pub capture_metadata: bool,
+    /// Pin `@timestamp` of the input events and `[event][created]` of the output events to the
+    /// given instant (e.g. `2024-01-01T00:00:00Z`), instead of removing the former and keeping
+    /// the latter
+    #[arg(long, value_name = "INSTANT", env = "LOTUS_CLOCK")]
+    pub clock: Option<String>,
+    /// Fail the run if Logstash logs a line matching the given regular expression while the test
+    /// cases run (e.g. `\[WARN` or `\[ERROR`), in addition to those in `lotus.toml`
+    #[arg(long, value_name = "REGEX")]
+    pub fail_on_log: Vec<LogPattern>,
+    /// The container engine that builds the Logstash image and runs its container
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ContainerRuntime::Auto,
+        env = "LOTUS_CONTAINER_RUNTIME"
+    )]
+    pub container_runtime: ContainerRuntime,
+    /// The address of a Docker daemon to use instead of the local one (e.g.
+    /// `tcp://build-host:2376`, or `ssh://user@build-host` to tunnel the connection and the ports
+    /// of the Logstash container through SSH). The ports of the Logstash container are then
+    /// reached on that host.
+    #[arg(long, value_name = "URL", env = "DOCKER_HOST")]
+    pub docker_host: Option<String>,
+    /// Connect to the Docker daemon with TLS, verifying its certificate and authenticating with a
+    /// client certificate
+    #[arg(long, env = "DOCKER_TLS_VERIFY")]
+    pub docker_tls_verify: bool,
+    /// The directory holding `ca.pem`, `cert.pem` and `key.pem` for TLS connections to the Docker
+    /// daemon [default: ~/.docker]
+    #[arg(long, value_name = "DIR", env = "DOCKER_CERT_PATH")]
+    pub docker_cert_path: Option<PathBuf>,
+    /// The host name under which the Logstash container reaches Lotus to send its output events,
+    /// e.g. the address of this machine when using a remote Docker daemon
+    #[arg(
+        long,
+        value_name = "HOST",
+        default_value = DEFAULT_CALLBACK_HOST,
+        env = "LOTUS_CALLBACK_HOST"
+    )]
+    pub callback_host: String,
+    /// The host port under which the HTTP input of Logstash is published [default: 5066]
+    #[arg(long, value_name = "PORT", env = "LOTUS_INPUT_PORT")]
+    pub input_port: Option<u16>,
+    /// The port on which Lotus receives the output events of Logstash [default: 5067]
+    #[arg(long, value_name = "PORT", env = "LOTUS_OUTPUT_PORT")]
+    pub output_port: Option<u16>,
+    /// The host port under which the API of Logstash is published [default: 9600]
+    #[arg(long, value_name = "PORT", env = "LOTUS_API_PORT")]
+    pub api_port: Option<u16>,
+    /// Let the operating system choose free ports instead, so that several runs of Lotus on the
+    /// same machine do not collide (a port may also be chosen freely by giving it as 0)
+    #[arg(
+        long,
+        env = "LOTUS_EPHEMERAL_PORTS",
+        conflicts_with_all = ["input_port", "output_port", "api_port", "reuse_container"]
+    )]
+    pub ephemeral_ports: bool,
+    /// How the differences between the actual and the expected output are displayed
+    #[arg(long, value_enum, default_value_t = DiffStyle::Paths, env = "LOTUS_DIFF_STYLE")]
+    pub diff_style: DiffStyle,
+    /// How the progress of the run is presented: `jsonl` streams one JSON object per lifecycle
+    /// event (e.g. a finished test case) to standard output
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human, env = "LOTUS_OUTPUT")]
+    pub output: OutputFormat,
+    /// The maximum time in seconds to wait for Logstash to accept each input event and to send
+    /// each output event, unless overridden by a test case [default: 60]
+    #[arg(long, value_name = "SECONDS", env = "LOTUS_TEST_TIMEOUT")]
+    pub test_timeout: Option<u64>,
+    /// How often the health status of each Logstash container is checked before giving up
+    /// [default: 10]
+    #[arg(long, value_name = "COUNT", env = "LOTUS_HEALTH_RETRIES")]
+    pub health_retries: Option<usize>,
+    /// The time in seconds between two checks of the health status of each Logstash container
+    /// [default: 10]
+    #[arg(long, value_name = "SECONDS", env = "LOTUS_HEALTH_INTERVAL")]
+    pub health_interval: Option<u64>,
+    /// The maximum time in seconds to wait for Logstash to start, after which its last log lines
+    /// are displayed [default: 300]
+    #[arg(long, value_name = "SECONDS", env = "LOTUS_STARTUP_TIMEOUT")]
+    pub startup_timeout: Option<u64>,
+    /// Stream the output of Logstash to standard error while the test cases run (e.g. to watch
+    /// grok failures and pipeline warnings). Disables the progress bar.
+    #[arg(long, env = "LOTUS_SHOW_LOGSTASH_LOGS")]
+    pub show_logstash_logs: bool,
+    /// Before starting Logstash, remove the containers left over by crashed runs of the same
+    /// target (which would otherwise occupy its ports) and its superseded images
+    #[arg(long, env = "LOTUS_REAP")]
+    pub reap: bool,
+    /// The number of times a failed test case is run again before it is reported as failed
+    #[arg(long, default_value_t = 0, env = "LOTUS_RETRIES")]
+    pub retries: usize,
+    /// The number of test cases run concurrently
+    #[arg(short, long, default_value_t = 1, env = "LOTUS_JOBS")]
+    pub jobs: usize,
+    /// Run the test cases in random order, determined by the given seed (or a random one, which
+    /// is printed for reproduction)
+    #[arg(long, value_name = "SEED", num_args = 0..=1)]
+    pub shuffle: Option<Option<u64>>,
+    /// Write a test report in the given format (`human`, `tap`, `json` or `html`) to standard
+    /// output, or to a file if a path is given (e.g. `html=report.html`) [default: human]
+    #[arg(long, value_name = "FORMAT[=PATH]", env = "LOTUS_REPORT")]
+    pub report: Vec<ReportTarget>,
+    /// Run all test cases and summarize the failures, instead of stopping at the first failure
+    #[arg(short, long)]
+    pub keep_going: bool,
+    /// Only run test cases whose name matches one of the given patterns: a glob (e.g. `apache*`),
+    /// a regular expression prefixed with `re:`, or otherwise a substring of the name
+    #[arg(short, long, value_name = "PATTERN")]
+    pub filter: Vec<NamePattern>,
+    /// Only run test cases with at least one of the given tags
+    #[arg(long, value_name = "TAG", value_delimiter = ',', env = "LOTUS_TAGS")]
+    pub tags: Vec<String>,
+    /// Do not run test cases with any of the given tags
+    #[arg(
+        long,
+        value_name = "TAG",
+        value_delimiter = ',',
+        env = "LOTUS_EXCLUDE_TAGS"
+    )]
+    pub exclude_tags: Vec<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Generate test cases from a file of raw log lines, recording their expected output
+    Generate(GenerateArguments),
+    /// Keep Logstash running, and run the test cases again whenever the project changes
+    Watch(WatchArguments),
+    /// Create the directories of a new project, with an example rule and test case
+    Init,
+    /// Create a new test case, optionally from a captured event and recording its expected output
+    NewTest(NewTestArguments),
+    /// Check the configuration of the pipeline without running any test cases
+    Validate,
+    /// Check the rules for common problems, without running Logstash
+    Lint(LintArguments),
+    /// Format the rules canonically, or check that they are formatted canonically
+    Fmt(FmtArguments),
+    /// Delete the caches of all projects, and the Docker images and containers created by Lotus
+    Clean(CleanArguments),
+    /// Check whether the environment is ready to run the test cases, and how to fix it if not
+    Doctor,
+    /// Send a stream of events through the pipeline and report its throughput and latency
+    Bench(BenchArguments),
+    /// Run the test cases against the rules at a git revision and those of the working tree, and
+    /// report how their output differs
+    Diff(DiffArguments),
+    /// Experimental: run the test cases against small changes of the rules, and report the
+    /// changes that no test case notices
+    Mutate,
+    /// Run the test cases (like without a subcommand), or pipe events from standard input through
+    /// the pipeline to standard output
+    Run(RunArguments),
 }
 
 impl DefaultArguments {
+    /// The resources each Logstash container may use at most
+    pub fn limits(&self) -> ResourceLimits {
+        ResourceLimits {
+            memory: self.memory,
+            memory_swap: self.memory_swap,
+            cpus: self.cpus,
+        }
+    }
+
+    /// How the container runtime and the Logstash container (through the given ports) are
+    /// reached
+    pub fn connection(&self, ports: Ports) -> Connection {
+        Connection {
+            runtime: self.container_runtime,
+            host: self.docker_host.clone(),
+            tls_verify: self.docker_tls_verify,
+            cert_path: self.docker_cert_path.clone(),
+            ports,
+        }
+    }
+
     #[instrument]
     fn target(&self) -> Result<PathBuf, anyhow::Error> {
         let Some(ref target) = self.target else {
@@ -74,107 +519,1419 @@ impl DefaultArguments {
 
         Ok(target.to_path_buf())
     }
+
+    /// The reports to write at the end of a run, by default a human-readable summary (unless
+    /// lifecycle events are streamed to standard output instead)
+    fn reports(&self) -> Vec<ReportTarget> {
+        match self.report.is_empty() {
+            true if self.output == OutputFormat::Jsonl => Vec::new(),
+            true => vec![ReportTarget {
+                format: ReportFormat::Human,
+                path: None,
+            }],
+            false => self.report.clone(),
+        }
+    }
+
+    /// How much detail of comparison failures is displayed
+    pub fn verbosity(&self) -> Verbosity {
+        match (self.quiet, self.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, _) => Verbosity::Verbose,
+        }
+    }
+
+    /// The level of the logs written unless overridden by `RUST_LOG`
+    pub fn log_level(&self) -> Level {
+        match (self.quiet, self.verbose) {
+            (true, _) => Level::ERROR,
+            (false, 0) => Level::WARN,
+            (false, 1) => Level::INFO,
+            (false, 2) => Level::DEBUG,
+            (false, _) => Level::TRACE,
+        }
+    }
 }
 
 impl Default for DefaultArguments {
     #[instrument]
     fn default() -> Self {
         Self {
+            command: None,
             target: None,
             no_delete_container: false,
+            reuse_container: false,
+            containers: 1,
+            mount: false,
+            memory: None,
+            memory_swap: None,
+            cpus: None,
+            java_opts: None,
+            workers: None,
+            env: Vec::default(),
             verbose: 0,
+            quiet: false,
             rules_dir: String::from(RULES_DIR),
             tests_dir: String::from(TESTS_DIR),
             scripts_dir: String::from(SCRIPTS_DIR),
             patterns_dir: String::from(PATTERNS_DIR),
+            ignore: Vec::default(),
+            compare: None,
+            update_expected: false,
+            input_codec: None,
+            input_protocol: None,
+            socket_codec: None,
+            syslog_format: None,
+            logstash_version: None,
+            base_image: None,
+            base_image_digest: None,
+            platform: None,
+            offline: false,
+            coverage: false,
+            capture_metadata: false,
+            clock: None,
+            fail_on_log: Vec::new(),
+            container_runtime: ContainerRuntime::Auto,
+            docker_host: None,
+            docker_tls_verify: false,
+            docker_cert_path: None,
+            callback_host: String::from(DEFAULT_CALLBACK_HOST),
+            input_port: None,
+            output_port: None,
+            api_port: None,
+            ephemeral_ports: false,
+            diff_style: DiffStyle::Paths,
+            output: OutputFormat::Human,
+            test_timeout: None,
+            health_retries: None,
+            health_interval: None,
+            startup_timeout: None,
+            show_logstash_logs: false,
+            reap: false,
+            retries: 0,
+            jobs: 1,
+            shuffle: None,
+            report: Vec::default(),
+            keep_going: false,
+            filter: Vec::default(),
+            tags: Vec::default(),
+            exclude_tags: Vec::default(),
         }
     }
 }
 
-#[instrument]
-pub async fn default_runner(args: &DefaultArguments) -> anyhow::Result<()> {
-    debug!("Determine the project data and cache directories");
-    let proj_dirs = ProjectDirs::from(FQAN[0], FQAN[1], FQAN[2]).ok_or(anyhow!(
+/// The outcome of a test run that was carried out to the end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// All selected test cases succeeded
+    Success,
+    /// Some of the selected test cases failed
+    TestsFailed { failed: usize, total: usize },
+    /// The pipeline configuration is invalid
+    Invalid,
+    /// The given number of problems were found in the rules
+    Problems(usize),
+    /// The given number of checks of the environment failed
+    Unhealthy(usize),
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Success => write!(f, "All test cases succeeded"),
+            Outcome::TestsFailed { failed, total } => {
+                write!(f, "{failed} of {total} test cases failed")
+            }
+            Outcome::Invalid => write!(f, "The pipeline configuration is invalid"),
+            Outcome::Problems(problems) => write!(f, "{problems} problem(s) found in the rules"),
+            Outcome::Unhealthy(failed) => write!(f, "{failed} check(s) of the environment failed"),
+        }
+    }
+}
+
+/// The process exit code for the result of a runner: 0 if all test cases succeeded, 1 if some of
+/// them failed (or the pipeline configuration is invalid), and 2 if the run itself could not be
+/// carried out (e.g. Docker is unavailable)
+pub fn exit_code(result: &anyhow::Result<Outcome>) -> ExitCode {
+    match result {
+        Ok(Outcome::Success) => ExitCode::SUCCESS,
+        Ok(
+            Outcome::TestsFailed { .. }
+            | Outcome::Invalid
+            | Outcome::Problems(_)
+            | Outcome::Unhealthy(_),
+        ) => ExitCode::from(TESTS_FAILED_EXIT_CODE),
+        Err(_) => ExitCode::from(ERROR_EXIT_CODE),
+    }
+}
+
+/// The resources of the project under test, shared by the default runner and all subcommands
+#[derive(Debug)]
+struct Project {
+    target: PathBuf,
+    config: ProjectConfig,
+    fixtures: Fixtures,
+    cache_dir: PathBuf,
+    tests_dir: PathBuf,
+    rules: Vec<PathBuf>,
+    scripts: Vec<PathBuf>,
+    patterns: Vec<PathBuf>,
+}
+
+/// The data and cache directories of Lotus
+fn project_dirs() -> anyhow::Result<ProjectDirs> {
+    ProjectDirs::from(FQAN[0], FQAN[1], FQAN[2]).ok_or(anyhow!(
         "Unable to determine the project directories based on the qualifier '{}'",
         FQAN.join(".")
-    ))?;
+    ))
+}
+
+impl Project {
+    #[instrument]
+    fn load(args: &DefaultArguments) -> anyhow::Result<Self> {
+        debug!("Determine the project data and cache directories");
+        let proj_dirs = project_dirs()?;
+
+        debug!("Retrieve the test target directory (i.e. project directory)");
+        let target = args
+            .target()
+            .context("Determining the target location i.e., your project location")?;
+
+        debug!("Load the project configuration");
+        let config = ProjectConfig::load(&target).context("Loading the project configuration")?;
+
+        debug!("Load the shared fixture variables");
+        let fixtures = Fixtures::load(&target).context("Loading the shared fixture variables")?;
+
+        debug!("Calculate a HashMap-based hash value for the target location");
+        let target_hash = {
+            let mut hasher = std::collections::hash_map::DefaultHasher::default();
+            target.hash(&mut hasher);
+            hasher.finish().to_string()
+        };
+
+        debug!("Determine the cache, Logstash rules, and tests directories");
+        let cache_dir = proj_dirs.cache_dir().join(target_hash);
+        let rules_dir = target.join(&args.rules_dir);
+        let tests_dir = target.join(&args.tests_dir);
+        let scripts_dir = target.join(&args.scripts_dir);
+        let patterns_dir = target.join(&args.patterns_dir);
+
+        debug!("Collect all Logstash rules");
+        let rules = collect_rules(&rules_dir).context("Collecting all rules")?;
+        if rules.is_empty() {
+            return Err(anyhow!("No rules were found"));
+        }
+
+        info!("Collected {} Logstash rule files", rules.len());
+
+        let scripts = if scripts_dir.is_dir() {
+            debug!("Collect all ruby scripts");
+            let scripts = collect_scripts(&scripts_dir).context("Collecting all ruby scripts")?;
+            info!("Collected {} ruby scripts", scripts.len());
+            scripts
+        } else {
+            Vec::default()
+        };
+
+        let patterns = if patterns_dir.is_dir() {
+            debug!("Collect all grok patterns");
+            let patterns =
+                collect_patterns(&patterns_dir).context("Collecting all grok patterns")?;
+            info!("Collected {} grok patterns", patterns.len());
+            patterns
+        } else {
+            Vec::default()
+        };
+
+        if !cache_dir.is_dir() {
+            debug!("Create a cache directory");
+            std::fs::create_dir_all(&cache_dir).with_context(|| {
+                format!("Creating the cache directory: {}", cache_dir.display())
+            })?;
+        }
+
+        Ok(Self {
+            target,
+            config,
+            fixtures,
+            cache_dir,
+            tests_dir,
+            rules,
+            scripts,
+            patterns,
+        })
+    }
+
+    /// The files of the project that the Logstash pipeline is assembled from
+    fn pipeline_files(&self) -> PipelineFiles {
+        PipelineFiles {
+            cache_dir: self.cache_dir.clone(),
+            rules: self.rules.clone(),
+            scripts: self.scripts.clone(),
+            patterns: self.patterns.clone(),
+        }
+    }
+
+    fn test_options(&self, args: &DefaultArguments) -> anyhow::Result<TestOptions> {
+        let connection = args.connection(ports(args, &self.config));
+        connection
+            .ports
+            .check_pool(args.containers)
+            .context("Checking the ports of the Logstash containers")?;
+
+        Ok(TestOptions {
+            ignore: self
+                .config
+                .ignore
+                .iter()
+                .chain(args.ignore.iter())
+                .cloned()
+                .collect(),
+            update_expected: args.update_expected,
+            codec: args
+                .input_codec
+                .clone()
+                .or(self.config.input_codec.clone())
+                .unwrap_or_else(|| String::from(DEFAULT_CODEC)),
+            input_protocol: args
+                .input_protocol
+                .or(self.config.input_protocol)
+                .unwrap_or_default(),
+            socket_codec: args
+                .socket_codec
+                .clone()
+                .or(self.config.socket_codec.clone()),
+            syslog_format: args
+                .syslog_format
+                .or(self.config.syslog_format)
+                .unwrap_or_default(),
+            logstash_version: logstash_version(args, &self.config),
+            base_image: args.base_image.clone().or(self.config.base_image.clone()),
+            base_image_digest: args
+                .base_image_digest
+                .clone()
+                .or(self.config.base_image_digest.clone()),
+            platform: args.platform.clone().or(self.config.platform.clone()),
+            offline: args.offline,
+            coverage: args.coverage,
+            capture_metadata: args.capture_metadata || self.config.capture_metadata,
+            clock: args.clock.clone().or(self.config.clock.clone()),
+            plugins: self.config.plugins.clone(),
+            outputs: self.config.outputs.clone(),
+            elasticsearch: self.config.elasticsearch.clone(),
+            file_output: self.config.file_output.clone(),
+            files_dir: self
+                .config
+                .file_output
+                .is_some()
+                .then(|| self.cache_dir.join(FILES_DIR)),
+            stats: self.config.stats.clone(),
+            fail_on_log: self
+                .config
+                .fail_on_log
+                .iter()
+                .chain(args.fail_on_log.iter())
+                .cloned()
+                .collect(),
+            callback_host: args.callback_host.clone(),
+            connection,
+            reuse_container: args.reuse_container,
+            containers: args.containers,
+            mount: args.mount,
+            limits: args.limits(),
+            env: ContainerEnv {
+                variables: self
+                    .config
+                    .env
+                    .clone()
+                    .into_iter()
+                    .chain(args.env.iter().map(|v| (v.name.clone(), v.value.clone())))
+                    .collect(),
+                java_opts: args.java_opts.clone().or(self.config.java_opts.clone()),
+            },
+            workers: args.workers.or(self.config.workers),
+            verbosity: args.verbosity(),
+            fixtures: self.fixtures.clone(),
+            keep_going: args.keep_going,
+            jobs: args.jobs,
+            retries: args.retries,
+            diff_style: args.diff_style,
+            output: args.output,
+            matcher: None,
+            compare: args.compare.or(self.config.compare).unwrap_or_default(),
+            timestamps: self.config.timestamps.clone(),
+            coerce_numbers: self.config.coerce_numbers,
+            case_insensitive: self.config.case_insensitive.clone(),
+            timestamp_tolerance: Duration::from_millis(
+                self.config.timestamp_tolerance.unwrap_or_default(),
+            ),
+            hooks: self.config.hooks.clone(),
+            project_dir: self.target.clone(),
+            show_logstash_logs: args.show_logstash_logs,
+            reap: args.reap,
+            delete_container: !args.no_delete_container,
+            progress: args.output == OutputFormat::Human
+                && !args.show_logstash_logs
+                && !args
+                    .reports()
+                    .iter()
+                    .any(|r| r.path.is_none() && r.format != ReportFormat::Human),
+            timeout: Some(Duration::from_secs(
+                args.test_timeout
+                    .or(self.config.test_timeout)
+                    .unwrap_or(DEFAULT_TEST_TIMEOUT),
+            )),
+            startup: StartupOptions {
+                health_retries: args
+                    .health_retries
+                    .or(self.config.health_retries)
+                    .unwrap_or(DEFAULT_HEALTH_RETRIES),
+                health_interval: Duration::from_secs(
+                    args.health_interval
+                        .or(self.config.health_interval)
+                        .unwrap_or(DEFAULT_HEALTH_INTERVAL),
+                ),
+                timeout: Duration::from_secs(
+                    args.startup_timeout
+                        .or(self.config.startup_timeout)
+                        .unwrap_or(DEFAULT_STARTUP_TIMEOUT),
+                ),
+            },
+        })
+    }
+
+    /// Collects the test cases of the project, and selects those requested on the command line
+    fn select_tests(&self, args: &DefaultArguments) -> anyhow::Result<Vec<TestCase>> {
+        debug!("Collect all test cases");
+        let test_cases = collect_tests(&self.tests_dir, args.update_expected)
+            .context("Collecting all test cases")?;
+        if test_cases.is_empty() {
+            return Err(anyhow!("No test cases were found"));
+        }
+
+        info!("Collected {} test cases", test_cases.len());
+
+        debug!("Select the test cases to run");
+        let filter = TestFilter {
+            names: args.filter.clone(),
+            tags: args.tags.clone(),
+            exclude_tags: args.exclude_tags.clone(),
+        };
+        let collected = test_cases.len();
+        let test_cases: Vec<TestCase> = test_cases
+            .into_iter()
+            .filter(|t| filter.matches(t))
+            .collect();
+        if test_cases.is_empty() {
+            return Err(anyhow!(
+                "None of the {collected} test cases match the selection"
+            ));
+        }
+
+        info!("Selected {} of {} test cases", test_cases.len(), collected);
+
+        Ok(test_cases)
+    }
+
+    /// Launches Logstash and runs the given test cases against it
+    #[instrument(skip(test_cases))]
+    async fn run(
+        self,
+        test_cases: Vec<TestCase>,
+        options: TestOptions,
+        reports: Vec<ReportTarget>,
+    ) -> anyhow::Result<Outcome> {
+        let report = self.report(test_cases, options).await?;
+        for target in &reports {
+            report.write(target).context("Writing the test report")?;
+        }
+        Ok(report.outcome())
+    }
+
+    /// Runs the test cases against a new test environment, and returns the report of the run
+    async fn report(
+        self,
+        test_cases: Vec<TestCase>,
+        options: TestOptions,
+    ) -> anyhow::Result<TestReport> {
+        let image_options = image_options(&test_cases, &options)?;
+        let files = self.pipeline_files();
+        with_event_server(options, |router, options| async move {
+            run_tests(router, files, test_cases, image_options, options)
+                .await
+                .context("Running the Logstash tests")
+        })
+        .await
+    }
+}
+
+/// The version of Logstash given on the command line, in the project configuration, or the
+/// default one
+fn logstash_version(args: &DefaultArguments, config: &ProjectConfig) -> String {
+    args.logstash_version
+        .clone()
+        .or(config.logstash_version.clone())
+        .unwrap_or_else(|| String::from(DEFAULT_LOGSTASH_VERSION))
+}
+
+/// The ports given on the command line, in the project configuration, or the default ones. Ports
+/// given as 0 are chosen freely when they are bound.
+fn ports(args: &DefaultArguments, config: &ProjectConfig) -> Ports {
+    if args.ephemeral_ports {
+        return Ports {
+            input: 0,
+            output: 0,
+            api: 0,
+        };
+    }
+    let defaults = Ports::default();
+    Ports {
+        input: args
+            .input_port
+            .or(config.input_port)
+            .unwrap_or(defaults.input),
+        output: args
+            .output_port
+            .or(config.output_port)
+            .unwrap_or(defaults.output),
+        api: args.api_port.or(config.api_port).unwrap_or(defaults.api),
+    }
+}
+
+/// Determines the contents of the Logstash image required by the given test cases
+fn image_options(test_cases: &[TestCase], options: &TestOptions) -> anyhow::Result<ImageOptions> {
+    debug!("Determine the codecs required to decode raw text input events");
+    let mut codecs: Vec<String> = test_cases
+        .iter()
+        .filter_map(|t| t.metadata.codec.clone())
+        .chain([options.codec.clone()])
+        .collect();
+    codecs.sort();
+    codecs.dedup();
+    if let Some(codec) = codecs
+        .iter()
+        .chain(&options.socket_codec)
+        .find(|c| !c.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+    {
+        return Err(anyhow!("Invalid Logstash codec name: '{codec}'"));
+    }
+
+    let logstash_version = options.logstash_version.clone();
+    if logstash_version.is_empty()
+        || !logstash_version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+    {
+        return Err(anyhow!("Invalid Logstash version: '{logstash_version}'"));
+    }
+
+    let clock = options.clock.clone();
+    if let Some(ref clock) = clock {
+        let instant =
+            Regex::new(r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(\.\d{1,9})?(Z|[+-]\d{2}:\d{2})$")?;
+        if !instant.is_match(clock) {
+            return Err(anyhow!(
+                "Invalid clock: '{clock}' (expected an RFC 3339 instant, e.g. 2024-01-01T00:00:00Z)"
+            ));
+        }
+    }
+
+    let custom_base_image = options.base_image.clone();
+    if let Some(ref image) = custom_base_image {
+        if image.is_empty()
+            || !image.chars().all(|c| {
+                c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-' | '/' | ':' | '@')
+            })
+        {
+            return Err(anyhow!("Invalid base image: '{image}'"));
+        }
+    }
+
+    let base_image_digest = options.base_image_digest.clone();
+    if let Some(ref digest) = base_image_digest {
+        let valid = digest
+            .strip_prefix("sha256:")
+            .is_some_and(|hex| hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()));
+        if !valid {
+            return Err(anyhow!("Invalid base image digest: '{digest}'"));
+        }
+    }
+
+    let mut plugins = options.plugins.clone();
+    plugins.sort();
+    plugins.dedup();
+    if let Some(plugin) = plugins.iter().find(|p| {
+        p.is_empty()
+            || !p
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+    }) {
+        return Err(anyhow!("Invalid Logstash plugin name: '{plugin}'"));
+    }
+
+    let outputs = options.outputs.clone();
+    for (i, output) in outputs.iter().enumerate() {
+        if output.name.is_empty()
+            || !output
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+        {
+            return Err(anyhow!("Invalid output channel name: '{}'", output.name));
+        }
+        if outputs[..i].iter().any(|o| o.name == output.name) {
+            return Err(anyhow!("Duplicate output channel: '{}'", output.name));
+        }
+    }
+    if let Some(ref file_output) = options.file_output {
+        let path = Path::new(&file_output.path);
+        if file_output.path.is_empty()
+            || file_output.path.contains('"')
+            || !path
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)))
+        {
+            return Err(anyhow!(
+                "Invalid path of the file output: '{}' (it must be relative)",
+                file_output.path
+            ));
+        }
+    }
+    if options.elasticsearch.is_some() && !outputs.is_empty() {
+        return Err(anyhow!(
+            "Output channels cannot be combined with the Elasticsearch output"
+        ));
+    }
+
+    let platform = options.platform.clone();
+    if let Some(ref platform) = platform {
+        let segments: Vec<&str> = platform.split('/').collect();
+        let valid = (2..=3).contains(&segments.len())
+            && segments.iter().all(|s| {
+                !s.is_empty()
+                    && s.chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            });
+        if !valid {
+            return Err(anyhow!("Invalid platform: '{platform}'"));
+        }
+    }
+
+    Ok(ImageOptions {
+        codecs,
+        logstash_version,
+        custom_base_image,
+        base_image_digest,
+        platform,
+        offline: options.offline,
+        coverage: options.coverage,
+        capture_metadata: options.capture_metadata,
+        clock,
+        plugins,
+        outputs,
+        callback_host: options.callback_host.clone(),
+        input_protocol: options.input_protocol,
+        socket_codec: options.socket_codec.clone(),
+        syslog_format: options.syslog_format,
+        workers: options.workers,
+        elasticsearch: options.elasticsearch.clone(),
+        file_output: options.file_output.clone(),
+    })
+}
+
+/// Runs the future returned by `run` alongside the event responder server, which listens on the
+/// output port of the options and routes the output events of Logstash with the router passed to
+/// `run`. The server is stopped once the future completes.
+async fn with_event_server<T, F>(
+    mut options: TestOptions,
+    run: impl FnOnce(EventRouter, TestOptions) -> F,
+) -> anyhow::Result<T>
+where
+    F: Future<Output = anyhow::Result<T>>,
+{
+    debug!("Create the router between the test executor and the test response handler");
+    let router = EventRouter::default();
+    let listener = bind_server(options.connection.ports.output)?;
+    options.connection.ports.output = listener.local_addr()?.port();
+    let mut server = tokio::spawn(run_server(router.clone(), listener));
+
+    debug!("Launch both the test executor and the test response handler");
+    let result = tokio::select!(
+        r = &mut server => {
+            r.context("Joining the event responder server")?
+                .context("Running the event responder server")?;
+            Err(anyhow!("The event responder server stopped unexpectedly"))
+        },
+        r = run(router, options) => r,
+    );
+    server.abort();
+    result
+}
+
+#[instrument]
+pub async fn default_runner(args: &DefaultArguments) -> anyhow::Result<Outcome> {
+    let project = Project::load(args)?;
+
+    let mut test_cases = project.select_tests(args)?;
+
+    if let Some(seed) = args.shuffle {
+        let seed = seed.unwrap_or_else(rand::random);
+        eprintln!("shuffling test cases with seed {seed}");
+        shuffle_tests(&mut test_cases, seed);
+    }
+
+    let options = project.test_options(args)?;
+    project.run(test_cases, options, args.reports()).await
+}
+
+/// Runs the test cases, or with `--stdin` pipes the events from standard input through the
+/// pipeline to standard output
+#[instrument]
+pub async fn run_runner(
+    args: &DefaultArguments,
+    run_args: &RunArguments,
+) -> anyhow::Result<Outcome> {
+    if !run_args.stdin {
+        return default_runner(args).await;
+    }
+
+    let session = Session::start(args).await?;
+    let result = session
+        .pipe(
+            tokio::io::BufReader::new(tokio::io::stdin()),
+            tokio::io::stdout(),
+        )
+        .await;
+    session.close().await?;
+    let count = result.context("Piping the events through the pipeline")?;
+    info!("Wrote {count} output events");
+
+    Ok(Outcome::Success)
+}
+
+/// Turns each line of a log file into a new test case, whose expected output is recorded from a
+/// single run of the pipeline
+#[instrument]
+pub async fn generate_runner(
+    args: &DefaultArguments,
+    generate_args: &GenerateArguments,
+) -> anyhow::Result<Outcome> {
+    let project = Project::load(args)?;
+
+    let name = match &generate_args.name {
+        Some(name) => name.clone(),
+        None => generate_args
+            .log_file
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .ok_or(anyhow!(
+                "Unable to derive a test case name from the log file: {}",
+                generate_args.log_file.display()
+            ))?,
+    };
+    let test_cases_dir = project.tests_dir.join(&name);
+    if test_cases_dir.exists() {
+        return Err(anyhow!(
+            "The test cases directory already exists: {}",
+            test_cases_dir.display()
+        ));
+    }
+
+    debug!("Write a test case for each line of the log file");
+    let lines = generate_test_cases(&generate_args.log_file, &test_cases_dir)
+        .context("Generating the test cases from the log file")?;
+    info!(
+        "Generated {} test cases in {}",
+        lines,
+        test_cases_dir.display()
+    );
+
+    let test_cases = collect_tests(&test_cases_dir, true)
+        .context("Collecting the generated test cases")?
+        .into_iter()
+        .map(|mut t| {
+            t.name = format!("{name}/{}", t.name);
+            t
+        })
+        .collect();
 
-    debug!("Retrieve the test target directory (i.e. project directory)");
+    let mut options = project.test_options(args)?;
+    options.update_expected = true;
+    project.run(test_cases, options, args.reports()).await
+}
+
+/// Creates a new test case, and records its expected output from a single run of the pipeline if
+/// requested
+#[instrument]
+pub async fn new_test_runner(
+    args: &DefaultArguments,
+    new_test_args: &NewTestArguments,
+) -> anyhow::Result<Outcome> {
+    let project = Project::load(args)?;
+
+    let components: Vec<_> = Path::new(&new_test_args.name).components().collect();
+    if components.is_empty()
+        || !components
+            .iter()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+    {
+        return Err(anyhow!(
+            "The test case name must be a path within the tests directory: {}",
+            new_test_args.name
+        ));
+    }
+    let name = components
+        .iter()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let test_case_dir = project.tests_dir.join(&name);
+    new_test_case(
+        &test_case_dir,
+        new_test_args.from_event.as_deref(),
+        new_test_args.record,
+    )
+    .context("Creating the test case")?;
+    info!("Created the test case {}", test_case_dir.display());
+
+    if !new_test_args.record {
+        return Ok(Outcome::Success);
+    }
+
+    let test_cases: Vec<TestCase> = collect_tests(&project.tests_dir, true)
+        .context("Collecting the new test case")?
+        .into_iter()
+        .filter(|t| t.name() == name)
+        .collect();
+
+    let mut options = project.test_options(args)?;
+    options.update_expected = true;
+    project.run(test_cases, options, args.reports()).await
+}
+
+/// Builds the Logstash image and checks the configuration of its pipeline, which reports syntax
+/// errors much faster than running the test cases
+#[instrument]
+pub async fn validate_runner(args: &DefaultArguments) -> anyhow::Result<Outcome> {
+    let project = Project::load(args)?;
+    let options = project.test_options(args)?;
+
+    debug!("Collect the test cases to determine the required codecs");
+    let test_cases = match project.tests_dir.is_dir() {
+        true => collect_tests(&project.tests_dir, true).context("Collecting all test cases")?,
+        false => Vec::new(),
+    };
+    let image_options = image_options(&test_cases, &options)?;
+
+    debug!("Connect to the container runtime");
+    let (docker, _tunnel) = options.connection.connect().await?;
+
+    let spinner = progress::spinner();
+    spinner.set_message("Building the Logstash image");
+    let image = build_container_image(
+        &docker,
+        &project.cache_dir,
+        &project.rules,
+        &project.scripts,
+        &project.patterns,
+        &image_options,
+        &spinner,
+    )
+    .await
+    .context("Building the Docker container image for Logstash")?;
+
+    spinner.set_message("Checking the pipeline configuration");
+    let (valid, output) = test_pipeline_config(&docker, &image, &options.env)
+        .await
+        .context("Checking the pipeline configuration")?;
+    spinner.finish_and_clear();
+
+    if !valid {
+        eprintln!("{}", logstash_errors(&output));
+        return Ok(Outcome::Invalid);
+    }
+
+    println!("the pipeline configuration is valid");
+    Ok(Outcome::Success)
+}
+
+/// Checks the rules for common problems without running Logstash, and prints each problem found
+#[instrument]
+pub fn lint_runner(args: &DefaultArguments, lint_args: &LintArguments) -> anyhow::Result<Outcome> {
+    let project = Project::load(args)?;
+
+    let mut known_patterns = defined_patterns(&project.patterns)?;
+    known_patterns.extend(lint_args.allowed_patterns.iter().cloned());
+
+    let mut problems = 0;
+    for rule in &project.rules {
+        let text = std::fs::read_to_string(rule)
+            .with_context(|| format!("Reading the rule file: {}", rule.display()))?;
+        for problem in lint_rule(rule, &text, &known_patterns) {
+            println!("{problem}");
+            problems += 1;
+        }
+    }
+
+    match problems {
+        0 => Ok(Outcome::Success),
+        n => Ok(Outcome::Problems(n)),
+    }
+}
+
+/// Formats the rules canonically in place. With `--check`, the rules are left untouched and those
+/// that are not formatted canonically are printed instead.
+#[instrument]
+pub fn fmt_runner(args: &DefaultArguments, fmt_args: &FmtArguments) -> anyhow::Result<Outcome> {
+    let project = Project::load(args)?;
+
+    let mut unformatted = 0;
+    for rule in &project.rules {
+        let text = std::fs::read_to_string(rule)
+            .with_context(|| format!("Reading the rule file: {}", rule.display()))?;
+        let formatted = format_rule(&text)
+            .with_context(|| format!("Formatting the rule file: {}", rule.display()))?;
+        if formatted == text {
+            continue;
+        }
+
+        if fmt_args.check {
+            println!("would reformat {}", rule.display());
+            unformatted += 1;
+        } else {
+            debug!("Reformat the rule file {}", rule.display());
+            std::fs::write(rule, formatted)
+                .with_context(|| format!("Writing the rule file: {}", rule.display()))?;
+            println!("reformatted {}", rule.display());
+        }
+    }
+
+    match unformatted {
+        0 => Ok(Outcome::Success),
+        n => Ok(Outcome::Problems(n)),
+    }
+}
+
+/// Deletes the caches of all projects and, unless only the caches are to be cleaned, the Docker
+/// images and containers created by Lotus
+#[instrument]
+pub async fn clean_runner(
+    args: &DefaultArguments,
+    clean_args: &CleanArguments,
+) -> anyhow::Result<Outcome> {
+    let proj_dirs = project_dirs()?;
+    for dir in remove_cache_dirs(proj_dirs.cache_dir()).context("Deleting the cache directories")? {
+        println!("deleted {}", dir.display());
+    }
+
+    if clean_args.cache_only {
+        return Ok(Outcome::Success);
+    }
+
+    debug!("Connect to the container runtime");
+    let ports = ports(args, &ProjectConfig::default());
+    let (docker, _tunnel) = args.connection(ports).connect().await?;
+    let (containers, images) = remove_artifacts(&docker)
+        .await
+        .context("Removing the Docker images and containers")?;
+    for container in &containers {
+        println!("removed container {container}");
+    }
+    for image in &images {
+        println!("removed image {image}");
+    }
+
+    Ok(Outcome::Success)
+}
+
+/// Checks whether the environment is ready to run the test cases: whether the Docker daemon
+/// responds and is recent enough, whether there is enough free disk space, whether the ports used
+/// by Lotus are available, and whether the Logstash base image can be pulled. Prints each finding
+/// along with a hint on how to fix failed checks.
+#[instrument]
+pub async fn doctor_runner(args: &DefaultArguments) -> anyhow::Result<Outcome> {
     let target = args
         .target()
         .context("Determining the target location i.e., your project location")?;
+    let config = ProjectConfig::load(&target).context("Loading the project configuration")?;
+    let ports = ports(args, &config);
+    let image_options = ImageOptions {
+        logstash_version: logstash_version(args, &config),
+        custom_base_image: args.base_image.clone().or(config.base_image),
+        base_image_digest: args.base_image_digest.clone().or(config.base_image_digest),
+        platform: args.platform.clone().or(config.platform),
+        ..Default::default()
+    };
 
-    debug!("Calculate a HashMap-based hash value for the target location");
-    let target_hash = {
-        let mut hasher = std::collections::hash_map::DefaultHasher::default();
-        target.hash(&mut hasher);
-        hasher.finish().to_string()
+    // The ports are checked before connecting, since an SSH tunnel would occupy them
+    let mut diagnoses: Vec<Diagnosis> = [ports.input, ports.output, ports.api]
+        .map(check_port)
+        .into();
+
+    debug!("Connect to the container runtime");
+    let connection = args.connection(ports).connect().await;
+    let docker = match connection {
+        Ok((ref docker, _)) => {
+            diagnoses.extend(check_docker(docker).await);
+            diagnoses.iter().all(Diagnosis::is_ok).then_some(docker)
+        }
+        Err(e) => {
+            diagnoses.push(Diagnosis::failed(
+                "Docker connectivity",
+                format!("unable to connect to the Docker API: {e}"),
+                DOCKER_REMEDY,
+            ));
+            None
+        }
     };
 
-    debug!("Determine the cache, Logstash rules, and tests directories");
-    let cache_dir = proj_dirs.cache_dir().join(target_hash);
-    let rules_dir = target.join(&args.rules_dir);
-    let tests_dir = target.join(&args.tests_dir);
-    let scripts_dir = target.join(&args.scripts_dir);
-    let patterns_dir = target.join(&args.patterns_dir);
+    diagnoses.push(check_disk_space("cache", project_dirs()?.cache_dir()));
+    if let Some(docker) = docker.filter(|_| args.docker_host.is_none()) {
+        let root_dir = docker.info().await.ok().and_then(|i| i.docker_root_dir);
+        if let Some(root_dir) = root_dir.map(PathBuf::from).filter(|d| d.exists()) {
+            diagnoses.push(check_disk_space("Docker", &root_dir));
+        }
+    }
 
-    debug!("Collect all Logstash rules");
-    let rules = collect_rules(&rules_dir).context("Collecting all rules")?;
-    if rules.is_empty() {
-        return Err(anyhow!("No rules were found"));
+    if let Some(docker) = docker {
+        diagnoses.push(check_base_image(docker, &image_options.base_image()).await);
     }
 
-    info!("Collected {} Logstash rule files", rules.len());
+    let jq_required = Project::load(args)
+        .and_then(|project| project.select_tests(args))
+        .is_ok_and(|test_cases| test_cases.iter().any(TestCase::uses_jq));
+    diagnoses.push(check_jq(jq_required));
+
+    for diagnosis in &diagnoses {
+        println!("{diagnosis}");
+    }
 
-    debug!("Collect all test cases");
-    let test_cases = collect_tests(&tests_dir).context("Collecting all test cases")?;
-    if test_cases.is_empty() {
-        return Err(anyhow!("No test cases were found"));
+    match diagnoses.iter().filter(|d| !d.is_ok()).count() {
+        0 => Ok(Outcome::Success),
+        n => Ok(Outcome::Unhealthy(n)),
     }
+}
 
-    info!("Collected {} test cases", test_cases.len());
+/// Measures the throughput and latency of the pipeline with a stream of generated events or
+/// events read from a file
+#[instrument]
+pub async fn bench_runner(
+    args: &DefaultArguments,
+    bench_args: &BenchArguments,
+) -> anyhow::Result<Outcome> {
+    let project = Project::load(args)?;
 
-    let scripts = if scripts_dir.is_dir() {
-        debug!("Collect all ruby scripts");
-        let scripts = collect_scripts(&scripts_dir).context("Collecting all ruby scripts")?;
-        info!("Collected {} ruby scripts", scripts.len());
-        scripts
-    } else {
-        Vec::default()
+    let events = match &bench_args.events {
+        Some(path) => load_events(path).context("Loading the benchmark events")?,
+        None => generate_events(bench_args.count.unwrap_or(DEFAULT_BENCH_EVENTS)),
     };
+    let count = bench_args.count.unwrap_or(events.len());
+
+    let options = project.test_options(args)?;
+    let image_options = image_options(&[], &options)?;
+    let files = project.pipeline_files();
+    let events = &events;
+
+    let report = with_event_server(options, |router, options| async move {
+        debug!("Create the test environment");
+        let spinner = progress::spinner();
+        let context = TestContext::new(router, files, image_options, &options, &spinner)
+            .await
+            .context("Bootstrapping the test environment")?;
+        spinner.finish_and_clear();
+
+        let report = run_bench(&context, events, count, bench_args.rate, &options).await;
+        context.close().await?;
+        report.context("Running the benchmark")
+    })
+    .await?;
+    println!("{report}");
+
+    Ok(Outcome::Success)
+}
 
-    let patterns = if patterns_dir.is_dir() {
-        debug!("Collect all grok patterns");
-        let patterns = collect_patterns(&patterns_dir).context("Collecting all grok patterns")?;
-        info!("Collected {} grok patterns", patterns.len());
-        patterns
-    } else {
-        Vec::default()
+/// Runs the input events of the test cases through the rules at a git revision and through those
+/// of the working tree, and reports the differences between their output events
+#[instrument]
+pub async fn diff_runner(
+    args: &DefaultArguments,
+    diff_args: &DiffArguments,
+) -> anyhow::Result<Outcome> {
+    let project = Project::load(args)?;
+    let test_cases = project.select_tests(args)?;
+    let target = args
+        .target()
+        .context("Determining the target location i.e., your project location")?;
+
+    debug!("Check out the rules at {}", diff_args.git_ref);
+    let checkout = tempfile::tempdir().context("Creating a directory for the rules")?;
+    let base_rules = checkout_rules(
+        &target,
+        &args.rules_dir,
+        &diff_args.git_ref,
+        checkout.path(),
+    )
+    .with_context(|| format!("Checking out the rules at {}", diff_args.git_ref))?;
+    let base_cache_dir = project.cache_dir.join(DIFF_CACHE_DIR);
+    std::fs::create_dir_all(&base_cache_dir)
+        .with_context(|| format!("Creating the cache directory: {}", base_cache_dir.display()))?;
+
+    // Both pipelines run one after the other on the same ports
+    let mut options = project.test_options(args)?;
+    options.reuse_container = false;
+    options.delete_container = true;
+    let image_options = image_options(&test_cases, &options)?;
+
+    let (project, test_cases) = (&project, &test_cases);
+    let runs = |router: EventRouter, options: TestOptions| async move {
+        eprintln!(
+            "running the test cases against the rules at {}",
+            diff_args.git_ref
+        );
+        let base = PipelineFiles {
+            cache_dir: base_cache_dir,
+            rules: base_rules,
+            ..project.pipeline_files()
+        };
+        let before = collect_test_outputs(
+            router.clone(),
+            base,
+            test_cases,
+            image_options.clone(),
+            &options,
+        )
+        .await
+        .context("Running the test cases against the rules of the git revision")?;
+        eprintln!("running the test cases against the rules of the working tree");
+        let after = collect_test_outputs(
+            router,
+            project.pipeline_files(),
+            test_cases,
+            image_options.clone(),
+            &options,
+        )
+        .await
+        .context("Running the test cases against the rules of the working tree")?;
+        anyhow::Ok((before, after))
     };
+    let (before, after) = with_event_server(options, runs).await?;
+
+    let diffs: Vec<OutputDiff> = test_cases
+        .iter()
+        .zip(before.into_iter().zip(after))
+        .map(|(test_case, outputs)| OutputDiff {
+            title: test_case.title().to_string(),
+            changes: match outputs {
+                (Ok(before), Ok(after)) => Ok(output_changes(before, after)),
+                (Err(e), _) | (_, Err(e)) => Err(format!("{e:#}")),
+            },
+        })
+        .collect();
 
-    if !cache_dir.is_dir() {
-        debug!("Create a cache directory");
-        std::fs::create_dir_all(&cache_dir)
-            .with_context(|| format!("Creating the cache directory: {}", cache_dir.display()))?;
+    let changed: Vec<&OutputDiff> = diffs
+        .iter()
+        .filter(|d| d.changes.as_ref().map_or(true, |c| !c.is_empty()))
+        .collect();
+    for diff in &changed {
+        println!("{diff}\n");
     }
+    println!(
+        "{} of {} test case(s) changed their output from {} to the working tree",
+        changed.len(),
+        diffs.len(),
+        diff_args.git_ref
+    );
+
+    match changed.len() {
+        0 => Ok(Outcome::Success),
+        failed => Ok(Outcome::TestsFailed {
+            failed,
+            total: diffs.len(),
+        }),
+    }
+}
 
-    debug!(
-        "Create a communication channel between the test executor and the test response handler"
+/// Runs the test cases against each mutant of the rules (see [`mutants`]), and reports the
+/// mutants that no test case kills
+#[instrument]
+pub async fn mutate_runner(args: &DefaultArguments) -> anyhow::Result<Outcome> {
+    let project = Project::load(args)?;
+    let test_cases = project.select_tests(args)?;
+
+    let mut all_mutants: Vec<(usize, Mutant)> = Vec::new();
+    for (i, rule) in project.rules.iter().enumerate() {
+        let text = std::fs::read_to_string(rule)
+            .with_context(|| format!("Reading the rule file: {}", rule.display()))?;
+        let rule_mutants = mutants(&text).with_context(|| {
+            format!("Deriving the mutants of the rule file: {}", rule.display())
+        })?;
+        all_mutants.extend(rule_mutants.into_iter().map(|m| (i, m)));
+    }
+    if all_mutants.is_empty() {
+        return Err(anyhow!("The rules contain nothing to mutate"));
+    }
+
+    // A mutant is killed by the first failing test case, and only the test cases count
+    let mut options = project.test_options(args)?;
+    options.keep_going = false;
+    options.progress = false;
+    options.update_expected = false;
+    options.coverage = false;
+    options.stats = Default::default();
+    options.fail_on_log.clear();
+    let image_options = image_options(&test_cases, &options)?;
+
+    let mutant_dir = tempfile::tempdir().context("Creating a directory for the mutants")?;
+    let (project, all_mutants) = (&project, &all_mutants);
+    let runs = |router: EventRouter, options: TestOptions| async move {
+        let run = |rules: Vec<PathBuf>| {
+            run_tests(
+                router.clone(),
+                PipelineFiles {
+                    rules,
+                    ..project.pipeline_files()
+                },
+                test_cases.clone(),
+                image_options.clone(),
+                options.clone(),
+            )
+        };
+        eprintln!("running the test cases against the unchanged rules");
+        let report = run(project.rules.clone())
+            .await
+            .context("Running the test cases against the unchanged rules")?;
+        if !report.is_success() {
+            return Err(anyhow!(
+                "The test cases must pass against the unchanged rules before mutants can be tested"
+            ));
+        }
+
+        let mut statuses = Vec::with_capacity(all_mutants.len());
+        for (n, (i, mutant)) in all_mutants.iter().enumerate() {
+            let rule = &project.rules[*i];
+            let mutated = mutant_dir.path().join(coverage::rule_name(rule));
+            std::fs::write(&mutated, &mutant.text)
+                .with_context(|| format!("Writing the mutant: {}", mutated.display()))?;
+            let mut rules = project.rules.clone();
+            rules[*i] = mutated;
+
+            let status = match run(rules).await {
+                Ok(report) if report.is_success() => MutantStatus::Survived,
+                Ok(_) => MutantStatus::Killed,
+                Err(e) => {
+                    info!("Mutant {n} could not run: {e:#}");
+                    MutantStatus::Invalid
+                }
+            };
+            println!(
+                "mutant {}/{} {}:{} {} ... {}",
+                n + 1,
+                all_mutants.len(),
+                coverage::rule_name(rule),
+                mutant.line,
+                mutant.description,
+                status
+            );
+            statuses.push(status);
+        }
+        anyhow::Ok(statuses)
+    };
+    let statuses = with_event_server(options, runs).await?;
+
+    let count = |status| statuses.iter().filter(|s| **s == status).count();
+    let (killed, survived) = (count(MutantStatus::Killed), count(MutantStatus::Survived));
+    println!(
+        "\nmutants: {} killed; {} survived; {} invalid",
+        killed,
+        survived,
+        count(MutantStatus::Invalid)
     );
-    let (sender_for_server, receiver_for_test_runner) = channel(CHANNEL_CAPACITY);
+    if survived > 0 {
+        println!("\nsurviving mutants:");
+        for ((i, mutant), _) in all_mutants
+            .iter()
+            .zip(&statuses)
+            .filter(|(_, s)| **s == MutantStatus::Survived)
+        {
+            println!(
+                "    {}:{}  {}",
+                coverage::rule_name(&project.rules[*i]),
+                mutant.line,
+                mutant.description
+            );
+        }
+    }
 
-    debug!("Launch both the test executor and the test response handler");
-    tokio::select!(
-        _ = tokio::spawn(async move {
-            run_server(sender_for_server)
-                .await
-                .context("Running the event responder server")
-                .unwrap()
-        }) => {},
-        e = run_tests(receiver_for_test_runner, cache_dir, rules, test_cases, scripts, patterns, !args.no_delete_container, args.verbose > 0) => {
-            e.expect("Error running Logstash tests");
+    match survived {
+        0 => Ok(Outcome::Success),
+        failed => Ok(Outcome::TestsFailed {
+            failed,
+            total: killed + survived,
+        }),
+    }
+}
+
+/// Keeps Logstash running and runs the selected test cases again whenever the project changes.
+/// The Logstash image is only rebuilt if the pipeline changed, and only the changed test cases
+/// are run if nothing else changed. Runs until interrupted with Ctrl-C, and returns the outcome
+/// of the last run.
+#[instrument]
+pub async fn watch_runner(
+    args: &DefaultArguments,
+    watch_args: &WatchArguments,
+) -> anyhow::Result<Outcome> {
+    if args.update_expected {
+        return Err(anyhow!(
+            "Recording the expected output is not supported in watch mode"
+        ));
+    }
+
+    let target = args
+        .target()
+        .context("Determining the target location i.e., your project location")?;
+    let paths = WatchedPaths::new(
+        &target,
+        &args.tests_dir,
+        &[&args.rules_dir, &args.scripts_dir, &args.patterns_dir],
+    )?;
+    let (_watcher, mut events) = watch(&paths).context("Watching the project for changes")?;
+    let config = ProjectConfig::load(&target).context("Loading the project configuration")?;
+
+    debug!("Create the router between the test executor and the test response handler");
+    let router = EventRouter::default();
+    let router_for_server = router.clone();
+    let listener = bind_server(ports(args, &config).output)?;
+    let output_port = listener.local_addr()?.port();
+
+    let debounce = Duration::from_millis(watch_args.debounce);
+    let mut environment: Option<(TestContext, ImageOptions)> = None;
+    let mut outcome = Outcome::Success;
+    let result = tokio::select!(
+        r = tokio::spawn(run_server(router_for_server, listener)) => {
+            r.context("Joining the event responder server")
+                .and_then(|r| r.context("Running the event responder server"))
+                .and_then(|()| Err(anyhow!("The event responder server stopped unexpectedly")))
         },
+        r = async {
+            let mut reaction = Reaction::Rebuild;
+            loop {
+                match run_watched(args, &router, output_port, &mut environment, &reaction).await {
+                    Ok(o) => outcome = o,
+                    Err(e) => eprintln!("Error: {e:?}"),
+                }
+
+                eprintln!("\nwatching for changes (press Ctrl-C to stop)");
+                reaction = loop {
+                    let changed = next_changes(&mut events, debounce).await?;
+                    debug!("Changed files: {changed:?}");
+                    if let Some(reaction) = paths.reaction(&changed) {
+                        break reaction;
+                    }
+                };
+            }
+        } => r,
+        r = tokio::signal::ctrl_c() => r.context("Waiting for Ctrl-C"),
     );
 
+    if let Some((context, _)) = environment {
+        context
+            .close()
+            .await
+            .context("Stopping the Logstash container")?;
+    }
+
+    result.map(|()| outcome)
+}
+
+/// Runs the test cases affected by a change in watch mode, and rebuilds the test environment
+/// first if necessary
+async fn run_watched(
+    args: &DefaultArguments,
+    router: &EventRouter,
+    output_port: u16,
+    environment: &mut Option<(TestContext, ImageOptions)>,
+    reaction: &Reaction,
+) -> anyhow::Result<Outcome> {
+    let started = std::time::Instant::now();
+    let project = Project::load(args)?;
+    let mut options = project.test_options(args)?;
+    options.connection.ports.output = output_port;
+    let mut test_cases = project.select_tests(args)?;
+    let image_options = image_options(&test_cases, &options)?;
+
+    let rebuild = *reaction == Reaction::Rebuild
+        || environment
+            .as_ref()
+            .is_none_or(|(_, built)| *built != image_options);
+    if rebuild {
+        if let Some((context, _)) = environment.take() {
+            context
+                .close()
+                .await
+                .context("Stopping the Logstash container")?;
+        }
+
+        debug!("Create the test environment");
+        let spinner = progress::spinner();
+        let context = TestContext::new(
+            router.clone(),
+            project.pipeline_files(),
+            image_options.clone(),
+            &options,
+            &spinner,
+        )
+        .await
+        .context("Bootstrapping the test environment")?;
+        spinner.finish_and_clear();
+        *environment = Some((context, image_options));
+    } else if let Reaction::Rerun(changed) = reaction {
+        debug!("Select the changed test cases");
+        test_cases.retain(|t| {
+            let location = t
+                .location()
+                .strip_prefix(&project.tests_dir)
+                .unwrap_or(t.location());
+            changed.iter().any(|c| c.starts_with(location))
+        });
+    }
+
+    let Some((context, _)) = environment.as_ref() else {
+        return Err(anyhow!("The test environment is not available"));
+    };
+    let results = run_test_cases(context, &test_cases, &options).await;
+    let report = TestReport {
+        results,
+        duration: started.elapsed(),
+        image_id: Some(context.image_id().to_string()),
+        container_id: Some(context.container_id().to_string()),
+        coverage: None,
+    };
+    for target in &args.reports() {
+        report.write(target).context("Writing the test report")?;
+    }
+
+    Ok(report.outcome())
+}
+
+/// Creates a new project in the target directory, which passes its tests right away
+#[instrument]
+pub fn init_runner(args: &DefaultArguments) -> anyhow::Result<()> {
+    let target = args
+        .target()
+        .context("Determining the target location i.e., your project location")?;
+    let layout = Layout {
+        rules_dir: PathBuf::from(&args.rules_dir),
+        tests_dir: PathBuf::from(&args.tests_dir),
+        scripts_dir: PathBuf::from(&args.scripts_dir),
+        patterns_dir: PathBuf::from(&args.patterns_dir),
+    };
+
+    let created = init_project(&target, &layout).context("Creating the project")?;
+    for path in &created {
+        println!("created {}", path.display());
+    }
+
     Ok(())
 }
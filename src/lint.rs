@@ -0,0 +1,312 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::Args;
+use regex::Regex;
+use tracing::instrument;
+
+use crate::conf::{parse, ConfValue, Item, Setting};
+
+#[derive(Debug, Args)]
+pub struct LintArguments {
+    /// A grok pattern that is available in Logstash, although it is neither defined in the
+    /// patterns directory nor one of the core grok patterns (may be repeated)
+    #[arg(long = "allow-pattern")]
+    pub allowed_patterns: Vec<String>,
+}
+
+/// The core grok patterns shipped with Logstash (the `grok-patterns`, `httpd`, `java` and
+/// `linux-syslog` sets)
+const CORE_GROK_PATTERNS: &[&str] = &[
+    "USERNAME",
+    "USER",
+    "EMAILLOCALPART",
+    "EMAILADDRESS",
+    "INT",
+    "BASE10NUM",
+    "NUMBER",
+    "BASE16NUM",
+    "BASE16FLOAT",
+    "POSINT",
+    "NONNEGINT",
+    "WORD",
+    "NOTSPACE",
+    "SPACE",
+    "DATA",
+    "GREEDYDATA",
+    "QUOTEDSTRING",
+    "UUID",
+    "URN",
+    "MAC",
+    "CISCOMAC",
+    "WINDOWSMAC",
+    "COMMONMAC",
+    "IPV6",
+    "IPV4",
+    "IP",
+    "HOSTNAME",
+    "IPORHOST",
+    "HOSTPORT",
+    "PATH",
+    "UNIXPATH",
+    "TTY",
+    "WINPATH",
+    "URIPROTO",
+    "URIHOST",
+    "URIPATH",
+    "URIQUERY",
+    "URIPARAM",
+    "URIPATHPARAM",
+    "URI",
+    "MONTH",
+    "MONTHNUM",
+    "MONTHNUM2",
+    "MONTHDAY",
+    "DAY",
+    "YEAR",
+    "HOUR",
+    "MINUTE",
+    "SECOND",
+    "TIME",
+    "DATE_US",
+    "DATE_EU",
+    "ISO8601_TIMEZONE",
+    "ISO8601_SECOND",
+    "TIMESTAMP_ISO8601",
+    "DATE",
+    "DATESTAMP",
+    "TZ",
+    "DATESTAMP_RFC822",
+    "DATESTAMP_RFC2822",
+    "DATESTAMP_OTHER",
+    "DATESTAMP_EVENTLOG",
+    "SYSLOGTIMESTAMP",
+    "PROG",
+    "SYSLOGPROG",
+    "SYSLOGHOST",
+    "SYSLOGFACILITY",
+    "HTTPDATE",
+    "QS",
+    "SYSLOGBASE",
+    "LOGLEVEL",
+    "HTTPDUSER",
+    "HTTPDERROR_DATE",
+    "HTTPD_COMMONLOG",
+    "HTTPD_COMBINEDLOG",
+    "HTTPD20_ERRORLOG",
+    "HTTPD24_ERRORLOG",
+    "HTTPD_ERRORLOG",
+    "COMMONAPACHELOG",
+    "COMBINEDAPACHELOG",
+    "JAVACLASS",
+    "JAVAFILE",
+    "JAVAMETHOD",
+    "JAVASTACKTRACEPART",
+    "JAVATHREAD",
+    "JAVALOGMESSAGE",
+    "CATALINA_DATESTAMP",
+    "CATALINALOG",
+    "TOMCAT_DATESTAMP",
+    "TOMCATLOG",
+    "SYSLOG5424PRINTASCII",
+    "SYSLOGBASE2",
+    "SYSLOGPAMSESSION",
+    "CRON_ACTION",
+    "CRONLOG",
+    "SYSLOGLINE",
+    "SYSLOG5424PRI",
+    "SYSLOG5424SD",
+    "SYSLOG5424BASE",
+    "SYSLOG5424LINE",
+    "NAGIOSTIME",
+];
+
+/// Mutate options whose hash keys are the fields they set
+const MUTATE_KEY_TARGETS: [&str; 3] = ["add_field", "replace", "update"];
+/// Mutate options whose hash values are the fields they set
+const MUTATE_VALUE_TARGETS: [&str; 2] = ["rename", "copy"];
+
+/// A problem found in a rule file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub file: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file.display(), self.line, self.message)
+    }
+}
+
+/// Collects the names of the grok patterns defined in the given pattern files
+#[instrument]
+pub fn defined_patterns(pattern_files: &[PathBuf]) -> anyhow::Result<HashSet<String>> {
+    let mut patterns = HashSet::new();
+    for file in pattern_files {
+        let text = std::fs::read_to_string(file)
+            .with_context(|| format!("Reading the grok pattern file: {}", file.display()))?;
+        patterns.extend(
+            text.lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .filter_map(|l| l.split_whitespace().next())
+                .map(String::from),
+        );
+    }
+    Ok(patterns)
+}
+
+/// Checks a rule file for common problems: `input` or `output` sections (which conflict with
+/// those injected by Lotus), fields set more than once by the same plugin, references to
+/// undefined grok patterns, and unreachable conditional branches
+pub fn lint_rule(file: &Path, text: &str, known_patterns: &HashSet<String>) -> Vec<Problem> {
+    let problem = |line: usize, message: String| Problem {
+        file: file.to_path_buf(),
+        line,
+        message,
+    };
+
+    let sections = match parse(text) {
+        Ok(sections) => sections,
+        Err(e) => return vec![problem(1, format!("Unable to parse the rule file: {e}"))],
+    };
+
+    let mut problems = Vec::new();
+    for section in &sections {
+        match section.kind.as_str() {
+            "filter" => (),
+            "input" | "output" => problems.push(problem(
+                section.line,
+                format!(
+                    "The rule declares an '{}' section, which conflicts with the {} injected by Lotus",
+                    section.kind, section.kind
+                ),
+            )),
+            kind => problems.push(problem(section.line, format!("Unknown section '{kind}'"))),
+        }
+        lint_items(&section.items, known_patterns, &mut |line, message| {
+            problems.push(problem(line, message))
+        });
+    }
+
+    problems
+}
+
+fn lint_items(
+    items: &[Item],
+    known_patterns: &HashSet<String>,
+    report: &mut dyn FnMut(usize, String),
+) {
+    for item in items {
+        match item {
+            Item::Plugin { name, settings } => {
+                if name == "mutate" {
+                    lint_mutate(settings, report);
+                } else if name == "grok" {
+                    lint_grok(settings, known_patterns, report);
+                }
+            }
+            Item::Conditional(branches) => {
+                let mut seen: HashSet<&str> = HashSet::new();
+                for (condition, body, line) in branches {
+                    if let Some(condition) = condition {
+                        if !seen.insert(condition) {
+                            report(
+                                *line,
+                                format!("The branch is unreachable, because an earlier branch has the same condition: {condition}"),
+                            );
+                        } else if is_constant_false(condition) {
+                            report(
+                                *line,
+                                format!("The branch is unreachable, because its condition is never true: {condition}"),
+                            );
+                        }
+                    }
+                    lint_items(body, known_patterns, report);
+                }
+            }
+        }
+    }
+}
+
+/// Whether the condition compares two different string literals for equality, or two equal
+/// ones for inequality
+fn is_constant_false(condition: &str) -> bool {
+    let re = Regex::new(r#"^"([^"]*)" (==|!=) "([^"]*)"$"#).expect("a valid regular expression");
+    re.captures(condition).is_some_and(|c| match &c[2] {
+        "==" => c[1] != c[3],
+        _ => c[1] == c[3],
+    })
+}
+
+fn lint_mutate(settings: &[Setting], report: &mut dyn FnMut(usize, String)) {
+    let mut targets: HashMap<&str, usize> = HashMap::new();
+    for setting in settings {
+        let ConfValue::Hash(entries) = &setting.value else {
+            continue;
+        };
+        for (key, value, line) in entries {
+            let target = if MUTATE_KEY_TARGETS.contains(&setting.name.as_str()) {
+                Some(key.as_str())
+            } else if MUTATE_VALUE_TARGETS.contains(&setting.name.as_str()) {
+                match value {
+                    ConfValue::Str(s) | ConfValue::Word(s) => Some(s.as_str()),
+                    _ => None,
+                }
+            } else {
+                None
+            };
+            let Some(target) = target else {
+                continue;
+            };
+            if let Some(first) = targets.insert(target, *line) {
+                report(
+                    *line,
+                    format!(
+                        "The field '{target}' is already set on line {first} by the same plugin"
+                    ),
+                );
+            }
+        }
+    }
+}
+
+fn lint_grok(
+    settings: &[Setting],
+    known_patterns: &HashSet<String>,
+    report: &mut dyn FnMut(usize, String),
+) {
+    let re = Regex::new(r"%\{(\w+)").expect("a valid regular expression");
+    let inline: HashSet<&str> = settings
+        .iter()
+        .filter(|s| s.name == "pattern_definitions")
+        .filter_map(|s| match &s.value {
+            ConfValue::Hash(entries) => Some(entries.iter().map(|(k, _, _)| k.as_str())),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+
+    for setting in settings.iter().filter(|s| s.name == "match") {
+        for pattern in setting.value.strings() {
+            for name in re
+                .captures_iter(pattern)
+                .map(|c| c.get(1).map_or("", |m| m.as_str()))
+            {
+                if !known_patterns.contains(name)
+                    && !inline.contains(name)
+                    && !CORE_GROK_PATTERNS.contains(&name)
+                {
+                    report(
+                        setting.line,
+                        format!("The grok pattern '{name}' is not defined"),
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::Context;
+use regex::Regex;
+use serde::Deserialize;
+
+/// A regular expression matched against each line of the output of Logstash (e.g. `\[WARN`),
+/// which fails the run if any line matches
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct LogPattern(Regex);
+
+impl LogPattern {
+    pub fn matches(&self, line: &str) -> bool {
+        self.0.is_match(line)
+    }
+}
+
+impl FromStr for LogPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Regex::new(s)
+            .map(LogPattern)
+            .with_context(|| format!("Compiling the log pattern '{s}'"))
+    }
+}
+
+impl TryFrom<String> for LogPattern {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// The size of a log file, i.e. the offset from which the lines logged later are read
+pub fn log_offset(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// The lines of a log file after the given offset that match any of the patterns
+pub fn matching_lines(
+    path: &Path,
+    offset: u64,
+    patterns: &[LogPattern],
+) -> anyhow::Result<Vec<String>> {
+    let data =
+        std::fs::read(path).with_context(|| format!("Reading the log file: {}", path.display()))?;
+    let start = usize::try_from(offset)
+        .unwrap_or(usize::MAX)
+        .min(data.len());
+    Ok(String::from_utf8_lossy(&data[start..])
+        .lines()
+        .filter(|l| patterns.iter().any(|p| p.matches(l)))
+        .map(String::from)
+        .collect())
+}
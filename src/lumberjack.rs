@@ -0,0 +1,56 @@
+use anyhow::{anyhow, Context};
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::{debug, instrument};
+
+const VERSION: u8 = b'2';
+const WINDOW_FRAME: u8 = b'W';
+const JSON_FRAME: u8 = b'J';
+const ACK_FRAME: u8 = b'A';
+
+/// Encodes the events as a window of JSON frames of the Lumberjack protocol (version 2), with
+/// sequence numbers starting at 1
+pub fn encode_window(events: &[Value]) -> anyhow::Result<Vec<u8>> {
+    let mut frames = vec![VERSION, WINDOW_FRAME];
+    frames.extend_from_slice(&u32::try_from(events.len())?.to_be_bytes());
+    for (i, event) in events.iter().enumerate() {
+        let payload = serde_json::to_vec(event).context("Serializing an input event")?;
+        frames.extend_from_slice(&[VERSION, JSON_FRAME]);
+        frames.extend_from_slice(&u32::try_from(i + 1)?.to_be_bytes());
+        frames.extend_from_slice(&u32::try_from(payload.len())?.to_be_bytes());
+        frames.extend_from_slice(&payload);
+    }
+    Ok(frames)
+}
+
+/// Sends the events to a Beats input of Logstash over the Lumberjack protocol, as Filebeat does,
+/// and waits until Logstash acknowledged all of them
+#[instrument(skip(events))]
+pub async fn send_events(host: &str, port: u16, events: &[Value]) -> anyhow::Result<()> {
+    let mut stream = TcpStream::connect((host, port))
+        .await
+        .with_context(|| format!("Connecting to the Beats input at {host}:{port}"))?;
+    stream
+        .write_all(&encode_window(events)?)
+        .await
+        .context("Sending a window of events")?;
+
+    // Logstash may acknowledge parts of the window (or send keep-alives) before the whole of it
+    let last = u32::try_from(events.len())?;
+    loop {
+        let mut ack = [0u8; 6];
+        stream
+            .read_exact(&mut ack)
+            .await
+            .context("Reading the acknowledgement of the events")?;
+        if ack[..2] != [VERSION, ACK_FRAME] {
+            return Err(anyhow!("Received an unexpected frame: {:?}", &ack[..2]));
+        }
+        let sequence = u32::from_be_bytes([ack[2], ack[3], ack[4], ack[5]]);
+        debug!("Logstash acknowledged the events up to {sequence}");
+        if sequence >= last {
+            return Ok(());
+        }
+    }
+}
@@ -5,24 +5,119 @@
 //! (first) and `output` (last) rules. Files in `/rules` are sorted lexicographically before
 //! concatenation.
 
+use std::process::ExitCode;
+
 use anyhow::Context;
 use clap::Parser;
 
-use lotus::{default_runner, DefaultArguments};
+use lotus::{
+    bench_runner, clean_runner, default_runner, diff_runner, doctor_runner, exit_code, fmt_runner,
+    generate_runner, init_runner, lint_runner, mutate_runner, new_test_runner, run_runner,
+    validate_runner, watch_runner, Command, DefaultArguments, Outcome,
+};
 use tracing::debug;
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() -> ExitCode {
+    let args = DefaultArguments::parse();
+
     tracing_subscriber::fmt()
         .with_writer(std::io::stderr)
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::builder()
+                .with_default_directive(args.log_level().into())
+                .from_env_lossy(),
+        )
         .init();
 
-    debug!("Will parse the command line arguments");
-    let args = DefaultArguments::parse();
+    let result = match &args.command {
+        Some(Command::Generate(generate_args)) => {
+            debug!("Will call the test case generator");
+            generate_runner(&args, generate_args)
+                .await
+                .context("During test case generation")
+        }
+        Some(Command::NewTest(new_test_args)) => {
+            debug!("Will call the test case creator");
+            new_test_runner(&args, new_test_args)
+                .await
+                .context("During test case creation")
+        }
+        Some(Command::Validate) => {
+            debug!("Will call the pipeline validator");
+            validate_runner(&args)
+                .await
+                .context("During pipeline validation")
+        }
+        Some(Command::Lint(lint_args)) => {
+            debug!("Will call the rule linter");
+            lint_runner(&args, lint_args).context("During rule linting")
+        }
+        Some(Command::Fmt(fmt_args)) => {
+            debug!("Will call the rule formatter");
+            fmt_runner(&args, fmt_args).context("During rule formatting")
+        }
+        Some(Command::Clean(clean_args)) => {
+            debug!("Will call the cleaner");
+            clean_runner(&args, clean_args)
+                .await
+                .context("During cleaning")
+        }
+        Some(Command::Doctor) => {
+            debug!("Will call the environment diagnostics");
+            doctor_runner(&args)
+                .await
+                .context("During environment diagnostics")
+        }
+        Some(Command::Bench(bench_args)) => {
+            debug!("Will call the benchmark");
+            bench_runner(&args, bench_args)
+                .await
+                .context("During benchmarking")
+        }
+        Some(Command::Diff(diff_args)) => {
+            debug!("Will call the output differ");
+            diff_runner(&args, diff_args)
+                .await
+                .context("During output comparison")
+        }
+        Some(Command::Mutate) => {
+            debug!("Will call the mutation tester");
+            mutate_runner(&args)
+                .await
+                .context("During mutation testing")
+        }
+        Some(Command::Run(run_args)) => {
+            debug!("Will call the run subcommand");
+            run_runner(&args, run_args)
+                .await
+                .context("During run subcommand execution")
+        }
+        Some(Command::Watch(watch_args)) => {
+            debug!("Will call the watch mode runner");
+            watch_runner(&args, watch_args)
+                .await
+                .context("During watch mode execution")
+        }
+        Some(Command::Init) => {
+            debug!("Will call the project initializer");
+            init_runner(&args)
+                .map(|()| Outcome::Success)
+                .context("During project initialization")
+        }
+        None => {
+            debug!("Will call the default test runner");
+            default_runner(&args)
+                .await
+                .context("During default test runner execution")
+        }
+    };
+
+    match &result {
+        Ok(Outcome::Success) => (),
+        Ok(outcome) => eprintln!("Error: {outcome}"),
+        Err(e) => eprintln!("Error: {e:?}"),
+    }
 
-    debug!("Will call the default test runner");
-    default_runner(&args)
-        .await
-        .context("During default test runner execution")
+    exit_code(&result)
 }
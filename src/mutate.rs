@@ -0,0 +1,177 @@
+use std::fmt;
+
+use crate::conf::{parse, spanned_tokens, Token};
+use crate::MUTANT_PATTERN;
+
+/// A rule file with a single small change that a thorough test suite notices
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mutant {
+    /// The line of the rule file that is changed
+    pub line: usize,
+    /// What was changed
+    pub description: String,
+    /// The changed rule file
+    pub text: String,
+}
+
+/// Whether the test suite noticed a mutant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutantStatus {
+    /// A test case failed
+    Killed,
+    /// All test cases passed
+    Survived,
+    /// The pipeline could not run with the mutant
+    Invalid,
+}
+
+impl fmt::Display for MutantStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MutantStatus::Killed => write!(f, "killed"),
+            MutantStatus::Survived => write!(f, "survived"),
+            MutantStatus::Invalid => write!(f, "invalid"),
+        }
+    }
+}
+
+/// What a block opened by a brace in a rule file is
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Block {
+    Section {
+        filter: bool,
+    },
+    Branch,
+    /// The body of a plugin, whose name starts at the given offset and line
+    Plugin {
+        name: String,
+        start: usize,
+        line: usize,
+    },
+    /// The hash of the `match` setting of a grok filter
+    GrokMatch,
+    /// Any other hash
+    Other,
+}
+
+/// Replaces a part of a text
+fn replaced(text: &str, start: usize, end: usize, replacement: &str) -> String {
+    format!("{}{}{}", &text[..start], replacement, &text[end..])
+}
+
+/// Derives the mutants of a rule file from its filter sections: each condition is negated, each
+/// mutate filter is removed, and each grok pattern (given as a hash) is replaced by one that
+/// never matches
+pub fn mutants(text: &str) -> anyhow::Result<Vec<Mutant>> {
+    parse(text)?;
+    let tokens: Vec<_> = spanned_tokens(text)?
+        .into_iter()
+        .filter(|(t, _, _)| !matches!(t, Token::Comment(_)))
+        .collect();
+
+    let mut mutants = Vec::new();
+    let mut stack: Vec<Block> = Vec::new();
+    let mut pending: Option<Block> = None;
+    let mut i = 0;
+    while let Some((token, line, offset)) = tokens.get(i) {
+        let in_filter = stack.first() == Some(&Block::Section { filter: true });
+        let item_level = matches!(
+            stack.last(),
+            None | Some(Block::Section { .. } | Block::Branch)
+        );
+        match token {
+            Token::Open => stack.push(pending.take().unwrap_or(Block::Other)),
+            Token::Close => {
+                if let Some(Block::Plugin { name, start, line }) = stack.pop() {
+                    if in_filter && name == "mutate" {
+                        mutants.push(Mutant {
+                            line,
+                            description: String::from("remove a mutate filter"),
+                            text: replaced(text, start, offset + 1, ""),
+                        });
+                    }
+                }
+            }
+            Token::Word(w) if item_level && pending.is_none() => {
+                if stack.is_empty() {
+                    pending = Some(Block::Section {
+                        filter: w == "filter",
+                    });
+                } else if w == "if" || w == "else" {
+                    let start = match tokens.get(i + 1) {
+                        Some((Token::Word(w), _, _)) if w == "if" => i + 2,
+                        _ if w == "if" => i + 1,
+                        _ => {
+                            pending = Some(Block::Branch);
+                            i += 1;
+                            continue;
+                        }
+                    };
+                    let mut end = start;
+                    let mut depth = 0i32;
+                    while let Some((token, _, _)) = tokens.get(end) {
+                        match token {
+                            Token::Open if depth == 0 => break,
+                            Token::OpenBracket => depth += 1,
+                            Token::CloseBracket => depth -= 1,
+                            _ => (),
+                        }
+                        end += 1;
+                    }
+                    if let (true, Some(first), Some(open)) =
+                        (in_filter, tokens.get(start), tokens.get(end))
+                    {
+                        let condition = text[first.2..open.2].trim_end();
+                        mutants.push(Mutant {
+                            line: first.1,
+                            description: format!("negate the condition {condition}"),
+                            text: replaced(
+                                text,
+                                first.2,
+                                first.2 + condition.len(),
+                                &format!("!({condition})"),
+                            ),
+                        });
+                    }
+                    pending = Some(Block::Branch);
+                    i = end;
+                    continue;
+                } else {
+                    pending = Some(Block::Plugin {
+                        name: w.clone(),
+                        start: *offset,
+                        line: *line,
+                    });
+                }
+            }
+            Token::Word(w)
+                if w == "match"
+                    && matches!(stack.last(), Some(Block::Plugin { name, .. }) if name == "grok")
+                    && matches!(tokens.get(i + 2), Some((Token::Open, _, _))) =>
+            {
+                pending = Some(Block::GrokMatch);
+                i += 2;
+                continue;
+            }
+            Token::Str(s, quote)
+                if in_filter
+                    && stack.last() == Some(&Block::GrokMatch)
+                    && !matches!(tokens.get(i + 1), Some((Token::Word(w), _, _)) if w == "=>") =>
+            {
+                let end = offset + s.len() + 2 * quote.len_utf8();
+                mutants.push(Mutant {
+                    line: *line,
+                    description: format!(
+                        "replace the grok pattern {quote}{s}{quote} with one that never matches"
+                    ),
+                    text: replaced(text, *offset, end, &format!("\"{MUTANT_PATTERN}\"")),
+                });
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+
+    mutants.sort_by_key(|m| m.line);
+    Ok(mutants)
+}
@@ -0,0 +1,103 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::anyhow;
+use serde_json::Value;
+
+/// A reference to a (possibly nested) field of a JSON event. Both the dotted notation (e.g.
+/// `host.ip`) and the Logstash field reference notation (e.g. `[host][ip]`) are accepted.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct FieldPath {
+    segments: Vec<String>,
+}
+
+impl FieldPath {
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    /// Returns a reference to the value at this path, if present
+    pub fn get<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.segments.iter().try_fold(value, |v, segment| match v {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+    }
+
+    /// Returns a mutable reference to the value at this path, if present
+    pub fn get_mut<'a>(&self, value: &'a mut Value) -> Option<&'a mut Value> {
+        self.segments.iter().try_fold(value, |v, segment| match v {
+            Value::Object(map) => map.get_mut(segment),
+            Value::Array(items) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(move |i| items.get_mut(i)),
+            _ => None,
+        })
+    }
+
+    /// Removes the value at this path, if present, and returns it
+    pub fn remove(&self, value: &mut Value) -> Option<Value> {
+        let (last, parents) = self.segments.split_last()?;
+        let parent = parents.iter().try_fold(value, |v, segment| match v {
+            Value::Object(map) => map.get_mut(segment),
+            Value::Array(items) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(move |i| items.get_mut(i)),
+            _ => None,
+        })?;
+
+        match parent {
+            Value::Object(map) => map.remove(last),
+            Value::Array(items) => {
+                let i = last.parse::<usize>().ok()?;
+                (i < items.len()).then(|| items.remove(i))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for FieldPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let segments: Vec<String> = if s.starts_with('[') {
+            let inner = s
+                .strip_prefix('[')
+                .and_then(|s| s.strip_suffix(']'))
+                .ok_or_else(|| anyhow!("Unbalanced brackets in the field reference '{s}'"))?;
+            inner.split("][").map(String::from).collect()
+        } else {
+            s.split('.').map(String::from).collect()
+        };
+
+        if segments
+            .iter()
+            .any(|s| s.is_empty() || s.contains(['[', ']']))
+        {
+            return Err(anyhow!("Invalid field path '{s}'"));
+        }
+
+        Ok(FieldPath { segments })
+    }
+}
+
+impl TryFrom<String> for FieldPath {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for FieldPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for segment in &self.segments {
+            write!(f, "[{segment}]")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A spinner for the preparation of the test environment, whose message describes the current
+/// step. It is hidden if standard error is not a terminal.
+pub fn spinner() -> ProgressBar {
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::with_template("{spinner:.cyan} {msg} [{elapsed}]")
+            .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+    );
+    spinner.enable_steady_tick(TICK_INTERVAL);
+    spinner
+}
+
+/// A progress bar over the test cases of a run, whose message tallies the results so far
+pub fn test_progress(test_cases: usize) -> ProgressBar {
+    let bar = ProgressBar::new(test_cases as u64);
+    bar.set_style(
+        ProgressStyle::with_template("{spinner:.cyan} [{pos}/{len}] {wide_bar:.cyan/blue} {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("=> "),
+    );
+    bar.enable_steady_tick(TICK_INTERVAL);
+    bar.set_message(tally(0, 0));
+    bar
+}
+
+pub fn tally(passed: usize, failed: usize) -> String {
+    format!(
+        "{} {} {} {}",
+        console::style("✔").green(),
+        passed,
+        console::style("✘").red(),
+        failed
+    )
+}
@@ -0,0 +1,362 @@
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use serde::{Serialize, Serializer};
+use tracing::instrument;
+
+use crate::assets::ReportAssets;
+use crate::compare::Mismatch;
+use crate::coverage::CoverageReport;
+use crate::{Outcome, REPORT_TEMPLATE_NAME, SLOWEST_TESTS};
+
+/// The format of a test report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// Causes of the failures and a summary table, for humans
+    Human,
+    /// The Test Anything Protocol, version 14
+    Tap,
+    /// A machine-readable document of all results
+    Json,
+    /// A standalone web page with collapsible output differences of the failed test cases
+    Html,
+}
+
+/// Where a report of the given format is written to (standard output if no path is given), parsed
+/// from `FORMAT[=PATH]`, e.g. `tap` or `tap=results.tap`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportTarget {
+    pub format: ReportFormat,
+    pub path: Option<PathBuf>,
+}
+
+impl FromStr for ReportTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, path) = match s.split_once('=') {
+            Some((format, path)) => (format, Some(PathBuf::from(path))),
+            None => (s, None),
+        };
+        let format = match format {
+            "human" => ReportFormat::Human,
+            "tap" => ReportFormat::Tap,
+            "json" => ReportFormat::Json,
+            "html" => ReportFormat::Html,
+            _ => {
+                return Err(anyhow!(
+                    "Unknown report format '{format}', expected human, tap, json or html"
+                ))
+            }
+        };
+        Ok(Self { format, path })
+    }
+}
+
+/// The outcome of a single test case
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    /// The test case failed, and is marked as expected to fail
+    XFailed,
+    /// The test case passed, but is marked as expected to fail
+    XPassed,
+    /// The test case was not run, because the run stopped at an earlier failure
+    Skipped,
+}
+
+impl TestStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TestStatus::Passed => "ok",
+            TestStatus::Failed => "FAILED",
+            TestStatus::XFailed => "xfail",
+            TestStatus::XPassed => "XPASS",
+            TestStatus::Skipped => "skipped",
+        }
+    }
+
+    /// Whether the outcome fails the run
+    pub fn is_failure(&self) -> bool {
+        matches!(self, TestStatus::Failed | TestStatus::XPassed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TestResult {
+    /// The identifier of the test case (i.e. its path relative to the tests directory)
+    pub name: String,
+    /// The name displayed for the test case
+    pub title: String,
+    pub description: Option<String>,
+    pub status: TestStatus,
+    /// The number of times the test case was run again after failing
+    pub retries: usize,
+    #[serde(rename = "duration_ms", serialize_with = "as_millis")]
+    pub duration: Duration,
+    /// The cause of the failure, including the comparison of the actual and expected output
+    #[serde(serialize_with = "without_colors")]
+    pub error: Option<String>,
+    /// The differences between the actual and expected output, if the comparison failed
+    pub mismatch: Option<Mismatch>,
+}
+
+/// The results of all test cases of a run, in the order they were run
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TestReport {
+    pub results: Vec<TestResult>,
+    #[serde(rename = "duration_ms", serialize_with = "as_millis")]
+    pub duration: Duration,
+    /// The ID of the Logstash container image the test cases were run against
+    pub image_id: Option<String>,
+    /// The ID of the Logstash container the test cases were run against
+    pub container_id: Option<String>,
+    /// Which parts of the rules the test cases exercised, if requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<CoverageReport>,
+}
+
+fn without_colors<S: Serializer>(text: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    match text {
+        Some(text) => serializer.serialize_some(&console::strip_ansi_codes(text)),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn as_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(duration.as_millis())
+}
+
+impl TestReport {
+    pub fn count(&self, status: TestStatus) -> usize {
+        self.results.iter().filter(|r| r.status == status).count()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &TestResult> {
+        self.results.iter().filter(|r| r.status.is_failure())
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        match self.is_success() {
+            true => Outcome::Success,
+            false => Outcome::TestsFailed {
+                failed: self.failures().count(),
+                total: self.results.len(),
+            },
+        }
+    }
+
+    /// The test cases that were run, ordered by descending duration, at most `n` of them
+    pub fn slowest(&self, n: usize) -> Vec<&TestResult> {
+        let mut results: Vec<&TestResult> = self
+            .results
+            .iter()
+            .filter(|r| r.status != TestStatus::Skipped)
+            .collect();
+        results.sort_by_key(|r| std::cmp::Reverse(r.duration));
+        results.truncate(n);
+        results
+    }
+}
+
+impl fmt::Display for TestReport {
+    /// Formats the causes of all failures, followed by a summary table of all test cases
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.is_success() {
+            writeln!(f, "\nfailures:")?;
+            for result in self.failures() {
+                writeln!(f, "\n---- {} ----", result.title)?;
+                if let Some(error) = &result.error {
+                    writeln!(f, "{error}")?;
+                }
+            }
+        }
+
+        writeln!(f, "\nsummary:")?;
+        for result in &self.results {
+            let duration = match result.status {
+                TestStatus::Skipped => String::new(),
+                _ => format!("{:.2}s", result.duration.as_secs_f64()),
+            };
+            write!(
+                f,
+                "    {:<8} {:>8}  {}",
+                result.status.label(),
+                duration,
+                result.title
+            )?;
+            match result.retries {
+                0 => writeln!(f)?,
+                1 => writeln!(f, " (retried once)")?,
+                n => writeln!(f, " (retried {n} times)")?,
+            }
+        }
+
+        if let Some(coverage) = &self.coverage {
+            writeln!(f, "\n{coverage}")?;
+        }
+
+        let slowest = self.slowest(SLOWEST_TESTS);
+        if self.results.len() > slowest.len() {
+            writeln!(f, "\n{} slowest tests:", slowest.len())?;
+            for result in slowest {
+                writeln!(
+                    f,
+                    "    {:>8}  {}",
+                    format!("{:.2}s", result.duration.as_secs_f64()),
+                    result.title
+                )?;
+            }
+        }
+
+        write!(
+            f,
+            "\ntest result: {}. {} passed; {} failed; {} xfail; {} skipped; finished in {:.2}s",
+            if self.is_success() { "ok" } else { "FAILED" },
+            self.count(TestStatus::Passed),
+            self.count(TestStatus::Failed) + self.count(TestStatus::XPassed),
+            self.count(TestStatus::XFailed),
+            self.count(TestStatus::Skipped),
+            self.duration.as_secs_f64()
+        )
+    }
+}
+
+impl TestReport {
+    /// Formats the report according to the Test Anything Protocol, version 14. Test cases expected
+    /// to fail are marked with the TODO directive, and skipped test cases with the SKIP directive.
+    pub fn to_tap(&self) -> anyhow::Result<String> {
+        let mut tap = format!("TAP version 14\n1..{}\n", self.results.len());
+        for (i, result) in self.results.iter().enumerate() {
+            let ok = match result.status {
+                TestStatus::Passed | TestStatus::Skipped => "ok",
+                TestStatus::Failed | TestStatus::XFailed | TestStatus::XPassed => "not ok",
+            };
+            let directive = match result.status {
+                TestStatus::XFailed => " # TODO expected to fail",
+                TestStatus::Skipped => " # SKIP not run after an earlier failure",
+                _ => "",
+            };
+            tap.push_str(&format!(
+                "{} {} - {}{}\n",
+                ok,
+                i + 1,
+                result.title,
+                directive
+            ));
+
+            if result.status == TestStatus::Skipped {
+                continue;
+            }
+            let mut diagnostics = serde_yaml::Mapping::new();
+            if let Some(error) = &result.error {
+                let error = console::strip_ansi_codes(error);
+                diagnostics.insert("message".into(), error.as_ref().into());
+            }
+            diagnostics.insert(
+                "duration_ms".into(),
+                (result.duration.as_millis() as u64).into(),
+            );
+            if result.retries > 0 {
+                diagnostics.insert("retries".into(), (result.retries as u64).into());
+            }
+            let yaml =
+                serde_yaml::to_string(&diagnostics).context("Serializing the TAP diagnostics")?;
+            tap.push_str("  ---\n");
+            for line in yaml.lines() {
+                tap.push_str(&format!("  {line}\n"));
+            }
+            tap.push_str("  ...\n");
+        }
+        Ok(tap)
+    }
+
+    /// Renders the report as a standalone HTML document
+    pub fn to_html(&self) -> anyhow::Result<String> {
+        let pretty = |v: &serde_json::Value| serde_json::to_string_pretty(v).unwrap_or_default();
+        let secs = |d: Duration| format!("{:.2}", d.as_secs_f64());
+        let class = |status: TestStatus| match status {
+            TestStatus::Passed => "ok",
+            s if s.is_failure() => "failed",
+            _ => "other",
+        };
+
+        let ctx = serde_json::json!({
+            "result": if self.is_success() { "ok" } else { "FAILED" },
+            "result_class": if self.is_success() { "ok" } else { "failed" },
+            "passed": self.count(TestStatus::Passed),
+            "failed": self.count(TestStatus::Failed) + self.count(TestStatus::XPassed),
+            "xfailed": self.count(TestStatus::XFailed),
+            "skipped": self.count(TestStatus::Skipped),
+            "duration": secs(self.duration),
+            "image_id": self.image_id,
+            "container_id": self.container_id,
+            "results": self.results.iter().map(|r| serde_json::json!({
+                "name": r.name,
+                "title": r.title,
+                "status": r.status.label(),
+                "class": class(r.status),
+                "duration": secs(r.duration),
+                "retries": r.retries,
+            })).collect::<Vec<_>>(),
+            "failures": self.failures().map(|r| serde_json::json!({
+                "title": r.title,
+                "description": r.description,
+                "error": r.error.as_deref().map(console::strip_ansi_codes),
+                "mismatch": r.mismatch.as_ref().map(|m| {
+                    let mut mismatch = serde_json::json!({
+                        "differences": m.differences.iter().map(|d| d.to_string()).collect::<Vec<_>>(),
+                        "documents": m.shows_documents(),
+                    });
+                    if m.shows_documents() {
+                        mismatch["actual"] = pretty(&m.actual).into();
+                        mismatch["expected"] = pretty(&m.expected).into();
+                    }
+                    mismatch
+                }),
+            })).collect::<Vec<_>>(),
+        });
+
+        let mut hbs = handlebars::Handlebars::new();
+        hbs.register_embed_templates::<ReportAssets>()
+            .context("Loading the report assets")?;
+        hbs.render(REPORT_TEMPLATE_NAME, &ctx)
+            .context("Rendering the HTML test report")
+    }
+
+    /// Writes the report in the format and to the location given by the target
+    #[instrument(skip(self))]
+    pub fn write(&self, target: &ReportTarget) -> anyhow::Result<()> {
+        let data = match (target.format, &target.path) {
+            (ReportFormat::Human, None) => format!("{self}\n"),
+            (ReportFormat::Human, Some(_)) => {
+                console::strip_ansi_codes(&format!("{self}\n")).into()
+            }
+            (ReportFormat::Tap, _) => self.to_tap()?,
+            (ReportFormat::Json, _) => {
+                let mut data = serde_json::to_string_pretty(self)
+                    .context("Serializing the test report as JSON")?;
+                data.push('\n');
+                data
+            }
+            (ReportFormat::Html, _) => self.to_html()?,
+        };
+        match &target.path {
+            Some(path) => std::fs::write(path, data)
+                .with_context(|| format!("Writing the test report: {}", path.display())),
+            None => std::io::stdout()
+                .write_all(data.as_bytes())
+                .context("Writing the test report to standard output"),
+        }
+    }
+}
@@ -1,83 +1,997 @@
-use std::{path::PathBuf, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    future::Future,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use assert_json_diff::assert_json_matches_no_panic;
-use bollard::Docker;
-use reqwest::Client;
+use bollard::{container::RemoveContainerOptions, Docker};
+use futures_util::{future, stream, StreamExt};
+use indicatif::ProgressBar;
+use reqwest::{header::CONTENT_TYPE, Client};
 
 use anyhow::{anyhow, Context};
-use serde_json::{from_reader, Value};
+use serde_json::{from_str, Deserializer, Value};
 use tokio::sync::mpsc::Receiver;
-use tracing::{debug, info_span, instrument, Instrument};
+use tokio::sync::OnceCell;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tracing::{debug, info, info_span, instrument, warn, Instrument};
 
-use crate::docker::{build_container_image, create_container, healthy, Container};
-use crate::{INPUT_PORT, LOCALHOST};
+use crate::assertions::{
+    check_assertions, check_file_assertions, check_presence, load_assertions, read_captured_files,
+    require_jq, uses_jq, Assertion,
+};
+use crate::compare::{
+    align_arrays, align_by, canonicalize_timestamps, coerce_numbers, current_date, fold_case,
+    render_placeholders, resolve_matchers, scoped_differences, CompareMode, DefaultMatcher,
+    DiffStyle, Matcher, Mismatch, Verbosity,
+};
+use crate::config::{CompareStage, ElasticsearchOutput, FileOutput, OutputChannel, TestMetadata};
+use crate::coverage::{instrument_rule, rule_name, CoveragePoint, CoverageReport};
+use crate::docker::{
+    bind_mounts, build_container_image, build_image_archive, build_pipeline_archive, capture_logs,
+    capture_mount, codec_content_type, container_hash, container_logs, core_grok_patterns,
+    create_container, healthy, image_available, project_name, published_ports, pull_base_image,
+    reap_leftovers, stream_logs, swap_pipeline, Connection, Container, ContainerEnv, Image,
+    ImageOptions, InputProtocol, Ports, ResourceLimits, StartupOptions, SyslogFormat,
+    WarmContainer,
+};
+use crate::events::{LifecycleEvent, OutputFormat};
+use crate::fixtures::Fixtures;
+use crate::grok::{self, pattern_definitions, GrokFilter};
+use crate::hooks::{run_hooks, HookKind, Hooks};
+use crate::logs::{log_offset, matching_lines, LogPattern};
+use crate::paths::FieldPath;
+use crate::progress::{spinner, tally, test_progress};
+use crate::report::{TestReport, TestResult, TestStatus};
+use crate::runs::create_run_dir;
+use crate::schema::{check_schema, load_schema};
+use crate::server::{EventRouter, StrayEvent};
+use crate::sockets;
+use crate::stats::{
+    add_plugin_stats, check_stats, fetch_plugin_stats, PluginStats, StatsAssertions,
+};
+use crate::tunnel::SshTunnel;
+use crate::{
+    AFTER_ALL_RESULT_NAME, BEFORE_ALL_RESULT_NAME, COVERAGE_ID_PREFIX, DROP_TIMEOUT,
+    LOGS_RESULT_NAME, LOG_MATCHES_SHOWN, NDJSON_EXTENSION, STALL_LOG_LINES, STARTUP_LOG_LINES,
+    STATS_RESULT_NAME, TEST_ID_HEADER, TEXT_EXTENSION,
+};
 
 #[derive(Debug)]
 pub struct TestContext {
     docker: Docker,
-    container: Container,
+    image: Image,
+    /// The pool of identical Logstash containers, in the order of their slots
+    containers: Vec<Container>,
+    /// The host ports of each container of the pool
+    ports: Vec<Ports>,
     http_client: Client,
-    receiver: Receiver<Value>,
+    router: EventRouter,
+    /// Keep the container running when the context is closed, so that later runs can reuse it
+    keep_running: bool,
+    /// The tasks streaming the output of the containers, if requested
+    log_streams: Vec<JoinHandle<()>>,
+    /// The tasks writing the output of the containers to the run directory
+    log_captures: Vec<JoinHandle<anyhow::Result<()>>>,
+    /// The files the output of the Logstash containers is written to, by slot
+    log_files: Vec<PathBuf>,
+    /// The grok filters of the pipeline and the grok patterns of the project, to explain grok
+    /// failures
+    grok_filters: Vec<GrokFilter>,
+    grok_definitions: HashMap<String, String>,
+    /// The grok patterns shipped with Logstash, read from the container on the first grok failure
+    core_grok_definitions: OnceCell<HashMap<String, String>>,
+    /// The coverage points injected into the rules, if requested
+    coverage_points: Vec<CoveragePoint>,
+    /// The protocol over which the input events are sent to Logstash
+    input_protocol: InputProtocol,
+    _tunnel: Option<SshTunnel>,
 }
 
 impl TestContext {
     #[instrument]
     pub async fn new(
-        receiver: Receiver<Value>,
-        cache_dir: PathBuf,
-        rules: Vec<PathBuf>,
-        scripts: Vec<PathBuf>,
-        patterns: Vec<PathBuf>,
-        delete_container: bool,
+        router: EventRouter,
+        files: PipelineFiles,
+        image_options: ImageOptions,
+        options: &TestOptions,
+        progress: &ProgressBar,
     ) -> anyhow::Result<Self> {
-        debug!("Connect to the Docker API");
-        let docker =
-            Docker::connect_with_local_defaults().context("Connecting to the Docker API")?;
+        let PipelineFiles {
+            cache_dir,
+            rules,
+            scripts,
+            patterns,
+        } = files;
+        if options.mount && !image_options.plugins.is_empty() {
+            return Err(anyhow!(
+                "Plugins cannot be installed into a container of the base image with bind-mounted \
+                 files"
+            ));
+        }
+        if options.mount && options.connection.is_remote() {
+            return Err(anyhow!(
+                "Files cannot be bind-mounted into a container of a remote Docker daemon"
+            ));
+        }
+        if options.reuse_container && options.connection.ports.are_ephemeral() {
+            return Err(anyhow!(
+                "A reused Logstash container cannot be reached through freely chosen ports"
+            ));
+        }
+        if options.containers > 1 && options.connection.is_tunneled() {
+            return Err(anyhow!(
+                "A pool of Logstash containers cannot be reached through an SSH tunnel"
+            ));
+        }
+
+        let mut grok_filters = Vec::new();
+        let mut coverage_points = Vec::new();
+        for (i, rule) in rules.iter().enumerate() {
+            let text = std::fs::read_to_string(rule)
+                .with_context(|| format!("Reading the rule file: {}", rule.display()))?;
+            grok_filters.extend(grok::grok_filters(&text));
+            if image_options.coverage {
+                let (_, points) = instrument_rule(&text, i, &rule_name(rule))
+                    .with_context(|| format!("Instrumenting the rule file: {}", rule.display()))?;
+                coverage_points.extend(points);
+            }
+        }
+        let mut grok_definitions = HashMap::new();
+        for file in &patterns {
+            let text = std::fs::read_to_string(file)
+                .with_context(|| format!("Reading the grok pattern file: {}", file.display()))?;
+            grok_definitions.extend(pattern_definitions(&text));
+        }
+
+        debug!("Connect to the container runtime");
+        let (docker, tunnel) = options.connection.connect().await?;
+        let http_client = reqwest::Client::new();
+        let run_dir = create_run_dir(&cache_dir).context("Creating the run directory")?;
+
+        let project = project_name(&cache_dir)?;
 
-        debug!("Build the Logstash container image");
-        let image = build_container_image(&docker, &cache_dir, &rules, &scripts, &patterns)
+        let capture = match image_options.file_output {
+            Some(_) => Some(capture_mount(&cache_dir)?),
+            None => None,
+        };
+
+        debug!("Render the Logstash configuration and pipeline");
+        build_image_archive(&cache_dir, &rules, &scripts, &patterns, &image_options)
+            .context("Creating the pipeline")?;
+        let binds = match options.mount {
+            true => bind_mounts(&cache_dir, &scripts, &patterns)?,
+            false => Vec::new(),
+        };
+        let container_hash = container_hash(
+            &cache_dir,
+            &image_options,
+            &options.connection,
+            options.limits,
+            &options.env,
+            &binds,
+            capture.as_deref(),
+        )?;
+
+        let warm = match options.reuse_container {
+            true => WarmContainer::load(&cache_dir)?,
+            false => None,
+        };
+        if options.reap {
+            debug!("Remove the leftovers of crashed runs");
+            let keep = warm.as_ref().map(|w| w.container_id.as_str());
+            let (containers, images) = reap_leftovers(&docker, project, keep)
+                .await
+                .context("Removing the leftovers of crashed runs")?;
+            if !containers.is_empty() || !images.is_empty() {
+                info!(
+                    "Removed {} leftover container(s) and {} superseded image(s)",
+                    containers.len(),
+                    images.len()
+                );
+            }
+        }
+        if let Some(warm) = warm {
+            if warm.container_hash == container_hash
+                && image_available(&docker, &warm.image_id, image_options.platform.as_deref()).await
+                && warm.is_ready(&docker).await
+            {
+                debug!("Reuse the running Logstash container {}", warm.container_id);
+                progress.set_message("Updating the pipeline of the running Logstash container");
+                let archive = build_pipeline_archive(&cache_dir, &scripts, &patterns)
+                    .context("Creating the pipeline archive")?;
+                let container = warm.container();
+                let log_streams = match options.show_logstash_logs {
+                    true => vec![stream_logs(&docker, &container, log_name(0), false)],
+                    false => Vec::new(),
+                };
+                let log_captures = vec![capture_logs(
+                    &docker,
+                    &container,
+                    run_dir.join(log_file_name(0)),
+                    false,
+                )];
+                swap_pipeline(&docker, &container, &options.connection, archive)
+                    .await
+                    .context("Replacing the pipeline of the running Logstash container")?;
+                options.output.emit(&LifecycleEvent::ContainerStarted {
+                    container_id: &container.id,
+                });
+
+                return Ok(Self {
+                    docker,
+                    image: warm.image(),
+                    containers: vec![container],
+                    ports: vec![options.connection.ports],
+                    http_client,
+                    router,
+                    keep_running: true,
+                    log_streams,
+                    log_captures,
+                    log_files: vec![run_dir.join(log_file_name(0))],
+                    grok_filters,
+                    grok_definitions,
+                    core_grok_definitions: OnceCell::new(),
+                    coverage_points,
+                    input_protocol: image_options.input_protocol,
+                    _tunnel: tunnel,
+                });
+            }
+
+            debug!(
+                "Remove the outdated Logstash container {}",
+                warm.container_id
+            );
+            let _ = docker
+                .remove_container(
+                    &warm.container_id,
+                    Some(RemoveContainerOptions {
+                        force: true,
+                        ..Default::default()
+                    }),
+                )
+                .await;
+        }
+
+        let image = if options.mount {
+            pull_base_image(&docker, &image_options, progress)
+                .await
+                .context("Pulling the base image")?
+        } else {
+            debug!("Build the Logstash container image");
+            progress.set_message("Building the Logstash image");
+            build_container_image(
+                &docker,
+                &cache_dir,
+                &rules,
+                &scripts,
+                &patterns,
+                &image_options,
+                progress,
+            )
             .await
-            .context("Building the Docker container image for Logstash")?;
+            .context("Building the Docker container image for Logstash")?
+        };
+        options.output.emit(&LifecycleEvent::ImageBuilt {
+            image_id: &image.id,
+        });
 
-        debug!("Create the Logstash container");
-        let container = create_container(&docker, &image, delete_container)
+        progress.set_message("Starting the Logstash container");
+        let mut containers = Vec::new();
+        let mut log_streams = Vec::new();
+        let mut log_captures = Vec::new();
+        for slot in 0..options.containers.max(1) {
+            debug!("Create the Logstash container {slot}");
+            let container = create_container(
+                &docker,
+                &image,
+                &options.connection,
+                options.delete_container && !options.reuse_container,
+                slot,
+                &binds,
+                options.limits,
+                &options.env,
+                project,
+                image_options.platform.as_deref(),
+                image_options.input_protocol,
+                capture.as_deref(),
+            )
             .await
             .context("Creating the Logstash Docker container")?;
 
-        debug!("Start the Logstash container");
-        docker
-            .start_container::<String>(&container.id, None)
-            .await
-            .context("Starting the Logstash Docker container")?;
+            debug!("Start the Logstash container {slot}");
+            docker
+                .start_container::<String>(&container.id, None)
+                .await
+                .context("Starting the Logstash Docker container")?;
+            log_captures.push(capture_logs(
+                &docker,
+                &container,
+                run_dir.join(log_file_name(slot)),
+                true,
+            ));
+            if options.show_logstash_logs {
+                log_streams.push(stream_logs(&docker, &container, log_name(slot), true));
+            }
+            containers.push(container);
+        }
 
-        debug!("Wait for the Logstash container to become healthy");
-        let retries = 10;
-        let delay = Duration::from_secs(10);
-        healthy(&docker, &container, retries, delay)
-            .await
-            .context("Waiting for the Docker container to be healthy")?;
+        debug!("Determine the published ports of the Logstash containers");
+        let mut ports = Vec::new();
+        for (slot, container) in containers.iter().enumerate() {
+            let slot_ports = options.connection.ports.slot(slot)?;
+            ports.push(
+                published_ports(&docker, container, slot_ports, image_options.input_protocol)
+                    .await?,
+            );
+        }
 
-        let http_client = reqwest::Client::new();
+        debug!("Wait for the Logstash containers to become healthy");
+        progress.set_message("Waiting for Logstash to become ready");
+        let started = future::try_join_all(containers.iter().map(|container| {
+            healthy(
+                &docker,
+                container,
+                options.startup.health_retries,
+                options.startup.health_interval,
+            )
+        }));
+        match tokio::time::timeout(options.startup.timeout, started).await {
+            Ok(r) => {
+                r.context("Waiting for the Docker container to be healthy")?;
+            }
+            Err(_) => {
+                let logs = container_logs(&docker, &containers[0], Some(STARTUP_LOG_LINES))
+                    .await
+                    .unwrap_or_default();
+                return Err(anyhow!(
+                    "Logstash did not start within {:?}, its last log lines were (see {} for all of them):\n{}",
+                    options.startup.timeout,
+                    run_dir.join(log_file_name(0)).display(),
+                    logs.trim_end()
+                ));
+            }
+        }
+        for container in &containers {
+            options.output.emit(&LifecycleEvent::ContainerStarted {
+                container_id: &container.id,
+            });
+        }
+
+        if options.reuse_container {
+            WarmContainer {
+                container_id: containers[0].id.clone(),
+                image_id: image.id.clone(),
+                container_hash,
+            }
+            .save(&cache_dir)
+            .context("Recording the running Logstash container")?;
+        }
 
+        let log_files = (0..containers.len())
+            .map(|slot| run_dir.join(log_file_name(slot)))
+            .collect();
         Ok(Self {
             docker,
-            container,
+            image,
+            containers,
+            ports,
             http_client,
-            receiver,
+            router,
+            keep_running: options.reuse_container,
+            log_streams,
+            log_captures,
+            log_files,
+            grok_filters,
+            grok_definitions,
+            core_grok_definitions: OnceCell::new(),
+            coverage_points,
+            input_protocol: image_options.input_protocol,
+            _tunnel: tunnel,
         })
     }
 
+    pub fn image_id(&self) -> &str {
+        &self.image.id
+    }
+
+    /// The ID of the first Logstash container of the pool
+    pub fn container_id(&self) -> &str {
+        &self.containers[0].id
+    }
+
+    /// The number of Logstash containers in the pool
+    pub fn pool_size(&self) -> usize {
+        self.containers.len()
+    }
+
+    pub fn router(&self) -> &EventRouter {
+        &self.router
+    }
+
+    /// Sends a JSON input event with the given correlation ID to the Logstash container in the
+    /// given slot of the pool
+    #[instrument(skip(self, event))]
+    pub async fn send_event(
+        &self,
+        host: &str,
+        slot: usize,
+        test_id: &str,
+        event: &Value,
+    ) -> anyhow::Result<()> {
+        let input_port = self.ports[slot % self.ports.len()].input;
+        if self.input_protocol != InputProtocol::Http {
+            let events = std::slice::from_ref(event);
+            return sockets::send_events(self.input_protocol, host, input_port, events, test_id)
+                .await;
+        }
+        self.http_client
+            .post(format!("http://{host}:{input_port}/"))
+            .header(TEST_ID_HEADER, test_id)
+            .json(event)
+            .send()
+            .await
+            .context("Sending an input event to the Logstash container via HTTP")?
+            .error_for_status()
+            .context("Logstash rejected the input event")?;
+        Ok(())
+    }
+
+    /// Explains why the grok patterns of the pipeline did not match the output events tagged with
+    /// a grok failure, if any
+    async fn explain_grok_failures(&self, events: &Value) -> Option<String> {
+        if self.grok_filters.is_empty() {
+            return None;
+        }
+        let core = self
+            .core_grok_definitions
+            .get_or_init(|| async {
+                match core_grok_patterns(&self.docker, &self.containers[0]).await {
+                    Ok(text) => pattern_definitions(&text),
+                    Err(e) => {
+                        warn!("Failed to read the grok patterns of Logstash: {e:?}");
+                        HashMap::new()
+                    }
+                }
+            })
+            .await;
+        let mut definitions = core.clone();
+        definitions.extend(self.grok_definitions.clone());
+        grok::explain_grok_failures(events, &self.grok_filters, &definitions)
+    }
+
+    /// Describes the state of the Logstash container in the given slot of the pool after it did
+    /// not send an output event in time: its last log lines and the counters of its plugins
+    async fn explain_output_timeout(&self, slot: usize, host: &str) -> String {
+        let slot = slot % self.containers.len();
+        let logs = match container_logs(&self.docker, &self.containers[slot], Some(STALL_LOG_LINES))
+            .await
+        {
+            Ok(logs) => logs.trim_end().to_string(),
+            Err(e) => format!("(unavailable: {e:#})"),
+        };
+        let mut explanation = format!(
+            "The last log lines of {} were (see {} for all of them):\n{}",
+            log_name(slot),
+            self.log_files[slot].display(),
+            logs
+        );
+
+        explanation.push_str(&format!(
+            "\n\nThe counters of the plugins of {} are:",
+            log_name(slot)
+        ));
+        match fetch_plugin_stats(&self.http_client, host, self.ports[slot].api).await {
+            Ok(stats) => {
+                for (id, stats) in stats
+                    .iter()
+                    .filter(|(id, _)| !id.starts_with(COVERAGE_ID_PREFIX))
+                {
+                    explanation.push_str(&format!(
+                        "\n    {} ({}): {} event(s) in, {} failure(s)",
+                        id, stats.name, stats.events_in, stats.failures
+                    ));
+                }
+            }
+            Err(e) => explanation.push_str(&format!(" (unavailable: {e:#})")),
+        }
+        explanation
+    }
+
+    /// The counters of the plugins of the pipeline, summed over all Logstash containers
+    #[instrument(skip(self))]
+    pub async fn plugin_stats(&self, host: &str) -> anyhow::Result<BTreeMap<String, PluginStats>> {
+        let mut total = BTreeMap::new();
+        for ports in &self.ports {
+            let stats = fetch_plugin_stats(&self.http_client, host, ports.api).await?;
+            add_plugin_stats(&mut total, stats);
+        }
+        Ok(total)
+    }
+
+    /// Which parts of the rules events passed through since the given snapshot of the counters of
+    /// the plugins
+    #[instrument(skip(self, before))]
+    pub async fn coverage(
+        &self,
+        host: &str,
+        before: &BTreeMap<String, PluginStats>,
+    ) -> anyhow::Result<CoverageReport> {
+        let after = self.plugin_stats(host).await?;
+        Ok(CoverageReport::new(&self.coverage_points, before, &after))
+    }
+
     #[instrument]
-    async fn close(self) -> anyhow::Result<()> {
-        self.docker.stop_container(&self.container.id, None).await?;
+    pub async fn close(self) -> anyhow::Result<()> {
+        for stream in &self.log_streams {
+            stream.abort();
+        }
+        if self.keep_running {
+            for capture in &self.log_captures {
+                capture.abort();
+            }
+            return Ok(());
+        }
+
+        for container in &self.containers {
+            self.docker.stop_container(&container.id, None).await?;
+        }
+        // The captures end with the output of the stopped containers
+        for capture in self.log_captures {
+            if let Err(e) = capture.await.context("Joining the log capture")? {
+                warn!("Failed to keep the Logstash logs: {e:?}");
+            }
+        }
         Ok(())
     }
 }
 
-#[derive(Debug)]
+/// The name of the file in the run directory holding the output of the Logstash container in the
+/// given slot of the pool
+fn log_file_name(slot: usize) -> String {
+    format!("{}.log", log_name(slot))
+}
+
+/// The prefix of the output lines of the Logstash container in the given slot of the pool
+fn log_name(slot: usize) -> String {
+    match slot {
+        0 => String::from("logstash"),
+        slot => format!("logstash-{}", slot + 1),
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct TestCase {
-    pub(crate) input: PathBuf,
-    pub(crate) expected: PathBuf,
+    pub(crate) name: String,
+    pub(crate) input: Source,
+    pub(crate) expectation: Expectation,
+    pub(crate) metadata: TestMetadata,
+}
+
+impl TestCase {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name displayed for the test case, which may be overridden in its metadata
+    pub fn title(&self) -> &str {
+        self.metadata.name.as_deref().unwrap_or(&self.name)
+    }
+
+    /// The fields stripped from the actual and expected output of the test case: those of the run
+    /// and of its metadata, or only the latter if they replace the former
+    pub fn ignored_fields<'a>(
+        &'a self,
+        options: &'a TestOptions,
+    ) -> impl Iterator<Item = &'a FieldPath> + Clone {
+        let inherited: &[FieldPath] = match self.metadata.replace_ignore {
+            true => &[],
+            false => &options.ignore,
+        };
+        inherited.iter().chain(self.metadata.ignore.iter())
+    }
+
+    /// How strictly the actual output of the test case is compared with its expected output, as
+    /// set in its metadata or else for the run
+    pub fn compare_mode(&self, options: &TestOptions) -> CompareMode {
+        self.metadata.compare.unwrap_or(options.compare)
+    }
+
+    /// Whether strings that encode a number are considered equal to that number, as set in the
+    /// metadata of the test case or else for the run
+    pub fn coerce_numbers(&self, options: &TestOptions) -> bool {
+        self.metadata
+            .coerce_numbers
+            .unwrap_or(options.coerce_numbers)
+    }
+
+    /// The fields of the test case compared as instants, and the maximum difference between
+    /// timestamps that are considered equal
+    pub fn timestamps(&self, options: &TestOptions) -> (Vec<FieldPath>, Duration) {
+        let paths = options
+            .timestamps
+            .iter()
+            .chain(self.metadata.timestamps.iter())
+            .cloned()
+            .collect();
+        let tolerance = self
+            .metadata
+            .timestamp_tolerance
+            .map(Duration::from_millis)
+            .unwrap_or(options.timestamp_tolerance);
+        (paths, tolerance)
+    }
+
+    /// The maximum time to wait for Logstash to accept each input event and to send each output
+    /// event, as set in the metadata of the test case or else for the run
+    pub fn timeout(&self, options: &TestOptions) -> Option<Duration> {
+        self.metadata
+            .timeout
+            .map(Duration::from_secs)
+            .or(options.timeout)
+    }
+
+    /// The number of times the test case is run again after it failed. A test case that is
+    /// expected to fail is never retried, lest it pass by chance.
+    pub fn retries(&self, options: &TestOptions) -> usize {
+        match self.metadata.xfail {
+            true => 0,
+            false => options.retries,
+        }
+    }
+
+    /// Whether the test case has assertions that are jq expressions, which require the `jq`
+    /// program
+    pub fn uses_jq(&self) -> bool {
+        match &self.expectation {
+            Expectation::Assertions(source) => read_assertions(source).is_ok_and(|a| uses_jq(&a)),
+            _ => false,
+        }
+    }
+
+    /// The directory of the test case, or its file if it is a single-file test case
+    pub fn location(&self) -> &Path {
+        match &self.input {
+            Source::File(path) => path.parent().unwrap_or(path),
+            Source::Inline { file, .. } => file,
+        }
+    }
+}
+
+/// Describes where a part of a test case (i.e. its input or expectation) is stored
+#[derive(Debug, Clone)]
+pub enum Source {
+    /// A dedicated file in the test case directory (e.g. `input.json`)
+    File(PathBuf),
+    /// A section of a single-file test case. The data is `null` if the section is missing.
+    Inline { file: PathBuf, data: Value },
+}
+
+impl Source {
+    pub fn path(&self) -> &Path {
+        match self {
+            Source::File(path) => path,
+            Source::Inline { file, .. } => file,
+        }
+    }
+}
+
+/// Describes how the Logstash output of a test case is verified
+#[derive(Debug, Clone)]
+pub enum Expectation {
+    /// The output must match the document in `expected.json`
+    Document(Source),
+    /// The output must satisfy every assertion in `assertions.json` or `assertions.yaml`
+    Assertions(Source),
+    /// Every output event must be valid against the JSON Schema in `expected.schema.json`
+    Schema(Source),
+    /// The pipeline must not emit any output at all (marked by the file `expected.drop`)
+    Drop,
+}
+
+/// The files of a project that the Logstash pipeline is assembled from
+#[derive(Debug, Clone)]
+pub struct PipelineFiles {
+    /// The directory the configuration and pipeline of Logstash are rendered into
+    pub cache_dir: PathBuf,
+    pub rules: Vec<PathBuf>,
+    pub scripts: Vec<PathBuf>,
+    pub patterns: Vec<PathBuf>,
+}
+
+/// Settings that apply to every test case of a run
+#[derive(Debug, Default, Clone)]
+pub struct TestOptions {
+    /// Fields stripped from the actual and expected output before comparison
+    pub ignore: Vec<FieldPath>,
+    /// Write the actual output to the expected output files instead of comparing them
+    pub update_expected: bool,
+    /// The Logstash codec that raw text input events are decoded with, unless overridden by a
+    /// test case
+    pub codec: String,
+    /// The protocol over which the input events are sent to Logstash
+    pub input_protocol: InputProtocol,
+    /// The codec of the `tcp` or `udp` input, instead of the default one of the protocol
+    pub socket_codec: Option<String>,
+    /// The format of the messages that the `syslog` input parses
+    pub syslog_format: SyslogFormat,
+    /// The version of Logstash the test cases run against
+    pub logstash_version: String,
+    /// An image that replaces the official Logstash image as the base of the container image
+    pub base_image: Option<String>,
+    /// The digest the base image is pinned to
+    pub base_image_digest: Option<String>,
+    /// The platform the images are built for and the containers run on
+    pub platform: Option<String>,
+    /// Never pull images from a registry
+    pub offline: bool,
+    /// Report which parts of the rules the test cases exercised
+    pub coverage: bool,
+    /// Include the `@metadata` of the output events in the actual output
+    pub capture_metadata: bool,
+    /// The instant that `@timestamp` and `[event][created]` are pinned to
+    pub clock: Option<String>,
+    /// Logstash plugins installed into the container image in addition to the bundled ones
+    pub plugins: Vec<String>,
+    /// Additional output channels that events are routed to by conditions
+    pub outputs: Vec<OutputChannel>,
+    /// The Elasticsearch output that the output events are sent to instead
+    pub elasticsearch: Option<ElasticsearchOutput>,
+    /// The file output that the output events are also written with
+    pub file_output: Option<FileOutput>,
+    /// The directory the files of the file output are captured in, one subdirectory per test
+    /// case
+    pub files_dir: Option<PathBuf>,
+    /// Assertions on what the plugins of the pipeline did over the whole run
+    pub stats: StatsAssertions,
+    /// Fail the run if Logstash logs a line matching any of these patterns while the test cases
+    /// run
+    pub fail_on_log: Vec<LogPattern>,
+    /// The host name under which the Logstash container reaches Lotus
+    pub callback_host: String,
+    /// How the container runtime and the Logstash container are reached
+    pub connection: Connection,
+    /// Remove the Logstash containers once they stop
+    pub delete_container: bool,
+    /// Keep the Logstash container running after the run, and reuse a running one
+    pub reuse_container: bool,
+    /// The number of identical Logstash containers the test cases are distributed across
+    pub containers: usize,
+    /// Bind-mount the pipeline into a container of the base image instead of building an image
+    pub mount: bool,
+    /// The resources each Logstash container may use at most
+    pub limits: ResourceLimits,
+    /// The environment of the Logstash container
+    pub env: ContainerEnv,
+    /// The number of workers of the pipeline, one if none
+    pub workers: Option<NonZeroUsize>,
+    /// How long to wait for the Logstash containers to become healthy
+    pub startup: StartupOptions,
+    /// Stream the output of the Logstash containers to standard error
+    pub show_logstash_logs: bool,
+    /// Remove the containers and images left over by crashed runs before starting Logstash
+    pub reap: bool,
+    /// How much detail of comparison failures is displayed
+    pub verbosity: Verbosity,
+    /// Shared variables substituted into the input events
+    pub fixtures: Fixtures,
+    /// Run all test cases instead of stopping at the first failure
+    pub keep_going: bool,
+    /// The number of test cases run concurrently
+    pub jobs: usize,
+    /// The maximum time to wait for Logstash to accept each input event and to send each output
+    /// event, unless overridden by a test case (indefinitely if none)
+    pub timeout: Option<Duration>,
+    /// The number of times a failed test case is run again before it is reported as failed
+    pub retries: usize,
+    /// Print the result of each test case as soon as it is known
+    pub progress: bool,
+    /// How the differences between the actual and the expected output are displayed
+    pub diff_style: DiffStyle,
+    /// How the progress of the run is presented
+    pub output: OutputFormat,
+    /// Compares the actual with the expected output of the test cases ([`DefaultMatcher`] if
+    /// none)
+    pub matcher: Option<Arc<dyn Matcher>>,
+    /// How strictly the actual output is compared with the expected output, unless overridden by
+    /// a test case
+    pub compare: CompareMode,
+    /// Fields compared as instants rather than strings
+    pub timestamps: Vec<FieldPath>,
+    /// The maximum difference between timestamps that are considered equal, unless overridden by
+    /// a test case
+    pub timestamp_tolerance: Duration,
+    /// Consider strings that encode a number equal to that number
+    pub coerce_numbers: bool,
+    /// Fields whose strings are compared regardless of case
+    pub case_insensitive: Vec<FieldPath>,
+    /// Run before and after all or each of the test cases
+    pub hooks: Hooks,
+    /// The project directory, in which the commands of the hooks run
+    pub project_dir: PathBuf,
+}
+
+impl TestOptions {
+    /// Whether the run stops after recording a result with the given status, or after blaming a
+    /// test case for leaked output events, instead of running the remaining test cases
+    pub fn stops_after(&self, status: TestStatus, leaked: bool) -> bool {
+        !self.keep_going && (status.is_failure() || leaked)
+    }
+
+    /// The matcher that compares the actual with the expected output of the test cases
+    pub fn matcher(&self) -> &dyn Matcher {
+        self.matcher.as_deref().unwrap_or(&DefaultMatcher)
+    }
+}
+
+/// Deserializes the input events of a test case, with its fixtures rendered
+async fn load_inputs(test_case: &TestCase, options: &TestOptions) -> anyhow::Result<Vec<Value>> {
+    debug!("Deserialize the input events");
+    let inputs = match &test_case.input {
+        Source::File(input) => {
+            let text = tokio::fs::read_to_string(input)
+                .await
+                .with_context(|| format!("When reading the input file: {}", input.display()))?;
+            let text = options.fixtures.render(&text)?;
+            parse_events(input, &text).context("When reading the input file")?
+        }
+        Source::Inline { data, .. } => {
+            let mut events = match data {
+                Value::Array(events) => events.clone(),
+                event => vec![event.clone()],
+            };
+            for event in events.iter_mut() {
+                options.fixtures.render_value(event)?;
+            }
+            events
+        }
+    };
+    if inputs.is_empty() {
+        return Err(anyhow!("The input file does not contain any events"));
+    }
+    Ok(inputs)
+}
+
+/// Posts the input events of a test case to the HTTP input of Logstash, raw text events encoded
+/// for the given codec, and returns the HTTP status with which Logstash answered each of them.
+/// Over the other protocols, the events are sent at once and deemed accepted once sent (or
+/// acknowledged, for Lumberjack).
+async fn send_inputs(
+    client: &Client,
+    inputs: &[Value],
+    codec: &str,
+    test_id: &str,
+    input_port: u16,
+    wait: Option<Duration>,
+    options: &TestOptions,
+) -> anyhow::Result<Vec<u16>> {
+    if options.input_protocol != InputProtocol::Http {
+        let logstash_host = options.connection.logstash_host();
+        debug!("Send the input events to Logstash running at {logstash_host}:{input_port}");
+        let protocol = options.input_protocol;
+        within(
+            wait,
+            sockets::send_events(protocol, &logstash_host, input_port, inputs, test_id),
+        )
+        .await
+        .ok_or_else(|| anyhow!("Logstash did not accept the input events within {wait:?}"))??;
+        return Ok(vec![200; inputs.len()]);
+    }
+
+    let mut responses = Vec::with_capacity(inputs.len());
+    for (i, input_data) in inputs.iter().enumerate() {
+        let request_span = info_span!("logstash_request");
+        let logstash_host = options.connection.logstash_host();
+        debug!("Post input event {i} to Logstash running at {logstash_host}:{input_port}");
+        let request = client
+            .post(format!("http://{}:{}/", logstash_host, input_port))
+            .header(TEST_ID_HEADER, test_id);
+        let request = match input_data {
+            Value::String(line) => request
+                .header(CONTENT_TYPE, codec_content_type(codec))
+                .body(line.clone()),
+            event => request.json(event),
+        };
+        let response = within(wait, request.send().instrument(request_span))
+            .await
+            .ok_or_else(|| anyhow!("Logstash did not accept input event {i} within {wait:?}"))?
+            .with_context(|| {
+                format!("Sending input event {i} to the Logstash container via HTTP")
+            })?;
+        responses.push(response.status().as_u16());
+    }
+    Ok(responses)
+}
+
+/// The events a test case exchanged with Logstash
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    /// The input events sent to Logstash
+    pub inputs: Vec<Value>,
+    /// The expected output document of the test case, if it has one
+    pub expected: Option<Value>,
+    /// The output events received from Logstash
+    pub outputs: Vec<Value>,
+    /// Why fewer output events were received than the test case expects, if they were
+    pub timeout: Option<OutputTimeout>,
+}
+
+/// Sends the input events of a test case to Logstash, and collects as many output events as the
+/// test case expects (fewer if no more arrive in time)
+#[instrument(skip(client, receiver))]
+pub async fn collect_outputs(
+    client: &Client,
+    receiver: &mut Receiver<Value>,
+    test_case: &TestCase,
+    test_id: &str,
+    input_port: u16,
+    options: &TestOptions,
+) -> anyhow::Result<Exchange> {
+    let codec = test_case.metadata.codec.as_ref().unwrap_or(&options.codec);
+    let wait = test_case.timeout(options);
+
+    let inputs = load_inputs(test_case, options).await?;
+
+    let expected = match &test_case.expectation {
+        Expectation::Document(Source::File(expected))
+            if options.update_expected && !expected.is_file() =>
+        {
+            None
+        }
+        Expectation::Document(Source::File(expected)) => {
+            debug!("Deserialize the expected output file as JSON");
+            let expected = read_events(expected)
+                .await
+                .context("When reading the expected output file")?;
+            expected.into_iter().next()
+        }
+        Expectation::Document(Source::Inline {
+            data: Value::Null, ..
+        }) => None,
+        Expectation::Document(Source::Inline { data, .. }) => Some(data.clone()),
+        Expectation::Assertions(_) | Expectation::Schema(_) | Expectation::Drop => None,
+    };
+
+    debug!("Determine the number of output events to wait for");
+    let output_count = match (&test_case.expectation, &expected) {
+        (Expectation::Drop, _) => 0,
+        (_, Some(Value::Array(expected_events))) => expected_events.len(),
+        (_, Some(_)) => 1,
+        (_, None) => inputs.len(),
+    };
+
+    let responses = send_inputs(client, &inputs, codec, test_id, input_port, wait, options).await?;
+
+    let mut outputs = Vec::with_capacity(output_count);
+    let mut stalled = None;
+    for i in 0..output_count {
+        let response_span = info_span!("logstash_response");
+        debug!("Wait for output event {i} from the Logstash response handler (MPSC channel)");
+        match within(wait, receiver.recv().instrument(response_span)).await {
+            Some(Some(output_data)) => outputs.push(output_data),
+            Some(None) => return Err(anyhow!("Logstash did not send output event data")),
+            None => {
+                stalled = Some(OutputTimeout {
+                    event: i,
+                    wait,
+                    responses,
+                });
+                break;
+            }
+        }
+    }
+
+    Ok(Exchange {
+        inputs,
+        expected,
+        outputs,
+        timeout: stalled,
+    })
 }
 
 #[instrument]
@@ -85,112 +999,1018 @@ pub async fn run_single_test(
     client: &Client,
     receiver: &mut Receiver<Value>,
     test_case: &TestCase,
-    verbose: bool,
+    test_id: &str,
+    input_port: u16,
+    options: &TestOptions,
 ) -> anyhow::Result<()> {
-    debug!("Deserialize the input file as JSON");
-    let input = tokio::fs::File::open(&test_case.input)
-        .await
-        .context("When opening the input file")?;
-    let input = input.into_std().await;
-    let input_data = tokio::task::spawn_blocking(|| {
-        from_reader::<_, Value>(input).context("When deserializing the input file")
-    })
-    .await??;
-
-    let request_span = info_span!("logstash_request");
-    debug!("Post the input data to Logstash running at {LOCALHOST}:{INPUT_PORT}");
-    client
-        .post(format!("http://{}:{}/", LOCALHOST, INPUT_PORT))
-        .json(&input_data)
-        .send()
-        .instrument(request_span)
-        .await
-        .context("Sending input data to the Logstash container via HTTP")?;
+    let ignore = test_case.ignored_fields(options);
+    let Exchange {
+        inputs,
+        expected: expected_data,
+        outputs,
+        timeout: stalled,
+    } = collect_outputs(client, receiver, test_case, test_id, input_port, options).await?;
+    if let Some(stalled) = stalled {
+        return Err(anyhow::Error::new(stalled));
+    }
 
-    let response_span = info_span!("logstash_response");
-    debug!("Wait for a message from the Logstash response handler (MPSC channel)");
-    let output_data = receiver
-        .recv()
-        .instrument(response_span)
-        .await
-        .ok_or(anyhow!("Logstash did not send output event data"))?;
+    for (i, output_data) in outputs.iter().enumerate() {
+        debug!("Check the presence and absence of fields in output event {i}");
+        check_presence(
+            &test_case.metadata.must_exist,
+            &test_case.metadata.must_not_exist,
+            output_data,
+        )
+        .with_context(|| format!("Checking the fields of the actual Logstash output event {i}"))?;
+    }
 
-    debug!("Deserialize the expected output file as JSON");
-    let expected = tokio::fs::File::open(&test_case.expected)
-        .await
-        .context("When opening the expected output file")?;
-    let expected = expected.into_std().await;
-    let expected_data = tokio::task::spawn_blocking(|| {
-        from_reader::<_, Value>(expected).context("Deserializing the expected output file")
-    })
-    .await??;
+    match (&test_case.expectation, expected_data) {
+        (Expectation::Document(expected), expected_data) if options.update_expected => {
+            let as_array = outputs.len() != 1 || expected_data.is_some_and(|e| e.is_array());
+            record_document(outputs, expected, ignore, as_array)
+                .await
+                .context("Recording the actual Logstash output as the expected output")
+        }
+        (Expectation::Document(expected), Some(expected_data)) => compare_document(
+            outputs,
+            expected_data,
+            &inputs,
+            test_case,
+            expected.path().parent().unwrap_or(Path::new(".")),
+            options,
+        )
+        .context("Comparing the actual Logstash output (lhs) with the expected output (rhs)"),
+        (Expectation::Assertions(assertions), _) => {
+            debug!("Load the assertions");
+            let source = assertions.clone();
+            let assertions =
+                tokio::task::spawn_blocking(move || read_assertions(&source)).await??;
+            require_jq(&assertions)?;
 
-    debug!("Compare the JSON objects of the Logstash output (lhs) and the expected output (rhs)");
-    let config = assert_json_diff::Config::new(assert_json_diff::CompareMode::Strict);
-    assert_json_matches_no_panic(&output_data, &expected_data, config)
-        .map_err(|e| {
-            let output_json = match serde_json::to_string_pretty(&output_data) {
-                Ok(oj) => oj,
-                Err(e) => return Into::<anyhow::Error>::into(e),
-            };
-            let expected_json = match serde_json::to_string_pretty(&expected_data) {
-                Ok(ej) => ej,
-                Err(e) => return Into::<anyhow::Error>::into(e),
+            for (i, output_data) in outputs.iter().enumerate() {
+                debug!("Evaluate the assertions against output event {i}");
+                check_assertions(&assertions, output_data).with_context(|| {
+                    format!("Checking the assertions against the actual Logstash output event {i}")
+                })?;
+            }
+
+            if assertions.iter().any(|a| a.file.is_some()) {
+                let files_dir = options.files_dir.as_ref().ok_or_else(|| {
+                    anyhow!("Assertions on files require a file output in lotus.toml")
+                })?;
+                debug!("Evaluate the assertions against the captured files");
+                let dir = files_dir.join(test_id);
+                let files =
+                    tokio::task::spawn_blocking(move || read_captured_files(&dir)).await??;
+                check_file_assertions(&assertions, &files)
+                    .context("Checking the assertions against the files of the file output")?;
+            }
+
+            Ok(())
+        }
+        (Expectation::Schema(schema), _) => {
+            debug!("Load the schema");
+            let schema = match schema {
+                Source::File(schema) => {
+                    let schema = schema.clone();
+                    tokio::task::spawn_blocking(move || load_schema(&schema)).await??
+                }
+                Source::Inline { data, .. } => data.clone(),
             };
 
-            if verbose {
-                anyhow!("{e}\n\nactual:\n{output_json}\n\nexpected:\n{expected_json}")
-            } else {
-                anyhow!("{e}")
+            for (i, output_data) in outputs.iter().enumerate() {
+                debug!("Validate output event {i} against the schema");
+                check_schema(&schema, output_data).with_context(|| {
+                    format!("Validating the actual Logstash output event {i} against the schema")
+                })?;
             }
-        })
-        .context("Comparing the actual Logstash output (lhs) with the expected output (rhs)")?;
+
+            Ok(())
+        }
+        (Expectation::Drop, _) => {
+            let response_span = info_span!("logstash_response");
+            debug!("Ensure that Logstash does not send an output event within {DROP_TIMEOUT:?}");
+            match timeout(DROP_TIMEOUT, receiver.recv())
+                .instrument(response_span)
+                .await
+            {
+                Ok(Some(output_data)) => Err(anyhow!(
+                    "Expected the event to be dropped, but Logstash sent output event data:\n{}",
+                    serde_json::to_string_pretty(&output_data)?
+                )),
+                Ok(None) | Err(_) => Ok(()),
+            }
+        }
+        (Expectation::Document(expected), None) => Err(anyhow!(
+            "The expected output is empty: {}",
+            expected.path().display()
+        )),
+    }
+}
+
+/// Passes the actual and the expected output through the comparison stages of the test case,
+/// after rendering the placeholders and stripping the ignored fields
+#[instrument(skip(test_case))]
+fn compare_document(
+    mut outputs: Vec<Value>,
+    mut expected_data: Value,
+    inputs: &[Value],
+    test_case: &TestCase,
+    test_case_dir: &Path,
+    options: &TestOptions,
+) -> anyhow::Result<()> {
+    let metadata = &test_case.metadata;
+    let mode = test_case.compare_mode(options);
+    let mut output_data = match expected_data {
+        Value::Array(_) => Value::Array(outputs),
+        _ => outputs.remove(0),
+    };
+
+    if !metadata.literal {
+        debug!("Render the placeholders of the expected output");
+        let today = current_date(options.clock.as_deref());
+        for (i, event) in events_mut(&mut expected_data).enumerate() {
+            render_placeholders(event, &today, inputs.get(i).or(inputs.first()));
+        }
+    }
+
+    debug!("Strip the ignored fields from the actual and expected output");
+    for event in events_mut(&mut output_data).chain(events_mut(&mut expected_data)) {
+        for path in test_case.ignored_fields(options) {
+            path.remove(event);
+        }
+    }
+
+    let default_stages = [CompareStage::Diff { compare: None }];
+    let stages = match metadata.stages.as_slice() {
+        [] => &default_stages[..],
+        stages => stages,
+    };
+    for (i, stage) in stages.iter().enumerate() {
+        debug!("Run the comparison stage {i}: {stage:?}");
+        match stage {
+            CompareStage::Ignore { paths } => {
+                for event in events_mut(&mut output_data).chain(events_mut(&mut expected_data)) {
+                    for path in paths {
+                        path.remove(event);
+                    }
+                }
+            }
+            CompareStage::Normalize { paths, transform } => {
+                for event in events_mut(&mut output_data).chain(events_mut(&mut expected_data)) {
+                    for path in paths {
+                        if let Some(value) = path.get_mut(event) {
+                            transform.apply(value);
+                        }
+                    }
+                }
+            }
+            CompareStage::Schema { file } => {
+                let schema = load_schema(&test_case_dir.join(file))?;
+                for (j, event) in events_mut(&mut output_data).enumerate() {
+                    check_schema(&schema, event).with_context(|| {
+                        format!(
+                            "Validating the actual Logstash output event {j} against the schema"
+                        )
+                    })?;
+                }
+            }
+            CompareStage::Diff { compare } => diff_document(
+                &mut output_data,
+                &mut expected_data,
+                compare.unwrap_or(mode),
+                test_case,
+                options,
+            )?,
+        }
+    }
 
     Ok(())
 }
 
-#[allow(clippy::too_many_arguments)]
+/// Compares the actual with the expected output in the given mode, after aligning unordered
+/// events and arrays and resolving the value matchers
+fn diff_document(
+    output_data: &mut Value,
+    expected_data: &mut Value,
+    mode: CompareMode,
+    test_case: &TestCase,
+    options: &TestOptions,
+) -> anyhow::Result<()> {
+    let metadata = &test_case.metadata;
+    let matcher = match metadata.default_matcher {
+        true => &DefaultMatcher,
+        false => options.matcher(),
+    };
+
+    let (timestamps, tolerance) = test_case.timestamps(options);
+    let case_insensitive: Vec<FieldPath> = options
+        .case_insensitive
+        .iter()
+        .chain(metadata.case_insensitive.iter())
+        .cloned()
+        .collect();
+    let normalize = |output_event: &mut Value, expected_event: &Value| -> anyhow::Result<()> {
+        debug!("Compare the timestamps as instants");
+        canonicalize_timestamps(output_event, expected_event, &timestamps, tolerance);
+
+        debug!("Compare the case-insensitive fields regardless of case");
+        fold_case(output_event, expected_event, &case_insensitive);
+
+        if test_case.coerce_numbers(options) {
+            debug!("Coerce the strings that encode numbers");
+            coerce_numbers(output_event, expected_event);
+        }
+
+        debug!("Align the elements of the unordered arrays with the expected ones");
+        align_arrays(
+            output_event,
+            expected_event,
+            &metadata.unordered_arrays,
+            false,
+            mode,
+            matcher,
+        )?;
+        align_arrays(
+            output_event,
+            expected_event,
+            &metadata.contains_arrays,
+            true,
+            mode,
+            matcher,
+        )
+    };
+
+    // Several workers may reorder the events of a test case, unlike a single one
+    let unordered = metadata.unordered || options.workers.is_some_and(|w| w.get() > 1);
+    if let (true, Value::Array(output_events), Value::Array(expected_events)) =
+        (unordered, &mut *output_data, &*expected_data)
+    {
+        debug!("Align the actual output events with the expected ones, regardless of order");
+        // Each pair is compared as the aligned events are below, so that events only differing
+        // in what is normalized or out of scope are paired
+        align_by(
+            output_events,
+            expected_events,
+            |output_event, expected_event| {
+                let mut output_event = output_event.clone();
+                let mut expected_event = expected_event.clone();
+                normalize(&mut output_event, &expected_event)?;
+                if !metadata.literal {
+                    resolve_matchers(&output_event, &mut expected_event)?;
+                }
+                Ok(scoped_differences(
+                    matcher,
+                    &output_event,
+                    &expected_event,
+                    mode,
+                    &metadata.compare_paths,
+                )
+                .is_empty())
+            },
+        )
+        .context("Aligning the actual output events with the expected output events")?;
+    }
+
+    for (output_event, expected_event) in events_mut(output_data).zip(events_mut(expected_data)) {
+        normalize(output_event, expected_event)?;
+    }
+
+    if !metadata.literal {
+        debug!("Resolve the value matchers in the expected output");
+        resolve_matchers(output_data, expected_data)
+            .context("Resolving the value matchers of the expected output")?;
+    }
+
+    debug!("Compare the JSON objects of the Logstash output (lhs) and the expected output (rhs)");
+    let scopes = match &*output_data {
+        Value::Array(events) => (0..events.len())
+            .flat_map(|i| {
+                metadata
+                    .compare_paths
+                    .iter()
+                    .map(move |(path, mode)| Ok((format!("[{i}]{path}").parse()?, *mode)))
+            })
+            .collect::<anyhow::Result<_>>()?,
+        _ => metadata.compare_paths.clone(),
+    };
+    let differences = scoped_differences(matcher, output_data, expected_data, mode, &scopes);
+    if differences.is_empty() {
+        return Ok(());
+    }
+
+    Err(anyhow::Error::new(Mismatch {
+        differences,
+        actual: output_data.clone(),
+        expected: expected_data.clone(),
+        verbosity: options.verbosity,
+        style: options.diff_style,
+    }))
+}
+
+/// Writes the actual output events to the expected output file, either as a single event or as
+/// an array of events
+#[instrument(skip(ignore))]
+async fn record_document<'a>(
+    mut outputs: Vec<Value>,
+    expected: &Source,
+    ignore: impl Iterator<Item = &'a FieldPath> + Clone,
+    as_array: bool,
+) -> anyhow::Result<()> {
+    debug!("Strip the ignored fields from the actual output");
+    for event in outputs.iter_mut() {
+        for path in ignore.clone() {
+            path.remove(event);
+        }
+    }
+
+    let document = match as_array {
+        true => Value::Array(outputs),
+        false => outputs.remove(0),
+    };
+
+    let path = expected.path();
+    debug!("Write the actual output to {}", path.display());
+    let data = match expected {
+        Source::File(_) => {
+            let mut data = serde_json::to_string_pretty(&document)?;
+            data.push('\n');
+            data
+        }
+        Source::Inline { file, .. } => {
+            let case = tokio::fs::read_to_string(file)
+                .await
+                .with_context(|| format!("Reading the test case file: {}", file.display()))?;
+            let mut case: serde_yaml::Mapping = serde_yaml::from_str(&case)
+                .with_context(|| format!("Parsing the test case file: {}", file.display()))?;
+            case.insert("expected".into(), serde_yaml::to_value(&document)?);
+            serde_yaml::to_string(&case)?
+        }
+    };
+    tokio::fs::write(path, data)
+        .await
+        .with_context(|| format!("Writing the expected output: {}", path.display()))
+}
+
+/// Reads the assertions of a test case from its file or from the test case file itself
+fn read_assertions(source: &Source) -> anyhow::Result<Vec<Assertion>> {
+    match source {
+        Source::File(path) => load_assertions(path),
+        Source::Inline { data, .. } => {
+            serde_json::from_value(data.clone()).context("Deserializing the inline assertions")
+        }
+    }
+}
+
+/// Reads a file containing a single JSON event, newline-delimited JSON events if its extension
+/// is `ndjson`, or raw text events (one per line, as JSON strings) if its extension is `txt`
+#[instrument]
+async fn read_events(path: &Path) -> anyhow::Result<Vec<Value>> {
+    let text = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("When reading the file: {}", path.display()))?;
+    parse_events(path, &text)
+}
+
+/// Parses the contents of an event file, whose format is determined by the extension of its path
+#[instrument(skip(text))]
+pub fn parse_events(path: &Path, text: &str) -> anyhow::Result<Vec<Value>> {
+    if path.extension().is_some_and(|e| e == TEXT_EXTENSION) {
+        return Ok(text
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|l| Value::String(l.to_string()))
+            .collect());
+    }
+
+    if path.extension().is_some_and(|e| e == NDJSON_EXTENSION) {
+        Deserializer::from_str(text)
+            .into_iter::<Value>()
+            .collect::<Result<Vec<_>, _>>()
+            .context("When deserializing the newline-delimited JSON events")
+    } else {
+        from_str::<Value>(text)
+            .map(|v| vec![v])
+            .context("When deserializing the JSON event")
+    }
+}
+
+/// Awaits the future for at most the given duration (indefinitely if none), returning `None` if
+/// it elapses
+async fn within<T>(wait: Option<Duration>, future: impl Future<Output = T>) -> Option<T> {
+    match wait {
+        Some(wait) => timeout(wait, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// Logstash did not send an output event that a test case expects in time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputTimeout {
+    /// The index of the output event that did not arrive
+    pub event: usize,
+    /// How long it was waited for
+    pub wait: Option<Duration>,
+    /// The HTTP status with which Logstash answered each input event of the test case (200 for
+    /// those acknowledged over the Lumberjack protocol)
+    pub responses: Vec<u16>,
+}
+
+impl fmt::Display for OutputTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Logstash did not send output event {} within {:?}",
+            self.event, self.wait
+        )?;
+        let rejected: Vec<String> = self
+            .responses
+            .iter()
+            .enumerate()
+            .filter(|(_, status)| !(200..300).contains(*status))
+            .map(|(i, status)| format!("input event {i} with HTTP {status}"))
+            .collect();
+        match rejected.is_empty() {
+            true => write!(
+                f,
+                ", although it accepted all {} input event(s)",
+                self.responses.len()
+            ),
+            false => write!(f, ", and it answered {}", rejected.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for OutputTimeout {}
+
+/// Iterates over the events of a document, which is either a single event or an array of events
+fn events_mut(document: &mut Value) -> std::slice::IterMut<'_, Value> {
+    match document {
+        Value::Array(events) => events.iter_mut(),
+        event => std::slice::from_mut(event).iter_mut(),
+    }
+}
+
 #[instrument]
 pub async fn run_tests(
-    receiver: Receiver<Value>,
-    cache_dir: PathBuf,
-    rules: Vec<PathBuf>,
+    router: EventRouter,
+    files: PipelineFiles,
     test_cases: Vec<TestCase>,
-    scripts: Vec<PathBuf>,
-    patterns: Vec<PathBuf>,
-    delete_container: bool,
-    verbose: bool,
-) -> anyhow::Result<()> {
-    let mut test_result: anyhow::Result<()> = Ok(());
+    image_options: ImageOptions,
+    options: TestOptions,
+) -> anyhow::Result<TestReport> {
+    let started = Instant::now();
+    let output = options.output;
+    output.emit(&LifecycleEvent::SuiteStarted {
+        tests: test_cases.len(),
+    });
 
     debug!("Create the test environment");
-    let mut context = TestContext::new(
-        receiver,
-        cache_dir,
-        rules,
-        scripts,
-        patterns,
-        delete_container,
-    )
-    .await
-    .context("Bootstrapping the test environment")?;
-
-    for (i, test_case) in test_cases.iter().enumerate() {
-        debug!("Run test case {i}: {test_case:?}");
-        let r = run_single_test(&context.http_client, &mut context.receiver, test_case, verbose)
+    let spinner = spinner();
+    let context = TestContext::new(router, files, image_options, &options, &spinner)
+        .await
+        .context("Bootstrapping the test environment")?;
+    spinner.finish_and_clear();
+
+    // The counters of the marker plugins are compared with those before the run, like the
+    // statistics of the pipeline
+    let logstash_host = options.connection.logstash_host();
+    let coverage_baseline = match options.coverage {
+        true => Some(context.plugin_stats(&logstash_host).await),
+        false => None,
+    };
+
+    let results = run_test_cases(&context, &test_cases, &options).await;
+    let coverage = match coverage_baseline {
+        Some(baseline) => {
+            let coverage = match baseline {
+                Ok(before) => context.coverage(&logstash_host, &before).await,
+                Err(e) => Err(e),
+            };
+            match coverage {
+                Ok(coverage) => Some(coverage),
+                Err(e) => {
+                    warn!("Failed to determine the coverage of the rules: {e:?}");
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+    output.emit(&LifecycleEvent::SuiteFinished {
+        passed: results
+            .iter()
+            .filter(|r| matches!(r.status, TestStatus::Passed | TestStatus::XFailed))
+            .count(),
+        failed: results.iter().filter(|r| r.status.is_failure()).count(),
+        skipped: results
+            .iter()
+            .filter(|r| r.status == TestStatus::Skipped)
+            .count(),
+        duration_ms: started.elapsed().as_millis(),
+    });
+
+    let image_id = context.image.id.clone();
+    let container_id = context.container_id().to_string();
+    context.close().await?;
+
+    Ok(TestReport {
+        results,
+        duration: started.elapsed(),
+        image_id: Some(image_id),
+        container_id: Some(container_id),
+        coverage,
+    })
+}
+
+/// Runs the input events of the given test cases through a new test environment, and collects
+/// their output events (or why they could not be collected) in the order of the test cases
+#[instrument(skip(router))]
+pub async fn collect_test_outputs(
+    router: EventRouter,
+    files: PipelineFiles,
+    test_cases: &[TestCase],
+    image_options: ImageOptions,
+    options: &TestOptions,
+) -> anyhow::Result<Vec<anyhow::Result<Vec<Value>>>> {
+    debug!("Create the test environment");
+    let spinner = spinner();
+    let context = TestContext::new(router, files, image_options, options, &spinner)
+        .await
+        .context("Bootstrapping the test environment")?;
+    spinner.finish_and_clear();
+
+    let jobs = options.jobs.max(context.pool_size());
+    let outputs = {
+        let context = &context;
+        stream::iter(test_cases.iter().enumerate())
+            .map(|(i, test_case)| async move {
+                let test_id = i.to_string();
+                let mut receiver = context.router.register(&test_id);
+                let input_port = context.ports[i % context.ports.len()].input;
+                let exchange = collect_outputs(
+                    &context.http_client,
+                    &mut receiver,
+                    test_case,
+                    &test_id,
+                    input_port,
+                    options,
+                )
+                .await;
+                context.router.unregister(&test_id);
+
+                let mut outputs = exchange
+                    .with_context(|| format!("Running test case {}: {}", i, test_case.name))?
+                    .outputs;
+                let ignore: Vec<&FieldPath> = test_case.ignored_fields(options).collect();
+                for event in outputs.iter_mut() {
+                    for path in &ignore {
+                        path.remove(event);
+                    }
+                }
+                Ok(outputs)
+            })
+            .buffered(jobs)
+            .collect()
             .await
-            .with_context(|| format!("Running test case {}: {}", i, test_case.input.display()));
+    };
+
+    context.close().await?;
+    Ok(outputs)
+}
+
+/// Makes an attempt, and makes another one while it fails, at most the given number of times.
+/// Returns the result of the last attempt and the number of retries it took.
+pub async fn retry<F, Fut>(retries: usize, mut attempt: F) -> (anyhow::Result<()>, usize)
+where
+    F: FnMut(usize) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let mut n = 0;
+    loop {
+        match attempt(n).await {
+            Err(e) if n < retries => {
+                info!("Retrying after attempt {n} failed: {e:#}");
+                n += 1;
+            }
+            r => return (r, n),
+        }
+    }
+}
+
+/// The status of the i-th test case and the error it is reported with, given the result of its
+/// last attempt. A test case that is expected to fail passes by failing, and fails by passing.
+pub fn test_status(
+    i: usize,
+    test_case: &TestCase,
+    r: anyhow::Result<()>,
+) -> (TestStatus, Option<String>) {
+    match (r, test_case.metadata.xfail) {
+        (Ok(()), false) => (TestStatus::Passed, None),
+        (Err(e), false) => (TestStatus::Failed, Some(format!("{e:?}"))),
+        (Err(e), true) => {
+            info!("Test case {i} failed as expected: {e:#}");
+            (TestStatus::XFailed, None)
+        }
+        (Ok(()), true) => (
+            TestStatus::XPassed,
+            Some(format!(
+                "Test case {} is marked as expected to fail, but passed: {}",
+                i, test_case.name
+            )),
+        ),
+    }
+}
+
+/// Runs the given test cases against a prepared test environment, which may be reused for
+/// further runs
+#[instrument(skip(context))]
+pub async fn run_test_cases(
+    context: &TestContext,
+    test_cases: &[TestCase],
+    options: &TestOptions,
+) -> Vec<TestResult> {
+    let output = options.output;
+    let mut results: Vec<TestResult> = test_cases
+        .iter()
+        .map(|t| TestResult {
+            name: t.name.clone(),
+            title: t.title().to_string(),
+            description: t.metadata.description.clone(),
+            status: TestStatus::Skipped,
+            retries: 0,
+            duration: Duration::ZERO,
+            error: None,
+            mismatch: None,
+        })
+        .collect();
+
+    let log_offsets: Vec<u64> = context.log_files.iter().map(|f| log_offset(f)).collect();
+    let logstash_host = options.connection.logstash_host();
+    let pool: Vec<usize> = (0..context.pool_size()).collect();
+
+    if !options.hooks.before_all.is_empty() {
+        output.emit(&LifecycleEvent::TestStarted {
+            name: BEFORE_ALL_RESULT_NAME,
+        });
+        let started = Instant::now();
+        let error = run_hooks(
+            context,
+            &options.hooks,
+            HookKind::BeforeAll,
+            &pool,
+            &options.project_dir,
+            &logstash_host,
+            None,
+        )
+        .await
+        .err()
+        .map(|e| format!("{e:?}"));
+        let failed = error.is_some();
+        results.push(run_check_result(
+            BEFORE_ALL_RESULT_NAME,
+            "Hook before all test cases",
+            started,
+            error,
+            options,
+        ));
+        if failed {
+            return results;
+        }
+    }
+
+    // The counters of the plugins are compared with those before the run, since a reused
+    // Logstash container has processed the events of earlier runs
+    let baseline = match options.stats.is_empty() {
+        true => None,
+        false => Some(context.plugin_stats(&logstash_host).await),
+    };
+
+    let progress = test_progress(test_cases.len());
+    let (mut passed, mut failed) = (0, 0);
+    let mut completed = vec![false; test_cases.len()];
+    let mut strays = Vec::new();
+    let pool_size = context.containers.len();
+    let jobs = options.jobs.max(pool_size);
+
+    {
+        debug!("Run up to {jobs} test cases concurrently on {pool_size} containers");
+        let progress = &progress;
+        let logstash_host = &logstash_host;
+        let mut results_stream = stream::iter(test_cases.iter().enumerate())
+            .map(|(i, test_case)| async move {
+                let retries = test_case.retries(options);
+                progress.suspend(|| {
+                    output.emit(&LifecycleEvent::TestStarted {
+                        name: &test_case.name,
+                    })
+                });
+                let started = Instant::now();
+                let (r, attempts) = retry(retries, |attempt| async move {
+                    // A separate correlation ID per attempt discards late events of the previous
+                    let test_id = format!("{i}-{attempt}");
+                    let mut receiver = context.router.register(&test_id);
+
+                    let slot = [i % pool_size];
+                    let before = run_hooks(
+                        context,
+                        &options.hooks,
+                        HookKind::BeforeEach,
+                        &slot,
+                        &options.project_dir,
+                        logstash_host,
+                        Some(&test_case.name),
+                    )
+                    .await;
 
-        match r {
-            Ok(()) => (),
-            Err(e) => {
-                test_result = Err(e);
+                    debug!("Run test case {i} (attempt {attempt}): {test_case:?}");
+                    let r = match before {
+                        Ok(()) => {
+                            run_single_test(
+                                &context.http_client,
+                                &mut receiver,
+                                test_case,
+                                &test_id,
+                                context.ports[slot[0]].input,
+                                options,
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    context.router.unregister(&test_id);
+
+                    let after = run_hooks(
+                        context,
+                        &options.hooks,
+                        HookKind::AfterEach,
+                        &slot,
+                        &options.project_dir,
+                        logstash_host,
+                        Some(&test_case.name),
+                    )
+                    .await;
+                    r.and(after)
+                })
+                .await;
+                let r = r.with_context(|| match &test_case.metadata.description {
+                    Some(description) => {
+                        format!(
+                            "Running test case {}: {} ({})",
+                            i, test_case.name, description
+                        )
+                    }
+                    None => format!("Running test case {}: {}", i, test_case.name),
+                });
+
+                (i, test_case, r, attempts, started.elapsed())
+            })
+            .buffered(jobs);
+
+        while let Some((i, test_case, r, retries, duration)) = results_stream.next().await {
+            let mismatch = r.as_ref().err().and_then(|e| {
+                e.chain()
+                    .find_map(|c| c.downcast_ref::<Mismatch>())
+                    .cloned()
+            });
+            let stalled = r
+                .as_ref()
+                .is_err_and(|e| e.chain().any(|c| c.is::<OutputTimeout>()));
+            let (status, mut error) = test_status(i, test_case, r);
+            if let (Some(error), true) = (error.as_mut(), stalled) {
+                let explanation = context.explain_output_timeout(i, logstash_host).await;
+                error.push_str("\n\n");
+                error.push_str(&explanation);
+            }
+            if let (Some(error), Some(mismatch)) = (error.as_mut(), &mismatch) {
+                if let Some(explanation) = context.explain_grok_failures(&mismatch.actual).await {
+                    error.push_str("\n\n");
+                    error.push_str(&explanation);
+                }
+            }
+            if options.progress {
+                progress.suspend(|| println!("test {} ... {}", test_case.title(), status.label()));
+            }
+            match status {
+                s if s.is_failure() => failed += 1,
+                TestStatus::Passed | TestStatus::XFailed => passed += 1,
+                _ => (),
+            }
+            progress.inc(1);
+            progress.set_message(tally(passed, failed));
+
+            let result = &mut results[i];
+            result.status = status;
+            result.retries = retries;
+            result.duration = duration;
+            result.error = error;
+            result.mismatch = mismatch;
+            completed[i] = true;
+            progress.suspend(|| output.emit(&LifecycleEvent::TestFinished { result }));
+
+            strays.extend(context.router.take_strays());
+            let blamed = blame_stray_events(&mut results, &completed, &mut strays);
+            for &j in &blamed {
+                let result = &results[j];
+                progress.suspend(|| output.emit(&LifecycleEvent::TestFinished { result }));
+            }
+            if !blamed.is_empty() {
+                passed = results
+                    .iter()
+                    .filter(|r| matches!(r.status, TestStatus::Passed | TestStatus::XFailed))
+                    .count();
+                failed = results.iter().filter(|r| r.status.is_failure()).count();
+                progress.set_message(tally(passed, failed));
+            }
+
+            if options.stops_after(status, !blamed.is_empty()) {
                 break;
             }
         }
     }
 
-    context.close().await?;
+    progress.finish_and_clear();
+    strays.extend(context.router.take_strays());
+    for j in blame_stray_events(&mut results, &completed, &mut strays) {
+        output.emit(&LifecycleEvent::TestFinished {
+            result: &results[j],
+        });
+    }
+
+    if !options.hooks.after_all.is_empty() {
+        output.emit(&LifecycleEvent::TestStarted {
+            name: AFTER_ALL_RESULT_NAME,
+        });
+        let started = Instant::now();
+        let error = run_hooks(
+            context,
+            &options.hooks,
+            HookKind::AfterAll,
+            &pool,
+            &options.project_dir,
+            &logstash_host,
+            None,
+        )
+        .await
+        .err()
+        .map(|e| format!("{e:?}"));
+        results.push(run_check_result(
+            AFTER_ALL_RESULT_NAME,
+            "Hook after all test cases",
+            started,
+            error,
+            options,
+        ));
+    }
+
+    // The counters are only meaningful if every test case ran
+    if let Some(baseline) = baseline {
+        if results.iter().all(|r| r.status != TestStatus::Skipped) {
+            output.emit(&LifecycleEvent::TestStarted {
+                name: STATS_RESULT_NAME,
+            });
+            let started = Instant::now();
+            let violations = match baseline {
+                Ok(before) => context
+                    .plugin_stats(&logstash_host)
+                    .await
+                    .map(|after| check_stats(&options.stats, &before, &after)),
+                Err(e) => Err(e),
+            };
+            let error = match violations {
+                Ok(violations) if violations.is_empty() => None,
+                Ok(violations) => Some(violations.join("\n")),
+                Err(e) => Some(format!("{e:?}")),
+            };
+            results.push(run_check_result(
+                STATS_RESULT_NAME,
+                "Pipeline statistics",
+                started,
+                error,
+                options,
+            ));
+        }
+    }
+
+    if !options.fail_on_log.is_empty() {
+        output.emit(&LifecycleEvent::TestStarted {
+            name: LOGS_RESULT_NAME,
+        });
+        let started = Instant::now();
+        let mut error = None;
+        let mut lines = Vec::new();
+        for (slot, (file, offset)) in context.log_files.iter().zip(log_offsets).enumerate() {
+            match matching_lines(file, offset, &options.fail_on_log) {
+                Ok(matches) => lines.extend(
+                    matches
+                        .into_iter()
+                        .map(|l| format!("{} | {}", log_name(slot), l)),
+                ),
+                Err(e) => error = Some(format!("{e:?}")),
+            }
+        }
+        if error.is_none() && !lines.is_empty() {
+            let shown = lines.len().min(LOG_MATCHES_SHOWN);
+            let mut message = format!(
+                "Logstash logged {} line(s) matching a forbidden pattern:\n{}",
+                lines.len(),
+                lines[..shown].join("\n")
+            );
+            if lines.len() > shown {
+                message.push_str(&format!("\n… and {} more", lines.len() - shown));
+            }
+            error = Some(message);
+        }
+        results.push(run_check_result(
+            LOGS_RESULT_NAME,
+            "Logstash logs",
+            started,
+            error,
+            options,
+        ));
+    }
+
+    results
+}
 
-    test_result
+/// Fails the test cases that Logstash sent output events for after they finished (e.g. because
+/// the pipeline emits more events than they expect), naming the leaked events, which would
+/// otherwise go unnoticed. Late events of an attempt that was retried are expected and ignored.
+/// The events of test cases whose result is not recorded yet (e.g. because a later test case
+/// finished first) are kept among the strays until it is. Returns the indices of the results that
+/// changed.
+pub fn blame_stray_events(
+    results: &mut [TestResult],
+    completed: &[bool],
+    strays: &mut Vec<StrayEvent>,
+) -> Vec<usize> {
+    let mut leaked: BTreeMap<usize, Vec<Value>> = BTreeMap::new();
+    for stray in std::mem::take(strays) {
+        let Some((i, attempt)) = stray
+            .test_id
+            .as_deref()
+            .and_then(|id| id.split_once('-'))
+            .and_then(|(i, a)| Some((i.parse::<usize>().ok()?, a.parse::<usize>().ok()?)))
+        else {
+            continue;
+        };
+        match (results.get(i), completed.get(i)) {
+            (Some(_), Some(false)) => strays.push(stray),
+            (Some(r), Some(true)) if r.retries == attempt && r.status != TestStatus::XFailed => {
+                leaked.entry(i).or_default().push(stray.event)
+            }
+            _ => (),
+        }
+    }
+
+    for (&i, events) in &leaked {
+        let result = &mut results[i];
+        let mut message = format!(
+            "Logstash sent {} output event(s) for test case {} after it finished:",
+            events.len(),
+            result.name
+        );
+        for event in events {
+            message.push_str(&format!("\n{event}"));
+        }
+        result.error = Some(match result.error.take() {
+            Some(error) => format!("{error}\n\n{message}"),
+            None => message,
+        });
+        if result.status == TestStatus::Passed {
+            result.status = TestStatus::Failed;
+        }
+    }
+    leaked.into_keys().collect()
+}
+
+/// Reports a check of the whole run (rather than of a single test case) as an additional result,
+/// which failed if there is an error
+fn run_check_result(
+    name: &str,
+    title: &str,
+    started: Instant,
+    error: Option<String>,
+    options: &TestOptions,
+) -> TestResult {
+    let result = TestResult {
+        name: String::from(name),
+        title: String::from(title),
+        description: None,
+        status: match error {
+            None => TestStatus::Passed,
+            Some(_) => TestStatus::Failed,
+        },
+        retries: 0,
+        duration: started.elapsed(),
+        error,
+        mismatch: None,
+    };
+    if options.progress {
+        println!("test {} ... {}", result.title, result.status.label());
+    }
+    options
+        .output
+        .emit(&LifecycleEvent::TestFinished { result: &result });
+    result
 }
@@ -0,0 +1,57 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use tracing::{debug, instrument};
+
+use crate::{MAX_RUN_DIRS, RUNS_DIR};
+
+/// Creates the directory of a new run in the cache directory (named after the current time in
+/// milliseconds since the Unix epoch), and deletes those of all but the most recent runs
+#[instrument]
+pub fn create_run_dir(cache_dir: &Path) -> anyhow::Result<PathBuf> {
+    let runs_dir = cache_dir.join(RUNS_DIR);
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("Determining the current time")?
+        .as_millis();
+    let run_dir = runs_dir.join(timestamp.to_string());
+    std::fs::create_dir_all(&run_dir)
+        .with_context(|| format!("Creating the run directory: {}", run_dir.display()))?;
+
+    prune_run_dirs(&runs_dir, MAX_RUN_DIRS)?;
+    Ok(run_dir)
+}
+
+/// Deletes the directories of all but the given number of most recent runs. Returns the deleted
+/// directories.
+#[instrument]
+pub fn prune_run_dirs(runs_dir: &Path, keep: usize) -> anyhow::Result<Vec<PathBuf>> {
+    let mut runs: Vec<(u128, PathBuf)> = Vec::new();
+    for entry in std::fs::read_dir(runs_dir)
+        .with_context(|| format!("Reading the runs directory: {}", runs_dir.display()))?
+    {
+        let path = entry
+            .with_context(|| format!("Reading the runs directory: {}", runs_dir.display()))?
+            .path();
+        let timestamp = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.parse().ok());
+        if let (true, Some(timestamp)) = (path.is_dir(), timestamp) {
+            runs.push((timestamp, path));
+        }
+    }
+
+    runs.sort();
+    let excess = runs.len().saturating_sub(keep);
+    let mut removed = Vec::new();
+    for (_, path) in runs.into_iter().take(excess) {
+        debug!("Delete the run directory {}", path.display());
+        std::fs::remove_dir_all(&path)
+            .with_context(|| format!("Deleting the run directory: {}", path.display()))?;
+        removed.push(path);
+    }
+
+    Ok(removed)
+}
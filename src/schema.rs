@@ -0,0 +1,408 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use regex::Regex;
+use serde_json::{Map, Value};
+use tracing::instrument;
+
+const DATE_TIME_PATTERN: &str =
+    r"^\d{4}-\d{2}-\d{2}[Tt ]\d{2}:\d{2}:\d{2}(\.\d+)?([Zz]|[+-]\d{2}:\d{2})$";
+const DATE_PATTERN: &str = r"^\d{4}-\d{2}-\d{2}$";
+const TIME_PATTERN: &str = r"^\d{2}:\d{2}:\d{2}(\.\d+)?([Zz]|[+-]\d{2}:\d{2})?$";
+const UUID_PATTERN: &str =
+    "^[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$";
+const EMAIL_PATTERN: &str = r"^[^@\s]+@[^@\s]+\.[^@\s]+$";
+const URI_PATTERN: &str = r"^[a-zA-Z][a-zA-Z0-9+.-]*:\S*$";
+
+/// Reads a JSON Schema from a file
+#[instrument]
+pub fn load_schema(path: &Path) -> anyhow::Result<Value> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("Reading the schema file: {}", path.display()))?;
+    serde_json::from_str(&data)
+        .with_context(|| format!("Parsing the schema file: {}", path.display()))
+}
+
+/// Validates an event against a JSON Schema and fails with a list of every violation, located by
+/// its path in Logstash notation (e.g. `[url][port]`)
+#[instrument(skip_all)]
+pub fn check_schema(schema: &Value, event: &Value) -> anyhow::Result<()> {
+    let violations = validate(schema, event)?;
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "The event violates the schema in {} place(s):\n{}",
+        violations.len(),
+        violations.join("\n")
+    ))
+}
+
+/// Lists the violations of a JSON Schema by an event. The keywords `type`, `enum`, `const`,
+/// `required`, `properties`, `patternProperties`, `additionalProperties`, `items`,
+/// `prefixItems`, `contains`, `minItems`, `maxItems`, `uniqueItems`, `minLength`, `maxLength`,
+/// `pattern`, `format`, `minimum`, `maximum`, `exclusiveMinimum`, `exclusiveMaximum`,
+/// `multipleOf`, `allOf`, `anyOf`, `oneOf`, `not` and local `$ref`s are supported; any other
+/// keyword is ignored.
+pub fn validate(schema: &Value, event: &Value) -> anyhow::Result<Vec<String>> {
+    let mut violations = Vec::new();
+    let validator = Validator {
+        root: schema,
+        references: RefCell::default(),
+    };
+    validator.validate(schema, event, "", &mut violations)?;
+    Ok(violations)
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+    /// The references being followed for each value (by its address), to detect cycles
+    references: RefCell<HashSet<(String, usize)>>,
+}
+
+impl Validator<'_> {
+    fn validate(
+        &self,
+        schema: &Value,
+        value: &Value,
+        path: &str,
+        violations: &mut Vec<String>,
+    ) -> anyhow::Result<()> {
+        let location = match path {
+            "" => "(root)",
+            p => p,
+        };
+        let schema = match schema {
+            Value::Bool(true) => return Ok(()),
+            Value::Bool(false) => {
+                violations.push(format!("{location}: no value is permitted, found {value}"));
+                return Ok(());
+            }
+            Value::Object(schema) => schema,
+            _ => return Err(anyhow!("Invalid schema at {location}: {schema}")),
+        };
+
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let target = self.resolve(reference)?;
+            let key = (reference.to_string(), value as *const Value as usize);
+            if !self.references.borrow_mut().insert(key.clone()) {
+                return Err(anyhow!(
+                    "Invalid schema at {location}: the reference {reference} refers to itself"
+                ));
+            }
+            let validated = self.validate(target, value, path, violations);
+            self.references.borrow_mut().remove(&key);
+            validated?;
+        }
+
+        if let Some(expected) = schema.get("type") {
+            let types: Vec<&str> = match expected {
+                Value::String(t) => vec![t.as_str()],
+                Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+                _ => return Err(anyhow!("Invalid type at {location}: {expected}")),
+            };
+            if !types.iter().any(|t| has_type(value, t)) {
+                violations.push(format!(
+                    "{location}: expected a value of type {}, found {value}",
+                    types.join(" or ")
+                ));
+                return Ok(());
+            }
+        }
+        if let Some(Value::Array(options)) = schema.get("enum") {
+            if !options.contains(value) {
+                violations.push(format!(
+                    "{location}: expected one of {}, found {value}",
+                    Value::Array(options.clone())
+                ));
+            }
+        }
+        if let Some(constant) = schema.get("const") {
+            if constant != value {
+                violations.push(format!("{location}: expected {constant}, found {value}"));
+            }
+        }
+
+        match value {
+            Value::Object(object) => self.validate_object(schema, object, path, violations)?,
+            Value::Array(items) => self.validate_array(schema, items, path, violations)?,
+            Value::String(s) => validate_string(schema, s, location, violations)?,
+            Value::Number(n) => {
+                validate_number(schema, n.as_f64().unwrap_or_default(), location, violations)
+            }
+            _ => (),
+        }
+
+        if let Some(Value::Array(subschemas)) = schema.get("allOf") {
+            for subschema in subschemas {
+                self.validate(subschema, value, path, violations)?;
+            }
+        }
+        if let Some(Value::Array(subschemas)) = schema.get("anyOf") {
+            if self.count_valid(subschemas, value)? == 0 {
+                violations.push(format!(
+                    "{location}: {value} matches none of the anyOf schemas"
+                ));
+            }
+        }
+        if let Some(Value::Array(subschemas)) = schema.get("oneOf") {
+            let valid = self.count_valid(subschemas, value)?;
+            if valid != 1 {
+                violations.push(format!(
+                    "{location}: {value} matches {valid} of the oneOf schemas, expected exactly one"
+                ));
+            }
+        }
+        if let Some(subschema) = schema.get("not") {
+            if self.count_valid(std::slice::from_ref(subschema), value)? == 1 {
+                violations.push(format!("{location}: {value} matches the schema of not"));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_object(
+        &self,
+        schema: &Map<String, Value>,
+        object: &Map<String, Value>,
+        path: &str,
+        violations: &mut Vec<String>,
+    ) -> anyhow::Result<()> {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(key) {
+                    violations.push(format!("{path}[{key}]: the required field is missing"));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let patterns = match schema.get("patternProperties").and_then(Value::as_object) {
+            Some(patterns) => patterns
+                .iter()
+                .map(|(p, s)| {
+                    Regex::new(p)
+                        .map(|re| (re, s))
+                        .with_context(|| format!("Compiling the property pattern '{p}'"))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+        for (key, value) in object {
+            let path = format!("{path}[{key}]");
+            let mut known = false;
+            if let Some(subschema) = properties.and_then(|p| p.get(key)) {
+                known = true;
+                self.validate(subschema, value, &path, violations)?;
+            }
+            for (re, subschema) in &patterns {
+                if re.is_match(key) {
+                    known = true;
+                    self.validate(subschema, value, &path, violations)?;
+                }
+            }
+            match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) if !known => {
+                    violations.push(format!("{path}: the field is not permitted, found {value}"))
+                }
+                Some(subschema) if !known => self.validate(subschema, value, &path, violations)?,
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_array(
+        &self,
+        schema: &Map<String, Value>,
+        items: &[Value],
+        path: &str,
+        violations: &mut Vec<String>,
+    ) -> anyhow::Result<()> {
+        let location = match path {
+            "" => "(root)",
+            p => p,
+        };
+        let prefix = match (schema.get("prefixItems"), schema.get("items")) {
+            (Some(Value::Array(prefix)), _) | (None, Some(Value::Array(prefix))) => {
+                prefix.as_slice()
+            }
+            _ => &[],
+        };
+        let rest = match schema.get("items") {
+            Some(items @ (Value::Object(_) | Value::Bool(_))) => Some(items),
+            _ => None,
+        };
+        for (i, item) in items.iter().enumerate() {
+            let item_path = format!("{path}[{i}]");
+            match (prefix.get(i), rest) {
+                (Some(subschema), _) | (None, Some(subschema)) => {
+                    self.validate(subschema, item, &item_path, violations)?
+                }
+                (None, None) => (),
+            }
+        }
+
+        if let Some(subschema) = schema.get("contains") {
+            let mut found = false;
+            for item in items {
+                found |= self.count_valid(std::slice::from_ref(subschema), item)? == 1;
+            }
+            if !found {
+                violations.push(format!(
+                    "{location}: no element matches the schema of contains"
+                ));
+            }
+        }
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if (items.len() as u64) < min {
+                violations.push(format!(
+                    "{location}: expected at least {min} element(s), found {}",
+                    items.len()
+                ));
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if items.len() as u64 > max {
+                violations.push(format!(
+                    "{location}: expected at most {max} element(s), found {}",
+                    items.len()
+                ));
+            }
+        }
+        if schema.get("uniqueItems") == Some(&Value::Bool(true))
+            && items
+                .iter()
+                .enumerate()
+                .any(|(i, item)| items[..i].contains(item))
+        {
+            violations.push(format!("{location}: the elements are not unique"));
+        }
+
+        Ok(())
+    }
+
+    /// The number of schemas that a value is valid against
+    fn count_valid(&self, schemas: &[Value], value: &Value) -> anyhow::Result<usize> {
+        let mut valid = 0;
+        for schema in schemas {
+            let mut violations = Vec::new();
+            self.validate(schema, value, "", &mut violations)?;
+            if violations.is_empty() {
+                valid += 1;
+            }
+        }
+        Ok(valid)
+    }
+
+    /// Resolves a reference within the schema (e.g. `#/$defs/address`)
+    fn resolve(&self, reference: &str) -> anyhow::Result<&Value> {
+        let pointer = reference
+            .strip_prefix('#')
+            .ok_or_else(|| anyhow!("Only local schema references are supported: {reference}"))?;
+        self.root
+            .pointer(pointer)
+            .ok_or_else(|| anyhow!("The schema reference could not be resolved: {reference}"))
+    }
+}
+
+fn has_type(value: &Value, kind: &str) -> bool {
+    match (kind, value) {
+        ("string", Value::String(_))
+        | ("number", Value::Number(_))
+        | ("boolean", Value::Bool(_))
+        | ("array", Value::Array(_))
+        | ("object", Value::Object(_))
+        | ("null", Value::Null) => true,
+        ("integer", Value::Number(n)) => n.as_f64().is_some_and(|f| f.fract() == 0.0),
+        _ => false,
+    }
+}
+
+fn validate_string(
+    schema: &Map<String, Value>,
+    s: &str,
+    location: &str,
+    violations: &mut Vec<String>,
+) -> anyhow::Result<()> {
+    let length = s.chars().count() as u64;
+    if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+        if length < min {
+            violations.push(format!(
+                "{location}: expected at least {min} character(s), found {s:?}"
+            ));
+        }
+    }
+    if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+        if length > max {
+            violations.push(format!(
+                "{location}: expected at most {max} character(s), found {s:?}"
+            ));
+        }
+    }
+    if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+        let re = Regex::new(pattern)
+            .with_context(|| format!("Compiling the regular expression '{pattern}'"))?;
+        if !re.is_match(s) {
+            violations.push(format!(
+                "{location}: {s:?} does not match the pattern {pattern:?}"
+            ));
+        }
+    }
+    if let Some(format) = schema.get("format").and_then(Value::as_str) {
+        let matches = |pattern: &str| Regex::new(pattern).is_ok_and(|re| re.is_match(s));
+        let valid = match format {
+            "date-time" => matches(DATE_TIME_PATTERN),
+            "date" => matches(DATE_PATTERN),
+            "time" => matches(TIME_PATTERN),
+            "uuid" => matches(UUID_PATTERN),
+            "email" => matches(EMAIL_PATTERN),
+            "uri" => matches(URI_PATTERN),
+            "ipv4" => s.parse::<Ipv4Addr>().is_ok(),
+            "ipv6" => s.parse::<Ipv6Addr>().is_ok(),
+            _ => true,
+        };
+        if !valid {
+            violations.push(format!("{location}: {s:?} is not a valid {format}"));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_number(
+    schema: &Map<String, Value>,
+    n: f64,
+    location: &str,
+    violations: &mut Vec<String>,
+) {
+    let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+    let checks = [
+        (bound("minimum").filter(|&min| n < min), ">="),
+        (bound("maximum").filter(|&max| n > max), "<="),
+        (bound("exclusiveMinimum").filter(|&min| n <= min), ">"),
+        (bound("exclusiveMaximum").filter(|&max| n >= max), "<"),
+    ];
+    for (violated, relation) in checks {
+        if let Some(limit) = violated {
+            violations.push(format!(
+                "{location}: expected a number {relation} {limit}, found {n}"
+            ));
+        }
+    }
+    if let Some(divisor) = bound("multipleOf").filter(|&d| d > 0.0) {
+        // The quotient of decimal fractions is inexact (e.g. 0.3 / 0.1 is 2.9999999999999996)
+        let quotient = n / divisor;
+        if (quotient - quotient.round()).abs() > 1e-9 * quotient.abs().max(1.0) {
+            violations.push(format!(
+                "{location}: expected a multiple of {divisor}, found {n}"
+            ));
+        }
+    }
+}
@@ -1,48 +1,196 @@
-use std::net::SocketAddr;
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
 
 use anyhow::Context;
-use axum::{extract::State, http::StatusCode, Json};
-use tokio::sync::mpsc::Sender;
-use tracing::{debug, info_span, instrument, Instrument};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::Value;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tracing::{debug, info_span, instrument, warn, Instrument};
 
-use crate::OUTPUT_PORT;
+use crate::elasticsearch;
+use crate::{
+    CHANNEL_CAPACITY, ELASTICSEARCH_PATH, METADATA_FIELD, OUTPUTS_PATH, OUTPUT_CHANNEL_FIELD,
+};
 
-#[derive(Debug, Clone)]
-struct ServerState {
-    sender: Sender<serde_json::Value>,
+/// Routes the output events of Logstash to the test cases that caused them, based on the
+/// correlation ID of each event
+#[derive(Debug, Default, Clone)]
+pub struct EventRouter {
+    routes: Arc<Mutex<HashMap<String, Sender<Value>>>>,
+    strays: Arc<Mutex<Vec<StrayEvent>>>,
+}
+
+/// An output event that arrived while no test case with its correlation ID was running, e.g.
+/// because the test case it belongs to had already finished
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrayEvent {
+    pub test_id: Option<String>,
+    pub event: Value,
+}
+
+impl EventRouter {
+    /// Returns the receiver of all output events with the given correlation ID
+    pub fn register(&self, test_id: &str) -> Receiver<Value> {
+        let (sender, receiver) = channel(CHANNEL_CAPACITY);
+        self.routes
+            .lock()
+            .unwrap()
+            .insert(test_id.to_string(), sender);
+        receiver
+    }
+
+    pub fn unregister(&self, test_id: &str) {
+        self.routes.lock().unwrap().remove(test_id);
+    }
+
+    /// Forwards an output event to the test case with the given correlation ID. Events without
+    /// one are attributed to the only running test case, if there is exactly one. Returns whether
+    /// the event was delivered, and keeps it as a stray event if not.
+    #[instrument(skip(self))]
+    pub async fn route(&self, test_id: Option<&str>, event: Value) -> bool {
+        let sender = {
+            let routes = self.routes.lock().unwrap();
+            match test_id {
+                Some(test_id) => routes.get(test_id).cloned(),
+                None if routes.len() == 1 => routes.values().next().cloned(),
+                None => None,
+            }
+        };
+
+        let event = match sender {
+            Some(sender) => match sender.send(event).await {
+                Ok(()) => return true,
+                Err(e) => e.0,
+            },
+            None => event,
+        };
+        self.strays.lock().unwrap().push(StrayEvent {
+            test_id: test_id.map(String::from),
+            event,
+        });
+        false
+    }
+
+    /// Removes and returns the stray events that arrived so far
+    pub fn take_strays(&self) -> Vec<StrayEvent> {
+        std::mem::take(&mut *self.strays.lock().unwrap())
+    }
+}
+
+#[instrument]
+async fn root(State(router): State<EventRouter>, Json(payload): Json<Value>) -> StatusCode {
+    forward(&router, None, payload).await
+}
+
+#[instrument]
+async fn test_case(
+    State(router): State<EventRouter>,
+    Path(test_id): Path<String>,
+    Json(payload): Json<Value>,
+) -> StatusCode {
+    forward(&router, Some(&test_id), payload).await
 }
 
 #[instrument]
-async fn root(
-    State(state): State<ServerState>,
-    Json(payload): Json<serde_json::Value>,
+async fn channel_root(
+    State(router): State<EventRouter>,
+    Path(channel): Path<String>,
+    Json(payload): Json<Value>,
 ) -> StatusCode {
+    forward(&router, None, with_channel(payload, &channel)).await
+}
+
+#[instrument]
+async fn channel_test_case(
+    State(router): State<EventRouter>,
+    Path((channel, test_id)): Path<(String, String)>,
+    Json(payload): Json<Value>,
+) -> StatusCode {
+    forward(&router, Some(&test_id), with_channel(payload, &channel)).await
+}
+
+/// Records the output channel an event arrived on in the event itself, so that the expected
+/// output can assert it
+pub fn with_channel(mut event: Value, channel: &str) -> Value {
+    if let Some(fields) = event.as_object_mut() {
+        fields.insert(
+            String::from(OUTPUT_CHANNEL_FIELD),
+            Value::String(channel.to_string()),
+        );
+    }
+    event
+}
+
+/// Restores the `@metadata` that the pipeline copied into a field of the event to be sent along,
+/// if it captures metadata
+pub fn with_metadata(mut event: Value) -> Value {
+    if let Some(fields) = event.as_object_mut() {
+        if let Some(metadata) = fields.remove(METADATA_FIELD) {
+            fields.insert(String::from("@metadata"), metadata);
+        }
+    }
+    event
+}
+
+async fn forward(router: &EventRouter, test_id: Option<&str>, payload: Value) -> StatusCode {
     let mpsc_span = info_span!("mpsc_sender_server");
 
-    debug!("Forward the request payload to the main task");
-    state
-        .sender
-        .send(payload)
+    debug!("Forward the request payload to the test case {test_id:?}");
+    if !router
+        .route(test_id, with_metadata(payload))
         .instrument(mpsc_span)
         .await
-        .context("When sending the request payload to the main task")
-        .unwrap();
+    {
+        warn!("Discarded an output event that does not belong to a running test case: {test_id:?}");
+    }
 
     StatusCode::NO_CONTENT
 }
 
+/// Binds the socket of the server to the given port, or to a free one if it is 0
 #[instrument]
-pub async fn run_server(sender: Sender<serde_json::Value>) -> anyhow::Result<()> {
-    let bind_addr = SocketAddr::from(([0, 0, 0, 0], OUTPUT_PORT));
-    let state = ServerState { sender };
+pub fn bind_server(port: u16) -> anyhow::Result<TcpListener> {
+    let bind_addr = SocketAddr::from(([0, 0, 0, 0], port));
+    debug!("Bind the axum server to {bind_addr}");
+    TcpListener::bind(bind_addr).with_context(|| format!("Binding the server to {bind_addr}"))
+}
+
+#[instrument]
+pub async fn run_server(router: EventRouter, listener: TcpListener) -> anyhow::Result<()> {
     let response_handler_span = info_span!("response_handler");
 
-    debug!("Bind the axum server to {bind_addr}");
-    axum::Server::bind(&bind_addr)
+    axum::Server::from_tcp(listener)
+        .context("Creating the axum server")?
         .serve(
             axum::Router::new()
                 .route("/", axum::routing::post(root))
-                .with_state(state)
+                .route("/:test_id", axum::routing::post(test_case))
+                .route(
+                    &format!("/{OUTPUTS_PATH}/:channel/"),
+                    axum::routing::post(channel_root),
+                )
+                .route(
+                    &format!("/{OUTPUTS_PATH}/:channel/:test_id"),
+                    axum::routing::post(channel_test_case),
+                )
+                .route(
+                    &format!("/{ELASTICSEARCH_PATH}/_bulk"),
+                    axum::routing::post(elasticsearch::bulk),
+                )
+                .route(
+                    &format!("/{ELASTICSEARCH_PATH}/"),
+                    axum::routing::any(elasticsearch::cluster),
+                )
+                .route(
+                    &format!("/{ELASTICSEARCH_PATH}/*request"),
+                    axum::routing::any(elasticsearch::cluster),
+                )
+                .with_state(router)
                 .into_make_service(),
         )
         .instrument(response_handler_span)
@@ -0,0 +1,204 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{anyhow, Context};
+use clap::Args;
+use serde_json::Value;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
+use tracing::{debug, instrument};
+
+use crate::bench::event_from_line;
+use crate::progress::spinner;
+use crate::runner::{TestContext, TestOptions};
+use crate::server::{bind_server, run_server, EventRouter};
+use crate::{
+    image_options, DefaultArguments, Project, DROP_TIMEOUT, SESSION_ID_PREFIX, SETTLE_TIMEOUT,
+};
+
+#[derive(Debug, Args)]
+pub struct RunArguments {
+    /// Read input events from standard input (one JSON object or raw log line per line), and
+    /// write the output events to standard output as NDJSON, instead of running the test cases
+    #[arg(long)]
+    pub stdin: bool,
+}
+
+/// A running Logstash environment of a project, which processes arbitrary events on demand (e.g.
+/// for a REPL, an editor integration or ad-hoc debugging)
+///
+/// ```no_run
+/// # async fn example() -> anyhow::Result<()> {
+/// let session = lotus::Harness::new().target("logstash").session().await?;
+/// let outputs = session.process(serde_json::json!({ "message": "hello" })).await?;
+/// println!("{outputs:?}");
+/// session.close().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Session {
+    context: TestContext,
+    server: JoinHandle<anyhow::Result<()>>,
+    options: TestOptions,
+    events: AtomicUsize,
+}
+
+impl Session {
+    /// Boots the Logstash environment of the project, or reuses a warm container if
+    /// `reuse_container` is set
+    #[instrument]
+    pub async fn start(args: &DefaultArguments) -> anyhow::Result<Self> {
+        let project = Project::load(args)?;
+        let mut options = project.test_options(args)?;
+        let image_options = image_options(&[], &options)?;
+
+        debug!("Launch the event responder server");
+        let router = EventRouter::default();
+        let listener = bind_server(options.connection.ports.output)?;
+        options.connection.ports.output = listener.local_addr()?.port();
+        let server = tokio::spawn(run_server(router.clone(), listener));
+
+        debug!("Create the test environment");
+        let spinner = spinner();
+        let context = TestContext::new(
+            router,
+            project.pipeline_files(),
+            image_options,
+            &options,
+            &spinner,
+        )
+        .await;
+        spinner.finish_and_clear();
+        let context = match context {
+            Ok(context) => context,
+            Err(e) => {
+                server.abort();
+                return Err(e.context("Bootstrapping the test environment"));
+            }
+        };
+
+        Ok(Session {
+            context,
+            server,
+            options,
+            events: AtomicUsize::new(0),
+        })
+    }
+
+    /// Runs a single event through the pipeline, and returns the output events it results in
+    /// (none if the pipeline drops it). Output events that arrive later than their predecessor
+    /// by more than a short time are not returned.
+    #[instrument(skip(self))]
+    pub async fn process(&self, event: Value) -> anyhow::Result<Vec<Value>> {
+        if self.server.is_finished() {
+            return Err(anyhow!("The event responder server stopped unexpectedly"));
+        }
+
+        let n = self.events.fetch_add(1, Ordering::Relaxed);
+        let test_id = format!("{SESSION_ID_PREFIX}{n}");
+        let mut receiver = self.context.router().register(&test_id);
+        let logstash_host = self.options.connection.logstash_host();
+        let outputs = async {
+            self.context
+                .send_event(&logstash_host, n, &test_id, &event)
+                .await?;
+
+            debug!("Collect the output events until none arrives for a while");
+            let mut outputs = Vec::new();
+            let mut wait = DROP_TIMEOUT;
+            while let Ok(Some(output)) = timeout(wait, receiver.recv()).await {
+                outputs.push(output);
+                wait = SETTLE_TIMEOUT;
+            }
+            Ok(outputs)
+        }
+        .await;
+        self.context.router().unregister(&test_id);
+        outputs
+    }
+
+    /// Runs a stream of input events (one JSON object or raw log line per line) through the
+    /// pipeline, and writes the output events as NDJSON as soon as they arrive, in no particular
+    /// order. Once the input ends, the output ends when no output event arrived for a while.
+    /// Returns the number of output events.
+    #[instrument(skip_all)]
+    pub async fn pipe(
+        &self,
+        input: impl AsyncBufRead + Unpin,
+        mut output: impl AsyncWrite + Unpin,
+    ) -> anyhow::Result<usize> {
+        if self.server.is_finished() {
+            return Err(anyhow!("The event responder server stopped unexpectedly"));
+        }
+
+        let n = self.events.fetch_add(1, Ordering::Relaxed);
+        let test_id = format!("{SESSION_ID_PREFIX}{n}");
+        let mut receiver = self.context.router().register(&test_id);
+        let logstash_host = self.options.connection.logstash_host();
+
+        let send = async {
+            let mut lines = input.lines();
+            let mut i = 0;
+            while let Some(line) = lines.next_line().await.context("Reading an input event")? {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                self.context
+                    .send_event(&logstash_host, i, &test_id, &event_from_line(&line))
+                    .await
+                    .with_context(|| format!("Sending input event {i}"))?;
+                i += 1;
+            }
+            debug!("Sent {i} input events");
+            anyhow::Ok(())
+        };
+        tokio::pin!(send);
+
+        let result = async {
+            let mut sending = true;
+            let mut count = 0;
+            loop {
+                tokio::select!(
+                    r = &mut send, if sending => {
+                        r?;
+                        sending = false;
+                    },
+                    event = receiver.recv() => {
+                        let Some(event) = event else { break };
+                        let mut line = serde_json::to_vec(&event)?;
+                        line.push(b'\n');
+                        output.write_all(&line).await.context("Writing an output event")?;
+                        output.flush().await.context("Writing an output event")?;
+                        count += 1;
+                    },
+                    _ = sleep(DROP_TIMEOUT), if !sending => break,
+                )
+            }
+            Ok(count)
+        }
+        .await;
+        self.context.router().unregister(&test_id);
+        result
+    }
+
+    /// Stops the Logstash environment
+    #[instrument]
+    pub async fn close(self) -> anyhow::Result<()> {
+        self.server.abort();
+        self.context
+            .close()
+            .await
+            .context("Stopping the Logstash container")
+    }
+}
+
+/// Boots the Logstash environment of the project, runs a single event through the pipeline,
+/// and returns the output events it results in
+#[instrument]
+pub async fn run_single_event(args: &DefaultArguments, event: Value) -> anyhow::Result<Vec<Value>> {
+    let session = Session::start(args).await?;
+    let outputs = session.process(event).await;
+    session.close().await?;
+    outputs
+}
@@ -0,0 +1,130 @@
+use anyhow::{anyhow, Context};
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{lookup_host, TcpStream, UdpSocket};
+use tracing::{debug, instrument};
+
+use crate::docker::InputProtocol;
+use crate::lumberjack;
+
+/// Turns an input event into a JSON document for an input without request headers: raw text
+/// events become the `message` field, and the correlation ID is carried in
+/// `[@metadata][lotus_test_id]`
+pub fn correlated_event(event: &Value, test_id: &str) -> Value {
+    let mut event = match event {
+        Value::Object(_) => event.clone(),
+        Value::String(line) => json!({ "message": line }),
+        other => json!({ "message": other.to_string() }),
+    };
+    if let Some(fields) = event.as_object_mut() {
+        let metadata = fields.entry("@metadata").or_insert_with(|| json!({}));
+        if let Some(metadata) = metadata.as_object_mut() {
+            metadata.insert(String::from("lotus_test_id"), json!(test_id));
+        }
+    }
+    event
+}
+
+/// Turns a raw text input event into a syslog frame, prefixed with the correlation ID that the
+/// grok pattern of the `syslog` input extracts
+pub fn syslog_frame(event: &Value, test_id: &str) -> anyhow::Result<Vec<u8>> {
+    match event {
+        Value::String(line) => Ok(format!("{test_id} {line}").into_bytes()),
+        _ => Err(anyhow!(
+            "Input events sent to a syslog input must be raw text (e.g. from `input.txt`)"
+        )),
+    }
+}
+
+/// Writes the payloads as lines to a TCP input of Logstash
+#[instrument(skip(payloads))]
+pub async fn send_lines(host: &str, port: u16, payloads: &[Vec<u8>]) -> anyhow::Result<()> {
+    let mut stream = TcpStream::connect((host, port))
+        .await
+        .with_context(|| format!("Connecting to the TCP input at {host}:{port}"))?;
+    let mut lines = Vec::new();
+    for payload in payloads {
+        lines.extend_from_slice(payload);
+        lines.push(b'\n');
+    }
+    stream
+        .write_all(&lines)
+        .await
+        .context("Writing the input events")?;
+    stream
+        .shutdown()
+        .await
+        .context("Closing the connection to the TCP input")?;
+    debug!("Wrote {} input events", payloads.len());
+    Ok(())
+}
+
+/// Sends each payload as a datagram to a UDP input of Logstash
+#[instrument(skip(payloads))]
+pub async fn send_datagrams(host: &str, port: u16, payloads: &[Vec<u8>]) -> anyhow::Result<()> {
+    let address = lookup_host((host, port))
+        .await
+        .with_context(|| format!("Resolving the address of the UDP input at {host}:{port}"))?
+        .next()
+        .with_context(|| format!("The host {host} has no address"))?;
+    let local = if address.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local)
+        .await
+        .context("Binding a UDP socket")?;
+    for datagram in payloads {
+        socket
+            .send_to(datagram, address)
+            .await
+            .with_context(|| format!("Sending an input event to the UDP input at {address}"))?;
+    }
+    debug!("Sent {} input events", payloads.len());
+    Ok(())
+}
+
+/// Sends the events to the input of Logstash of a protocol without request headers, each
+/// carrying the correlation ID
+pub async fn send_events(
+    protocol: InputProtocol,
+    host: &str,
+    port: u16,
+    events: &[Value],
+    test_id: &str,
+) -> anyhow::Result<()> {
+    let payloads = match protocol {
+        InputProtocol::Beats => {
+            let events: Vec<Value> = events
+                .iter()
+                .map(|e| correlated_event(e, test_id))
+                .collect();
+            return lumberjack::send_events(host, port, &events)
+                .await
+                .context("Sending the input events to the Logstash container via Lumberjack");
+        }
+        InputProtocol::Tcp | InputProtocol::Udp => events
+            .iter()
+            .map(|e| serde_json::to_vec(&correlated_event(e, test_id)))
+            .collect::<Result<Vec<_>, _>>()
+            .context("Serializing an input event")?,
+        InputProtocol::SyslogUdp | InputProtocol::SyslogTcp => events
+            .iter()
+            .map(|e| syslog_frame(e, test_id))
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        InputProtocol::Http => {
+            return Err(anyhow!(
+                "HTTP input events carry the correlation ID in a request header"
+            ))
+        }
+    };
+    match protocol.transport() {
+        "udp" => send_datagrams(host, port, &payloads)
+            .await
+            .context("Sending the input events to the Logstash container via UDP"),
+        _ => send_lines(host, port, &payloads)
+            .await
+            .context("Sending the input events to the Logstash container via TCP"),
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::instrument;
+
+use crate::PIPELINE_ID;
+
+/// Assertions on what the plugins of the pipeline did over a whole run, checked against the node
+/// stats API of Logstash
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatsAssertions {
+    /// No grok filter may fail to match an event (i.e. tag it with `_grokparsefailure`)
+    pub no_grok_failures: bool,
+    /// No plugin may report a failure (e.g. a grok or date filter that did not match an event)
+    pub no_plugin_failures: bool,
+    /// The exact number of events that each filter with the given ID must have processed
+    pub filter_events: BTreeMap<String, u64>,
+}
+
+impl StatsAssertions {
+    pub fn is_empty(&self) -> bool {
+        !self.no_grok_failures && !self.no_plugin_failures && self.filter_events.is_empty()
+    }
+}
+
+/// The counters of a plugin of the pipeline
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PluginStats {
+    /// The name of the plugin (e.g. `grok`)
+    pub name: String,
+    /// The number of events the plugin received
+    pub events_in: u64,
+    /// The number of events the plugin failed to process, if it reports them
+    pub failures: u64,
+}
+
+/// Extracts the counters of all plugins from the response of the node stats API, by plugin ID
+pub fn plugin_stats(stats: &Value) -> BTreeMap<String, PluginStats> {
+    let plugins = &stats["pipelines"][PIPELINE_ID]["plugins"];
+    ["inputs", "filters", "outputs"]
+        .iter()
+        .filter_map(|kind| plugins[kind].as_array())
+        .flatten()
+        .filter_map(|plugin| {
+            let id = plugin["id"].as_str()?;
+            let stats = PluginStats {
+                name: plugin["name"].as_str().unwrap_or_default().to_string(),
+                events_in: plugin["events"]["in"].as_u64().unwrap_or(0),
+                failures: plugin["failures"].as_u64().unwrap_or(0),
+            };
+            Some((id.to_string(), stats))
+        })
+        .collect()
+}
+
+/// Requests the counters of all plugins from the node stats API of a Logstash container
+#[instrument(skip(client))]
+pub async fn fetch_plugin_stats(
+    client: &Client,
+    host: &str,
+    api_port: u16,
+) -> anyhow::Result<BTreeMap<String, PluginStats>> {
+    let stats: Value = client
+        .get(format!(
+            "http://{host}:{api_port}/_node/stats/pipelines/{PIPELINE_ID}"
+        ))
+        .send()
+        .await
+        .context("Requesting the pipeline statistics of Logstash")?
+        .json()
+        .await
+        .context("Reading the pipeline statistics of Logstash")?;
+    Ok(plugin_stats(&stats))
+}
+
+/// Adds the counters of the plugins of another Logstash container (e.g. of a pool) to the given
+/// ones
+pub fn add_plugin_stats(
+    total: &mut BTreeMap<String, PluginStats>,
+    other: BTreeMap<String, PluginStats>,
+) {
+    for (id, stats) in other {
+        let entry = total.entry(id).or_insert_with(|| PluginStats {
+            name: stats.name.clone(),
+            ..Default::default()
+        });
+        entry.events_in += stats.events_in;
+        entry.failures += stats.failures;
+    }
+}
+
+/// Checks the assertions against the counters accumulated between the two snapshots. Returns a
+/// description of each violated assertion.
+pub fn check_stats(
+    assertions: &StatsAssertions,
+    before: &BTreeMap<String, PluginStats>,
+    after: &BTreeMap<String, PluginStats>,
+) -> Vec<String> {
+    let delta = |id: &str, stats: &PluginStats| {
+        let previous = before.get(id).cloned().unwrap_or_default();
+        (
+            stats.events_in.saturating_sub(previous.events_in),
+            stats.failures.saturating_sub(previous.failures),
+        )
+    };
+
+    let mut violations = Vec::new();
+    for (id, stats) in after {
+        let (_, failures) = delta(id, stats);
+        if failures == 0 {
+            continue;
+        }
+        if assertions.no_plugin_failures || (assertions.no_grok_failures && stats.name == "grok") {
+            violations.push(format!(
+                "The {} plugin {} failed to process {} event(s)",
+                stats.name, id, failures
+            ));
+        }
+    }
+    for (id, expected) in &assertions.filter_events {
+        match after.get(id) {
+            Some(stats) => {
+                let (events, _) = delta(id, stats);
+                if events != *expected {
+                    violations.push(format!(
+                        "The filter {id} processed {events} event(s) instead of {expected}"
+                    ));
+                }
+            }
+            None => violations.push(format!("The pipeline has no plugin with the ID {id}")),
+        }
+    }
+
+    violations
+}
@@ -0,0 +1,92 @@
+use std::sync::{Arc, Mutex, OnceLock};
+
+use libtest_mimic::{Arguments, Conclusion, Failed, Trial};
+use tracing::instrument;
+
+use crate::report::{TestReport, TestStatus};
+use crate::Harness;
+
+/// Whether libtest runs the test with the given name, according to its filter (`--exact`,
+/// `--skip`) and whether ignored tests are run exclusively (`--ignored`)
+pub fn is_selected(args: &Arguments, name: &str) -> bool {
+    if args.ignored {
+        return false;
+    }
+    let matches = |filter: &str| match args.exact {
+        true => name == filter,
+        false => name.contains(filter),
+    };
+    args.filter.as_deref().is_none_or(matches) && !args.skip.iter().any(|s| matches(s))
+}
+
+/// The outcome of a test case as that of a libtest trial
+fn conclude(report: &Result<TestReport, String>, name: &str) -> Result<(), Failed> {
+    let report = report.as_ref().map_err(Failed::from)?;
+    let result = report
+        .results
+        .iter()
+        .find(|r| r.name == name)
+        .ok_or_else(|| Failed::from("The test case did not run"))?;
+    match result.status {
+        TestStatus::Passed | TestStatus::XFailed => Ok(()),
+        TestStatus::Skipped => Err(Failed::from("The test case did not run")),
+        TestStatus::Failed | TestStatus::XPassed => Err(Failed::from(
+            result
+                .error
+                .as_deref()
+                .map(console::strip_ansi_codes)
+                .unwrap_or_default(),
+        )),
+    }
+}
+
+/// Turns each test case of the harness into a libtest trial. The test cases selected by the
+/// arguments run together against a single Logstash container when the first trial runs, and
+/// each trial reports the result of its test case.
+#[instrument]
+pub fn trials(harness: Harness, args: &Arguments) -> anyhow::Result<Vec<Trial>> {
+    let names = harness.test_names()?;
+    let selected: Vec<String> = names
+        .iter()
+        .filter(|n| is_selected(args, n))
+        .cloned()
+        .collect();
+    let harness = Arc::new(Mutex::new(Some(harness.keep_going(true).only(selected))));
+    let report: Arc<OnceLock<Result<TestReport, String>>> = Arc::default();
+
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            let harness = harness.clone();
+            let report = report.clone();
+            Trial::test(name.clone(), move || {
+                let report = report.get_or_init(|| {
+                    let harness = harness
+                        .lock()
+                        .map_err(|e| e.to_string())?
+                        .take()
+                        .ok_or("The test cases already ran")?;
+                    tokio::runtime::Runtime::new()
+                        .map_err(|e| e.to_string())?
+                        .block_on(harness.run())
+                        .map_err(|e| format!("{e:?}"))
+                });
+                conclude(report, &name)
+            })
+        })
+        .collect())
+}
+
+/// Runs the test cases of the harness as the trials of a test target without the default test
+/// harness (`harness = false`), with the arguments given to `cargo test`
+pub fn run(harness: Harness) -> Conclusion {
+    let args = Arguments::from_args();
+    let trials = match trials(harness, &args) {
+        Ok(trials) => trials,
+        Err(e) => {
+            let error = format!("{e:?}");
+            vec![Trial::test("lotus", move || Err(Failed::from(&error)))]
+        }
+    };
+    libtest_mimic::run(&args, trials)
+}
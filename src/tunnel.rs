@@ -0,0 +1,124 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context};
+use tempfile::TempDir;
+use tokio::process::{Child, Command};
+use tracing::{debug, instrument};
+
+use crate::docker::Ports;
+use crate::{DOCKER_SOCKET, LOCALHOST};
+
+/// How long to wait for SSH to establish the tunnel
+const TUNNEL_TIMEOUT: Duration = Duration::from_secs(15);
+const TUNNEL_SOCKET_NAME: &str = "docker.sock";
+
+/// An SSH connection to a remote host, forwarding a local socket to its Docker daemon, the input
+/// and API ports of the Logstash container to the local host, and the output port of Lotus to the
+/// remote host. The connection is closed when the tunnel is dropped.
+#[derive(Debug)]
+pub struct SshTunnel {
+    child: Child,
+    socket: PathBuf,
+    _dir: TempDir,
+}
+
+/// The arguments of `ssh` for an `ssh://[user@]host[:port]` address, forwarding the given local
+/// socket to the Docker daemon of the remote host, and the given ports
+pub fn ssh_arguments(address: &str, socket: &Path, ports: Ports) -> anyhow::Result<Vec<String>> {
+    let url =
+        reqwest::Url::parse(address).with_context(|| format!("Parsing the address: {address}"))?;
+    if url.scheme() != "ssh" {
+        return Err(anyhow!("Not an SSH address: {address}"));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("The SSH address names no host: {address}"))?;
+    let destination = match url.username() {
+        "" => host.to_string(),
+        user => format!("{user}@{host}"),
+    };
+
+    let mut args: Vec<String> = [
+        "-o",
+        "ExitOnForwardFailure=yes",
+        "-o",
+        "BatchMode=yes",
+        "-N",
+        "-T",
+    ]
+    .map(String::from)
+    .into();
+    if let Some(port) = url.port() {
+        args.extend([String::from("-p"), port.to_string()]);
+    }
+    args.extend([
+        String::from("-L"),
+        format!("{}:{}", socket.display(), DOCKER_SOCKET),
+    ]);
+    for port in [ports.input, ports.api] {
+        args.extend([
+            String::from("-L"),
+            format!("{LOCALHOST}:{port}:{LOCALHOST}:{port}"),
+        ]);
+    }
+    args.extend([
+        String::from("-R"),
+        format!("0.0.0.0:{0}:{LOCALHOST}:{0}", ports.output),
+    ]);
+    args.push(destination);
+
+    Ok(args)
+}
+
+impl SshTunnel {
+    /// Opens the tunnel to the host of an `ssh://[user@]host[:port]` address, and waits until the
+    /// forwarded Docker socket is available
+    #[instrument]
+    pub async fn open(address: &str, ports: Ports) -> anyhow::Result<Self> {
+        let dir = tempfile::tempdir().context("Creating a directory for the tunnel socket")?;
+        let socket = dir.path().join(TUNNEL_SOCKET_NAME);
+        let args = ssh_arguments(address, &socket, ports)?;
+
+        debug!("Open the SSH tunnel: ssh {}", args.join(" "));
+        let mut child = Command::new("ssh")
+            .args(&args)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Starting ssh")?;
+
+        let started = Instant::now();
+        while !socket.exists() {
+            if let Some(status) = child.try_wait().context("Waiting for ssh")? {
+                return Err(anyhow!(
+                    "The SSH tunnel to {address} could not be opened ({status})"
+                ));
+            }
+            if started.elapsed() > TUNNEL_TIMEOUT {
+                return Err(anyhow!(
+                    "The SSH tunnel to {address} was not opened within {TUNNEL_TIMEOUT:?}"
+                ));
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        Ok(Self {
+            child,
+            socket,
+            _dir: dir,
+        })
+    }
+
+    /// The local socket forwarded to the Docker daemon of the remote host
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+}
+
+impl Drop for SshTunnel {
+    fn drop(&mut self) {
+        let _ = self.child.start_kill();
+    }
+}
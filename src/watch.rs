@@ -0,0 +1,149 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use clap::Args;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tracing::{debug, instrument};
+
+use crate::{CONFIG_FILE, FIXTURES_DIR};
+
+#[derive(Debug, Args)]
+pub struct WatchArguments {
+    /// How long to wait for further changes (in milliseconds) before running the test cases again
+    #[arg(long, default_value_t = 500)]
+    pub debounce: u64,
+}
+
+/// What is run again in response to changes of the project
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reaction {
+    /// The Logstash image is rebuilt and all test cases are run, because the pipeline changed
+    Rebuild,
+    /// All test cases are run, because the shared fixture variables changed
+    RerunAll,
+    /// The test cases at the given locations (relative to the tests directory) are run
+    Rerun(Vec<PathBuf>),
+}
+
+/// The locations of the project that are watched for changes
+#[derive(Debug, Clone)]
+pub struct WatchedPaths {
+    pub target: PathBuf,
+    pub config_file: PathBuf,
+    pub fixtures_dir: PathBuf,
+    pub tests_dir: PathBuf,
+    /// The directories whose contents are part of the Logstash image (i.e. rules, ruby scripts
+    /// and grok patterns)
+    pub pipeline_dirs: Vec<PathBuf>,
+}
+
+impl WatchedPaths {
+    /// Resolves the watched locations of the project in the target directory, so that they can be
+    /// compared to the absolute paths reported by the file watcher
+    pub fn new(target: &Path, tests_dir: &str, pipeline_dirs: &[&str]) -> anyhow::Result<Self> {
+        let target = target
+            .canonicalize()
+            .with_context(|| format!("Resolving the target location: {}", target.display()))?;
+        let resolve = |dir: &str| {
+            let dir = target.join(dir);
+            dir.canonicalize().unwrap_or(dir)
+        };
+
+        Ok(Self {
+            config_file: target.join(CONFIG_FILE),
+            fixtures_dir: resolve(FIXTURES_DIR),
+            tests_dir: resolve(tests_dir),
+            pipeline_dirs: pipeline_dirs.iter().map(|d| resolve(d)).collect(),
+            target,
+        })
+    }
+
+    /// Determines what is run again in response to the changed paths, if anything
+    pub fn reaction(&self, changed: &[PathBuf]) -> Option<Reaction> {
+        let mut fixtures_changed = false;
+        let mut test_cases = Vec::new();
+        for path in changed {
+            if *path == self.config_file || self.pipeline_dirs.iter().any(|d| path.starts_with(d)) {
+                return Some(Reaction::Rebuild);
+            } else if path.starts_with(&self.fixtures_dir) {
+                fixtures_changed = true;
+            } else if let Ok(test_case) = path.strip_prefix(&self.tests_dir) {
+                if test_case.components().next().is_some() {
+                    test_cases.push(test_case.to_path_buf());
+                }
+            }
+        }
+
+        if fixtures_changed {
+            Some(Reaction::RerunAll)
+        } else if !test_cases.is_empty() {
+            Some(Reaction::Rerun(test_cases))
+        } else {
+            None
+        }
+    }
+}
+
+/// Starts watching the project for changes. Changes are reported as long as the returned watcher
+/// is kept.
+#[instrument]
+pub fn watch(
+    paths: &WatchedPaths,
+) -> anyhow::Result<(RecommendedWatcher, UnboundedReceiver<notify::Result<Event>>)> {
+    let (sender, receiver) = unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = sender.send(event);
+    })
+    .context("Creating the file watcher")?;
+
+    watcher
+        .watch(&paths.target, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Watching the directory: {}", paths.target.display()))?;
+    for dir in paths
+        .pipeline_dirs
+        .iter()
+        .chain([&paths.fixtures_dir, &paths.tests_dir])
+        .filter(|d| d.is_dir())
+    {
+        debug!("Watch the directory {}", dir.display());
+        watcher
+            .watch(dir, RecursiveMode::Recursive)
+            .with_context(|| format!("Watching the directory: {}", dir.display()))?;
+    }
+
+    Ok((watcher, receiver))
+}
+
+/// Waits for the next files to be created, modified or removed, and returns their paths once no
+/// further changes were seen for the debounce period
+pub async fn next_changes(
+    events: &mut UnboundedReceiver<notify::Result<Event>>,
+    debounce: Duration,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut changed: Vec<PathBuf> = Vec::new();
+    loop {
+        let event = if changed.is_empty() {
+            events.recv().await
+        } else {
+            match tokio::time::timeout(debounce, events.recv()).await {
+                Ok(event) => event,
+                Err(_) => break,
+            }
+        };
+        let event = event
+            .ok_or(anyhow!("The file watcher stopped unexpectedly"))?
+            .context("Watching the project for changes")?;
+        if matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) {
+            changed.extend(event.paths);
+        }
+    }
+
+    changed.sort();
+    changed.dedup();
+    Ok(changed)
+}
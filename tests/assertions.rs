@@ -0,0 +1,166 @@
+use lotus::assertions::{
+    check_assertions, check_file_assertions, check_presence, load_assertions, read_captured_files,
+    require_jq, uses_jq, Assertion,
+};
+use lotus::paths::FieldPath;
+use serde_json::json;
+
+fn assertions(data: serde_json::Value) -> anyhow::Result<Vec<Assertion>> {
+    Ok(serde_json::from_value(data)?)
+}
+
+#[test]
+fn satisfied_assertions_pass() -> anyhow::Result<()> {
+    let event = json! {{
+        "url": { "port": 443, "path": "/index.html" },
+        "tags": ["parsed", "web"],
+        "message": "GET /index.html",
+    }};
+    let assertions = assertions(json! {[
+        { "path": "$.url.port", "equals": 443 },
+        { "path": "$.url.path", "equals": "{{regex:/.*\\.html}}" },
+        { "path": "$.tags", "contains": "parsed" },
+        { "path": "$.message", "matches": "^GET " },
+        { "path": "$.event.original", "exists": false },
+    ]})?;
+
+    check_assertions(&assertions, &event)
+}
+
+#[test]
+fn failed_assertions_are_all_reported() -> anyhow::Result<()> {
+    let event = json! {{
+        "url": { "port": 80 },
+        "tags": ["_grokparsefailure"],
+    }};
+    let assertions = assertions(json! {[
+        { "path": "$.url.port", "equals": 443 },
+        { "path": "$.tags", "contains": "parsed" },
+        { "path": "$.tags[0]", "exists": true },
+    ]})?;
+
+    let error = check_assertions(&assertions, &event)
+        .unwrap_err()
+        .to_string();
+    assert!(error.starts_with("2 of 3 assertions failed"), "{error}");
+    assert!(
+        error.contains("$.url.port: found 80, which does not equal 443"),
+        "{error}"
+    );
+    assert!(
+        error.contains(r#"$.tags: found ["_grokparsefailure"], which does not contain "parsed""#),
+        "{error}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn jq_expressions_must_be_truthy() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let path = tmp_dir.path().join("assertions.jq");
+    std::fs::write(
+        &path,
+        "# Parsed requests over TLS\n.url.port == 443 and (.tags | index(\"parsed\"))\n\n.message | startswith(\"GET\")\n",
+    )?;
+    let event = json! {{
+        "url": { "port": 443 },
+        "tags": ["beats", "parsed"],
+        "message": "POST /index.html",
+    }};
+
+    let expressions = load_assertions(&path)?;
+    assert_eq!(expressions.len(), 2);
+    let error = check_assertions(&expressions, &event)
+        .unwrap_err()
+        .to_string();
+    assert!(error.starts_with("1 of 2 assertions failed"), "{error}");
+    assert!(error.contains("startswith"), "{error}");
+
+    let scoped = assertions(json! {[{ "path": "$.url", "jq": ".port > 80" }]})?;
+    check_assertions(&scoped, &event)?;
+
+    let invalid = assertions(json! {[{ "path": "$", "jq": ".url.port ==" }]})?;
+    assert!(check_assertions(&invalid, &event).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn field_presence_violations_are_all_reported() -> anyhow::Result<()> {
+    let event = json! {{
+        "user": { "id": "u1", "email": "a@example.com" },
+        "source": { "port": 22 },
+    }};
+    let paths = |paths: &[&str]| -> anyhow::Result<Vec<FieldPath>> {
+        paths.iter().map(|p| p.parse()).collect()
+    };
+
+    check_presence(
+        &paths(&["user.id", "[source][port]"])?,
+        &paths(&["source.ip"])?,
+        &event,
+    )?;
+
+    let error = check_presence(
+        &paths(&["user.id", "user.name"])?,
+        &paths(&["[user][email]", "source.ip"])?,
+        &event,
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(
+        error.starts_with("2 of 4 field presence checks failed"),
+        "{error}"
+    );
+    assert!(error.contains("[user][name]"), "{error}");
+    assert!(error.contains("[user][email]"), "{error}");
+
+    Ok(())
+}
+
+#[test]
+fn file_assertions_check_the_lines_of_the_captured_files() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    std::fs::create_dir_all(tmp_dir.path().join("web"))?;
+    std::fs::write(
+        tmp_dir.path().join("web/access.log"),
+        "{\"message\":\"GET /\"}\nplain text\n",
+    )?;
+
+    let files = read_captured_files(tmp_dir.path())?;
+    assert_eq!(
+        files.get("web/access.log"),
+        Some(&json!([{ "message": "GET /" }, "plain text"]))
+    );
+
+    let checks = assertions(json! {[
+        { "file": "web/access.log", "path": "$[0].message", "equals": "GET /" },
+        { "file": "web/access.log", "path": "$[1]", "matches": "^plain" },
+        { "file": "web/error.log", "path": "$[0]", "exists": false },
+        { "path": "$.message", "exists": true },
+    ]})?;
+    check_file_assertions(&checks, &files)?;
+    check_assertions(&checks, &json!({ "message": "GET /" }))?;
+
+    let missing = assertions(json! {[
+        { "file": "web/error.log", "path": "$[0]", "exists": true },
+    ]})?;
+    let error = check_file_assertions(&missing, &files)
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("web/error.log: $[0]"), "{error}");
+
+    Ok(())
+}
+
+#[test]
+fn jq_is_only_required_by_jq_assertions() -> anyhow::Result<()> {
+    let plain = assertions(json! {[{ "path": "$.url.port", "equals": 443 }]})?;
+    assert!(!uses_jq(&plain));
+    require_jq(&plain)?;
+
+    let scoped = assertions(json! {[{ "path": "$.url", "jq": ".port > 80" }]})?;
+    assert!(uses_jq(&scoped));
+    require_jq(&scoped)
+}
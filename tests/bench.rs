@@ -0,0 +1,81 @@
+use std::io::Write;
+use std::time::Duration;
+
+use lotus::bench::{
+    bench_id, generate_events, load_events, parse_bench_id, percentile, BenchReport,
+};
+use serde_json::json;
+
+#[test]
+fn the_time_of_sending_is_carried_by_the_correlation_id() {
+    let id = bench_id(42, Duration::from_micros(1_234_567));
+    assert_eq!(id, "bench-42-1234567");
+    assert_eq!(
+        parse_bench_id(&id),
+        Some((42, Duration::from_micros(1_234_567)))
+    );
+    assert_eq!(parse_bench_id("0-0"), None);
+    assert_eq!(parse_bench_id("bench-x-1"), None);
+}
+
+#[test]
+fn percentiles_use_the_nearest_rank() {
+    let sorted: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+    assert_eq!(percentile(&sorted, 50.0), Some(Duration::from_millis(50)));
+    assert_eq!(percentile(&sorted, 95.0), Some(Duration::from_millis(95)));
+    assert_eq!(percentile(&sorted, 99.0), Some(Duration::from_millis(99)));
+    assert_eq!(percentile(&sorted, 0.0), Some(Duration::from_millis(1)));
+    assert_eq!(
+        percentile(&[Duration::from_millis(7)], 99.0),
+        Some(Duration::from_millis(7))
+    );
+    assert_eq!(percentile(&[], 50.0), None);
+}
+
+#[test]
+fn the_report_summarizes_throughput_and_latency() {
+    let report = BenchReport {
+        sent: 5,
+        latencies: vec![
+            Duration::from_millis(30),
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+            Duration::from_millis(40),
+        ],
+        duration: Duration::from_secs(2),
+    };
+    assert_eq!(report.throughput(), 2.0);
+    assert_eq!(report.percentile(50.0), Some(Duration::from_millis(20)));
+
+    let text = report.to_string();
+    assert!(text.contains("events received: 4 (1 lost)"), "{text}");
+    assert!(text.contains("throughput:      2.0 events/s"), "{text}");
+    assert!(text.contains("latency p99:     40.0 ms"), "{text}");
+}
+
+#[test]
+fn benchmark_events_are_json_objects_or_raw_lines() -> anyhow::Result<()> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    writeln!(file, r#"{{"message": "structured"}}"#)?;
+    writeln!(file)?;
+    writeln!(file, "<13>Jan  1 00:00:00 host app: raw")?;
+    assert_eq!(
+        load_events(file.path())?,
+        vec![
+            json!({ "message": "structured" }),
+            json!({ "message": "<13>Jan  1 00:00:00 host app: raw" }),
+        ]
+    );
+
+    let empty = tempfile::NamedTempFile::new()?;
+    assert!(load_events(empty.path()).is_err());
+
+    assert_eq!(
+        generate_events(2),
+        vec![
+            json!({ "message": "lotus benchmark event 0" }),
+            json!({ "message": "lotus benchmark event 1" }),
+        ]
+    );
+    Ok(())
+}
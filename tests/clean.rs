@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use std::fs::{create_dir_all, File};
+
+use lotus::clean::remove_cache_dirs;
+use lotus::docker::{hostname, is_leftover, label};
+
+#[test]
+fn all_cache_directories_are_deleted() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let cache_dir = tmp_dir.path().join("cache");
+    for name in ["123", "456"] {
+        create_dir_all(cache_dir.join(name))?;
+        File::create(cache_dir.join(name).join("image.tar"))?;
+    }
+
+    let removed = remove_cache_dirs(&cache_dir)?;
+
+    assert_eq!(removed, vec![cache_dir.join("123"), cache_dir.join("456")]);
+    assert_eq!(std::fs::read_dir(&cache_dir)?.count(), 0);
+    Ok(())
+}
+
+#[test]
+fn a_missing_cache_directory_is_not_an_error() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+
+    assert!(remove_cache_dirs(&tmp_dir.path().join("cache"))?.is_empty());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn only_containers_of_ended_runs_on_this_machine_are_leftovers() {
+    let labels = |host: &str, pid: &str| -> HashMap<String, String> {
+        [
+            (label("hostname"), host.to_string()),
+            (label("pid"), pid.to_string()),
+        ]
+        .into()
+    };
+    let this_run = std::process::id().to_string();
+
+    assert!(!is_leftover(&labels(&hostname(), &this_run), true));
+    assert!(is_leftover(&labels(&hostname(), &this_run), false));
+    assert!(is_leftover(&labels(&hostname(), "not a pid"), true));
+    // The first process runs, even if it belongs to another user
+    assert!(!is_leftover(&labels(&hostname(), "1"), true));
+    let mut ended = std::process::Command::new("true").spawn().unwrap();
+    ended.wait().unwrap();
+    assert!(is_leftover(
+        &labels(&hostname(), &ended.id().to_string()),
+        true
+    ));
+    assert!(!is_leftover(
+        &labels("elsewhere.example", "not a pid"),
+        true
+    ));
+    assert!(!is_leftover(&labels("elsewhere.example", &this_run), false));
+    assert!(!is_leftover(&HashMap::new(), false));
+}
@@ -0,0 +1,286 @@
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::process::ExitCode;
+
+use anyhow::anyhow;
+use clap::{CommandFactory, Parser};
+use lotus::compare::Verbosity;
+use lotus::docker::{
+    label, project_name, ContainerEnv, ContainerRuntime, EnvVar, MemorySize, Ports, StartupOptions,
+};
+use lotus::events::OutputFormat;
+use lotus::{exit_code, Command, DefaultArguments, Outcome};
+use tracing::Level;
+
+#[test]
+fn command_line_interface_is_consistent() {
+    DefaultArguments::command().debug_assert();
+}
+
+#[test]
+fn generate_subcommand_is_distinguished_from_the_target() -> anyhow::Result<()> {
+    let args = DefaultArguments::try_parse_from(["lotus", "-vv", "project"])?;
+    assert_eq!(args.target.as_deref(), Some("project".as_ref()));
+    assert_eq!(args.verbose, 2);
+    assert!(args.command.is_none());
+    assert!(args.shuffle.is_none());
+
+    let args = DefaultArguments::try_parse_from(["lotus", "--shuffle"])?;
+    assert_eq!(args.shuffle, Some(None));
+    let args = DefaultArguments::try_parse_from(["lotus", "--shuffle", "42", "project"])?;
+    assert_eq!(args.shuffle, Some(Some(42)));
+    assert_eq!(args.target.as_deref(), Some("project".as_ref()));
+
+    let args = DefaultArguments::try_parse_from(["lotus", "generate", "sample.log"])?;
+    let Some(Command::Generate(generate_args)) = args.command else {
+        panic!("Expected the generate subcommand");
+    };
+    assert_eq!(generate_args.log_file, std::path::Path::new("sample.log"));
+
+    Ok(())
+}
+
+#[test]
+fn events_are_piped_through_the_pipeline_with_run_stdin() -> anyhow::Result<()> {
+    let args = DefaultArguments::try_parse_from(["lotus", "run", "--stdin"])?;
+    let Some(Command::Run(run_args)) = args.command else {
+        panic!("Expected the run subcommand");
+    };
+    assert!(run_args.stdin);
+
+    let args = DefaultArguments::try_parse_from(["lotus", "run"])?;
+    let Some(Command::Run(run_args)) = args.command else {
+        panic!("Expected the run subcommand");
+    };
+    assert!(!run_args.stdin);
+
+    Ok(())
+}
+
+#[test]
+fn test_failures_are_distinguished_from_errors_by_exit_code() {
+    assert_eq!(exit_code(&Ok(Outcome::Success)), ExitCode::SUCCESS);
+    assert_eq!(
+        exit_code(&Ok(Outcome::TestsFailed {
+            failed: 1,
+            total: 2
+        })),
+        ExitCode::from(1)
+    );
+    assert_eq!(exit_code(&Ok(Outcome::Invalid)), ExitCode::from(1));
+    assert_eq!(exit_code(&Ok(Outcome::Unhealthy(1))), ExitCode::from(1));
+    assert_eq!(
+        exit_code(&Err(anyhow!("Docker is unavailable"))),
+        ExitCode::from(2)
+    );
+}
+
+#[test]
+fn verbosity_is_controlled_by_verbose_and_quiet() -> anyhow::Result<()> {
+    let args = DefaultArguments::try_parse_from(["lotus"])?;
+    assert_eq!(args.verbosity(), Verbosity::Normal);
+    assert_eq!(args.log_level(), Level::WARN);
+
+    let args = DefaultArguments::try_parse_from(["lotus", "-vv"])?;
+    assert_eq!(args.verbosity(), Verbosity::Verbose);
+    assert_eq!(args.log_level(), Level::DEBUG);
+
+    let args = DefaultArguments::try_parse_from(["lotus", "-q"])?;
+    assert_eq!(args.verbosity(), Verbosity::Quiet);
+    assert_eq!(args.log_level(), Level::ERROR);
+
+    assert!(DefaultArguments::try_parse_from(["lotus", "-q", "-v"]).is_err());
+
+    let args = DefaultArguments::try_parse_from(["lotus", "--output", "jsonl"])?;
+    assert_eq!(args.output, OutputFormat::Jsonl);
+
+    Ok(())
+}
+
+#[test]
+fn the_container_runtime_is_selectable() -> anyhow::Result<()> {
+    let args = DefaultArguments::try_parse_from(["lotus"])?;
+    assert_eq!(args.container_runtime, ContainerRuntime::Auto);
+
+    let args = DefaultArguments::try_parse_from(["lotus", "--container-runtime", "podman"])?;
+    assert_eq!(args.container_runtime, ContainerRuntime::Podman);
+
+    assert!(DefaultArguments::try_parse_from(["lotus", "--container-runtime", "lxc"]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn a_remote_docker_daemon_is_reached_at_its_host() -> anyhow::Result<()> {
+    let args =
+        DefaultArguments::try_parse_from(["lotus", "--docker-host", "unix:///run/docker.sock"])?;
+    assert_eq!(
+        args.connection(Ports::default()).logstash_host(),
+        "127.0.0.1"
+    );
+    assert_eq!(
+        args.connection(Ports::default())
+            .publish_address()
+            .to_string(),
+        "127.0.0.1"
+    );
+
+    let args = DefaultArguments::try_parse_from([
+        "lotus",
+        "--docker-host",
+        "tcp://build-host:2376",
+        "--docker-tls-verify",
+    ])?;
+    let connection = args.connection(Ports::default());
+    assert!(connection.tls_verify);
+    assert_eq!(connection.logstash_host(), "build-host");
+    assert_eq!(connection.publish_address().to_string(), "0.0.0.0");
+
+    Ok(())
+}
+
+#[test]
+fn a_container_pool_cannot_be_reused() -> anyhow::Result<()> {
+    let args = DefaultArguments::try_parse_from(["lotus", "--containers", "4"])?;
+    assert_eq!(args.containers, 4);
+
+    assert!(
+        DefaultArguments::try_parse_from(["lotus", "--containers", "4", "--reuse-container"])
+            .is_err()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn the_ports_are_configurable() -> anyhow::Result<()> {
+    let args = DefaultArguments::try_parse_from(["lotus", "--api-port", "19600"])?;
+    assert_eq!(args.input_port, None);
+    assert_eq!(args.api_port, Some(19600));
+
+    assert!(DefaultArguments::try_parse_from(["lotus", "--output-port", "70000"]).is_err());
+
+    let args = DefaultArguments::try_parse_from(["lotus", "--ephemeral-ports"])?;
+    assert!(args.ephemeral_ports);
+    assert!(DefaultArguments::try_parse_from([
+        "lotus",
+        "--ephemeral-ports",
+        "--api-port",
+        "19600"
+    ])
+    .is_err());
+    assert!(Ports {
+        input: 0,
+        ..Default::default()
+    }
+    .are_ephemeral());
+    assert!(!Ports::default().are_ephemeral());
+
+    Ok(())
+}
+
+#[test]
+fn the_resources_of_the_containers_can_be_limited() -> anyhow::Result<()> {
+    let args = DefaultArguments::try_parse_from(["lotus", "--memory", "512m", "--cpus", "1.5"])?;
+    assert_eq!(args.memory, Some(MemorySize(512 * 1024 * 1024)));
+    assert_eq!(args.cpus, Some(1.5));
+
+    assert_eq!(
+        "2G".parse::<MemorySize>()?,
+        MemorySize(2 * 1024 * 1024 * 1024)
+    );
+    assert_eq!("1024".parse::<MemorySize>()?, MemorySize(1024));
+    assert!("lots".parse::<MemorySize>().is_err());
+    assert!("-1g".parse::<MemorySize>().is_err());
+
+    assert!(DefaultArguments::try_parse_from(["lotus", "--memory-swap", "3g"]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn jvm_options_and_variables_are_passed_to_logstash() -> anyhow::Result<()> {
+    let args = DefaultArguments::try_parse_from([
+        "lotus",
+        "--java-opts",
+        "-Xms512m -Xmx512m",
+        "-e",
+        "MY_VAR=a=b",
+    ])?;
+    assert_eq!(args.java_opts.as_deref(), Some("-Xms512m -Xmx512m"));
+    assert_eq!(
+        args.env,
+        vec![EnvVar {
+            name: String::from("MY_VAR"),
+            value: String::from("a=b"),
+        }]
+    );
+    assert!(DefaultArguments::try_parse_from(["lotus", "--env", "=b"]).is_err());
+
+    let env = ContainerEnv {
+        variables: [(String::from("MY_VAR"), String::from("a=b"))].into(),
+        java_opts: args.java_opts,
+    };
+    assert_eq!(
+        env.variables(5067),
+        vec![
+            "MY_VAR=a=b",
+            "LS_JAVA_OPTS=-Xms512m -Xmx512m",
+            "LOTUS_OUTPUT_PORT=5067"
+        ]
+    );
+    assert_eq!(
+        ContainerEnv::default().variables(5067),
+        vec!["LOTUS_OUTPUT_PORT=5067"]
+    );
+
+    let args = DefaultArguments::try_parse_from(["lotus", "--workers", "4"])?;
+    assert_eq!(args.workers, NonZeroUsize::new(4));
+    assert!(DefaultArguments::try_parse_from(["lotus", "--workers", "0"]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn the_startup_of_logstash_can_be_tuned() -> anyhow::Result<()> {
+    let args = DefaultArguments::try_parse_from([
+        "lotus",
+        "--health-retries",
+        "30",
+        "--health-interval",
+        "2",
+        "--startup-timeout",
+        "90",
+    ])?;
+    assert_eq!(args.health_retries, Some(30));
+    assert_eq!(args.health_interval, Some(2));
+    assert_eq!(args.startup_timeout, Some(90));
+
+    let defaults = StartupOptions::default();
+    assert_eq!(defaults.health_retries, 10);
+    assert_eq!(defaults.health_interval.as_secs(), 10);
+    assert_eq!(defaults.timeout.as_secs(), 300);
+
+    Ok(())
+}
+
+#[test]
+fn registry_pulls_can_be_forbidden() -> anyhow::Result<()> {
+    assert!(!DefaultArguments::try_parse_from(["lotus"])?.offline);
+    assert!(DefaultArguments::try_parse_from(["lotus", "--offline"])?.offline);
+
+    Ok(())
+}
+
+#[test]
+fn leftovers_of_crashed_runs_are_reaped_on_request() -> anyhow::Result<()> {
+    assert!(!DefaultArguments::try_parse_from(["lotus"])?.reap);
+    assert!(DefaultArguments::try_parse_from(["lotus", "--reap"])?.reap);
+    assert_eq!(label("project"), "net.nausicaea.lotus.project");
+    assert_eq!(
+        project_name(Path::new("/home/user/.cache/lotus/0123abcd"))?,
+        "0123abcd"
+    );
+
+    Ok(())
+}
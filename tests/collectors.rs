@@ -0,0 +1,373 @@
+use lotus::collectors::{collect_tests, shuffle_tests, NamePattern, TestFilter};
+use lotus::compare::{CompareMode, Normalization};
+use lotus::config::{CompareStage, TestMetadata};
+use lotus::generate::{generate_test_cases, new_test_case};
+use lotus::runner::TestOptions;
+use std::fs::File;
+use std::io::Write;
+
+mod common;
+
+use common::{write_empty_test_case, write_test_case};
+
+#[test]
+fn each_kind_of_expectation_is_collected() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    write_empty_test_case(tmp_dir.path(), "document", None)?;
+    write_test_case(
+        tmp_dir.path(),
+        "assertions",
+        &[("input.ndjson", "{}\n{}\n"), ("assertions.yaml", "[]")],
+    )?;
+    write_test_case(
+        tmp_dir.path(),
+        "drop",
+        &[("input.json", "{}"), ("expected.drop", "")],
+    )?;
+    write_test_case(
+        tmp_dir.path(),
+        "schema",
+        &[("input.json", "{}"), ("expected.schema.json", "{}")],
+    )?;
+
+    let test_cases = collect_tests(tmp_dir.path(), false)?;
+    assert_eq!(test_cases.len(), 4);
+
+    Ok(())
+}
+
+#[test]
+fn ambiguous_expectations_are_rejected() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    write_test_case(
+        tmp_dir.path(),
+        "ambiguous",
+        &[
+            ("input.json", "{}"),
+            ("expected.json", "{}"),
+            ("expected.drop", ""),
+        ],
+    )?;
+
+    assert!(collect_tests(tmp_dir.path(), false).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn missing_inputs_are_rejected() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    write_test_case(tmp_dir.path(), "no_input", &[("expected.json", "{}")])?;
+
+    assert!(collect_tests(tmp_dir.path(), false).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn missing_expectations_are_accepted_in_record_mode() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    write_test_case(tmp_dir.path(), "new", &[("input.json", "{}")])?;
+
+    assert!(collect_tests(tmp_dir.path(), false).is_err());
+    assert_eq!(collect_tests(tmp_dir.path(), true)?.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn nested_test_cases_are_named_by_their_relative_path() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    write_empty_test_case(tmp_dir.path(), "apache/access/basic", None)?;
+    write_empty_test_case(tmp_dir.path(), "apache/error", None)?;
+    write_empty_test_case(tmp_dir.path(), "nginx", None)?;
+    File::create(tmp_dir.path().join("apache/greeting.yaml"))?
+        .write_all(b"input: {}\nexpected: {}\n")?;
+
+    let test_cases = collect_tests(tmp_dir.path(), false)?;
+    let names: Vec<&str> = test_cases.iter().map(|t| t.name()).collect();
+    assert_eq!(
+        names,
+        [
+            "apache/access/basic",
+            "apache/error",
+            "apache/greeting",
+            "nginx"
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn single_file_test_cases_are_collected() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let case = r#"
+xfail: true
+ignore: ["@timestamp"]
+input:
+  message: hello
+expected:
+  message: hello
+  dummy: "true"
+"#;
+    File::create(tmp_dir.path().join("greeting.yaml"))?.write_all(case.as_bytes())?;
+    File::create(tmp_dir.path().join("README.md"))?.write_all(b"not a test case")?;
+
+    let test_cases = collect_tests(tmp_dir.path(), false)?;
+    assert_eq!(test_cases.len(), 1);
+    assert_eq!(test_cases[0].name(), "greeting");
+
+    Ok(())
+}
+
+#[test]
+fn single_file_test_cases_require_exactly_one_expectation() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let case = "input: {}\nexpected: {}\ndrop: true\n";
+    File::create(tmp_dir.path().join("ambiguous.yml"))?.write_all(case.as_bytes())?;
+
+    assert!(collect_tests(tmp_dir.path(), false).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_metadata_is_loaded() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let metadata = r#"
+name = "Apache access log"
+description = "Parses a combined log line"
+tags = ["grok", "slow"]
+timeout = 30
+compare = "inclusive"
+ignore = ["@timestamp"]
+"#;
+    write_test_case(
+        tmp_dir.path(),
+        "apache",
+        &[
+            ("input.txt", "127.0.0.1 - - [...]"),
+            ("expected.json", "{}"),
+            ("test.toml", metadata),
+        ],
+    )?;
+
+    let test_cases = collect_tests(tmp_dir.path(), false)?;
+    assert_eq!(test_cases[0].name(), "apache");
+    assert_eq!(test_cases[0].title(), "Apache access log");
+
+    Ok(())
+}
+
+#[test]
+fn test_metadata_overrides_the_comparison_settings_of_the_run() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    write_empty_test_case(tmp_dir.path(), "inherits", None)?;
+    write_empty_test_case(tmp_dir.path(), "overrides", Some("compare = \"strict\"\ncoerce_numbers = false\nreplace_ignore = true\nignore = [\"host.ip\"]\n"))?;
+    let options = TestOptions {
+        ignore: vec!["@timestamp".parse()?],
+        compare: CompareMode::Inclusive,
+        coerce_numbers: true,
+        ..TestOptions::default()
+    };
+
+    let test_cases = collect_tests(tmp_dir.path(), false)?;
+    let ignored = |i: usize| -> Vec<String> {
+        test_cases[i]
+            .ignored_fields(&options)
+            .map(|p| p.to_string())
+            .collect()
+    };
+    assert_eq!(test_cases[0].name(), "inherits");
+    assert_eq!(test_cases[0].compare_mode(&options), CompareMode::Inclusive);
+    assert_eq!(ignored(0), vec!["[@timestamp]"]);
+    assert!(test_cases[0].coerce_numbers(&options));
+    assert_eq!(test_cases[1].compare_mode(&options), CompareMode::Strict);
+    assert_eq!(ignored(1), vec!["[host][ip]"]);
+    assert!(!test_cases[1].coerce_numbers(&options));
+
+    Ok(())
+}
+
+#[test]
+fn comparison_stages_are_loaded_in_order() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let metadata = r#"
+[[stages]]
+stage = "ignore"
+paths = ["@timestamp"]
+
+[[stages]]
+stage = "normalize"
+paths = ["host.name"]
+transform = "lowercase"
+
+[[stages]]
+stage = "schema"
+file = "structure.schema.json"
+
+[[stages]]
+stage = "diff"
+compare = "strict"
+"#;
+    std::fs::write(tmp_dir.path().join("test.toml"), metadata)?;
+
+    let metadata = TestMetadata::load(tmp_dir.path())?;
+    assert_eq!(
+        metadata.stages,
+        vec![
+            CompareStage::Ignore {
+                paths: vec!["@timestamp".parse()?],
+            },
+            CompareStage::Normalize {
+                paths: vec!["host.name".parse()?],
+                transform: Normalization::Lowercase,
+            },
+            CompareStage::Schema {
+                file: "structure.schema.json".into(),
+            },
+            CompareStage::Diff {
+                compare: Some(CompareMode::Strict),
+            },
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn unknown_metadata_is_rejected() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    write_empty_test_case(tmp_dir.path(), "typo", Some("tgas = [\"slow\"]"))?;
+
+    assert!(collect_tests(tmp_dir.path(), false).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_cases_are_filtered_by_tags() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    for (name, tags) in [
+        ("a", "[\"grok\"]"),
+        ("b", "[\"grok\", \"slow\"]"),
+        ("c", "[]"),
+    ] {
+        write_empty_test_case(tmp_dir.path(), name, Some(&format!("tags = {tags}")))?;
+    }
+    let test_cases = collect_tests(tmp_dir.path(), false)?;
+    let selected = |filter: TestFilter| {
+        let mut names: Vec<&str> = test_cases
+            .iter()
+            .filter(|t| filter.matches(t))
+            .map(|t| t.name())
+            .collect();
+        names.sort();
+        names
+    };
+
+    assert_eq!(selected(TestFilter::default()), ["a", "b", "c"]);
+    assert_eq!(
+        selected(TestFilter {
+            tags: vec![String::from("grok")],
+            ..Default::default()
+        }),
+        ["a", "b"]
+    );
+    assert_eq!(
+        selected(TestFilter {
+            tags: vec![String::from("grok")],
+            exclude_tags: vec![String::from("slow")],
+            ..Default::default()
+        }),
+        ["a"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn name_patterns_match_globs_regexes_and_substrings() -> anyhow::Result<()> {
+    let glob: NamePattern = "apache_*".parse()?;
+    assert!(glob.matches("apache_access"));
+    assert!(!glob.matches("nginx_apache_access"));
+
+    let regex: NamePattern = "re:^(apache|nginx)_error$".parse()?;
+    assert!(regex.matches("nginx_error"));
+    assert!(!regex.matches("nginx_error_2"));
+
+    let substring: NamePattern = "access".parse()?;
+    assert!(substring.matches("apache_access_log"));
+    assert!(!substring.matches("apache_error"));
+
+    assert!("re:(".parse::<NamePattern>().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn generated_test_cases_are_collected_in_record_mode() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let log_file = tmp_dir.path().join("sample.log");
+    File::create(&log_file)?.write_all(b"GET /a\n\nGET /b\n")?;
+
+    let test_cases_dir = tmp_dir.path().join("tests/sample");
+    assert_eq!(generate_test_cases(&log_file, &test_cases_dir)?, 2);
+
+    let test_cases = collect_tests(&test_cases_dir, true)?;
+    let names: Vec<&str> = test_cases.iter().map(|t| t.name()).collect();
+    assert_eq!(names, ["0001", "0002"]);
+
+    Ok(())
+}
+
+#[test]
+fn shuffling_is_reproducible_with_the_same_seed() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    for name in ["a", "b", "c", "d", "e", "f", "g", "h"] {
+        write_test_case(
+            tmp_dir.path(),
+            name,
+            &[("input.json", "{}"), ("expected.json", "{}")],
+        )?;
+    }
+    let order = |seed: u64| -> anyhow::Result<Vec<String>> {
+        let mut test_cases = collect_tests(tmp_dir.path(), false)?;
+        shuffle_tests(&mut test_cases, seed);
+        Ok(test_cases.iter().map(|t| t.name().to_string()).collect())
+    };
+
+    assert_eq!(order(42)?, order(42)?);
+    assert_ne!(order(42)?, order(43)?);
+
+    let mut sorted = order(42)?;
+    sorted.sort();
+    assert_eq!(sorted, ["a", "b", "c", "d", "e", "f", "g", "h"]);
+
+    Ok(())
+}
+
+#[test]
+fn new_test_cases_are_created_from_captured_events() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let tests_dir = tmp_dir.path().join("tests");
+    let event_file = tmp_dir.path().join("event.json");
+    std::fs::write(&event_file, r#"{"message": "captured"}"#)?;
+
+    new_test_case(&tests_dir.join("apache/captured"), Some(&event_file), false)?;
+    new_test_case(&tests_dir.join("recorded"), None, true)?;
+    assert!(new_test_case(&tests_dir.join("recorded"), None, true).is_err());
+
+    let input: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        tests_dir.join("apache/captured/input.json"),
+    )?)?;
+    assert_eq!(input["message"], "captured");
+    assert!(tests_dir.join("apache/captured/expected.json").is_file());
+    assert!(!tests_dir.join("recorded/expected.json").exists());
+
+    assert!(collect_tests(&tests_dir, false).is_err());
+    assert_eq!(collect_tests(&tests_dir, true)?.len(), 2);
+
+    Ok(())
+}
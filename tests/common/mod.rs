@@ -0,0 +1,27 @@
+// Each test crate uses only some of the helpers
+#![allow(dead_code)]
+
+use std::fs::{create_dir_all, File};
+use std::io::Write;
+use std::path::Path;
+
+/// Writes the given files of a test case to its directory under the tests directory
+pub fn write_test_case(tests_dir: &Path, name: &str, files: &[(&str, &str)]) -> anyhow::Result<()> {
+    let test_dir = tests_dir.join(name);
+    create_dir_all(&test_dir)?;
+    for (file, content) in files {
+        File::create(test_dir.join(file))?.write_all(content.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Writes a test case that expects its empty input event unchanged, with the given metadata
+pub fn write_empty_test_case(
+    tests_dir: &Path,
+    name: &str,
+    metadata: Option<&str>,
+) -> anyhow::Result<()> {
+    let mut files = vec![("input.json", "{}"), ("expected.json", "{}")];
+    files.extend(metadata.map(|m| ("test.toml", m)));
+    write_test_case(tests_dir, name, &files)
+}
@@ -0,0 +1,616 @@
+use lotus::collectors::collect_tests;
+use lotus::compare::{
+    align_arrays, align_unordered, canonicalize_timestamps, coerce_numbers, current_date,
+    differences, fold_case, numbers_equal, parse_instant, render_placeholders, resolve_matchers,
+    scoped_differences, side_by_side, CompareMode, DefaultMatcher, DiffStyle, Difference, Matcher,
+    Mismatch, Normalization, Verbosity,
+};
+use lotus::paths::FieldPath;
+use lotus::runner::{run_single_test, TestOptions};
+use lotus::server::{bind_server, run_server, EventRouter};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+mod common;
+use common::write_test_case;
+
+#[test]
+fn satisfied_regex_matchers_are_resolved() -> anyhow::Result<()> {
+    let actual = json! {{
+        "@timestamp": "2023-07-16T13:35:40.682Z",
+        "source": { "port": 443 },
+    }};
+    let mut expected = json! {{
+        "@timestamp": "{{regex:\\d{4}-.*Z}}",
+        "source": { "port": "{{regex:\\d+}}" },
+    }};
+
+    resolve_matchers(&actual, &mut expected)?;
+    assert_eq!(actual, expected);
+
+    Ok(())
+}
+
+#[test]
+fn unsatisfied_regex_matchers_are_left_in_place() -> anyhow::Result<()> {
+    let actual = json! {{ "message": "hello world" }};
+    let mut expected = json! {{ "message": "{{regex:hello}}" }};
+
+    resolve_matchers(&actual, &mut expected)?;
+    assert_eq!(expected, json! {{ "message": "{{regex:hello}}" }});
+
+    Ok(())
+}
+
+#[test]
+fn invalid_regex_matchers_are_an_error() {
+    let actual = json! {{ "message": "hello" }};
+    let mut expected = json! {{ "message": "{{regex:(}}" }};
+
+    assert!(resolve_matchers(&actual, &mut expected).is_err());
+}
+
+#[test]
+fn type_matchers_match_values_of_their_type() -> anyhow::Result<()> {
+    let actual = json! {{
+        "s": "a", "n": 1.5, "i": 3, "b": true, "a": [1], "o": {}, "z": null, "wrong": 1,
+    }};
+    let mut expected = json! {{
+        "s": "{{type:string}}", "n": "{{type:number}}", "i": "{{type:integer}}",
+        "b": "{{type:boolean}}", "a": "{{type:array}}", "o": "{{type:object}}",
+        "z": "{{type:null}}", "wrong": "{{type:string}}", "absent": "{{type:string}}",
+    }};
+    resolve_matchers(&actual, &mut expected)?;
+    assert_eq!(
+        expected,
+        json! {{
+            "s": "a", "n": 1.5, "i": 3, "b": true, "a": [1], "o": {}, "z": null,
+            "wrong": "{{type:string}}", "absent": "{{type:string}}",
+        }}
+    );
+
+    let mut expected = json! {{ "n": "{{type:float}}" }};
+    assert!(resolve_matchers(&actual, &mut expected).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn uuid_matchers_match_uuids_only() -> anyhow::Result<()> {
+    let actual = json! {{ "a": "6f1c2e0a-9b3d-4c5e-8f7a-1b2c3d4e5f60", "b": "not-a-uuid" }};
+    let mut expected = json! {{ "a": "{{uuid}}", "b": "{{uuid}}" }};
+    resolve_matchers(&actual, &mut expected)?;
+    assert_eq!(
+        expected,
+        json! {{ "a": "6f1c2e0a-9b3d-4c5e-8f7a-1b2c3d4e5f60", "b": "{{uuid}}" }}
+    );
+
+    Ok(())
+}
+
+#[test]
+fn placeholders_are_rendered_from_the_date_and_the_input_event() {
+    let input = json! {{ "host": "web-1", "url": { "port": 443 } }};
+    let mut expected = json! {{
+        "index": "logs-{{today:%Y.%m.%d}}",
+        "date": "{{today}}",
+        "origin": "{{input.host}}:{{input.url.port}}",
+        "port": "{{input.url.port}}",
+        "missing": "{{input.nothing}}",
+        "id": "{{uuid}}",
+    }};
+    render_placeholders(&mut expected, "2024-03-09", Some(&input));
+    assert_eq!(
+        expected,
+        json! {{
+            "index": "logs-2024.03.09",
+            "date": "2024-03-09",
+            "origin": "web-1:443",
+            "port": 443,
+            "missing": "{{input.nothing}}",
+            "id": "{{uuid}}",
+        }}
+    );
+
+    let mut expected = json! {{ "message": "{{input.message}}" }};
+    render_placeholders(&mut expected, "2024-03-09", Some(&json!("raw line")));
+    assert_eq!(expected, json! {{ "message": "raw line" }});
+}
+
+#[test]
+fn the_current_date_is_that_of_the_pinned_clock() {
+    assert_eq!(current_date(Some("2024-01-01T23:59:59Z")), "2024-01-01");
+    let today = current_date(None);
+    assert_eq!(today.len(), 10, "{today}");
+    assert!(today.starts_with("20"), "{today}");
+}
+
+#[test]
+fn unordered_events_are_aligned_with_their_expected_counterparts() -> anyhow::Result<()> {
+    let mut actual = vec![
+        json! {{ "id": 2, "kind": "clone" }},
+        json! {{ "id": 3, "kind": "unexpected" }},
+        json! {{ "id": 1, "kind": "original" }},
+    ];
+    let expected = vec![
+        json! {{ "id": "{{regex:\\d}}", "kind": "original" }},
+        json! {{ "id": 2, "kind": "clone" }},
+        json! {{ "id": 4, "kind": "missing" }},
+    ];
+
+    align_unordered(&mut actual, &expected, CompareMode::Strict, &DefaultMatcher)?;
+    assert_eq!(
+        actual,
+        vec![
+            json! {{ "id": 1, "kind": "original" }},
+            json! {{ "id": 2, "kind": "clone" }},
+            json! {{ "id": 3, "kind": "unexpected" }},
+        ]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unordered_events_are_paired_after_their_normalization() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    write_test_case(
+        tmp_dir.path(),
+        "unordered",
+        &[
+            (
+                "input.ndjson",
+                "{ \"level\": \"INFO\", \"n\": 1 }\n{ \"level\": \"warn\", \"n\": 2 }\n",
+            ),
+            (
+                "expected.json",
+                r#"[{ "level": "WARN", "n": 2 }, { "level": "info", "n": 1 }]"#,
+            ),
+            (
+                "test.toml",
+                "unordered = true\ncase_insensitive = [\"level\"]\n",
+            ),
+        ],
+    )?;
+    let test_cases = collect_tests(tmp_dir.path(), false)?;
+    let options = TestOptions {
+        timeout: Some(Duration::from_secs(5)),
+        ..TestOptions::default()
+    };
+
+    // The event responder server of Lotus stands in for Logstash, and passes the input events
+    // through unchanged
+    let router = EventRouter::default();
+    let listener = bind_server(0)?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(run_server(router.clone(), listener));
+
+    // The events only match their expected counterparts regardless of case
+    let mut receiver = router.register("0");
+    run_single_test(
+        &reqwest::Client::new(),
+        &mut receiver,
+        &test_cases[0],
+        "0",
+        port,
+        &options,
+    )
+    .await
+}
+
+#[test]
+fn unordered_arrays_are_compared_as_multisets() -> anyhow::Result<()> {
+    let paths: Vec<FieldPath> = vec!["tags".parse()?, "[user][roles]".parse()?];
+    let mut actual = json! {{
+        "tags": ["b", "a", "b"],
+        "user": { "roles": ["admin", "dev"] },
+        "other": [2, 1],
+    }};
+    let expected = json! {{
+        "tags": ["a", "b", "b"],
+        "user": { "roles": ["dev", "{{regex:ad.*}}"] },
+        "other": [1, 2],
+    }};
+
+    align_arrays(
+        &mut actual,
+        &expected,
+        &paths,
+        false,
+        CompareMode::Strict,
+        &DefaultMatcher,
+    )?;
+    assert_eq!(
+        actual,
+        json! {{
+            "tags": ["a", "b", "b"],
+            "user": { "roles": ["dev", "admin"] },
+            "other": [2, 1],
+        }}
+    );
+
+    let mut actual = json! {{ "tags": ["b", "a", "c"], "user": {}, "other": [] }};
+    align_arrays(
+        &mut actual,
+        &expected,
+        &paths,
+        false,
+        CompareMode::Strict,
+        &DefaultMatcher,
+    )?;
+    assert_eq!(
+        differences(&actual["tags"], &expected["tags"], CompareMode::Strict),
+        vec![Difference::Changed {
+            path: String::from("[2]"),
+            actual: json!("c"),
+            expected: json!("b"),
+        }]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn contains_arrays_permit_additional_elements() -> anyhow::Result<()> {
+    let paths: Vec<FieldPath> = vec!["tags".parse()?];
+    let expected = json! {{ "tags": ["parsed", "geoip"] }};
+
+    let mut actual = json! {{ "tags": ["beats", "geoip", "x", "parsed"] }};
+    align_arrays(
+        &mut actual,
+        &expected,
+        &paths,
+        true,
+        CompareMode::Strict,
+        &DefaultMatcher,
+    )?;
+    assert_eq!(actual, expected);
+
+    let mut actual = json! {{ "tags": ["beats", "geoip"] }};
+    align_arrays(
+        &mut actual,
+        &expected,
+        &paths,
+        true,
+        CompareMode::Strict,
+        &DefaultMatcher,
+    )?;
+    assert_eq!(
+        differences(&actual, &expected, CompareMode::Strict),
+        vec![Difference::Changed {
+            path: String::from("[tags][0]"),
+            actual: json!("beats"),
+            expected: json!("parsed"),
+        }]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn scoped_paths_are_compared_in_their_own_mode() -> anyhow::Result<()> {
+    let actual = json! {{
+        "event": { "kind": "event", "extra": 1, "original": { "a": 1, "b": 2 } },
+        "host": { "name": "web", "ip": "10.0.0.1" },
+        "extra": true,
+    }};
+    let expected = json! {{
+        "event": { "kind": "event", "original": { "a": 1 } },
+        "host": { "name": "web" },
+    }};
+    let scopes = BTreeMap::from([
+        ("event".parse()?, CompareMode::Strict),
+        ("[event][original]".parse()?, CompareMode::Inclusive),
+        ("absent".parse()?, CompareMode::Strict),
+    ]);
+
+    assert_eq!(
+        scoped_differences(
+            &DefaultMatcher,
+            &actual,
+            &expected,
+            CompareMode::Inclusive,
+            &scopes,
+        ),
+        vec![Difference::Unexpected {
+            path: String::from("[event][extra]"),
+            actual: json!(1),
+        }]
+    );
+
+    let scopes = BTreeMap::from([("host".parse()?, CompareMode::Inclusive)]);
+    assert_eq!(
+        scoped_differences(
+            &DefaultMatcher,
+            &actual,
+            &expected,
+            CompareMode::Strict,
+            &scopes
+        ),
+        vec![
+            Difference::Unexpected {
+                path: String::from("[event][original][b]"),
+                actual: json!(2),
+            },
+            Difference::Unexpected {
+                path: String::from("[event][extra]"),
+                actual: json!(1),
+            },
+            Difference::Unexpected {
+                path: String::from("[extra]"),
+                actual: json!(true),
+            },
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn normalizations_transform_strings_and_arrays() {
+    let mut value = json!(["  B ", "a"]);
+    Normalization::Trim.apply(&mut value);
+    assert_eq!(value, json!(["B", "a"]));
+    Normalization::Lowercase.apply(&mut value);
+    assert_eq!(value, json!(["b", "a"]));
+    Normalization::Sort.apply(&mut value);
+    assert_eq!(value, json!(["a", "b"]));
+
+    let mut value = json!("Web-01");
+    Normalization::Uppercase.apply(&mut value);
+    assert_eq!(value, json!("WEB-01"));
+
+    let mut value = json!(42);
+    Normalization::Lowercase.apply(&mut value);
+    assert_eq!(value, json!(42));
+}
+
+#[test]
+fn instants_are_parsed_regardless_of_their_format() {
+    let instant = parse_instant("2023-07-16T13:35:40.682Z");
+    assert_eq!(instant, Some(1_689_514_540_682_000_000));
+    assert_eq!(parse_instant("2023-07-16T13:35:40.682+00:00"), instant);
+    assert_eq!(parse_instant("2023-07-16T15:35:40.682000+02:00"), instant);
+    assert_eq!(parse_instant("2023-07-16 13:35:40.682z"), instant);
+    assert_eq!(parse_instant("1970-01-01T00:00:00Z"), Some(0));
+    assert_eq!(parse_instant("2023-07-16"), None);
+    assert_eq!(parse_instant("2023-13-16T13:35:40Z"), None);
+}
+
+#[test]
+fn equal_timestamps_are_canonicalized_within_the_tolerance() -> anyhow::Result<()> {
+    let paths: Vec<FieldPath> = vec!["@timestamp".parse()?, "event.created".parse()?];
+    let expected = json! {{
+        "@timestamp": "2023-07-16T13:35:40.682Z",
+        "event": { "created": "2023-07-16T13:35:40Z" },
+        "message": "2023-07-16T13:35:40Z",
+    }};
+
+    let mut actual = json! {{
+        "@timestamp": "2023-07-16T13:35:40.682+00:00",
+        "event": { "created": "2023-07-16T13:35:41.5Z" },
+        "message": "2023-07-16T13:35:40+00:00",
+    }};
+    canonicalize_timestamps(&mut actual, &expected, &paths, Duration::ZERO);
+    assert_eq!(actual["@timestamp"], expected["@timestamp"]);
+    assert_eq!(actual["event"]["created"], json!("2023-07-16T13:35:41.5Z"));
+    assert_eq!(actual["message"], json!("2023-07-16T13:35:40+00:00"));
+
+    canonicalize_timestamps(&mut actual, &expected, &paths, Duration::from_secs(2));
+    assert_eq!(actual["event"]["created"], expected["event"]["created"]);
+
+    Ok(())
+}
+
+#[test]
+fn numbers_are_compared_by_value() {
+    let actual: Value = serde_json::from_str(r#"{ "a": 1, "b": 1000, "c": 0.5, "d": 2 }"#).unwrap();
+    let expected: Value =
+        serde_json::from_str(r#"{ "a": 1.0, "b": 1e3, "c": 5e-1, "d": 2.5 }"#).unwrap();
+
+    assert_eq!(
+        differences(&actual, &expected, CompareMode::Strict),
+        vec![Difference::Changed {
+            path: String::from("[d]"),
+            actual: json!(2),
+            expected: json!(2.5),
+        }]
+    );
+    let number = |s: &str| s.parse::<serde_json::Number>().unwrap();
+    assert!(!numbers_equal(
+        &number("9007199254740993"),
+        &number("9007199254740992.0")
+    ));
+    assert!(numbers_equal(
+        &number("9007199254740992"),
+        &number("9007199254740992.0")
+    ));
+    assert!(numbers_equal(&number("-3"), &number("-3.0")));
+    assert!(!numbers_equal(&number("3"), &number("3.5")));
+    assert!(!numbers_equal(
+        &number("18446744073709551615"),
+        &number("1.8446744073709552e19")
+    ));
+}
+
+#[test]
+fn strings_encoding_equal_numbers_are_coerced() {
+    let mut actual =
+        json! {{ "port": "443", "bytes": 1024, "ratio": " 0.5 ", "id": "007x", "n": "8" }};
+    let expected = json! {{ "port": 443, "bytes": "1024.0", "ratio": 0.5, "id": 7, "n": 9 }};
+
+    coerce_numbers(&mut actual, &expected);
+    assert_eq!(
+        actual,
+        json! {{ "port": 443, "bytes": "1024.0", "ratio": 0.5, "id": "007x", "n": "8" }}
+    );
+}
+
+#[test]
+fn case_insensitive_fields_are_folded() -> anyhow::Result<()> {
+    let paths: Vec<FieldPath> = vec!["http.request.method".parse()?, "host.names".parse()?];
+    let mut actual = json! {{
+        "http": { "request": { "method": "get" } },
+        "host": { "names": ["WEB-01", "Db-01"] },
+        "message": "GET",
+    }};
+    let expected = json! {{
+        "http": { "request": { "method": "GET" } },
+        "host": { "names": ["web-01", "cache-01"] },
+        "message": "get",
+    }};
+
+    fold_case(&mut actual, &expected, &paths);
+    assert_eq!(
+        actual,
+        json! {{
+            "http": { "request": { "method": "GET" } },
+            "host": { "names": ["web-01", "Db-01"] },
+            "message": "GET",
+        }}
+    );
+
+    Ok(())
+}
+
+#[test]
+fn differences_are_located_by_path() {
+    let actual = json!({ "url": { "port": 443 }, "tags": ["a", "b"], "extra": true });
+    let expected = json!({ "url": { "port": 80 }, "tags": ["a"], "dummy": "true" });
+
+    assert_eq!(
+        differences(&actual, &expected, CompareMode::Strict),
+        [
+            Difference::Missing {
+                path: String::from("[dummy]"),
+                expected: json!("true"),
+            },
+            Difference::Unexpected {
+                path: String::from("[tags][1]"),
+                actual: json!("b"),
+            },
+            Difference::Changed {
+                path: String::from("[url][port]"),
+                actual: json!(443),
+                expected: json!(80),
+            },
+            Difference::Unexpected {
+                path: String::from("[extra]"),
+                actual: json!(true),
+            },
+        ]
+    );
+    assert_eq!(
+        differences(&actual, &expected, CompareMode::Inclusive),
+        [
+            Difference::Missing {
+                path: String::from("[dummy]"),
+                expected: json!("true"),
+            },
+            Difference::Changed {
+                path: String::from("[url][port]"),
+                actual: json!(443),
+                expected: json!(80),
+            },
+        ]
+    );
+    assert!(differences(&actual, &actual, CompareMode::Strict).is_empty());
+}
+
+#[test]
+fn side_by_side_aligns_differing_lines() {
+    console::set_colors_enabled(false);
+    let actual = json!({ "a": 1, "b": 2 });
+    let expected = json!({ "a": 1, "b": 3, "c": 4 });
+
+    assert_eq!(
+        side_by_side(&actual, &expected),
+        [
+            "actual      expected",
+            "{           {",
+            "  \"a\": 1,     \"a\": 1,",
+            "  \"b\": 2  |   \"b\": 3,",
+            "          >   \"c\": 4",
+            "}           }",
+        ]
+        .join("\n")
+    );
+}
+
+/// Only compares the `id` of events
+#[derive(Debug)]
+struct IdMatcher;
+
+impl Matcher for IdMatcher {
+    fn differences(&self, actual: &Value, expected: &Value, mode: CompareMode) -> Vec<Difference> {
+        differences(&actual["id"], &expected["id"], mode)
+    }
+}
+
+#[test]
+fn unordered_events_are_aligned_by_the_matcher() -> anyhow::Result<()> {
+    let mut actual = vec![
+        json! {{ "id": 2, "message": "b" }},
+        json! {{ "id": 1, "message": "a" }},
+    ];
+    let expected = [json! {{ "id": 1 }}, json! {{ "id": 2 }}];
+    align_unordered(&mut actual, &expected, CompareMode::Strict, &IdMatcher)?;
+    assert_eq!(actual[0]["message"], "a");
+    assert_eq!(actual[1]["message"], "b");
+
+    Ok(())
+}
+
+#[test]
+fn mismatch_detail_depends_on_verbosity() {
+    let actual = json! {{ "a": 1, "b": 2 }};
+    let expected = json! {{ "a": 1, "b": 3 }};
+    let mismatch = |verbosity| Mismatch {
+        differences: differences(&actual, &expected, CompareMode::Strict),
+        actual: actual.clone(),
+        expected: expected.clone(),
+        verbosity,
+        style: DiffStyle::Paths,
+    };
+
+    let quiet = console::strip_ansi_codes(&mismatch(Verbosity::Quiet).to_string()).into_owned();
+    assert!(!quiet.contains("[b]"), "{quiet}");
+    let normal = console::strip_ansi_codes(&mismatch(Verbosity::Normal).to_string()).into_owned();
+    assert!(
+        normal.contains("[b]") && !normal.contains("expected:"),
+        "{normal}"
+    );
+    let verbose = console::strip_ansi_codes(&mismatch(Verbosity::Verbose).to_string()).into_owned();
+    assert!(
+        verbose.contains("[b]") && verbose.contains("expected:"),
+        "{verbose}"
+    );
+}
+
+#[test]
+fn paths_only_mismatches_omit_the_documents() -> anyhow::Result<()> {
+    let actual = json! {{ "a": 1, "b": 2 }};
+    let expected = json! {{ "a": 1, "b": 3 }};
+    let mismatch = Mismatch {
+        differences: differences(&actual, &expected, CompareMode::Strict),
+        actual,
+        expected,
+        verbosity: Verbosity::Verbose,
+        style: DiffStyle::PathsOnly,
+    };
+
+    let verbose = console::strip_ansi_codes(&mismatch.to_string()).into_owned();
+    assert!(
+        verbose.contains("[b]") && !verbose.contains("expected:"),
+        "{verbose}"
+    );
+    assert_eq!(
+        serde_json::to_value(&mismatch)?,
+        json! {{ "differences": [
+            { "kind": "changed", "path": "[b]", "actual": 2, "expected": 3 },
+        ] }}
+    );
+
+    Ok(())
+}
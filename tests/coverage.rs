@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+
+use lotus::coverage::{instrument_rule, CoverageReport};
+use lotus::report::TestReport;
+use lotus::stats::PluginStats;
+
+const RULE: &str = r#"filter {
+    # Parse the access log
+    if [type] == "access" {
+        grok { match => { "message" => "%{COMMONAPACHELOG}" } }
+        if [response] =~ /^5\d\d/ {
+            mutate { add_tag => ["error"] }
+        } else if [response] == "404" {
+        } else {
+            mutate { add_field => { "[ok]" => "{}" } }
+        }
+    }
+}
+output {
+    if [type] == "x" { stdout { } }
+}
+"#;
+
+#[test]
+fn markers_are_injected_into_each_filter_section_and_branch() -> anyhow::Result<()> {
+    let (instrumented, points) = instrument_rule(RULE, 3, "10-access.conf")?;
+
+    let branches: Vec<_> = points
+        .iter()
+        .map(|p| (p.id.as_str(), p.line, p.branch.as_str()))
+        .collect();
+    assert_eq!(
+        branches,
+        vec![
+            ("lotus-coverage-3-0", 1, "filter"),
+            ("lotus-coverage-3-1", 3, r#"if [ type ] == "access""#),
+            ("lotus-coverage-3-2", 5, "if [ response ] =~ /^5\\d\\d/"),
+            ("lotus-coverage-3-3", 7, r#"else if [ response ] == "404""#),
+            ("lotus-coverage-3-4", 8, "else"),
+        ]
+    );
+
+    // The line numbers of the rule are kept, and plugins and hashes are left alone
+    assert_eq!(instrumented.lines().count(), RULE.lines().count());
+    assert!(instrumented.starts_with(r#"filter { mutate { id => "lotus-coverage-3-0" }"#));
+    assert!(instrumented
+        .contains(r#"} else if [response] == "404" { mutate { id => "lotus-coverage-3-3" }"#));
+    assert!(instrumented.contains(r#"add_field => { "[ok]" => "{}" } }"#));
+    assert!(instrumented.contains(r#"if [type] == "x" { stdout { } }"#));
+
+    assert!(instrument_rule("filter {", 0, "broken.conf").is_err());
+    Ok(())
+}
+
+#[test]
+fn untested_branches_are_reported() -> anyhow::Result<()> {
+    let (_, points) = instrument_rule(RULE, 0, "10-access.conf")?;
+    let counters = |counts: &[(&str, u64)]| -> BTreeMap<String, PluginStats> {
+        counts
+            .iter()
+            .map(|(id, events_in)| {
+                let stats = PluginStats {
+                    name: String::from("mutate"),
+                    events_in: *events_in,
+                    failures: 0,
+                };
+                (id.to_string(), stats)
+            })
+            .collect()
+    };
+    let before = counters(&[("lotus-coverage-0-0", 5), ("lotus-coverage-0-4", 1)]);
+    let after = counters(&[
+        ("lotus-coverage-0-0", 9),
+        ("lotus-coverage-0-1", 4),
+        ("lotus-coverage-0-2", 1),
+        ("lotus-coverage-0-4", 1),
+    ]);
+
+    let coverage = CoverageReport::new(&points, &before, &after);
+    let untested: Vec<_> = coverage.untested().map(|p| p.line).collect();
+    assert_eq!(untested, vec![7, 8]);
+    assert_eq!(
+        coverage.to_string(),
+        "coverage: 3 of 5 sections and branches exercised (60%)\n    \
+         untested  10-access.conf:7  else if [ response ] == \"404\"\n    \
+         untested  10-access.conf:8  else"
+    );
+
+    let report = TestReport {
+        coverage: Some(coverage),
+        ..Default::default()
+    };
+    assert!(report.to_string().contains("coverage: 3 of 5"));
+    let json = serde_json::to_value(&report)?;
+    assert_eq!(
+        json["coverage"]["points"][3]["branch"],
+        "else if [ response ] == \"404\""
+    );
+    assert_eq!(json["coverage"]["points"][0]["events"], 4);
+    Ok(())
+}
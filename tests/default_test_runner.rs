@@ -1,4 +1,4 @@
-use lotus::{default_runner, DefaultArguments};
+use lotus::{default_runner, DefaultArguments, Outcome};
 use serde_json::{json, to_writer};
 use std::fs::{create_dir, File};
 use std::io::Write;
@@ -44,7 +44,7 @@ async fn empty_json_objects() -> anyhow::Result<()> {
         no_delete_container: true,
         ..Default::default()
     };
-    default_runner(&args).await?;
+    assert_eq!(default_runner(&args).await?, Outcome::Success);
 
     Ok(())
 }
@@ -0,0 +1,78 @@
+use std::path::Path;
+use std::process::Command;
+
+use lotus::diff::{checkout_rules, describe_change, output_changes, OutputDiff};
+use serde_json::json;
+
+fn git(dir: &Path, args: &[&str]) -> anyhow::Result<()> {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args([
+            "-c",
+            "user.name=lotus",
+            "-c",
+            "user.email=lotus@example.com",
+        ])
+        .args(args)
+        .status()?;
+    anyhow::ensure!(status.success(), "git {args:?} failed");
+    Ok(())
+}
+
+#[test]
+fn the_rules_are_checked_out_at_a_git_revision() -> anyhow::Result<()> {
+    let repo = tempfile::tempdir()?;
+    let project = repo.path().join("project");
+    std::fs::create_dir_all(project.join("rules/nested"))?;
+    std::fs::write(project.join("rules/00-old.conf"), "filter { }")?;
+    std::fs::write(project.join("rules/nested/10-deep.conf"), "filter { }")?;
+    std::fs::write(project.join("rules/README.md"), "not a rule")?;
+    git(repo.path(), &["init", "-q"])?;
+    git(repo.path(), &["add", "-A"])?;
+    git(repo.path(), &["commit", "-q", "-m", "rules"])?;
+    std::fs::write(project.join("rules/00-old.conf"), "filter { mutate { } }")?;
+    std::fs::write(project.join("rules/20-new.conf"), "filter { }")?;
+
+    let dest = tempfile::tempdir()?;
+    let rules = checkout_rules(&project, "rules", "HEAD", dest.path())?;
+    let names: Vec<_> = rules
+        .iter()
+        .map(|r| r.strip_prefix(dest.path()).unwrap().display().to_string())
+        .collect();
+    assert_eq!(names, vec!["00-old.conf"]);
+    assert_eq!(
+        std::fs::read_to_string(dest.path().join("00-old.conf"))?,
+        "filter { }"
+    );
+
+    let dest = tempfile::tempdir()?;
+    assert!(checkout_rules(&project, "rules", "no-such-ref", dest.path()).is_err());
+    Ok(())
+}
+
+#[test]
+fn changes_lead_from_the_git_revision_to_the_working_tree() {
+    let changes = output_changes(
+        vec![json!({ "status": 200, "verb": "GET" })],
+        vec![json!({ "status": "200", "user": "alice" })],
+    );
+    let described: Vec<_> = changes.iter().map(describe_change).collect();
+    assert_eq!(
+        described,
+        vec![
+            r#"~ [0][status] changed from 200 to "200""#,
+            r#"- [0][verb] removed, was "GET""#,
+            r#"+ [0][user] added: "alice""#,
+        ]
+    );
+
+    let diff = OutputDiff {
+        title: String::from("access/get"),
+        changes: Ok(changes),
+    };
+    assert!(diff
+        .to_string()
+        .starts_with("test access/get: 3 change(s)\n    ~ [0][status]"));
+    assert!(output_changes(vec![json!({ "a": 1 })], vec![json!({ "a": 1 })]).is_empty());
+}
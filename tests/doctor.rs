@@ -0,0 +1,32 @@
+use std::net::TcpListener;
+
+use lotus::doctor::{check_docker_version, check_jq, check_port};
+
+#[test]
+fn old_docker_versions_are_diagnosed() {
+    assert!(check_docker_version("24.0.7", "1.43").is_ok());
+    assert!(check_docker_version("20.10.0", "1.41").is_ok());
+
+    let diagnosis = check_docker_version("19.03.15", "1.40");
+    assert!(!diagnosis.is_ok());
+    assert!(diagnosis.remedy.unwrap().contains("1.41"));
+}
+
+#[test]
+fn bound_ports_are_diagnosed() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+
+    let diagnosis = check_port(port);
+    assert!(!diagnosis.is_ok());
+    assert!(diagnosis.to_string().contains("hint:"));
+
+    drop(listener);
+    assert!(check_port(port).is_ok());
+    Ok(())
+}
+
+#[test]
+fn jq_is_only_diagnosed_if_required() {
+    assert!(check_jq(false).is_ok());
+}
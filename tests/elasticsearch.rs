@@ -0,0 +1,114 @@
+use lotus::elasticsearch::{bulk_operations, BulkOperation};
+use lotus::server::{bind_server, run_server, EventRouter};
+use serde_json::json;
+
+#[test]
+fn bulk_requests_are_parsed_into_documents_with_their_target() -> anyhow::Result<()> {
+    let body = [
+        json!({ "index": { "_index": "logs-2024", "pipeline": "geoip", "routing": null } }),
+        json!({ "message": "a", "_lotus_test_id": "0-0" }),
+        json!({ "create": { "_index": "logs-nginx.access-default" } }),
+        json!({ "message": "b" }),
+        json!({ "update": { "_index": "hosts", "_id": "h1" } }),
+        json!({ "doc": { "host": "h1", "_lotus_test_id": "1-0" }, "doc_as_upsert": true }),
+        json!({ "delete": { "_index": "hosts", "_id": "h2" } }),
+    ]
+    .iter()
+    .map(|l| format!("{l}\n"))
+    .collect::<String>();
+
+    assert_eq!(
+        bulk_operations(&body)?,
+        vec![
+            BulkOperation {
+                test_id: Some(String::from("0-0")),
+                action: String::from("index"),
+                event: json!({
+                    "message": "a",
+                    "@lotus_elasticsearch": { "action": "index", "index": "logs-2024", "pipeline": "geoip" },
+                }),
+            },
+            BulkOperation {
+                test_id: None,
+                action: String::from("create"),
+                event: json!({
+                    "message": "b",
+                    "@lotus_elasticsearch": { "action": "create", "index": "logs-nginx.access-default" },
+                }),
+            },
+            BulkOperation {
+                test_id: Some(String::from("1-0")),
+                action: String::from("update"),
+                event: json!({
+                    "host": "h1",
+                    "@lotus_elasticsearch": { "action": "update", "index": "hosts", "id": "h1" },
+                }),
+            },
+            BulkOperation {
+                test_id: None,
+                action: String::from("delete"),
+                event: json!({
+                    "@lotus_elasticsearch": { "action": "delete", "index": "hosts", "id": "h2" },
+                }),
+            },
+        ]
+    );
+    assert!(bulk_operations(r#"{"index":{}}"#).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn the_server_mocks_an_elasticsearch_cluster() -> anyhow::Result<()> {
+    let router = EventRouter::default();
+    let mut events = router.register("3-0");
+    let listener = bind_server(0)?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(run_server(router, listener));
+
+    let client = reqwest::Client::new();
+    let response = client
+        .get(format!("http://127.0.0.1:{port}/_elasticsearch/"))
+        .send()
+        .await?;
+    assert_eq!(
+        response.headers()["X-Elastic-Product"],
+        "Elasticsearch",
+        "{response:?}"
+    );
+    let info: serde_json::Value = response.json().await?;
+    assert!(info["version"]["number"].is_string(), "{info}");
+    let status = client
+        .head(format!(
+            "http://127.0.0.1:{port}/_elasticsearch/_index_template/ecs-logstash"
+        ))
+        .send()
+        .await?
+        .status();
+    assert!(status.is_success(), "{status}");
+
+    let response: serde_json::Value = client
+        .post(format!("http://127.0.0.1:{port}/_elasticsearch/_bulk"))
+        .header("Content-Type", "application/x-ndjson")
+        .body(concat!(
+            r#"{"index":{"_index":"logs"}}"#,
+            "\n",
+            r#"{"message":"a","_lotus_test_id":"3-0"}"#,
+            "\n"
+        ))
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(response["errors"], json!(false));
+    assert_eq!(response["items"][0]["index"]["status"], json!(201));
+    assert_eq!(
+        events.recv().await,
+        Some(json!({
+            "message": "a",
+            "@lotus_elasticsearch": { "action": "index", "index": "logs" },
+        }))
+    );
+
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use lotus::report::{TestResult, TestStatus};
+use lotus::runner::blame_stray_events;
+use lotus::server::{bind_server, run_server, EventRouter, StrayEvent};
+use serde_json::json;
+
+#[tokio::test]
+async fn events_are_routed_by_correlation_id() -> anyhow::Result<()> {
+    let router = EventRouter::default();
+    let mut a = router.register("0");
+
+    assert!(router.route(None, json!({ "n": 1 })).await);
+    assert_eq!(a.recv().await, Some(json!({ "n": 1 })));
+
+    let mut b = router.register("1");
+    assert!(router.route(Some("1"), json!({ "n": 2 })).await);
+    assert!(router.route(Some("0"), json!({ "n": 3 })).await);
+    assert_eq!(b.recv().await, Some(json!({ "n": 2 })));
+    assert_eq!(a.recv().await, Some(json!({ "n": 3 })));
+
+    assert!(!router.route(None, json!({ "n": 4 })).await);
+    router.unregister("1");
+    assert!(!router.route(Some("1"), json!({ "n": 5 })).await);
+
+    assert_eq!(
+        router.take_strays(),
+        [
+            StrayEvent {
+                test_id: None,
+                event: json!({ "n": 4 })
+            },
+            StrayEvent {
+                test_id: Some(String::from("1")),
+                event: json!({ "n": 5 })
+            },
+        ]
+    );
+    assert!(router.take_strays().is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_cases_are_blamed_for_events_that_arrive_after_they_finished() {
+    let result = |name: &str, status, retries| TestResult {
+        name: name.to_string(),
+        title: name.to_string(),
+        description: None,
+        status,
+        retries,
+        duration: Duration::ZERO,
+        error: None,
+        mismatch: None,
+    };
+    let mut results = vec![
+        result("leaky", TestStatus::Passed, 0),
+        result("retried", TestStatus::Passed, 1),
+        result("pending", TestStatus::Skipped, 0),
+    ];
+    let stray = |test_id: &str| StrayEvent {
+        test_id: Some(test_id.to_string()),
+        event: json!({ "message": test_id }),
+    };
+
+    let mut strays = vec![
+        stray("0-0"),
+        stray("1-0"),
+        stray("2-0"),
+        stray("lotus-hook-before_each"),
+    ];
+    let blamed = blame_stray_events(&mut results, &[true, true, false], &mut strays);
+    assert_eq!(blamed, [0]);
+    assert_eq!(strays, [stray("2-0")]);
+    assert_eq!(results[0].status, TestStatus::Failed);
+    let error = results[0].error.as_deref().unwrap_or_default();
+    assert!(
+        error.contains("test case leaky") && error.contains(r#"{"message":"0-0"}"#),
+        "{error}"
+    );
+    assert_eq!(results[1].status, TestStatus::Passed);
+    assert_eq!(results[2].status, TestStatus::Skipped);
+}
+
+#[test]
+fn stray_events_are_blamed_once_the_result_of_their_test_case_is_recorded() {
+    let result = |name: &str, status| TestResult {
+        name: name.to_string(),
+        title: name.to_string(),
+        description: None,
+        status,
+        retries: 0,
+        duration: Duration::ZERO,
+        error: None,
+        mismatch: None,
+    };
+    // With several jobs, test case 1 finishes and leaks an event while test case 0 is running
+    let mut results = vec![
+        result("slow", TestStatus::Skipped),
+        result("leaky", TestStatus::Skipped),
+    ];
+    let mut strays = vec![StrayEvent {
+        test_id: Some(String::from("1-0")),
+        event: json!({ "message": "late" }),
+    }];
+
+    results[0].status = TestStatus::Passed;
+    assert!(blame_stray_events(&mut results, &[true, false], &mut strays).is_empty());
+    assert_eq!(strays.len(), 1);
+
+    results[1].status = TestStatus::Passed;
+    assert_eq!(
+        blame_stray_events(&mut results, &[true, true], &mut strays),
+        [1]
+    );
+    assert!(strays.is_empty());
+    assert_eq!(results[0].status, TestStatus::Passed);
+    assert_eq!(results[1].status, TestStatus::Failed);
+}
+
+#[test]
+fn the_server_binds_to_a_free_port_if_given_none() -> anyhow::Result<()> {
+    let listener = bind_server(0)?;
+    assert_ne!(listener.local_addr()?.port(), 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn events_are_attributed_to_the_output_channel_they_arrived_on() -> anyhow::Result<()> {
+    let router = EventRouter::default();
+    let mut events = router.register("7");
+    let listener = bind_server(0)?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(run_server(router, listener));
+
+    let client = reqwest::Client::new();
+    for path in ["7", "outputs/errors/7", "outputs/errors/"] {
+        let status = client
+            .post(format!("http://127.0.0.1:{port}/{path}"))
+            .json(&json!({ "n": 1 }))
+            .send()
+            .await?
+            .status();
+        assert_eq!(status, reqwest::StatusCode::NO_CONTENT, "{path}");
+    }
+
+    assert_eq!(events.recv().await, Some(json!({ "n": 1 })));
+    for _ in 0..2 {
+        assert_eq!(
+            events.recv().await,
+            Some(json!({ "n": 1, "@lotus_output": "errors" }))
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn captured_metadata_is_restored_as_such() -> anyhow::Result<()> {
+    let router = EventRouter::default();
+    let mut events = router.register("8");
+    let listener = bind_server(0)?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(run_server(router, listener));
+
+    reqwest::Client::new()
+        .post(format!("http://127.0.0.1:{port}/8"))
+        .json(&json!({ "n": 1, "@lotus_metadata": { "index": "logs-web" } }))
+        .send()
+        .await?
+        .error_for_status()?;
+    assert_eq!(
+        events.recv().await,
+        Some(json!({ "n": 1, "@metadata": { "index": "logs-web" } }))
+    );
+
+    Ok(())
+}
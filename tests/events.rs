@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+use lotus::events::LifecycleEvent;
+use lotus::report::{TestResult, TestStatus};
+
+#[test]
+fn lifecycle_events_are_tagged_json_objects() -> anyhow::Result<()> {
+    let event = serde_json::to_value(LifecycleEvent::SuiteStarted { tests: 3 })?;
+    assert_eq!(
+        event,
+        serde_json::json!({ "event": "suite_started", "tests": 3 })
+    );
+
+    let result = TestResult {
+        name: String::from("apache/access"),
+        title: String::from("apache/access"),
+        description: None,
+        status: TestStatus::Failed,
+        retries: 1,
+        duration: Duration::from_millis(1500),
+        error: Some(format!("{}", console::style("mismatch").red())),
+        mismatch: None,
+    };
+    let event = serde_json::to_value(LifecycleEvent::TestFinished { result: &result })?;
+    assert_eq!(event["event"], "test_finished");
+    assert_eq!(event["name"], "apache/access");
+    assert_eq!(event["status"], "failed");
+    assert_eq!(event["duration_ms"], 1500);
+    assert_eq!(event["error"], "mismatch");
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use lotus::paths::FieldPath;
+use serde_json::json;
+
+#[test]
+fn dotted_and_bracketed_paths_are_equivalent() -> anyhow::Result<()> {
+    let dotted: FieldPath = "host.ip".parse()?;
+    let bracketed: FieldPath = "[host][ip]".parse()?;
+    assert_eq!(dotted, bracketed);
+    assert_eq!(dotted.to_string(), "[host][ip]");
+
+    assert!("host..ip".parse::<FieldPath>().is_err());
+    assert!("[host][ip".parse::<FieldPath>().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn remove_strips_nested_fields() -> anyhow::Result<()> {
+    let mut event = json! {{
+        "@timestamp": "2023-07-16T13:35:40.682Z",
+        "host": { "ip": "10.0.0.1", "name": "web-01" },
+        "tags": ["a", "b"],
+    }};
+
+    let timestamp: FieldPath = "@timestamp".parse()?;
+    let host_ip: FieldPath = "host.ip".parse()?;
+    let second_tag: FieldPath = "tags.1".parse()?;
+    let missing: FieldPath = "event.original".parse()?;
+
+    assert_eq!(
+        timestamp.remove(&mut event),
+        Some(json!("2023-07-16T13:35:40.682Z"))
+    );
+    assert_eq!(host_ip.remove(&mut event), Some(json!("10.0.0.1")));
+    assert_eq!(second_tag.remove(&mut event), Some(json!("b")));
+    assert_eq!(missing.remove(&mut event), None);
+
+    assert_eq!(
+        event,
+        json! {{
+            "host": { "name": "web-01" },
+            "tags": ["a"],
+        }}
+    );
+
+    Ok(())
+}
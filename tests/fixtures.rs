@@ -0,0 +1,28 @@
+use lotus::fixtures::Fixtures;
+use std::fs::{create_dir, File};
+use std::io::Write;
+
+#[test]
+fn placeholders_are_replaced_with_shared_variables() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    create_dir(tmp_dir.path().join("fixtures"))?;
+    File::create(tmp_dir.path().join("fixtures/vars.toml"))?
+        .write_all(b"hostname = \"web-01\"\n\n[client]\nip = \"10.0.0.1\"\n")?;
+
+    let fixtures = Fixtures::load(tmp_dir.path())?;
+    assert_eq!(
+        fixtures.render(r#"{"host": "{{ hostname }}", "ip": "{{ client.ip }}", "q": "a&b"}"#)?,
+        r#"{"host": "web-01", "ip": "10.0.0.1", "q": "a&b"}"#
+    );
+
+    let mut event = serde_json::json!({ "message": ["{{ hostname }}"], "count": 1 });
+    fixtures.render_value(&mut event)?;
+    assert_eq!(
+        event,
+        serde_json::json!({ "message": ["web-01"], "count": 1 })
+    );
+
+    assert!(fixtures.render("{{ unknown }}").is_err());
+
+    Ok(())
+}
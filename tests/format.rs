@@ -0,0 +1,65 @@
+use lotus::format::format_rule;
+
+#[test]
+fn rules_are_formatted_canonically() {
+    let rule = r#"filter
+{
+  # Parse the access log
+  grok { match => { 'message' => '%{IPORHOST:[source][ip]} %{GREEDYDATA:rest}' } }
+
+
+  if [source][ip] =~ /^10\./ and ! [tags] {
+      mutate { add_tag => [ 'internal' , "private" ] }   # trailing comment
+  }
+  else if "vpn" in [tags] { mutate { replace => { "[network]" => 'say "vpn"' } } }
+  else { drop { } }
+}
+"#;
+    let expected = r#"filter {
+    # Parse the access log
+    grok {
+        match => {
+            "message" => "%{IPORHOST:[source][ip]} %{GREEDYDATA:rest}"
+        }
+    }
+
+    if [source][ip] =~ /^10\./ and ![tags] {
+        mutate {
+            add_tag => ["internal", "private"]
+        } # trailing comment
+    } else if "vpn" in [tags] {
+        mutate {
+            replace => {
+                "[network]" => 'say "vpn"'
+            }
+        }
+    } else {
+        drop {}
+    }
+}
+"#;
+
+    assert_eq!(format_rule(rule).unwrap(), expected);
+}
+
+#[test]
+fn formatting_is_idempotent() {
+    let rule = r#"filter {
+  mutate {
+    add_field => { "[a]" => "b" }
+    remove_field => [
+      "x",
+      "y"
+    ]
+  }
+  if ("x" in [tags]) { drop {} }
+}"#;
+    let formatted = format_rule(rule).unwrap();
+
+    assert_eq!(format_rule(&formatted).unwrap(), formatted);
+}
+
+#[test]
+fn malformed_rules_are_not_formatted() {
+    assert!(format_rule("filter { mutate { ").is_err());
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use lotus::grok::{
+    expand_pattern, explain_grok_failures, explain_mismatch, grok_filters, pattern_definitions,
+};
+use serde_json::json;
+
+fn definitions() -> HashMap<String, String> {
+    pattern_definitions(
+        r#"
+# Core patterns
+WORD \b\w+\b
+INT (?:[+-]?(?:[0-9]+))
+IPV4 (?<![0-9])(?:(?:[0-1]?[0-9]{1,2}|2[0-4][0-9]|25[0-5])[.]){3}(?:[0-1]?[0-9]{1,2}|2[0-4][0-9]|25[0-5])(?![0-9])
+IP %{IPV4}
+"#,
+    )
+}
+
+#[test]
+fn grok_filters_are_collected_from_the_rules() {
+    let filters = grok_filters(
+        r#"
+filter {
+    if [type] == "access" {
+        grok {
+            match => { "message" => ["%{IP:client} %{WORD:method}", "%{WORD:method}"] }
+            pattern_definitions => { "METHOD" => "GET|POST" }
+        }
+    }
+    grok { match => ["[event][original]", "%{INT:code}"] }
+}
+"#,
+    );
+
+    assert_eq!(filters.len(), 2);
+    assert_eq!(
+        filters[0].matches,
+        vec![(
+            String::from("message"),
+            vec![
+                String::from("%{IP:client} %{WORD:method}"),
+                String::from("%{WORD:method}")
+            ]
+        )]
+    );
+    assert_eq!(filters[0].definitions["METHOD"], "GET|POST");
+    assert_eq!(
+        filters[1].matches,
+        vec![(
+            String::from("[event][original]"),
+            vec![String::from("%{INT:code}")]
+        )]
+    );
+}
+
+#[test]
+fn pattern_references_are_expanded() -> anyhow::Result<()> {
+    let expanded = expand_pattern("%{INT:code:int} (?<rest>.*)", &definitions())?;
+    assert_eq!(expanded, "(?:(?:[+-]?(?:[0-9]+))) (?:.*)");
+    assert!(expand_pattern("%{MISSING}", &definitions()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn the_part_of_the_pattern_where_the_match_stopped_is_reported() {
+    let definitions = definitions();
+    assert_eq!(
+        explain_mismatch(
+            "%{IP:client} %{INT:status} %{WORD:method}",
+            &definitions,
+            "10.0.0.1 200 /index"
+        ),
+        r#""%{IP:client} %{INT:status} " matched "10.0.0.1 200 ", but "%{WORD:method}" does not match what follows: "/index""#
+    );
+    assert_eq!(
+        explain_mismatch("%{INT:status}", &definitions, "ok"),
+        r#""%{INT:status}" does not match anywhere"#
+    );
+    assert_eq!(
+        explain_mismatch("%{UNKNOWN}", &definitions, "ok"),
+        "The grok pattern 'UNKNOWN' is not defined"
+    );
+}
+
+#[test]
+fn only_events_tagged_with_a_grok_failure_are_explained() {
+    let filters = grok_filters(r#"filter { grok { match => { "message" => "%{INT:status}" } } }"#);
+    let definitions = definitions();
+
+    assert_eq!(
+        explain_grok_failures(&json!({ "message": "ok" }), &filters, &definitions),
+        None
+    );
+    let events = json!([
+        { "message": "200" },
+        { "message": "ok", "tags": ["_grokparsefailure"] }
+    ]);
+    assert_eq!(
+        explain_grok_failures(&events, &filters, &definitions).as_deref(),
+        Some(
+            "Output event 1 is tagged with _grokparsefailure:\n  [message] \"%{INT:status}\": \"%{INT:status}\" does not match anywhere"
+        )
+    );
+}
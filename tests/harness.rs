@@ -0,0 +1,37 @@
+use lotus::Harness;
+
+#[tokio::test]
+async fn the_harness_loads_the_project_from_the_given_directories() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+
+    let error = Harness::new()
+        .target(tmp_dir.path())
+        .run()
+        .await
+        .expect_err("the project has no rules");
+    let rules_dir = tmp_dir.path().join("rules");
+    assert!(
+        format!("{error:#}").contains(&rules_dir.display().to_string()),
+        "{error:#}"
+    );
+
+    std::fs::create_dir_all(tmp_dir.path().join("logstash/rules"))?;
+    std::fs::write(
+        tmp_dir.path().join("logstash/rules/00-dummy.conf"),
+        "filter { }",
+    )?;
+    std::fs::create_dir_all(tmp_dir.path().join("logstash/cases"))?;
+    let error = Harness::new()
+        .target(tmp_dir.path())
+        .rules_dir("logstash/rules")
+        .tests_dir("logstash/cases")
+        .run()
+        .await
+        .expect_err("the project has no test cases");
+    assert!(
+        format!("{error:#}").contains("No test cases were found"),
+        "{error:#}"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use lotus::config::ProjectConfig;
+use lotus::hooks::{run_command, Hook, HookKind};
+use serde_json::json;
+
+#[test]
+fn hooks_are_read_from_the_project_configuration() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    std::fs::write(
+        tmp_dir.path().join("lotus.toml"),
+        r#"
+[[hooks.before_all]]
+command = "./stubs/start.sh"
+
+[[hooks.before_each]]
+events = [{ type = "reset" }]
+"#,
+    )?;
+
+    let config = ProjectConfig::load(tmp_dir.path())?;
+    assert_eq!(
+        config.hooks.before_all,
+        [Hook::Command {
+            command: String::from("./stubs/start.sh")
+        }]
+    );
+    assert_eq!(
+        config.hooks.before_each,
+        [Hook::Events {
+            events: vec![json!({ "type": "reset" })]
+        }]
+    );
+    assert!(config.hooks.after_each.is_empty() && config.hooks.after_all.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn hook_commands_run_in_the_project_directory() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+
+    run_command(
+        "echo \"$LOTUS_HOOK $LOTUS_TEST_NAME\" > hook.txt",
+        tmp_dir.path(),
+        HookKind::BeforeEach,
+        Some("nginx/access"),
+    )
+    .await?;
+    assert_eq!(
+        std::fs::read_to_string(tmp_dir.path().join("hook.txt"))?,
+        "before_each nginx/access\n"
+    );
+
+    let error = run_command(
+        "echo broken >&2; exit 3",
+        tmp_dir.path(),
+        HookKind::AfterAll,
+        None,
+    )
+    .await
+    .expect_err("the command fails");
+    assert!(format!("{error:#}").contains("broken"), "{error:#}");
+
+    Ok(())
+}
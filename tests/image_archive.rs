@@ -0,0 +1,601 @@
+use lotus::config::{ElasticsearchOutput, FileOutput, OutputChannel};
+use lotus::docker::{
+    bind_mounts, build_image_archive, build_pipeline_archive, container_hash, content_hash,
+    has_digest, host_port, is_platform, logstash_errors, BuiltImage, Connection, ContainerEnv,
+    ImageOptions, InputProtocol, Ports, ResourceLimits, SyslogFormat, WarmContainer,
+};
+use std::fs::{read_to_string, File};
+use std::io::Write;
+use std::num::NonZeroUsize;
+
+#[test]
+fn pipeline_contains_rules_and_codecs() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let rule = tmp_dir.path().join("00-dummy.conf");
+    File::create(&rule)?
+        .write_all(br#"filter { mutate { add_field => { "[dummy]" => "true" } } }"#)?;
+
+    let cache_dir = tmp_dir.path().join("cache");
+    std::fs::create_dir(&cache_dir)?;
+    let options = ImageOptions {
+        codecs: vec![String::from("line"), String::from("plain")],
+        logstash_version: String::from("7.17.16"),
+        custom_base_image: None,
+        base_image_digest: None,
+        platform: None,
+        offline: false,
+        coverage: false,
+        capture_metadata: false,
+        clock: None,
+        plugins: vec![String::from("logstash-filter-tld")],
+        outputs: vec![OutputChannel {
+            name: String::from("errors"),
+            condition: String::from(r#""error" in [tags]"#),
+        }],
+        callback_host: String::from("lotus.example.com"),
+        input_protocol: InputProtocol::Http,
+        socket_codec: None,
+        syslog_format: SyslogFormat::Rfc3164,
+        workers: None,
+        elasticsearch: None,
+        file_output: None,
+    };
+    let archive = build_image_archive(&cache_dir, &[rule], &[], &[], &options)?;
+    assert!(archive.is_file());
+
+    let dockerfile = read_to_string(cache_dir.join("Dockerfile"))?;
+    assert!(
+        dockerfile.starts_with("FROM docker.elastic.co/logstash/logstash:7.17.16\n"),
+        "{dockerfile}"
+    );
+    assert!(
+        dockerfile.contains("\nRUN bin/logstash-plugin install logstash-filter-tld\n"),
+        "{dockerfile}"
+    );
+
+    let pipeline = read_to_string(cache_dir.join("logstash.conf"))?;
+    assert!(pipeline.contains(r#""[dummy]" => "true""#), "{pipeline}");
+    assert!(
+        pipeline.contains(r#""text/x-lotus-line" => "line""#),
+        "{pipeline}"
+    );
+    assert!(
+        pipeline.contains(r#""text/x-lotus-plain" => "plain""#),
+        "{pipeline}"
+    );
+    assert!(
+        pipeline.contains("[headers][x_lotus_test_id]"),
+        "{pipeline}"
+    );
+    assert!(
+        pipeline.contains("http://lotus.example.com:${LOTUS_OUTPUT_PORT}/"),
+        "{pipeline}"
+    );
+    assert!(
+        pipeline.contains(r#"if [@metadata][lotus_output] == "" and ("error" in [tags]) {"#),
+        "{pipeline}"
+    );
+    assert!(
+        pipeline.contains(r#""[@metadata][lotus_output]" => "outputs/errors/""#),
+        "{pipeline}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn the_pipeline_receives_beats_events_with_the_beats_protocol() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let rule = tmp_dir.path().join("00-dummy.conf");
+    File::create(&rule)?.write_all(b"filter { }")?;
+
+    let options = ImageOptions {
+        input_protocol: InputProtocol::Beats,
+        ..Default::default()
+    };
+    build_image_archive(tmp_dir.path(), &[rule], &[], &[], &options)?;
+
+    let pipeline = read_to_string(tmp_dir.path().join("logstash.conf"))?;
+    assert!(pipeline.contains("    beats {"), "{pipeline}");
+    assert!(!pipeline.contains("response_code"), "{pipeline}");
+    assert!(!pipeline.contains("[headers]"), "{pipeline}");
+
+    Ok(())
+}
+
+#[test]
+fn the_pipeline_receives_json_lines_with_the_tcp_protocol() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let rules = [tmp_dir.path().join("00-dummy.conf")];
+    File::create(&rules[0])?.write_all(b"filter { }")?;
+
+    let options = ImageOptions {
+        input_protocol: InputProtocol::Tcp,
+        ..Default::default()
+    };
+    build_image_archive(tmp_dir.path(), &rules, &[], &[], &options)?;
+    let pipeline = read_to_string(tmp_dir.path().join("logstash.conf"))?;
+    assert!(pipeline.contains("    tcp {"), "{pipeline}");
+    assert!(pipeline.contains("codec => json_lines"), "{pipeline}");
+    assert!(pipeline.contains(r#""[port]""#), "{pipeline}");
+
+    let options = ImageOptions {
+        input_protocol: InputProtocol::Udp,
+        socket_codec: Some(String::from("json_lines")),
+        ..Default::default()
+    };
+    build_image_archive(tmp_dir.path(), &rules, &[], &[], &options)?;
+    let pipeline = read_to_string(tmp_dir.path().join("logstash.conf"))?;
+    assert!(pipeline.contains("    udp {"), "{pipeline}");
+    assert!(pipeline.contains("codec => json_lines"), "{pipeline}");
+    let dockerfile = read_to_string(tmp_dir.path().join("Dockerfile"))?;
+    assert!(dockerfile.contains("5066/udp"), "{dockerfile}");
+
+    Ok(())
+}
+
+#[test]
+fn the_syslog_input_extracts_the_correlation_id_from_the_frames() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let rules = [tmp_dir.path().join("00-dummy.conf")];
+    File::create(&rules[0])?.write_all(b"filter { }")?;
+
+    let options = ImageOptions {
+        input_protocol: InputProtocol::SyslogUdp,
+        syslog_format: SyslogFormat::Rfc5424,
+        ..Default::default()
+    };
+    build_image_archive(tmp_dir.path(), &rules, &[], &[], &options)?;
+    let pipeline = read_to_string(tmp_dir.path().join("logstash.conf"))?;
+    assert!(pipeline.contains("    syslog {"), "{pipeline}");
+    assert!(
+        pipeline.contains(
+            r#"grok_pattern => "%{NOTSPACE:[@metadata][lotus_test_id]} %{SYSLOG5424LINE}""#
+        ),
+        "{pipeline}"
+    );
+    assert!(!pipeline.contains("codec => json"), "{pipeline}");
+    let dockerfile = read_to_string(tmp_dir.path().join("Dockerfile"))?;
+    assert!(dockerfile.contains("5066/udp"), "{dockerfile}");
+
+    let options = ImageOptions {
+        input_protocol: InputProtocol::SyslogTcp,
+        ..Default::default()
+    };
+    build_image_archive(tmp_dir.path(), &rules, &[], &[], &options)?;
+    let pipeline = read_to_string(tmp_dir.path().join("logstash.conf"))?;
+    assert!(
+        pipeline.contains("<%{POSINT:[log][syslog][priority]:int}>%{SYSLOGLINE}"),
+        "{pipeline}"
+    );
+    let dockerfile = read_to_string(tmp_dir.path().join("Dockerfile"))?;
+    assert!(dockerfile.contains("5066/tcp"), "{dockerfile}");
+
+    Ok(())
+}
+
+#[test]
+fn the_pipeline_writes_to_the_mock_elasticsearch_cluster() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let rules = [tmp_dir.path().join("00-dummy.conf")];
+    File::create(&rules[0])?.write_all(b"filter { }")?;
+
+    let options = ImageOptions {
+        elasticsearch: Some(ElasticsearchOutput {
+            data_stream: true,
+            data_stream_dataset: Some(String::from("nginx.access")),
+            pipeline: Some(String::from("%{[@metadata][pipeline]}")),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    build_image_archive(tmp_dir.path(), &rules, &[], &[], &options)?;
+    let pipeline = read_to_string(tmp_dir.path().join("logstash.conf"))?;
+    assert!(
+        pipeline.contains(
+            r#"hosts => ["http://host.docker.internal:${LOTUS_OUTPUT_PORT}/_elasticsearch"]"#
+        ),
+        "{pipeline}"
+    );
+    assert!(
+        pipeline.contains(r#"data_stream_dataset => "nginx.access""#),
+        "{pipeline}"
+    );
+    assert!(
+        pipeline.contains(r#"pipeline => "%{[@metadata][pipeline]}""#),
+        "{pipeline}"
+    );
+    assert!(
+        pipeline.contains(r#""[@metadata][lotus_test_id]" => "[_lotus_test_id]""#),
+        "{pipeline}"
+    );
+    assert!(!pipeline.contains("ilm_enabled"), "{pipeline}");
+    assert!(!pipeline.contains("http_method"), "{pipeline}");
+
+    Ok(())
+}
+
+#[test]
+fn the_file_output_writes_to_the_capture_directory() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let rules = [tmp_dir.path().join("00-dummy.conf")];
+    File::create(&rules[0])?.write_all(b"filter { }")?;
+
+    let options = ImageOptions {
+        file_output: Some(FileOutput {
+            path: String::from("%{[service]}/out.log"),
+            codec: Some(String::from(r#"line { format => "%{message}" }"#)),
+        }),
+        ..Default::default()
+    };
+    build_image_archive(tmp_dir.path(), &rules, &[], &[], &options)?;
+    let pipeline = read_to_string(tmp_dir.path().join("logstash.conf"))?;
+    assert!(
+        pipeline.contains(
+            r#"path => "/usr/share/logstash/lotus-files/%{[@metadata][lotus_test_id]}/%{[service]}/out.log""#
+        ),
+        "{pipeline}"
+    );
+    assert!(
+        pipeline.contains(r#"codec => line { format => "%{message}" }"#),
+        "{pipeline}"
+    );
+    assert!(pipeline.contains("http_method"), "{pipeline}");
+
+    Ok(())
+}
+
+#[test]
+fn the_pipeline_sends_the_metadata_along_if_captured() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let rules = [tmp_dir.path().join("00-dummy.conf")];
+    File::create(&rules[0])?.write_all(b"filter { }")?;
+
+    build_image_archive(tmp_dir.path(), &rules, &[], &[], &ImageOptions::default())?;
+    let pipeline = read_to_string(tmp_dir.path().join("logstash.conf"))?;
+    assert!(!pipeline.contains("@lotus_metadata"), "{pipeline}");
+
+    let options = ImageOptions {
+        capture_metadata: true,
+        ..Default::default()
+    };
+    build_image_archive(tmp_dir.path(), &rules, &[], &[], &options)?;
+    let pipeline = read_to_string(tmp_dir.path().join("logstash.conf"))?;
+    assert!(
+        pipeline.contains(r#"event.set("[@lotus_metadata]", metadata)"#),
+        "{pipeline}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn the_clock_pins_the_timestamps_of_the_events() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let rules = [tmp_dir.path().join("00-dummy.conf")];
+    File::create(&rules[0])?.write_all(b"filter { }")?;
+
+    build_image_archive(tmp_dir.path(), &rules, &[], &[], &ImageOptions::default())?;
+    let pipeline = read_to_string(tmp_dir.path().join("logstash.conf"))?;
+    assert!(pipeline.contains(r#""[@timestamp]","#), "{pipeline}");
+    assert!(!pipeline.contains("parse_iso8601"), "{pipeline}");
+
+    let options = ImageOptions {
+        clock: Some(String::from("2024-01-01T00:00:00Z")),
+        ..Default::default()
+    };
+    build_image_archive(tmp_dir.path(), &rules, &[], &[], &options)?;
+    let pipeline = read_to_string(tmp_dir.path().join("logstash.conf"))?;
+    assert!(!pipeline.contains(r#""[@timestamp]","#), "{pipeline}");
+    assert!(
+        pipeline.contains(
+            r#"event.timestamp = LogStash::Timestamp.parse_iso8601("2024-01-01T00:00:00Z")"#
+        ),
+        "{pipeline}"
+    );
+    assert!(
+        pipeline.contains(r#"event.set("[event][created]", LogStash::Timestamp.parse_iso8601("2024-01-01T00:00:00Z"))"#),
+        "{pipeline}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn logstash_errors_are_extracted_from_its_output() {
+    let output = "\
+[2024-01-01T00:00:00,000][INFO ][logstash.runner          ] Starting Logstash
+[2024-01-01T00:00:01,000][FATAL][logstash.runner          ] The given configuration is invalid. Reason: Expected one of [ \\t\\r\\n], \"#\", \"{\" at line 3
+[2024-01-01T00:00:02,000][INFO ][logstash.runner          ] Logstash shut down.
+";
+    assert_eq!(
+        logstash_errors(output),
+        "[2024-01-01T00:00:01,000][FATAL][logstash.runner          ] The given configuration is invalid. Reason: Expected one of [ \\t\\r\\n], \"#\", \"{\" at line 3"
+    );
+    assert_eq!(logstash_errors("no errors\n"), "no errors");
+}
+
+#[test]
+fn a_custom_base_image_replaces_the_official_one() {
+    let options = ImageOptions {
+        custom_base_image: Some(String::from(
+            "registry.example.com/logstash-with-plugins:8.12.0",
+        )),
+        ..Default::default()
+    };
+    assert_eq!(
+        options.base_image(),
+        "registry.example.com/logstash-with-plugins:8.12.0"
+    );
+
+    let options = ImageOptions {
+        logstash_version: String::from("7.17.16"),
+        ..Default::default()
+    };
+    assert_eq!(
+        options.base_image(),
+        "docker.elastic.co/logstash/logstash:7.17.16"
+    );
+}
+
+#[test]
+fn the_base_image_is_pinned_by_digest() {
+    let digest = "sha256:4f3c2a1b0e9d8c7b6a5f4e3d2c1b0a9f8e7d6c5b4a3f2e1d0c9b8a7f6e5d4c3b";
+    let options = ImageOptions {
+        base_image_digest: Some(String::from(digest)),
+        ..Default::default()
+    };
+    assert_eq!(
+        options.base_image(),
+        format!("docker.elastic.co/logstash/logstash:8.6.2@{digest}")
+    );
+
+    let repo_digests = vec![format!("docker.elastic.co/logstash/logstash@{digest}")];
+    assert!(has_digest(&repo_digests, digest));
+    assert!(!has_digest(&repo_digests, "sha256:0000"));
+    assert!(!has_digest(&[], digest));
+}
+
+#[test]
+fn images_are_matched_against_the_requested_platform() {
+    assert!(is_platform("linux/amd64", "linux/amd64"));
+    assert!(is_platform("linux/arm64/v8", "linux/arm64"));
+    assert!(is_platform("linux/arm64/v8", "linux/arm64/v8"));
+    assert!(!is_platform("linux/arm64", "linux/amd64"));
+    assert!(!is_platform("linux/arm64/v8", "linux/arm"));
+    assert!(!is_platform("linux/arm/v7", "linux/arm/v6"));
+}
+
+#[test]
+fn the_pipeline_archive_is_laid_out_as_the_logstash_home_directory() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let rule = tmp_dir.path().join("00-dummy.conf");
+    File::create(&rule)?.write_all(b"filter { }")?;
+    let script = tmp_dir.path().join("dummy.rb");
+    File::create(&script)?.write_all(b"def filter(event) [event] end")?;
+
+    let cache_dir = tmp_dir.path().join("cache");
+    std::fs::create_dir(&cache_dir)?;
+    let scripts = vec![script];
+    build_image_archive(&cache_dir, &[rule], &scripts, &[], &Default::default())?;
+    let archive = build_pipeline_archive(&cache_dir, &scripts, &[])?;
+
+    let mut names: Vec<String> = tar::Archive::new(archive.as_slice())
+        .entries()?
+        .map(|e| Ok(e?.path()?.display().to_string()))
+        .collect::<anyhow::Result<_>>()?;
+    names.sort();
+    assert_eq!(names, vec!["pipeline/logstash.conf", "scripts/dummy.rb"]);
+
+    Ok(())
+}
+
+#[test]
+fn the_warm_container_is_recorded_in_the_cache_directory() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    assert_eq!(WarmContainer::load(tmp_dir.path())?, None);
+
+    let warm = WarmContainer {
+        container_id: String::from("c0ffee"),
+        image_id: String::from("sha256:beef"),
+        container_hash: String::from("0123456789abcdef"),
+    };
+    warm.save(tmp_dir.path())?;
+    assert_eq!(WarmContainer::load(tmp_dir.path())?, Some(warm));
+
+    // Records of earlier versions lack the hash, so that their container is recreated
+    std::fs::write(
+        tmp_dir.path().join("container.json"),
+        r#"{ "container_id": "c0ffee", "image_id": "sha256:beef", "base_image": "logstash" }"#,
+    )?;
+    assert_eq!(
+        WarmContainer::load(tmp_dir.path())?.map(|w| w.container_hash),
+        Some(String::new())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn the_container_hash_changes_with_the_settings_of_the_container() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let hash = |options: &ImageOptions, connection: &Connection, env: &ContainerEnv| {
+        build_image_archive(tmp_dir.path(), &[], &[], &[], options)?;
+        container_hash(
+            tmp_dir.path(),
+            options,
+            connection,
+            ResourceLimits::default(),
+            env,
+            &[],
+            None,
+        )
+    };
+    let options = ImageOptions::default();
+    let connection = Connection::default();
+    let env = ContainerEnv::default();
+    let base = hash(&options, &connection, &env)?;
+    assert_eq!(hash(&options, &connection, &env)?, base);
+
+    let workers = ImageOptions {
+        workers: NonZeroUsize::new(4),
+        ..Default::default()
+    };
+    assert_ne!(hash(&workers, &connection, &env)?, base);
+
+    let mut moved = connection.clone();
+    moved.ports.output += 1;
+    assert_ne!(hash(&options, &moved, &env)?, base);
+
+    let variables = ContainerEnv {
+        variables: [(String::from("MY_VAR"), String::from("a"))].into(),
+        ..Default::default()
+    };
+    assert_ne!(hash(&options, &connection, &variables)?, base);
+
+    let udp = ImageOptions {
+        input_protocol: InputProtocol::Udp,
+        ..Default::default()
+    };
+    assert_ne!(hash(&udp, &connection, &env)?, base);
+
+    Ok(())
+}
+
+#[test]
+fn the_ports_of_pooled_containers_are_offset() {
+    assert_eq!(host_port(5066, 0).unwrap(), 5066);
+    assert_eq!(host_port(5066, 1).unwrap(), 5076);
+    assert_eq!(host_port(9600, 3).unwrap(), 9630);
+    assert_eq!(host_port(0, 3).unwrap(), 0);
+    assert!(host_port(65530, 1).is_err());
+    assert!(host_port(5066, 7000).is_err());
+}
+
+#[test]
+fn the_ports_of_pooled_containers_must_not_collide() {
+    let ports = Ports {
+        input: 5066,
+        output: 8080,
+        api: 9600,
+    };
+    assert!(ports.check_pool(4).is_ok());
+    let error = Ports {
+        input: 65530,
+        ..ports
+    }
+    .check_pool(2)
+    .unwrap_err();
+    assert!(error.to_string().contains("65530"), "{error}");
+    let error = Ports { api: 5076, ..ports }.check_pool(2).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "The port 5076 is both the API port of container 0 and the input port of container 1"
+    );
+    let error = Ports {
+        output: 9610,
+        ..ports
+    }
+    .check_pool(2)
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "The port 9610 is both the output port and the API port of container 1"
+    );
+    assert!(Ports {
+        input: 0,
+        api: 0,
+        ..ports
+    }
+    .check_pool(8)
+    .is_ok());
+}
+
+#[test]
+fn the_rendered_files_are_bind_mounted_into_the_logstash_home_directory() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let script = tmp_dir.path().join("dummy.rb");
+    File::create(&script)?.write_all(b"def filter(event) [event] end")?;
+    let scripts = vec![script];
+
+    let cache_dir = tmp_dir.path().join("cache");
+    std::fs::create_dir(&cache_dir)?;
+    build_image_archive(&cache_dir, &[], &scripts, &[], &ImageOptions::default())?;
+
+    let mounts = bind_mounts(&cache_dir, &scripts, &[])?;
+    let targets: Vec<_> = mounts
+        .iter()
+        .map(|m| m.rsplit(':').nth(1).unwrap_or_default())
+        .collect();
+    assert!(
+        targets.contains(&"/usr/share/logstash/config/logstash.yml"),
+        "{mounts:?}"
+    );
+    assert!(
+        targets.contains(&"/usr/share/logstash/pipeline/logstash.conf"),
+        "{mounts:?}"
+    );
+    assert!(
+        targets.contains(&"/usr/share/logstash/scripts/dummy.rb"),
+        "{mounts:?}"
+    );
+    assert!(
+        !targets.iter().any(|t| t.ends_with("Dockerfile")),
+        "{mounts:?}"
+    );
+    assert!(mounts
+        .iter()
+        .all(|m| m.starts_with('/') && m.ends_with(":ro")));
+
+    Ok(())
+}
+
+#[test]
+fn the_workers_of_the_pipeline_are_set_in_the_logstash_settings() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    build_image_archive(tmp_dir.path(), &[], &[], &[], &ImageOptions::default())?;
+    let settings = read_to_string(tmp_dir.path().join("logstash.yml"))?;
+    assert!(settings.contains("pipeline.workers: 1\n"), "{settings}");
+
+    let options = ImageOptions {
+        workers: NonZeroUsize::new(4),
+        ..Default::default()
+    };
+    build_image_archive(tmp_dir.path(), &[], &[], &[], &options)?;
+    let settings = read_to_string(tmp_dir.path().join("logstash.yml"))?;
+    assert!(settings.contains("pipeline.workers: 4\n"), "{settings}");
+    assert!(settings.contains("pipeline.ordered: false\n"), "{settings}");
+
+    Ok(())
+}
+
+#[test]
+fn the_content_hash_changes_only_with_the_archive_contents() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let rule = tmp_dir.path().join("00-dummy.conf");
+    File::create(&rule)?.write_all(br#"filter { mutate { add_tag => ["a"] } }"#)?;
+    let rules = vec![rule];
+
+    let cache_dir = tmp_dir.path().join("cache");
+    std::fs::create_dir(&cache_dir)?;
+    let options = ImageOptions::default();
+    let hash = |rules: &[std::path::PathBuf]| -> anyhow::Result<String> {
+        let archive = build_image_archive(&cache_dir, rules, &[], &[], &options)?;
+        Ok(content_hash(&std::fs::read(archive)?))
+    };
+
+    let first = hash(&rules)?;
+    assert_eq!(hash(&rules)?, first);
+    File::create(&rules[0])?.write_all(br#"filter { mutate { add_tag => ["b"] } }"#)?;
+    assert_ne!(hash(&rules)?, first);
+
+    assert_eq!(BuiltImage::load(&cache_dir)?, None);
+    let built = BuiltImage {
+        content_hash: first,
+        image_id: String::from("sha256:beef"),
+    };
+    built.save(&cache_dir)?;
+    assert_eq!(BuiltImage::load(&cache_dir)?, Some(built));
+
+    Ok(())
+}
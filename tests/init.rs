@@ -0,0 +1,21 @@
+use lotus::collectors::{collect_rules, collect_tests};
+use lotus::init::{init_project, Layout};
+
+#[test]
+fn initialized_project_contains_a_rule_and_a_test_case() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let layout = Layout::default();
+
+    let created = init_project(tmp_dir.path(), &layout)?;
+    assert!(created.contains(&tmp_dir.path().join("lotus.toml")));
+    assert!(tmp_dir.path().join("scripts").is_dir());
+    assert!(tmp_dir.path().join("patterns").is_dir());
+    assert_eq!(collect_rules(&tmp_dir.path().join("rules"))?.len(), 1);
+    let test_cases = collect_tests(&tmp_dir.path().join("tests"), false)?;
+    assert_eq!(test_cases.len(), 1);
+    assert_eq!(test_cases[0].name(), "example");
+
+    assert!(init_project(tmp_dir.path(), &layout).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::path::Path;
+
+use lotus::runner::parse_events;
+use serde_json::json;
+
+#[test]
+fn newline_delimited_events_are_parsed_in_order() -> anyhow::Result<()> {
+    let events = parse_events(
+        Path::new("input.ndjson"),
+        "{\"message\": \"a\"}\n\n{\"message\": \"b\"}\n  \n{\"message\": \"c\"}\n\n",
+    )?;
+    assert_eq!(
+        events,
+        vec![
+            json!({ "message": "a" }),
+            json!({ "message": "b" }),
+            json!({ "message": "c" }),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn json_arrays_are_single_events() -> anyhow::Result<()> {
+    let events = parse_events(
+        Path::new("input.json"),
+        "[\n  {\"a\": 1},\n  {\"a\": 2}\n]\n",
+    )?;
+    assert_eq!(events, vec![json!([{ "a": 1 }, { "a": 2 }])]);
+
+    let events = parse_events(Path::new("input.ndjson"), "[1, 2]\n{\"a\": 1}\n")?;
+    assert_eq!(events, vec![json!([1, 2]), json!({ "a": 1 })]);
+    Ok(())
+}
+
+#[test]
+fn blank_lines_of_text_events_are_skipped() -> anyhow::Result<()> {
+    let events = parse_events(Path::new("input.txt"), "first line\n\nsecond line\r\n\n")?;
+    assert_eq!(events, vec![json!("first line"), json!("second line")]);
+    Ok(())
+}
+
+#[test]
+fn malformed_events_are_rejected() {
+    let error = parse_events(
+        Path::new("input.ndjson"),
+        "{\"a\": 1}\n{\"a\": \n{\"a\": 3}\n",
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "When deserializing the newline-delimited JSON events"
+    );
+
+    let error = parse_events(Path::new("input.json"), "{\"a\": 1}\n{\"a\": 2}\n").unwrap_err();
+    assert_eq!(error.to_string(), "When deserializing the JSON event");
+}
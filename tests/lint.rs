@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use lotus::lint::{defined_patterns, lint_rule};
+
+fn problems(text: &str, known_patterns: &[&str]) -> Vec<(usize, String)> {
+    let known_patterns: HashSet<String> = known_patterns.iter().map(|p| p.to_string()).collect();
+    lint_rule(Path::new("rules/00-test.conf"), text, &known_patterns)
+        .into_iter()
+        .map(|p| (p.line, p.message))
+        .collect()
+}
+
+#[test]
+fn valid_rules_have_no_problems() {
+    let rule = r#"
+filter {
+    # Parse the access log
+    grok {
+        match => { "message" => "%{IPORHOST:[source][ip]} %{APPNAME:[app]}" }
+    }
+    if [source][ip] =~ /^10\.{1}/ {
+        mutate { add_field => { "[network]" => "internal" } rename => { "app" => "[service][name]" } }
+    } else if "vpn" in [tags] {
+        mutate { add_field => { "[network]" => "vpn" } }
+    } else {
+        drop {}
+    }
+}
+"#;
+    assert_eq!(problems(rule, &["APPNAME"]), []);
+}
+
+#[test]
+fn common_problems_are_found() {
+    let rule = r#"input { stdin {} }
+filter {
+    grok {
+        match => { "message" => "%{APPNAME:[app]}" }
+    }
+    mutate {
+        add_field => { "[network]" => "internal" }
+        replace => { "[network]" => "external" }
+    }
+    if [type] == "a" {
+        drop {}
+    } else if [type] == "a" {
+        drop {}
+    }
+    if "x" == "y" {
+        drop {}
+    }
+}
+"#;
+    let found = problems(rule, &[]);
+    let lines: Vec<usize> = found.iter().map(|(line, _)| *line).collect();
+    assert_eq!(lines, [1, 4, 8, 12, 15], "{found:?}");
+    assert!(found[0].1.contains("'input' section"), "{found:?}");
+    assert!(found[1].1.contains("'APPNAME'"), "{found:?}");
+    assert!(found[2].1.contains("already set on line 7"), "{found:?}");
+}
+
+#[test]
+fn unparsable_rules_are_reported() {
+    let found = problems("filter { mutate { add_field => ", &[]);
+    assert_eq!(found.len(), 1);
+    assert!(found[0].1.starts_with("Unable to parse"), "{found:?}");
+}
+
+#[test]
+fn patterns_are_defined_by_pattern_files() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let file = tmp_dir.path().join("custom");
+    std::fs::write(
+        &file,
+        "# Custom patterns\nAPPNAME [a-z]+\n\nAPPVERSION \\d+\n",
+    )?;
+
+    let patterns = defined_patterns(&[file])?;
+    assert_eq!(
+        patterns,
+        HashSet::from([String::from("APPNAME"), String::from("APPVERSION")])
+    );
+
+    Ok(())
+}
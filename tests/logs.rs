@@ -0,0 +1,30 @@
+use std::fs::File;
+use std::io::Write;
+
+use lotus::logs::{log_offset, matching_lines, LogPattern};
+
+#[test]
+fn only_lines_logged_after_the_offset_are_matched() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let path = tmp_dir.path().join("logstash.log");
+    let mut file = File::create(&path)?;
+    file.write_all(b"[2024-01-01T00:00:00,000][WARN ][logstash.config] Deprecated option\n")?;
+    let offset = log_offset(&path);
+    file.write_all(b"[2024-01-01T00:00:01,000][INFO ][logstash.pipeline] Started\n")?;
+    file.write_all(b"[2024-01-01T00:00:02,000][WARN ][logstash.filters.grok] Timeout\n")?;
+
+    let patterns: Vec<LogPattern> = vec![r"\[WARN".parse()?, r"\[ERROR".parse()?];
+    assert_eq!(
+        matching_lines(&path, offset, &patterns)?,
+        vec!["[2024-01-01T00:00:02,000][WARN ][logstash.filters.grok] Timeout"]
+    );
+    assert_eq!(matching_lines(&path, 0, &patterns)?.len(), 2);
+    assert_eq!(log_offset(&tmp_dir.path().join("missing.log")), 0);
+
+    Ok(())
+}
+
+#[test]
+fn invalid_log_patterns_are_rejected() {
+    assert!("[WARN".parse::<LogPattern>().is_err());
+}
@@ -0,0 +1,37 @@
+use lotus::lumberjack::{encode_window, send_events};
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[test]
+fn events_are_encoded_as_a_window_of_json_frames() -> anyhow::Result<()> {
+    let frames = encode_window(&[json!({ "a": 1 })])?;
+    let mut expected = b"2W\0\0\0\x012J\0\0\0\x01\0\0\0\x07".to_vec();
+    expected.extend_from_slice(br#"{"a":1}"#);
+    assert_eq!(frames, expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn sending_waits_for_the_acknowledgement_of_the_whole_window() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    let events = [json!({ "n": 1 }), json!({ "n": 2 })];
+    let expected = encode_window(&events)?;
+
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await?;
+        let mut received = vec![0u8; expected.len()];
+        stream.read_exact(&mut received).await?;
+        // A partial acknowledgement first, as Logstash may send
+        stream.write_all(b"2A\0\0\0\x01").await?;
+        stream.write_all(b"2A\0\0\0\x02").await?;
+        anyhow::Ok(received == expected)
+    });
+
+    send_events("127.0.0.1", port, &events).await?;
+    assert!(server.await??);
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use lotus::mutate::mutants;
+
+const RULE: &str = r#"filter {
+    if [type] == "access" {
+        grok { match => { "message" => ["%{COMMONAPACHELOG}", '%{COMBINEDAPACHELOG}'] } }
+        mutate {
+            add_tag => ["access"]
+        }
+    } else if "x" in [tags] {
+        drop { }
+    }
+}
+output {
+    if [type] == "x" { stdout { } }
+}
+"#;
+
+#[test]
+fn mutants_change_one_thing_each() -> anyhow::Result<()> {
+    let mutants = mutants(RULE)?;
+    let described: Vec<_> = mutants
+        .iter()
+        .map(|m| (m.line, m.description.as_str()))
+        .collect();
+    assert_eq!(
+        described,
+        vec![
+            (2, r#"negate the condition [type] == "access""#),
+            (
+                3,
+                r#"replace the grok pattern "%{COMMONAPACHELOG}" with one that never matches"#
+            ),
+            (
+                3,
+                "replace the grok pattern '%{COMBINEDAPACHELOG}' with one that never matches"
+            ),
+            (4, "remove a mutate filter"),
+            (7, r#"negate the condition "x" in [tags]"#),
+        ]
+    );
+
+    assert!(mutants[0].text.contains(r#"if !([type] == "access") {"#));
+    assert!(mutants[1]
+        .text
+        .contains(r#"["^lotus-mutant$", '%{COMBINEDAPACHELOG}']"#));
+    assert!(mutants[3]
+        .text
+        .contains("'%{COMBINEDAPACHELOG}'] } }\n        \n    } else if"));
+    assert!(mutants[4].text.contains(r#"} else if !("x" in [tags]) {"#));
+    // Outputs are replaced by Lotus, and are therefore not mutated
+    assert!(mutants
+        .iter()
+        .all(|m| m.text.contains(r#"if [type] == "x""#)));
+
+    assert!(lotus::mutate::mutants("filter {").is_err());
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use lotus::collectors::collect_tests;
+use lotus::report::{TestReport, TestResult, TestStatus};
+use lotus::runner::{retry, test_status, TestCase, TestOptions};
+use lotus::Outcome;
+
+mod common;
+
+use common::write_empty_test_case;
+
+fn expected_failure() -> anyhow::Result<TestCase> {
+    let tmp_dir = tempfile::tempdir()?;
+    write_empty_test_case(tmp_dir.path(), "known-bug", Some("xfail = true\n"))?;
+    let mut test_cases = collect_tests(tmp_dir.path(), false)?;
+    Ok(test_cases.remove(0))
+}
+
+fn result(name: &str, status: TestStatus, error: Option<String>) -> TestResult {
+    TestResult {
+        name: name.to_string(),
+        title: name.to_string(),
+        description: None,
+        status,
+        retries: 0,
+        duration: Duration::from_millis(250),
+        error,
+        mismatch: None,
+    }
+}
+
+fn report(status: TestStatus, error: Option<String>) -> TestReport {
+    TestReport {
+        results: vec![result("known-bug", status, error)],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn expected_failures_that_fail_succeed() -> anyhow::Result<()> {
+    let test_case = expected_failure()?;
+
+    let (status, error) = test_status(0, &test_case, Err(anyhow!("json atoms are not equal")));
+    assert_eq!(status, TestStatus::XFailed);
+    assert_eq!(error, None);
+    assert_eq!(report(status, error).outcome(), Outcome::Success);
+
+    Ok(())
+}
+
+#[test]
+fn expected_failures_that_pass_fail() -> anyhow::Result<()> {
+    let test_case = expected_failure()?;
+
+    let (status, error) = test_status(0, &test_case, Ok(()));
+    assert_eq!(status, TestStatus::XPassed);
+    assert_eq!(
+        error.as_deref(),
+        Some("Test case 0 is marked as expected to fail, but passed: known-bug")
+    );
+    assert_eq!(
+        report(status, error).outcome(),
+        Outcome::TestsFailed {
+            failed: 1,
+            total: 1
+        }
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_attempts_are_retried_until_one_passes() {
+    let (r, retries) = retry(3, |attempt| async move {
+        match attempt {
+            0 | 1 => Err(anyhow!("attempt {attempt} failed")),
+            _ => Ok(()),
+        }
+    })
+    .await;
+    assert!(r.is_ok());
+    assert_eq!(retries, 2);
+}
+
+#[tokio::test]
+async fn the_last_attempt_decides_the_result() {
+    let mut attempts = Vec::new();
+    let (r, retries) = retry(2, |attempt| {
+        attempts.push(attempt);
+        async move { Err::<(), _>(anyhow!("attempt {attempt} failed")) }
+    })
+    .await;
+    assert_eq!(r.unwrap_err().to_string(), "attempt 2 failed");
+    assert_eq!(retries, 2);
+    assert_eq!(attempts, vec![0, 1, 2]);
+
+    let (r, retries) = retry(0, |_| async { Err(anyhow!("failed")) }).await;
+    assert!(r.is_err());
+    assert_eq!(retries, 0);
+}
+
+#[test]
+fn expected_failures_are_not_retried() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    write_empty_test_case(tmp_dir.path(), "flaky", None)?;
+    write_empty_test_case(tmp_dir.path(), "known-bug", Some("xfail = true\n"))?;
+    let options = TestOptions {
+        retries: 2,
+        ..TestOptions::default()
+    };
+
+    let test_cases = collect_tests(tmp_dir.path(), false)?;
+    assert_eq!(test_cases[0].name(), "flaky");
+    assert_eq!(test_cases[0].retries(&options), 2);
+    assert_eq!(test_cases[1].retries(&options), 0);
+
+    Ok(())
+}
+
+/// Records the given statuses of the test cases in order, like a run, until the run stops
+fn run(statuses: &[TestStatus], options: &TestOptions) -> TestReport {
+    let mut report = TestReport {
+        results: (0..statuses.len())
+            .map(|i| result(&format!("case-{i}"), TestStatus::Skipped, None))
+            .collect(),
+        ..Default::default()
+    };
+    for (i, &status) in statuses.iter().enumerate() {
+        let result = &mut report.results[i];
+        result.status = status;
+        result.error = status.is_failure().then(|| format!("case-{i} failed"));
+        if options.stops_after(status, false) {
+            break;
+        }
+    }
+    report
+}
+
+#[test]
+fn runs_stop_at_the_first_failure_unless_they_keep_going() {
+    let statuses = [
+        TestStatus::Passed,
+        TestStatus::Failed,
+        TestStatus::XFailed,
+        TestStatus::XPassed,
+        TestStatus::Passed,
+    ];
+    let failures = |report: &TestReport| -> Vec<String> {
+        report.failures().map(|r| r.name.clone()).collect()
+    };
+
+    let stopped = run(&statuses, &TestOptions::default());
+    assert_eq!(failures(&stopped), vec!["case-1"]);
+    assert_eq!(stopped.count(TestStatus::Skipped), 3);
+
+    let options = TestOptions {
+        keep_going: true,
+        ..TestOptions::default()
+    };
+    let completed = run(&statuses, &options);
+    assert_eq!(failures(&completed), vec!["case-1", "case-3"]);
+    assert_eq!(completed.count(TestStatus::Skipped), 0);
+    assert_eq!(
+        completed.outcome(),
+        Outcome::TestsFailed {
+            failed: 2,
+            total: 5
+        }
+    );
+
+    assert!(TestOptions::default().stops_after(TestStatus::Passed, true));
+    assert!(!options.stops_after(TestStatus::Passed, true));
+}
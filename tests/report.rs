@@ -0,0 +1,227 @@
+use std::time::Duration;
+
+use lotus::compare::{DiffStyle, Difference, Mismatch, Verbosity};
+use lotus::report::{ReportFormat, ReportTarget, TestReport, TestResult, TestStatus};
+
+fn result(name: &str, status: TestStatus, error: Option<&str>) -> TestResult {
+    TestResult {
+        name: name.to_string(),
+        title: name.to_string(),
+        description: None,
+        status,
+        retries: 0,
+        duration: Duration::from_millis(250),
+        error: error.map(String::from),
+        mismatch: None,
+    }
+}
+
+#[test]
+fn summary_lists_every_test_case_and_failure() {
+    let report = TestReport {
+        results: vec![
+            result("a", TestStatus::Passed, None),
+            result(
+                "b",
+                TestStatus::Failed,
+                Some("json atoms at path \".dummy\" are not equal"),
+            ),
+            result("c", TestStatus::XFailed, None),
+            result("d", TestStatus::Skipped, None),
+        ],
+        duration: Duration::from_secs(1),
+        ..Default::default()
+    };
+    assert!(!report.is_success());
+
+    let summary = report.to_string();
+    assert!(summary.contains("---- b ----\njson atoms"), "{summary}");
+    assert!(summary.contains("    ok          0.25s  a\n"), "{summary}");
+    assert!(summary.contains("    skipped            d\n"), "{summary}");
+    assert!(
+        summary.ends_with(
+            "test result: FAILED. 1 passed; 1 failed; 1 xfail; 1 skipped; finished in 1.00s"
+        ),
+        "{summary}"
+    );
+}
+
+#[test]
+fn tap_report_marks_failures_and_directives() -> anyhow::Result<()> {
+    let report = TestReport {
+        results: vec![
+            result("a", TestStatus::Passed, None),
+            result("b", TestStatus::Failed, Some("first line\nsecond line")),
+            result("c", TestStatus::XFailed, None),
+            result("d", TestStatus::Skipped, None),
+        ],
+        duration: Duration::from_secs(1),
+        ..Default::default()
+    };
+
+    let tap = report.to_tap()?;
+    let lines: Vec<&str> = tap.lines().collect();
+    assert_eq!(lines[..3], ["TAP version 14", "1..4", "ok 1 - a"]);
+    assert!(
+        tap.contains("not ok 2 - b\n  ---\n  message: |-\n    first line\n    second line\n"),
+        "{tap}"
+    );
+    assert!(tap.contains("not ok 3 - c # TODO"), "{tap}");
+    assert!(
+        tap.ends_with("ok 4 - d # SKIP not run after an earlier failure\n"),
+        "{tap}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn report_targets_are_parsed() -> anyhow::Result<()> {
+    assert_eq!(
+        "tap".parse::<ReportTarget>()?,
+        ReportTarget {
+            format: ReportFormat::Tap,
+            path: None
+        }
+    );
+    assert_eq!(
+        "tap=out/results.tap".parse::<ReportTarget>()?,
+        ReportTarget {
+            format: ReportFormat::Tap,
+            path: Some("out/results.tap".into())
+        }
+    );
+    assert_eq!("json".parse::<ReportTarget>()?.format, ReportFormat::Json);
+    assert_eq!(
+        "html=report.html".parse::<ReportTarget>()?.format,
+        ReportFormat::Html
+    );
+    assert!("junit".parse::<ReportTarget>().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn json_report_contains_results_and_mismatches() -> anyhow::Result<()> {
+    let mut failed = result("b", TestStatus::Failed, Some("json atoms are not equal"));
+    failed.mismatch = Some(Mismatch {
+        differences: vec![Difference::Changed {
+            path: String::from("[dummy]"),
+            actual: serde_json::json!("false"),
+            expected: serde_json::json!("true"),
+        }],
+        actual: serde_json::json!({ "dummy": "false" }),
+        expected: serde_json::json!({ "dummy": "true" }),
+        verbosity: Verbosity::Normal,
+        style: DiffStyle::Paths,
+    });
+    let report = TestReport {
+        results: vec![result("a", TestStatus::Passed, None), failed],
+        duration: Duration::from_secs(1),
+        image_id: Some(String::from("sha256:abc")),
+        container_id: Some(String::from("def")),
+        coverage: None,
+    };
+
+    let json = serde_json::to_value(&report)?;
+    assert_eq!(json["duration_ms"], 1000);
+    assert_eq!(json["image_id"], "sha256:abc");
+    assert_eq!(json["results"][0]["status"], "passed");
+    assert_eq!(json["results"][0]["duration_ms"], 250);
+    assert_eq!(json["results"][1]["status"], "failed");
+    assert_eq!(json["results"][1]["mismatch"]["expected"]["dummy"], "true");
+    assert_eq!(
+        json["results"][1]["mismatch"]["differences"][0]["kind"],
+        "changed"
+    );
+    assert!(json["results"][1]["mismatch"].get("verbosity").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn html_report_escapes_and_collapses_failures() -> anyhow::Result<()> {
+    let mut failed = result("<b>", TestStatus::Failed, Some("json atoms are not equal"));
+    failed.mismatch = Some(Mismatch {
+        differences: vec![Difference::Changed {
+            path: String::from("[dummy]"),
+            actual: serde_json::json!("false"),
+            expected: serde_json::json!("true"),
+        }],
+        actual: serde_json::json!({ "dummy": "false" }),
+        expected: serde_json::json!({ "dummy": "true" }),
+        verbosity: Verbosity::Normal,
+        style: DiffStyle::Paths,
+    });
+    let report = TestReport {
+        results: vec![result("a", TestStatus::Passed, None), failed],
+        duration: Duration::from_secs(1),
+        ..Default::default()
+    };
+
+    let html = report.to_html()?;
+    assert!(html.starts_with("<!DOCTYPE html>"), "{html}");
+    assert!(
+        html.contains("<summary class=\"failed\">&lt;b&gt;</summary>"),
+        "{html}"
+    );
+    assert!(
+        html.contains("&quot;dummy&quot;: &quot;true&quot;"),
+        "{html}"
+    );
+    assert!(!html.contains("<b>"), "{html}");
+
+    Ok(())
+}
+
+#[test]
+fn html_report_omits_the_documents_of_paths_only_mismatches() -> anyhow::Result<()> {
+    let mut failed = result("a", TestStatus::Failed, None);
+    failed.mismatch = Some(Mismatch {
+        differences: vec![Difference::Changed {
+            path: String::from("[dummy]"),
+            actual: serde_json::json!("false"),
+            expected: serde_json::json!("true"),
+        }],
+        actual: serde_json::json!({ "dummy": "false", "secret": "hunter2" }),
+        expected: serde_json::json!({ "dummy": "true" }),
+        verbosity: Verbosity::Verbose,
+        style: DiffStyle::PathsOnly,
+    });
+    let report = TestReport {
+        results: vec![failed],
+        ..Default::default()
+    };
+
+    let html = report.to_html()?;
+    assert!(html.contains("[dummy]"), "{html}");
+    assert!(!html.contains("hunter2"), "{html}");
+    assert!(!html.contains("<h4>Actual</h4>"), "{html}");
+
+    Ok(())
+}
+
+#[test]
+fn slowest_tests_are_listed_in_descending_order() {
+    let mut results: Vec<TestResult> = (0..12)
+        .map(|i| {
+            let mut r = result(&format!("t{i}"), TestStatus::Passed, None);
+            r.duration = Duration::from_millis(100 * i);
+            r
+        })
+        .collect();
+    results[11].status = TestStatus::Skipped;
+    let report = TestReport {
+        results,
+        ..Default::default()
+    };
+
+    let slowest: Vec<&str> = report.slowest(3).iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(slowest, ["t10", "t9", "t8"]);
+
+    let summary = report.to_string();
+    assert!(
+        summary.contains("10 slowest tests:\n       1.00s  t10\n       0.90s  t9\n"),
+        "{summary}"
+    );
+}
@@ -0,0 +1,36 @@
+use std::fs::{create_dir_all, File};
+
+use lotus::runs::{create_run_dir, prune_run_dirs};
+
+#[test]
+fn only_the_most_recent_runs_are_kept() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let runs_dir = tmp_dir.path().join("runs");
+    for name in ["900", "1000", "1100"] {
+        create_dir_all(runs_dir.join(name))?;
+        File::create(runs_dir.join(name).join("logstash.log"))?;
+    }
+    File::create(runs_dir.join("notes.txt"))?;
+
+    let removed = prune_run_dirs(&runs_dir, 2)?;
+
+    assert_eq!(removed, vec![runs_dir.join("900")]);
+    assert!(runs_dir.join("1000").is_dir());
+    assert!(runs_dir.join("1100").is_dir());
+    assert!(runs_dir.join("notes.txt").is_file());
+    Ok(())
+}
+
+#[test]
+fn a_run_directory_is_created_in_the_cache_directory() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+
+    let run_dir = create_run_dir(tmp_dir.path())?;
+
+    assert!(run_dir.is_dir());
+    assert_eq!(
+        run_dir.parent(),
+        Some(tmp_dir.path().join("runs").as_path())
+    );
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use lotus::schema::{check_schema, validate};
+use serde_json::json;
+
+#[test]
+fn valid_events_pass() -> anyhow::Result<()> {
+    let schema = json! {{
+        "type": "object",
+        "required": ["@timestamp", "url", "event"],
+        "properties": {
+            "@timestamp": { "type": "string", "format": "date-time" },
+            "url": {
+                "type": "object",
+                "properties": {
+                    "port": { "type": "integer", "minimum": 1, "maximum": 65535 },
+                    "scheme": { "enum": ["http", "https"] },
+                },
+                "additionalProperties": false,
+            },
+            "source": { "$ref": "#/$defs/address" },
+            "tags": { "type": "array", "items": { "type": "string" }, "uniqueItems": true },
+            "event": { "type": "object", "properties": { "kind": { "const": "event" } } },
+        },
+        "$defs": {
+            "address": { "type": "object", "properties": { "ip": { "format": "ipv4" } } },
+        },
+    }};
+    let event = json! {{
+        "@timestamp": "2023-07-16T13:35:40.682Z",
+        "url": { "port": 443, "scheme": "https" },
+        "source": { "ip": "10.0.0.1" },
+        "tags": ["a", "b"],
+        "event": { "kind": "event" },
+        "message": "anything",
+    }};
+
+    check_schema(&schema, &event)
+}
+
+#[test]
+fn violations_are_all_reported_by_path() -> anyhow::Result<()> {
+    let schema = json! {{
+        "type": "object",
+        "required": ["event"],
+        "properties": {
+            "url": {
+                "properties": { "port": { "type": "integer" } },
+                "additionalProperties": false,
+            },
+            "source": { "properties": { "ip": { "format": "ipv4" } } },
+            "tags": { "items": { "type": "string" }, "minItems": 3 },
+            "level": { "anyOf": [{ "const": "info" }, { "const": "warn" }] },
+        },
+    }};
+    let event = json! {{
+        "url": { "port": "443", "path": "/" },
+        "source": { "ip": "not-an-ip" },
+        "tags": ["a", 1],
+        "level": "debug",
+    }};
+
+    let violations = validate(&schema, &event)?;
+    assert_eq!(
+        violations,
+        vec![
+            "[event]: the required field is missing",
+            "[level]: \"debug\" matches none of the anyOf schemas",
+            "[source][ip]: \"not-an-ip\" is not a valid ipv4",
+            "[tags][1]: expected a value of type string, found 1",
+            "[tags]: expected at least 3 element(s), found 2",
+            "[url][path]: the field is not permitted, found \"/\"",
+            "[url][port]: expected a value of type integer, found \"443\"",
+        ]
+    );
+
+    let error = check_schema(&schema, &event).unwrap_err().to_string();
+    assert!(
+        error.starts_with("The event violates the schema in 7 place(s)"),
+        "{error}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn unresolvable_references_are_rejected() {
+    let schema = json! {{ "$ref": "#/$defs/missing" }};
+    assert!(validate(&schema, &json!({})).is_err());
+}
+
+#[test]
+fn cyclic_references_are_rejected() {
+    let schema = json! {{ "$ref": "#" }};
+    let error = validate(&schema, &json!({})).unwrap_err();
+    assert!(error.to_string().contains("refers to itself"), "{error}");
+
+    let schema = json! {{
+        "$ref": "#/$defs/a",
+        "$defs": { "a": { "$ref": "#/$defs/b" }, "b": { "$ref": "#/$defs/a" } }
+    }};
+    assert!(validate(&schema, &json!(1)).is_err());
+}
+
+#[test]
+fn recursive_schemas_validate_nested_values() -> anyhow::Result<()> {
+    let schema = json! {{
+        "type": "object",
+        "properties": { "child": { "$ref": "#" }, "n": { "type": "integer" } }
+    }};
+    assert!(validate(
+        &schema,
+        &json!({ "n": 1, "child": { "n": 2, "child": {} } })
+    )?
+    .is_empty());
+    assert_eq!(
+        validate(&schema, &json!({ "child": { "child": { "n": "3" } } }))?.len(),
+        1
+    );
+    Ok(())
+}
+
+#[test]
+fn multiples_of_decimal_fractions_are_accepted() -> anyhow::Result<()> {
+    let schema = json! {{ "multipleOf": 0.1 }};
+    assert!(validate(&schema, &json!(0.3))?.is_empty());
+    assert!(validate(&schema, &json!(12.7))?.is_empty());
+    assert_eq!(validate(&schema, &json!(0.35))?.len(), 1);
+
+    let schema = json! {{ "multipleOf": 3 }};
+    assert!(validate(&schema, &json!(9))?.is_empty());
+    assert_eq!(validate(&schema, &json!(10))?.len(), 1);
+    Ok(())
+}
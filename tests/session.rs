@@ -0,0 +1,32 @@
+use lotus::{run_single_event, DefaultArguments, Harness};
+use serde_json::json;
+
+#[tokio::test]
+async fn a_session_needs_the_rules_of_the_project() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let rules_dir = tmp_dir.path().join("rules");
+
+    let error = Harness::new()
+        .target(tmp_dir.path())
+        .session()
+        .await
+        .expect_err("the project has no rules");
+    assert!(
+        format!("{error:#}").contains(&rules_dir.display().to_string()),
+        "{error:#}"
+    );
+
+    let args = DefaultArguments {
+        target: Some(tmp_dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let error = run_single_event(&args, json!({ "message": "hello" }))
+        .await
+        .expect_err("the project has no rules");
+    assert!(
+        format!("{error:#}").contains(&rules_dir.display().to_string()),
+        "{error:#}"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use lotus::docker::InputProtocol;
+use lotus::sockets::{correlated_event, send_events, syslog_frame};
+use serde_json::json;
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, UdpSocket};
+
+#[test]
+fn events_carry_the_correlation_id_in_their_metadata() {
+    assert_eq!(
+        correlated_event(
+            &json!({ "message": "a", "@metadata": { "beat": "filebeat" } }),
+            "0-0"
+        ),
+        json!({ "message": "a", "@metadata": { "beat": "filebeat", "lotus_test_id": "0-0" } })
+    );
+    assert_eq!(
+        correlated_event(&json!("raw line"), "1-0"),
+        json!({ "message": "raw line", "@metadata": { "lotus_test_id": "1-0" } })
+    );
+}
+
+#[test]
+fn syslog_frames_are_prefixed_with_the_correlation_id() -> anyhow::Result<()> {
+    assert_eq!(
+        syslog_frame(&json!("<34>Oct 11 22:14:15 host su: failed"), "2-1")?,
+        b"2-1 <34>Oct 11 22:14:15 host su: failed"
+    );
+    assert!(syslog_frame(&json!({ "message": "a" }), "2-1").is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn syslog_frames_are_written_as_lines_over_tcp() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await?;
+        let mut received = String::new();
+        stream.read_to_string(&mut received).await?;
+        anyhow::Ok(received)
+    });
+
+    let events = [
+        json!("<13>Oct 11 22:14:15 host app: one"),
+        json!("<13>Oct 11 22:14:16 host app: two"),
+    ];
+    send_events(InputProtocol::SyslogTcp, "127.0.0.1", port, &events, "0-0").await?;
+    assert_eq!(
+        server.await??,
+        "0-0 <13>Oct 11 22:14:15 host app: one\n0-0 <13>Oct 11 22:14:16 host app: two\n"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn events_are_written_as_json_lines_over_tcp() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await?;
+        let mut received = String::new();
+        stream.read_to_string(&mut received).await?;
+        anyhow::Ok(received)
+    });
+
+    let events = [json!({ "n": 1 }), json!("raw")];
+    send_events(InputProtocol::Tcp, "127.0.0.1", port, &events, "0-0").await?;
+    assert_eq!(
+        server.await??,
+        concat!(
+            r#"{"@metadata":{"lotus_test_id":"0-0"},"n":1}"#,
+            "\n",
+            r#"{"@metadata":{"lotus_test_id":"0-0"},"message":"raw"}"#,
+            "\n"
+        )
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn events_are_sent_as_one_datagram_each_over_udp() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    let port = socket.local_addr()?.port();
+
+    let events = [json!({ "n": 1 }), json!({ "n": 2 })];
+    send_events(InputProtocol::Udp, "127.0.0.1", port, &events, "1-0").await?;
+    let mut buffer = [0u8; 1024];
+    for n in 1..=2 {
+        let len = socket.recv(&mut buffer).await?;
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&buffer[..len])?,
+            json!({ "n": n, "@metadata": { "lotus_test_id": "1-0" } })
+        );
+    }
+
+    Ok(())
+}
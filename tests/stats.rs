@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use lotus::stats::{add_plugin_stats, check_stats, plugin_stats, PluginStats, StatsAssertions};
+use serde_json::json;
+
+fn node_stats(grok_in: u64, grok_failures: u64, date_failures: u64) -> serde_json::Value {
+    json!({
+        "pipelines": {
+            "main": {
+                "plugins": {
+                    "inputs": [{ "id": "lotus_input", "name": "http", "events": { "out": grok_in } }],
+                    "filters": [
+                        {
+                            "id": "parse_access_log",
+                            "name": "grok",
+                            "events": { "in": grok_in, "out": grok_in },
+                            "matches": grok_in - grok_failures,
+                            "failures": grok_failures
+                        },
+                        { "id": "parse_date", "name": "date", "failures": date_failures },
+                        { "id": "unused", "name": "mutate" }
+                    ],
+                    "outputs": []
+                }
+            }
+        }
+    })
+}
+
+#[test]
+fn plugin_counters_are_read_from_the_node_stats() {
+    let stats = plugin_stats(&node_stats(5, 1, 0));
+
+    assert_eq!(
+        stats.get("parse_access_log"),
+        Some(&PluginStats {
+            name: String::from("grok"),
+            events_in: 5,
+            failures: 1,
+        })
+    );
+    assert_eq!(stats.get("unused").map(|s| s.events_in), Some(0));
+    assert_eq!(stats.len(), 4);
+}
+
+#[test]
+fn assertions_are_checked_against_the_counters_of_the_run() {
+    let before = plugin_stats(&node_stats(10, 2, 1));
+    let after = plugin_stats(&node_stats(13, 2, 2));
+
+    let assertions = StatsAssertions {
+        no_grok_failures: true,
+        filter_events: BTreeMap::from([(String::from("parse_access_log"), 3)]),
+        ..Default::default()
+    };
+    assert!(check_stats(&assertions, &before, &after).is_empty());
+
+    let assertions = StatsAssertions {
+        no_plugin_failures: true,
+        filter_events: BTreeMap::from([
+            (String::from("parse_access_log"), 4),
+            (String::from("missing"), 1),
+        ]),
+        ..Default::default()
+    };
+    assert_eq!(
+        check_stats(&assertions, &before, &after),
+        vec![
+            "The date plugin parse_date failed to process 1 event(s)",
+            "The pipeline has no plugin with the ID missing",
+            "The filter parse_access_log processed 3 event(s) instead of 4",
+        ]
+    );
+}
+
+#[test]
+fn the_counters_of_a_pool_are_summed() {
+    let mut total = plugin_stats(&node_stats(2, 1, 0));
+    add_plugin_stats(&mut total, plugin_stats(&node_stats(3, 0, 0)));
+
+    assert_eq!(total["parse_access_log"].events_in, 5);
+    assert_eq!(total["parse_access_log"].failures, 1);
+}
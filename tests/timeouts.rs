@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use lotus::collectors::collect_tests;
+use lotus::runner::{run_single_test, OutputTimeout, TestOptions};
+use lotus::server::{bind_server, run_server, EventRouter};
+
+mod common;
+
+use common::write_empty_test_case;
+
+#[test]
+fn output_timeouts_tell_whether_logstash_accepted_the_input_events() {
+    let accepted = OutputTimeout {
+        event: 1,
+        wait: Some(Duration::from_secs(60)),
+        responses: vec![200, 200],
+    };
+    assert_eq!(
+        accepted.to_string(),
+        "Logstash did not send output event 1 within Some(60s), although it accepted all 2 input event(s)"
+    );
+
+    let rejected = OutputTimeout {
+        event: 0,
+        wait: Some(Duration::from_secs(5)),
+        responses: vec![200, 429],
+    };
+    assert_eq!(
+        rejected.to_string(),
+        "Logstash did not send output event 0 within Some(5s), and it answered input event 1 with HTTP 429"
+    );
+}
+
+#[test]
+fn test_metadata_overrides_the_timeout_of_the_run() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    write_empty_test_case(tmp_dir.path(), "inherits", None)?;
+    write_empty_test_case(tmp_dir.path(), "overrides", Some("timeout = 5\n"))?;
+    let test_cases = collect_tests(tmp_dir.path(), false)?;
+
+    let options = TestOptions {
+        timeout: Some(Duration::from_secs(60)),
+        ..TestOptions::default()
+    };
+    assert_eq!(test_cases[0].name(), "inherits");
+    assert_eq!(
+        test_cases[0].timeout(&options),
+        Some(Duration::from_secs(60))
+    );
+    assert_eq!(
+        test_cases[1].timeout(&options),
+        Some(Duration::from_secs(5))
+    );
+
+    let options = TestOptions::default();
+    assert_eq!(test_cases[0].timeout(&options), None);
+    assert_eq!(
+        test_cases[1].timeout(&options),
+        Some(Duration::from_secs(5))
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cases_time_out_when_logstash_sends_no_output_event() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    write_empty_test_case(tmp_dir.path(), "stalled", None)?;
+    let test_cases = collect_tests(tmp_dir.path(), false)?;
+    let options = TestOptions {
+        timeout: Some(Duration::from_millis(100)),
+        ..TestOptions::default()
+    };
+
+    // The event responder server of Lotus stands in for Logstash, and passes the input events
+    // through unchanged
+    let router = EventRouter::default();
+    let listener = bind_server(0)?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(run_server(router.clone(), listener));
+    let client = reqwest::Client::new();
+
+    let mut receiver = router.register("0");
+    run_single_test(&client, &mut receiver, &test_cases[0], "0", port, &options).await?;
+    router.unregister("0");
+
+    // Nothing is registered to receive the output event any more
+    let (_sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    let error = run_single_test(&client, &mut receiver, &test_cases[0], "1", port, &options)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OutputTimeout>(),
+        Some(&OutputTimeout {
+            event: 0,
+            wait: Some(Duration::from_millis(100)),
+            responses: vec![204],
+        })
+    );
+    assert_eq!(
+        error.to_string(),
+        "Logstash did not send output event 0 within Some(100ms), although it accepted all 1 input event(s)"
+    );
+
+    Ok(())
+}
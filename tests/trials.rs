@@ -0,0 +1,51 @@
+#![cfg(feature = "libtest-mimic")]
+
+use libtest_mimic::Arguments;
+use lotus::trials::{is_selected, trials};
+use lotus::Harness;
+
+fn arguments(args: &[&str]) -> Arguments {
+    Arguments::from_iter(std::iter::once("lotus").chain(args.iter().copied()))
+}
+
+#[test]
+fn trials_are_selected_like_libtest_tests() {
+    assert!(is_selected(&arguments(&[]), "nginx/access"));
+    assert!(is_selected(&arguments(&["nginx"]), "nginx/access"));
+    assert!(!is_selected(
+        &arguments(&["--exact", "nginx"]),
+        "nginx/access"
+    ));
+    assert!(is_selected(
+        &arguments(&["--exact", "nginx/access"]),
+        "nginx/access"
+    ));
+    assert!(!is_selected(
+        &arguments(&["--skip", "access"]),
+        "nginx/access"
+    ));
+    assert!(!is_selected(&arguments(&["--ignored"]), "nginx/access"));
+}
+
+#[test]
+fn each_test_case_becomes_a_trial() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    std::fs::create_dir_all(tmp_dir.path().join("rules"))?;
+    std::fs::write(tmp_dir.path().join("rules/00-dummy.conf"), "filter { }")?;
+    for name in ["access", "error"] {
+        let test_dir = tmp_dir.path().join("tests").join(name);
+        std::fs::create_dir_all(&test_dir)?;
+        std::fs::write(test_dir.join("input.json"), "{}")?;
+        std::fs::write(test_dir.join("expected.json"), "{}")?;
+    }
+
+    let trials = trials(
+        Harness::new().target(tmp_dir.path()),
+        &arguments(&["--exact", "error"]),
+    )?;
+    let mut names: Vec<_> = trials.iter().map(|t| t.name().to_string()).collect();
+    names.sort();
+    assert_eq!(names, ["access", "error"]);
+
+    Ok(())
+}
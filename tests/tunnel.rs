@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use lotus::docker::Ports;
+use lotus::tunnel::ssh_arguments;
+
+#[test]
+fn the_docker_socket_and_the_ports_are_forwarded() -> anyhow::Result<()> {
+    let args = ssh_arguments(
+        "ssh://ci@build-host:2222",
+        Path::new("/tmp/lotus/docker.sock"),
+        Ports::default(),
+    )?;
+
+    let forwards: Vec<(&str, &str)> = args
+        .windows(2)
+        .filter(|w| w[0] == "-L" || w[0] == "-R" || w[0] == "-p")
+        .map(|w| (w[0].as_str(), w[1].as_str()))
+        .collect();
+    assert_eq!(
+        forwards,
+        vec![
+            ("-p", "2222"),
+            ("-L", "/tmp/lotus/docker.sock:/var/run/docker.sock"),
+            ("-L", "127.0.0.1:5066:127.0.0.1:5066"),
+            ("-L", "127.0.0.1:9600:127.0.0.1:9600"),
+            ("-R", "0.0.0.0:5067:127.0.0.1:5067"),
+        ]
+    );
+    assert_eq!(args.last().map(String::as_str), Some("ci@build-host"));
+
+    Ok(())
+}
+
+#[test]
+fn configured_ports_are_forwarded() -> anyhow::Result<()> {
+    let ports = Ports {
+        input: 15066,
+        output: 15067,
+        api: 19600,
+    };
+    let args = ssh_arguments(
+        "ssh://build-host",
+        Path::new("/tmp/lotus/docker.sock"),
+        ports,
+    )?;
+
+    assert!(args.contains(&String::from("127.0.0.1:15066:127.0.0.1:15066")));
+    assert!(args.contains(&String::from("127.0.0.1:19600:127.0.0.1:19600")));
+    assert!(args.contains(&String::from("0.0.0.0:15067:127.0.0.1:15067")));
+
+    Ok(())
+}
+
+#[test]
+fn only_ssh_addresses_are_tunneled() {
+    let socket = Path::new("/tmp/lotus/docker.sock");
+
+    assert!(ssh_arguments("tcp://build-host:2376", socket, Ports::default()).is_err());
+    assert_eq!(
+        ssh_arguments("ssh://build-host", socket, Ports::default())
+            .unwrap()
+            .last()
+            .map(String::as_str),
+        Some("build-host")
+    );
+}
@@ -0,0 +1,52 @@
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+
+use lotus::watch::{Reaction, WatchedPaths};
+
+fn watched_paths() -> anyhow::Result<(tempfile::TempDir, WatchedPaths)> {
+    let tmp_dir = tempfile::tempdir()?;
+    create_dir_all(tmp_dir.path().join("rules"))?;
+    create_dir_all(tmp_dir.path().join("tests/apache/access"))?;
+    let paths = WatchedPaths::new(tmp_dir.path(), "tests", &["rules", "scripts", "patterns"])?;
+    Ok((tmp_dir, paths))
+}
+
+#[test]
+fn pipeline_changes_rebuild_the_image() -> anyhow::Result<()> {
+    let (_tmp_dir, paths) = watched_paths()?;
+
+    let changed = [
+        paths.tests_dir.join("apache/access/input.json"),
+        paths.target.join("rules/00-filter.conf"),
+    ];
+    assert_eq!(paths.reaction(&changed), Some(Reaction::Rebuild));
+    let changed = [paths.target.join("scripts/enrich.rb")];
+    assert_eq!(paths.reaction(&changed), Some(Reaction::Rebuild));
+    let changed = [paths.target.join("lotus.toml")];
+    assert_eq!(paths.reaction(&changed), Some(Reaction::Rebuild));
+
+    Ok(())
+}
+
+#[test]
+fn test_case_changes_rerun_the_changed_test_cases() -> anyhow::Result<()> {
+    let (_tmp_dir, paths) = watched_paths()?;
+
+    let changed = [
+        paths.tests_dir.join("apache/access/expected.json"),
+        paths.tests_dir.join("greeting.yaml"),
+    ];
+    assert_eq!(
+        paths.reaction(&changed),
+        Some(Reaction::Rerun(vec![
+            PathBuf::from("apache/access/expected.json"),
+            PathBuf::from("greeting.yaml"),
+        ]))
+    );
+    let changed = [paths.target.join("fixtures/vars.toml")];
+    assert_eq!(paths.reaction(&changed), Some(Reaction::RerunAll));
+    let changed = [paths.target.join("README.md"), paths.tests_dir.clone()];
+    assert_eq!(paths.reaction(&changed), None);
+
+    Ok(())
+}